@@ -0,0 +1,20 @@
+use mvt::{Error, StreamSink, StreamSource, TileSink, TileSource};
+use std::env;
+
+/// Filter a tile stream by zoom level, for use in a Unix pipeline:
+///
+/// `generator | stream_filter 0 14 | writer`
+fn main() -> Result<(), Error> {
+    let mut args = env::args().skip(1).map(|a| a.parse::<u32>());
+    let min_zoom = args.next().and_then(|z| z.ok()).unwrap_or(0);
+    let max_zoom = args.next().and_then(|z| z.ok()).unwrap_or(u32::MAX);
+    let mut source = StreamSource::stdin();
+    let mut sink = StreamSink::stdout();
+    while let Some((tid, data)) = source.next_tile()? {
+        if (min_zoom..=max_zoom).contains(&tid.z()) {
+            sink.put_tile(tid, &data)?;
+        }
+    }
+    sink.finish()?;
+    Ok(())
+}
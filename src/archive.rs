@@ -0,0 +1,411 @@
+// archive.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Bulk processing of tile archives.
+//!
+use crate::duplicate::DuplicateTiles;
+use crate::error::Result;
+use crate::mapgrid::TileId;
+use crate::tile::Tile;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// Number of tiles read per worker for each batch
+const BATCH_PER_WORKER: usize = 16;
+
+/// Source of encoded tiles, such as an archive or directory.
+pub trait TileSource {
+    /// Read the next tile.
+    ///
+    /// Returns `None` after the last tile.
+    fn next_tile(&mut self) -> Result<Option<(TileId, Vec<u8>)>>;
+}
+
+/// Sink for encoded tiles.
+pub trait TileSink {
+    /// Write one tile.
+    fn put_tile(&mut self, tid: TileId, data: &[u8]) -> Result<()>;
+}
+
+/// Tile source for a `{z}/{x}/{y}.mvt` directory tree.
+///
+/// Files with a `.pbf` extension are also accepted.  Tiles are read in
+/// z / x / y order.
+#[derive(Clone, Debug)]
+pub struct DirSource {
+    tiles: Vec<(TileId, PathBuf)>,
+    pos: usize,
+}
+
+/// Tile source for an iterator of encoded tiles.
+///
+/// # Example
+/// ```
+/// # use mvt::{IterSource, Tile, TileId, TileSource};
+/// # fn main() -> Result<(), mvt::Error> {
+/// let tid = TileId::new(0, 0, 0)?;
+/// let mut source = IterSource::new([(tid, Tile::new(4096).to_bytes()?)]);
+/// assert!(source.next_tile()?.is_some());
+/// assert!(source.next_tile()?.is_none());
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct IterSource<I> {
+    iter: I,
+}
+
+/// Tile sink for a `{z}/{x}/{y}.mvt` directory tree.
+#[derive(Clone, Debug)]
+pub struct DirSink {
+    path: PathBuf,
+    dups: Option<DuplicateTiles>,
+}
+
+/// Parse a numeric path component
+fn parse_num(path: &Path) -> Option<u32> {
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+/// Get numbered entries of a directory
+fn numbered(path: &Path, dirs: bool) -> Result<Vec<(u32, PathBuf)>> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() != dirs {
+            continue;
+        }
+        if !dirs {
+            let ext = path.extension().and_then(|e| e.to_str());
+            if !matches!(ext, Some("mvt") | Some("pbf")) {
+                continue;
+            }
+        }
+        if let Some(n) = parse_num(&path) {
+            entries.push((n, path));
+        }
+    }
+    entries.sort_by_key(|e| e.0);
+    Ok(entries)
+}
+
+impl DirSource {
+    /// Open a tile directory.
+    ///
+    /// * `path` Root directory, containing zoom level directories.
+    ///
+    /// Entries which are not named like tiles are ignored.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut tiles = vec![];
+        for (z, zpath) in numbered(path.as_ref(), true)? {
+            for (x, xpath) in numbered(&zpath, true)? {
+                for (y, ypath) in numbered(&xpath, false)? {
+                    if let Ok(tid) = TileId::new(x, y, z) {
+                        tiles.push((tid, ypath));
+                    }
+                }
+            }
+        }
+        Ok(DirSource { tiles, pos: 0 })
+    }
+
+    /// Get the number of tiles.
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Check if the directory has no tiles.
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+impl TileSource for DirSource {
+    fn next_tile(&mut self) -> Result<Option<(TileId, Vec<u8>)>> {
+        match self.tiles.get(self.pos) {
+            Some((tid, path)) => {
+                self.pos += 1;
+                Ok(Some((*tid, fs::read(path)?)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<I> IterSource<I>
+where
+    I: Iterator<Item = (TileId, Vec<u8>)>,
+{
+    /// Create a tile source from an iterator of encoded tiles.
+    pub fn new<T>(tiles: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        IterSource {
+            iter: tiles.into_iter(),
+        }
+    }
+}
+
+impl<I> TileSource for IterSource<I>
+where
+    I: Iterator<Item = (TileId, Vec<u8>)>,
+{
+    fn next_tile(&mut self) -> Result<Option<(TileId, Vec<u8>)>> {
+        Ok(self.iter.next())
+    }
+}
+
+impl DirSink {
+    /// Create a tile directory sink.
+    ///
+    /// * `path` Root directory (created as needed).
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        DirSink { path, dups: None }
+    }
+
+    /// Hard link duplicate tiles to the first identical tile written.
+    ///
+    /// Duplicates are found by digest, then compared byte for byte.  If
+    /// linking fails (e.g. unsupported file system), the tile is written
+    /// normally.  Tiles are written to a temporary file, then renamed, so
+    /// replacing one never changes tiles linked to it.
+    pub fn with_hardlinks(mut self) -> Self {
+        self.dups = Some(DuplicateTiles::default());
+        self
+    }
+
+    /// Get the duplicate tiles found, if hard linking is enabled.
+    pub fn duplicates(&self) -> Option<&DuplicateTiles> {
+        self.dups.as_ref()
+    }
+
+    /// Get the file path of a tile
+    fn tile_path(&self, tid: TileId) -> PathBuf {
+        let mut path = self.path.join(tid.z().to_string());
+        path.push(tid.x().to_string());
+        path.push(format!("{}.mvt", tid.y()));
+        path
+    }
+}
+
+impl TileSink for DirSink {
+    fn put_tile(&mut self, tid: TileId, data: &[u8]) -> Result<()> {
+        let path = self.tile_path(tid);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // replace by renaming, so that tiles linked to the old file are
+        // not changed
+        let tmp = path.with_extension("tmp");
+        let orig = self.dups.as_mut().and_then(|d| d.add(tid, data));
+        if let Some(orig) = orig {
+            let orig = self.tile_path(orig);
+            // digests can collide, so only link identical files
+            if fs::read(&orig).is_ok_and(|d| d == data) {
+                let _ = fs::remove_file(&tmp);
+                if fs::hard_link(&orig, &tmp).is_ok() {
+                    fs::rename(&tmp, &path)?;
+                    return Ok(());
+                }
+            }
+        }
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+impl TileSink for Vec<(TileId, Vec<u8>)> {
+    fn put_tile(&mut self, tid: TileId, data: &[u8]) -> Result<()> {
+        self.push((tid, data.to_vec()));
+        Ok(())
+    }
+}
+
+/// Process one tile
+fn process_tile<F>(tid: TileId, data: &[u8], f: &F) -> Result<Option<Vec<u8>>>
+where
+    F: Fn(TileId, Tile) -> Result<Option<Tile>>,
+{
+    match f(tid, Tile::from_bytes(data)?)? {
+        Some(tile) => Ok(Some(tile.to_bytes()?)),
+        None => Ok(None),
+    }
+}
+
+/// Stream every tile of a source through a function.
+///
+/// * `source` Source of encoded tiles.
+/// * `parallelism` Number of worker threads (at least 1).
+/// * `sink` Sink for resulting tiles.
+/// * `f` Function called with each decoded tile (return `None` to drop it).
+///
+/// Tiles are written to the sink in source order.  Processing stops at the
+/// first error.  Returns the number of tiles written.
+///
+/// # Example
+/// ```no_run
+/// # use mvt::{DirSink, DirSource, Error, for_each_tile};
+/// # fn main() -> Result<(), Error> {
+/// let mut source = DirSource::open("tiles")?;
+/// let mut sink = DirSink::new("pruned");
+/// for_each_tile(&mut source, 4, &mut sink, |_tid, tile| {
+///     Ok((tile.num_layers() > 0).then_some(tile))
+/// })?;
+/// # Ok(()) }
+/// ```
+pub fn for_each_tile<S, K, F>(
+    source: &mut S,
+    parallelism: usize,
+    sink: &mut K,
+    f: F,
+) -> Result<usize>
+where
+    S: TileSource + ?Sized,
+    K: TileSink + ?Sized,
+    F: Fn(TileId, Tile) -> Result<Option<Tile>> + Sync,
+{
+    let workers = parallelism.max(1);
+    let mut written = 0;
+    loop {
+        let mut batch = vec![];
+        while batch.len() < workers * BATCH_PER_WORKER {
+            match source.next_tile()? {
+                Some(tile) => batch.push(tile),
+                None => break,
+            }
+        }
+        if batch.is_empty() {
+            return Ok(written);
+        }
+        let chunk_len = batch.len().div_ceil(workers);
+        let results = thread::scope(|s| {
+            let handles: Vec<_> = batch
+                .chunks(chunk_len)
+                .map(|chunk| {
+                    let f = &f;
+                    s.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|(tid, data)| process_tile(*tid, data, f))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| {
+                    h.join().unwrap_or_else(|e| panic::resume_unwind(e))
+                })
+                .collect::<Vec<_>>()
+        });
+        for ((tid, _), res) in batch.iter().zip(results) {
+            if let Some(data) = res? {
+                sink.put_tile(*tid, &data)?;
+                written += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use pointy::Transform;
+
+    fn tile(n_layers: usize) -> Tile {
+        let mut tile = Tile::new(4096);
+        for i in 0..n_layers {
+            let layer = tile.create_layer(&format!("Layer {i}"));
+            let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(1.0, 2.0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            tile.add_layer(layer.into_feature(geom).into_layer())
+                .unwrap();
+        }
+        tile
+    }
+
+    #[test]
+    fn test_dir_round_trip() {
+        let dir = std::env::temp_dir()
+            .join(format!("mvt-archive-{}", std::process::id()));
+        let mut sink = DirSink::new(&dir);
+        for (x, y, z) in [(1, 0, 1), (0, 1, 1), (0, 0, 0)] {
+            let tid = TileId::new(x, y, z).unwrap();
+            sink.put_tile(tid, &tile(2).to_bytes().unwrap()).unwrap();
+        }
+        fs::write(dir.join("1").join("0").join("notes.txt"), b"x").unwrap();
+        assert!(sink.duplicates().is_none());
+        let mut source = DirSource::open(&dir).unwrap();
+        assert_eq!(source.len(), 3);
+        let mut out: Vec<(TileId, Vec<u8>)> = vec![];
+        let n = for_each_tile(&mut source, 2, &mut out, |tid, tile| {
+            Ok((tid.z() > 0).then_some(tile))
+        })
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(n, 2);
+        assert_eq!(out[0].0.to_string(), "1/0/1");
+        assert_eq!(out[1].0.to_string(), "1/1/0");
+        assert_eq!(Tile::from_bytes(&out[0].1).unwrap().num_layers(), 2);
+    }
+
+    #[test]
+    fn test_parallel_order() {
+        let mut tiles = IterSource::new((0..100).map(|i| {
+            let tid = TileId::new(i, 0, 7).unwrap();
+            (tid, tile(i as usize % 3).to_bytes().unwrap())
+        }));
+        let mut out: Vec<(TileId, Vec<u8>)> = vec![];
+        let n =
+            for_each_tile(&mut tiles, 3, &mut out, |_tid, tile| Ok(Some(tile)))
+                .unwrap();
+        assert_eq!(n, 100);
+        for (i, (tid, data)) in out.iter().enumerate() {
+            assert_eq!(tid.x(), i as u32);
+            assert_eq!(Tile::from_bytes(data).unwrap().num_layers(), i % 3);
+        }
+    }
+
+    #[test]
+    fn test_error() {
+        let mut tiles =
+            IterSource::new([(TileId::new(0, 0, 0).unwrap(), vec![0xFF])]);
+        let mut out: Vec<(TileId, Vec<u8>)> = vec![];
+        assert!(
+            for_each_tile(&mut tiles, 1, &mut out, |_, t| Ok(Some(t))).is_err()
+        );
+    }
+
+    #[test]
+    fn test_hardlinks() {
+        let dir = std::env::temp_dir()
+            .join(format!("mvt-hardlinks-{}", std::process::id()));
+        let mut sink = DirSink::new(&dir).with_hardlinks();
+        let data = tile(1).to_bytes().unwrap();
+        for x in 0..4 {
+            let tid = TileId::new(x, 0, 2).unwrap();
+            sink.put_tile(tid, &data).unwrap();
+        }
+        // replacing a linked tile must not change the others
+        let other = tile(2).to_bytes().unwrap();
+        sink.put_tile(TileId::new(1, 0, 2).unwrap(), &other)
+            .unwrap();
+        let source = DirSource::open(&dir).unwrap();
+        let read = fs::read(dir.join("2").join("3").join("0.mvt")).unwrap();
+        let read1 = fs::read(dir.join("2").join("1").join("0.mvt")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(source.len(), 4);
+        assert_eq!(read, data);
+        assert_eq!(read1, other);
+        assert_eq!(sink.duplicates().unwrap().num_duplicates(), 3);
+    }
+}
@@ -0,0 +1,218 @@
+// assertions.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Tile QA assertions.
+//!
+use crate::error::{Error, Result};
+use crate::tile::Tile;
+use crate::view::LayerView;
+use std::collections::BTreeMap;
+
+/// Assertions on one layer
+#[derive(Clone, Debug, Default)]
+struct LayerAssertions {
+    /// Layer is required
+    required: bool,
+
+    /// Minimum number of features
+    min_features: Option<usize>,
+
+    /// Maximum number of features
+    max_features: Option<usize>,
+
+    /// Keys required on every feature
+    keys: Vec<String>,
+}
+
+/// QA assertions, for checking generated tiles.
+///
+/// Each failed assertion is reported as [Error::AssertionFailed], with a
+/// message naming the layer (and feature) involved.  Feature counts do not
+/// include [metadata] features.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{Tile, TileAssertions};
+///
+/// let assertions = TileAssertions::default()
+///     .with_required_layer("roads")
+///     .with_max_features("roads", 10_000)
+///     .with_required_key("roads", "name")
+///     .with_max_size(500_000);
+/// let mut tile = Tile::new(4096);
+/// tile.add_layer(tile.create_layer("water"))?;
+/// let err = assertions.check(&tile).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Tile assertion failed: missing required layer \"roads\""
+/// );
+/// # Ok(()) }
+/// ```
+///
+/// [Error::AssertionFailed]: enum.Error.html#variant.AssertionFailed
+/// [metadata]: struct.Layer.html#method.with_metadata
+#[derive(Clone, Debug, Default)]
+pub struct TileAssertions {
+    /// Assertions by layer name
+    layers: BTreeMap<String, LayerAssertions>,
+
+    /// Maximum encoded size (bytes)
+    max_size: Option<usize>,
+}
+
+impl TileAssertions {
+    /// Get assertions for a layer
+    fn layer(&mut self, name: &str) -> &mut LayerAssertions {
+        self.layers.entry(name.to_string()).or_default()
+    }
+
+    /// Require a layer.
+    pub fn with_required_layer(mut self, name: &str) -> Self {
+        self.layer(name).required = true;
+        self
+    }
+
+    /// Set the minimum number of features in a layer (if present).
+    pub fn with_min_features(mut self, name: &str, min: usize) -> Self {
+        self.layer(name).min_features = Some(min);
+        self
+    }
+
+    /// Set the maximum number of features in a layer.
+    pub fn with_max_features(mut self, name: &str, max: usize) -> Self {
+        self.layer(name).max_features = Some(max);
+        self
+    }
+
+    /// Require a tag key on every feature in a layer.
+    pub fn with_required_key(mut self, name: &str, key: &str) -> Self {
+        self.layer(name).keys.push(key.to_string());
+        self
+    }
+
+    /// Set the maximum encoded tile size (bytes).
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Check a tile, failing on the first assertion which does not hold.
+    pub fn check(&self, tile: &Tile) -> Result<()> {
+        if let Some(max_size) = self.max_size {
+            let size = tile.compute_size();
+            if size > max_size {
+                return Err(Error::AssertionFailed(format!(
+                    "tile size {size} exceeds {max_size} bytes"
+                )));
+            }
+        }
+        let layers = tile.layers()?;
+        for (name, assertions) in &self.layers {
+            match layers.iter().find(|layer| layer.name() == name) {
+                Some(layer) => assertions.check(layer)?,
+                None if assertions.required => {
+                    return Err(Error::AssertionFailed(format!(
+                        "missing required layer {name:?}"
+                    )));
+                }
+                None => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LayerAssertions {
+    /// Check a layer
+    fn check(&self, layer: &LayerView) -> Result<()> {
+        let name = layer.name();
+        let mut count = 0;
+        for (i, feature) in layer.features().enumerate() {
+            let feature = feature?;
+            if feature.geom_type().is_none() {
+                continue;
+            }
+            count += 1;
+            for key in &self.keys {
+                if !feature.tags(layer).any(|(k, _)| k == key) {
+                    return Err(Error::AssertionFailed(format!(
+                        "layer {name:?} feature {i} missing key {key:?}"
+                    )));
+                }
+            }
+        }
+        if let Some(min) = self.min_features {
+            if count < min {
+                return Err(Error::AssertionFailed(format!(
+                    "layer {name:?} has {count} features, fewer than {min}"
+                )));
+            }
+        }
+        if let Some(max) = self.max_features {
+            if count > max {
+                return Err(Error::AssertionFailed(format!(
+                    "layer {name:?} has {count} features, more than {max}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use pointy::Transform;
+
+    fn tile() -> Tile {
+        let mut tile = Tile::new(4096);
+        let mut layer =
+            tile.create_layer("places").with_metadata("source", "test");
+        for (i, name) in ["a", "b", ""].iter().enumerate() {
+            let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(i as f64, 0.0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            if !name.is_empty() {
+                feature.add_tag_string("name", name);
+            }
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        tile
+    }
+
+    fn err(assertions: TileAssertions) -> Option<String> {
+        match assertions.check(&tile()) {
+            Err(Error::AssertionFailed(msg)) => Some(msg),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_assertions() {
+        let a = TileAssertions::default();
+        assert_eq!(err(a.clone()), None);
+        let b = a.clone().with_min_features("places", 3);
+        assert_eq!(err(b.with_max_features("places", 3)), None);
+        assert_eq!(
+            err(a.clone().with_max_features("places", 2)).unwrap(),
+            "layer \"places\" has 3 features, more than 2"
+        );
+        assert!(err(a.clone().with_min_features("places", 4)).is_some());
+        assert_eq!(err(a.clone().with_min_features("roads", 1)), None);
+        assert!(err(a.clone().with_required_layer("roads")).is_some());
+        assert_eq!(
+            err(a.clone().with_required_key("places", "name")).unwrap(),
+            "layer \"places\" feature 3 missing key \"name\""
+        );
+        assert!(err(a.clone().with_max_size(10)).is_some());
+        assert_eq!(err(a.with_max_size(1000)), None);
+    }
+}
@@ -0,0 +1,134 @@
+// bearing.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Bearing (orientation) calculations.
+//!
+use pointy::{Float, Transform};
+
+/// Calculate the bearing from one point to another, in tile space.
+///
+/// * `transform` Transform to tile coördinates.
+/// * `from` Starting point.
+/// * `to` Ending point.
+///
+/// Returns degrees clockwise from tile "up" (at least 0, less than 360), or
+/// `None` if the points coincide after transform.  This is the rotation a
+/// renderer applies to an icon pointing up, so it is suitable for stamping as
+/// a tag.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, GeomEncoder, GeomType, Tile};
+/// # use pointy::Transform;
+/// # fn main() -> Result<(), Error> {
+/// let t = Transform::default();
+/// let tile = Tile::new(4096);
+/// let layer = tile.create_layer("vehicles");
+/// let geom_data = GeomEncoder::new(GeomType::Point, t)
+///     .point(20.0, 10.0)?
+///     .encode()?;
+/// let mut feature = layer.into_feature(geom_data);
+/// if let Some(deg) = mvt::bearing(&t, (10.0, 10.0), (20.0, 10.0)) {
+///     assert_eq!(deg, 90.0);
+///     feature.add_tag_double("bearing", deg);
+/// }
+/// let layer = feature.into_layer();
+/// # Ok(()) }
+/// ```
+pub fn bearing<F>(
+    transform: &Transform<F>,
+    from: (F, F),
+    to: (F, F),
+) -> Option<F>
+where
+    F: Float,
+{
+    let p0 = *transform * from;
+    let p1 = *transform * to;
+    let dx = p1.x() - p0.x();
+    let dy = p1.y() - p0.y();
+    if dx == F::zero() && dy == F::zero() {
+        return None;
+    }
+    // tile Y increases downward
+    let full = F::from(360).unwrap();
+    // Euclidean remainder; tiny negative angles round up to 360
+    let deg = dx.atan2(-dy).to_degrees() % full;
+    let deg = if deg < F::zero() { deg + full } else { deg };
+    // fold 360 and -0 to 0
+    Some(if deg >= full || deg == F::zero() {
+        F::zero()
+    } else {
+        deg
+    })
+}
+
+/// Calculate start and end bearings of a linestring, in tile space.
+///
+/// * `transform` Transform to tile coördinates.
+/// * `line` Linestring vertices.
+///
+/// Returns (start, end) bearings, skipping zero-length segments, or `None` if
+/// the line has no length.
+pub fn line_bearings<F>(
+    transform: &Transform<F>,
+    line: &[(F, F)],
+) -> Option<(F, F)>
+where
+    F: Float,
+{
+    let start = line
+        .windows(2)
+        .find_map(|w| bearing(transform, w[0], w[1]))?;
+    let end = line
+        .windows(2)
+        .rev()
+        .find_map(|w| bearing(transform, w[0], w[1]))?;
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bearing() {
+        let t = Transform::default();
+        assert_eq!(bearing(&t, (0.0, 0.0), (0.0, -1.0)), Some(0.0));
+        assert_eq!(bearing(&t, (0.0, 0.0), (1.0, 0.0)), Some(90.0));
+        assert_eq!(bearing(&t, (0.0, 0.0), (0.0, 1.0)), Some(180.0));
+        assert_eq!(bearing(&t, (0.0, 0.0), (-1.0, 0.0)), Some(270.0));
+        assert_eq!(bearing(&t, (1.0, 1.0), (1.0, 1.0)), None);
+    }
+
+    #[test]
+    fn test_bearing_range() {
+        let t = Transform::default();
+        let deg = bearing(&t, (0.0, 0.0), (-0.0, -1.0)).unwrap();
+        assert_eq!(deg, 0.0);
+        assert!(deg.is_sign_positive());
+        let deg = bearing(&t, (0.0, 0.0), (-1e-20, -1.0)).unwrap();
+        assert_eq!(deg, 0.0);
+        let deg = bearing(&t, (0.0, 0.0), (-1e-9, -1.0)).unwrap();
+        assert!(deg > 359.0 && deg < 360.0);
+        let deg = bearing(&t, (0.0, 0.0), (1e-20, -1.0)).unwrap();
+        assert!(deg >= 0.0 && deg < 1e-15);
+        assert_eq!(bearing(&t, (0.0, 0.0), (-0.0, 1.0)), Some(180.0));
+    }
+
+    #[test]
+    fn test_flipped() {
+        // map coördinates have Y increasing upward
+        let t = Transform::with_scale(1.0, -1.0);
+        assert_eq!(bearing(&t, (0.0, 0.0), (0.0, 1.0)), Some(0.0));
+    }
+
+    #[test]
+    fn test_line_bearings() {
+        let t = Transform::default();
+        let line = [(0.0, 0.0), (0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (1.0, 1.0)];
+        assert_eq!(line_bearings(&t, &line), Some((90.0, 180.0)));
+        assert_eq!(line_bearings(&t, &[(0.0, 0.0)]), None);
+    }
+}
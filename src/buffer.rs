@@ -0,0 +1,130 @@
+// buffer.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Geodesic point buffers.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use crate::geometry::EARTH_RADIUS_M;
+use crate::mapgrid::TileId;
+use pointy::Transform;
+use std::f64::consts::TAU;
+
+/// Get the point at a distance and bearing from a lon/lat point (degrees)
+///
+/// Longitude is not wrapped, so rings crossing the antimeridian stay
+/// contiguous.
+fn destination(lonlat: (f64, f64), dist: f64, bearing: f64) -> (f64, f64) {
+    let (lon, lat) = (lonlat.0.to_radians(), lonlat.1.to_radians());
+    let lat2 = (lat.sin() * dist.cos()
+        + lat.cos() * dist.sin() * bearing.cos())
+    .asin();
+    let lon2 = lon
+        + (bearing.sin() * dist.sin() * lat.cos())
+            .atan2(dist.cos() - lat.sin() * lat2.sin());
+    (lon2.to_degrees(), lat2.to_degrees())
+}
+
+/// Make a circle polygon of a ground distance around a point.
+///
+/// * `lonlat` Center longitude / latitude (degrees).
+/// * `meters` Radius on the ground (meters).
+/// * `tid` Tile ID.
+/// * `extent` Tile extent.
+///
+/// Vertices are found on a sphere, then projected to tile units, so the
+/// polygon accounts for Web Mercator scale distortion: it grows toward the
+/// poles, and is taller than wide at high latitudes.  The number of
+/// vertices is chosen so the ring deviates by at most half a tile unit.
+///
+/// Returns [Error::InvalidValue] if `meters` is not positive, or
+/// [Error::InvalidGeometry] if the circle covers a pole.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, TileId};
+/// # fn main() -> Result<(), Error> {
+/// use mvt::point_buffer_m;
+///
+/// let tid = TileId::new(246, 368, 10)?;
+/// // 1 km around Minneapolis
+/// let geom_data = point_buffer_m((-93.265, 44.978), 1000.0, tid, 4096)?;
+/// geom_data.validate()?;
+/// # Ok(()) }
+/// ```
+///
+/// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+/// [Error::InvalidValue]: enum.Error.html#variant.InvalidValue
+pub fn point_buffer_m(
+    lonlat: (f64, f64),
+    meters: f64,
+    tid: TileId,
+    extent: u32,
+) -> Result<GeomData> {
+    if !(meters > 0.0 && meters.is_finite()) {
+        return Err(Error::InvalidValue());
+    }
+    let dist = meters / EARTH_RADIUS_M;
+    let lat = lonlat.1.to_radians();
+    if lat.abs() + dist >= TAU / 4.0 {
+        return Err(Error::InvalidGeometry());
+    }
+    // radius (tile units) is largest at the poleward edge
+    let scale = f64::from(extent) * f64::from(1 << tid.z()) / TAU;
+    let radius = scale * dist / (lat.abs() + dist).cos();
+    let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default());
+    let n = enc.circle_segments(radius, 0);
+    let extent = f64::from(extent);
+    // clockwise bearings are exterior rings in tile coördinates
+    for i in 0..n {
+        let bearing = TAU * f64::from(i) / f64::from(n);
+        let (lon, lat) = destination(lonlat, dist, bearing);
+        let (x, y) = tid.tile_xy(lon, lat);
+        enc.add_point(x * extent, y * extent)?;
+    }
+    enc.complete()?.encode()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::decode_parts;
+    use crate::options::ring_area;
+
+    #[test]
+    fn test_destination() {
+        let (lon, lat) = destination((0.0, 0.0), TAU / 4.0, TAU / 4.0);
+        assert!((lon - 90.0).abs() < 1e-9 && lat.abs() < 1e-9);
+        let (lon, lat) = destination((10.0, 60.0), 0.01, 0.0);
+        assert!((lon - 10.0).abs() < 1e-9);
+        assert!((lat - 60.0 - 0.01f64.to_degrees()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_point_buffer() {
+        let tid = TileId::new(0, 0, 0).unwrap();
+        let size = |lat: f64, meters: f64| {
+            let data = point_buffer_m((0.0, lat), meters, tid, 4096)
+                .unwrap()
+                .into_vec();
+            let ring = &decode_parts(&data).unwrap()[0];
+            assert!(ring_area(ring) > 0);
+            let (xs, ys): (Vec<i32>, Vec<i32>) = ring.iter().copied().unzip();
+            let width = xs.iter().max().unwrap() - xs.iter().min().unwrap();
+            let height = ys.iter().max().unwrap() - ys.iter().min().unwrap();
+            (width, height)
+        };
+        // 200 km is about 1/200 of the equator
+        let (w0, h0) = size(0.0, 100_000.0);
+        assert!((20..=21).contains(&w0) && (20..=21).contains(&h0));
+        // scale is doubled at 60°
+        let (w60, _) = size(60.0, 100_000.0);
+        assert!((40..=42).contains(&w60));
+        // taller than wide, since scale increases to the north
+        let (w60, h60) = size(60.0, 1_000_000.0);
+        assert!(h60 > w60 + 4);
+        assert!(point_buffer_m((0.0, 0.0), 0.0, tid, 4096).is_err());
+        assert!(point_buffer_m((0.0, 89.0), 200_000.0, tid, 4096).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+// builder.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Typestate feature builder.
+//!
+use crate::encoder::GeomData;
+use crate::tile::{Feature, Layer};
+
+/// Sealed builder state traits
+mod sealed {
+    use crate::tile::Feature;
+
+    /// Any builder state
+    pub trait Sealed {}
+
+    /// Builder state with a feature
+    pub trait WithFeature {
+        /// Take the feature
+        fn feature(self) -> Feature;
+    }
+}
+
+/// State of a [FeatureBuilder].
+///
+/// This trait is sealed; the only states are [NoGeometry], [NoTags] and
+/// [Tagged].
+///
+/// [FeatureBuilder]: struct.FeatureBuilder.html
+/// [NoGeometry]: struct.NoGeometry.html
+/// [NoTags]: struct.NoTags.html
+/// [Tagged]: struct.Tagged.html
+pub trait BuilderState: sealed::Sealed {}
+
+/// [FeatureBuilder] state which can accept tags (sealed).
+///
+/// [FeatureBuilder]: struct.FeatureBuilder.html
+pub trait TagState: BuilderState + sealed::WithFeature {}
+
+/// Builder state: geometry not yet set
+pub struct NoGeometry(Layer);
+
+/// Builder state: geometry set; ID may be set
+pub struct NoTags(Feature);
+
+/// Builder state: ID or a tag set; only tags may follow
+pub struct Tagged(Feature);
+
+impl sealed::Sealed for NoGeometry {}
+impl sealed::Sealed for NoTags {}
+impl sealed::Sealed for Tagged {}
+impl BuilderState for NoGeometry {}
+impl BuilderState for NoTags {}
+impl BuilderState for Tagged {}
+
+impl sealed::WithFeature for NoTags {
+    fn feature(self) -> Feature {
+        self.0
+    }
+}
+
+impl sealed::WithFeature for Tagged {
+    fn feature(self) -> Feature {
+        self.0
+    }
+}
+
+impl TagState for NoTags {}
+impl TagState for Tagged {}
+
+/// Feature builder with ordering checked at compile time.
+///
+/// Geometry must be set first, then optionally an ID, then any tags.  The
+/// feature is added to the layer only by [into_layer], so it is not possible
+/// to modify a feature after it has been pushed.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{FeatureBuilder, GeomEncoder, GeomType, Tile};
+/// use pointy::Transform;
+///
+/// let tile = Tile::new(4096);
+/// let geom_data = GeomEncoder::new(GeomType::Point, Transform::default())
+///     .point(1.0, 2.0)?
+///     .encode()?;
+/// let layer = FeatureBuilder::new(tile.create_layer("First Layer"))
+///     .geometry(geom_data)
+///     .id(1)
+///     .tag_string("name", "First")
+///     .tag_uint("rank", 3)
+///     .into_layer();
+/// assert_eq!(layer.num_features(), 1);
+/// # Ok(()) }
+/// ```
+///
+/// Setting the ID after a tag does not compile:
+/// ```compile_fail
+/// # use mvt::{FeatureBuilder, GeomEncoder, GeomType, Tile};
+/// # use pointy::Transform;
+/// # let tile = Tile::new(4096);
+/// # let geom_data = GeomEncoder::<f64>::new(GeomType::Point, Transform::default())
+/// #     .encode().unwrap();
+/// let layer = FeatureBuilder::new(tile.create_layer("First Layer"))
+///     .geometry(geom_data)
+///     .tag_string("name", "First")
+///     .id(1)
+///     .into_layer();
+/// ```
+///
+/// [into_layer]: struct.FeatureBuilder.html#method.into_layer
+#[must_use = "call `into_layer` to add the feature to its layer"]
+pub struct FeatureBuilder<S: BuilderState> {
+    state: S,
+}
+
+impl FeatureBuilder<NoGeometry> {
+    /// Create a new feature builder, taking ownership of a layer.
+    pub fn new(layer: Layer) -> Self {
+        FeatureBuilder {
+            state: NoGeometry(layer),
+        }
+    }
+
+    /// Set the feature geometry.
+    ///
+    /// * `geom_data` Geometry data (consumed by this method).
+    pub fn geometry(self, geom_data: GeomData) -> FeatureBuilder<NoTags> {
+        let feature = self.state.0.into_feature(geom_data);
+        FeatureBuilder {
+            state: NoTags(feature),
+        }
+    }
+
+    /// Get the layer, without adding a feature.
+    pub fn layer(self) -> Layer {
+        self.state.0
+    }
+}
+
+impl FeatureBuilder<NoTags> {
+    /// Set the feature ID.
+    pub fn id(mut self, id: u64) -> FeatureBuilder<Tagged> {
+        self.state.0.set_id(id);
+        FeatureBuilder {
+            state: Tagged(self.state.0),
+        }
+    }
+}
+
+impl<S: TagState> FeatureBuilder<S> {
+    /// Add a tag, moving to the tagged state
+    fn tag<T>(self, add: T) -> FeatureBuilder<Tagged>
+    where
+        T: FnOnce(&mut Feature),
+    {
+        let mut feature = self.state.feature();
+        add(&mut feature);
+        FeatureBuilder {
+            state: Tagged(feature),
+        }
+    }
+
+    /// Add a tag of string type.
+    pub fn tag_string(self, key: &str, val: &str) -> FeatureBuilder<Tagged> {
+        self.tag(|f| {
+            f.add_tag_string(key, val);
+        })
+    }
+
+    /// Add a tag of double type.
+    pub fn tag_double(self, key: &str, val: f64) -> FeatureBuilder<Tagged> {
+        self.tag(|f| {
+            f.add_tag_double(key, val);
+        })
+    }
+
+    /// Add a tag of float type.
+    pub fn tag_float(self, key: &str, val: f32) -> FeatureBuilder<Tagged> {
+        self.tag(|f| {
+            f.add_tag_float(key, val);
+        })
+    }
+
+    /// Add a tag of int type.
+    pub fn tag_int(self, key: &str, val: i64) -> FeatureBuilder<Tagged> {
+        self.tag(|f| {
+            f.add_tag_int(key, val);
+        })
+    }
+
+    /// Add a tag of uint type.
+    pub fn tag_uint(self, key: &str, val: u64) -> FeatureBuilder<Tagged> {
+        self.tag(|f| {
+            f.add_tag_uint(key, val);
+        })
+    }
+
+    /// Add a tag of sint type.
+    pub fn tag_sint(self, key: &str, val: i64) -> FeatureBuilder<Tagged> {
+        self.tag(|f| {
+            f.add_tag_sint(key, val);
+        })
+    }
+
+    /// Add a tag of bool type.
+    pub fn tag_bool(self, key: &str, val: bool) -> FeatureBuilder<Tagged> {
+        self.tag(|f| {
+            f.add_tag_bool(key, val);
+        })
+    }
+
+    /// Complete the feature, returning ownership of the layer.
+    pub fn into_layer(self) -> Layer {
+        self.state.feature().into_layer()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    #[test]
+    fn test_builder() {
+        let tile = Tile::new(4096);
+        let geom = || {
+            GeomEncoder::<f64>::new(GeomType::Point, Transform::default())
+                .point(1.0, 2.0)
+                .unwrap()
+                .encode()
+                .unwrap()
+        };
+        let layer = FeatureBuilder::new(tile.create_layer("test"))
+            .geometry(geom())
+            .into_layer();
+        let layer = FeatureBuilder::new(layer)
+            .geometry(geom())
+            .tag_bool("b", true)
+            .tag_int("i", -1)
+            .into_layer();
+        let layer = FeatureBuilder::new(layer).layer();
+        assert_eq!(layer.num_features(), 2);
+        let mut tile = tile;
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let vt = &tile.vt_layers()[0];
+        assert_eq!(vt.features[0].tags.len(), 0);
+        assert_eq!(vt.features[1].tags, vec![0, 0, 1, 1]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Feature dropped without into_layer")]
+    fn test_dropped() {
+        let tile = Tile::new(4096);
+        let geom_data =
+            GeomEncoder::<f64>::new(GeomType::Point, Transform::default())
+                .encode()
+                .unwrap();
+        let _ = FeatureBuilder::new(tile.create_layer("test"))
+            .geometry(geom_data)
+            .id(1);
+    }
+}
@@ -0,0 +1,337 @@
+// cache.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Encoded layer cache.
+//!
+use crate::duplicate::TileDigest;
+use crate::error::{Error, Result};
+use crate::options::EncodeOptions;
+use crate::tile::{Layer, Tile};
+#[cfg(feature = "gzip")]
+use flate2::{write::GzEncoder, Compression};
+use protobuf::Message;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "gzip")]
+use std::io::Write;
+
+/// Protobuf key of `Tile.layers` (field 3, length-delimited)
+pub(crate) const LAYER_KEY: u8 = 0x1a;
+
+/// Layer encoded as a framed `Tile.layers` submessage.
+///
+/// Encoded tiles are a sequence of these, so cached layers can be appended
+/// to encoded tile bytes without decoding or re-encoding anything.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CachedLayer {
+    /// Layer name
+    name: String,
+
+    /// Layer extent
+    extent: u32,
+
+    /// Framed layer bytes
+    data: Vec<u8>,
+
+    /// Framed layer bytes, as a gzip member
+    #[cfg(feature = "gzip")]
+    gzip: Vec<u8>,
+}
+
+/// Cache of encoded layers, keyed by content digest.
+///
+/// Layers reused across many tiles (such as static boundaries) are encoded
+/// (and gzip compressed, with the `gzip` feature) once, then spliced into
+/// each tile with [to_bytes].  Identical layers share one entry.
+///
+/// Layers are checked with the cache's [EncodeOptions] when inserted.  With
+/// a [capacity], the oldest entries are evicted to make room for new ones.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{LayerCache, Tile};
+///
+/// let mut cache = LayerCache::default();
+/// let tile = Tile::new(4096);
+/// let digest = cache.insert(tile.create_layer("boundaries"))?;
+/// let mut tile = Tile::new(4096);
+/// tile.add_layer(tile.create_layer("roads"))?;
+/// let boundaries = cache.get(&digest).unwrap();
+/// let data = LayerCache::to_bytes(&tile, &[boundaries])?;
+/// let tile = Tile::from_bytes(&data)?;
+/// assert_eq!(tile.num_layers(), 2);
+/// # Ok(()) }
+/// ```
+///
+/// [capacity]: struct.LayerCache.html#method.with_capacity
+/// [EncodeOptions]: struct.EncodeOptions.html
+/// [to_bytes]: struct.LayerCache.html#method.to_bytes
+#[derive(Clone, Debug, Default)]
+pub struct LayerCache {
+    /// Validation options
+    options: EncodeOptions,
+
+    /// Maximum number of cached layers
+    capacity: Option<usize>,
+
+    /// Cached layers
+    layers: HashMap<TileDigest, CachedLayer>,
+
+    /// Digests in insertion order, for eviction
+    order: VecDeque<TileDigest>,
+
+    /// Number of insertions of already cached layers
+    hits: usize,
+}
+
+impl CachedLayer {
+    /// Create a cached layer from framed bytes (compressing if enabled).
+    fn new(name: String, extent: u32, data: Vec<u8>) -> Result<Self> {
+        #[cfg(feature = "gzip")]
+        let gzip = {
+            let v = Vec::with_capacity(data.len());
+            let mut enc = GzEncoder::new(v, Compression::default());
+            enc.write_all(&data)?;
+            enc.finish()?
+        };
+        Ok(CachedLayer {
+            name,
+            extent,
+            data,
+            #[cfg(feature = "gzip")]
+            gzip,
+        })
+    }
+
+    /// Get the layer name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the layer extent.
+    pub fn extent(&self) -> u32 {
+        self.extent
+    }
+
+    /// Get the framed layer bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Get the framed layer bytes, compressed as one gzip member.
+    ///
+    /// Concatenated gzip members are a valid multi-member gzip stream, which
+    /// decompresses to the concatenated (spliced) tile.
+    #[cfg(feature = "gzip")]
+    pub fn as_gzip(&self) -> &[u8] {
+        &self.gzip
+    }
+}
+
+impl LayerCache {
+    /// Set validation options, applied when layers are inserted.
+    pub fn with_options(mut self, options: EncodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the maximum number of cached layers.
+    ///
+    /// When full, the oldest layer is evicted before inserting another.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self.evict(capacity);
+        self
+    }
+
+    /// Evict the oldest layers, until no more than `max` are cached
+    fn evict(&mut self, max: usize) {
+        while self.layers.len() > max {
+            match self.order.pop_front() {
+                Some(digest) => {
+                    self.layers.remove(&digest);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Get the number of cached layers.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Check if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Get the number of insertions of layers which were already cached.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Encode and cache a layer, returning its content digest.
+    ///
+    /// The layer is checked using the cache's [EncodeOptions].  If an
+    /// identical layer is already cached, it is reused (without compressing
+    /// again).  Returns [Error::DigestCollision] if a different layer with
+    /// the same digest is cached, or an error if a strict check fails.
+    ///
+    /// [EncodeOptions]: struct.EncodeOptions.html
+    /// [Error::DigestCollision]: enum.Error.html#variant.DigestCollision
+    pub fn insert(&mut self, layer: Layer) -> Result<TileDigest> {
+        let mut layer = layer.into_vt();
+        self.options.check_layer(&mut layer, (0, 0))?;
+        let mut data = vec![LAYER_KEY];
+        data.extend(layer.write_length_delimited_to_bytes()?);
+        let digest = TileDigest::new(&data);
+        match self.layers.entry(digest) {
+            Entry::Occupied(e) => {
+                if e.get().data != data {
+                    return Err(Error::DigestCollision());
+                }
+                self.hits += 1;
+            }
+            Entry::Vacant(e) => {
+                let name = layer.name().to_string();
+                e.insert(CachedLayer::new(name, layer.extent(), data)?);
+                self.order.push_back(digest);
+                if let Some(capacity) = self.capacity {
+                    self.evict(capacity);
+                }
+            }
+        }
+        Ok(digest)
+    }
+
+    /// Get a cached layer by content digest.
+    pub fn get(&self, digest: &TileDigest) -> Option<&CachedLayer> {
+        self.layers.get(digest)
+    }
+
+    /// Check that cached layers can be spliced into a tile
+    fn check(tile: &Tile, layers: &[&CachedLayer]) -> Result<()> {
+        for (i, layer) in layers.iter().enumerate() {
+            if layer.extent != tile.extent() {
+                return Err(Error::WrongExtent());
+            }
+            if tile.has_layer(&layer.name)
+                || layers[..i].iter().any(|l| l.name == layer.name)
+            {
+                return Err(Error::DuplicateName());
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode a tile with cached layers appended.
+    ///
+    /// Returns an error if a layer name is duplicated, or a layer extent does
+    /// not match the tile extent.
+    pub fn to_bytes(tile: &Tile, layers: &[&CachedLayer]) -> Result<Vec<u8>> {
+        Self::check(tile, layers)?;
+        let mut data = tile.to_bytes()?;
+        for layer in layers {
+            data.extend_from_slice(&layer.data);
+        }
+        Ok(data)
+    }
+
+    /// Encode a tile as gzip-compressed bytes, with cached layers appended.
+    ///
+    /// Only the tile is compressed; cached layers are appended as
+    /// pre-compressed gzip members.
+    #[cfg(feature = "gzip")]
+    pub fn to_bytes_gzip(
+        tile: &Tile,
+        layers: &[&CachedLayer],
+    ) -> Result<Vec<u8>> {
+        Self::check(tile, layers)?;
+        let mut data = tile.to_bytes_gzip()?;
+        for layer in layers {
+            data.extend_from_slice(&layer.gzip);
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomData, GeomType};
+
+    #[test]
+    fn test_cache() {
+        let tile = Tile::new(256);
+        let mut cache = LayerCache::default();
+        let a = cache.insert(tile.create_layer("a")).unwrap();
+        let b = cache.insert(tile.create_layer("b")).unwrap();
+        assert_eq!(cache.insert(tile.create_layer("a")).unwrap(), a);
+        assert_eq!((cache.len(), cache.hits()), (2, 1));
+        // same digest, different bytes
+        let mut other = cache.clone();
+        other.layers.get_mut(&a).unwrap().data.push(0);
+        assert!(matches!(
+            other.insert(tile.create_layer("a")),
+            Err(Error::DigestCollision())
+        ));
+        assert_eq!(other.hits(), 1);
+        let a = cache.get(&a).unwrap();
+        let b = cache.get(&b).unwrap();
+        assert_eq!(a.name(), "a");
+        let mut tile = Tile::new(256);
+        tile.add_layer(tile.create_layer("c")).unwrap();
+        let mut full = Tile::new(256);
+        for name in ["c", "a", "b"] {
+            full.add_layer(full.create_layer(name)).unwrap();
+        }
+        let data = LayerCache::to_bytes(&tile, &[a, b]).unwrap();
+        assert_eq!(data, full.to_bytes().unwrap());
+        assert!(LayerCache::to_bytes(&tile, &[a, a]).is_err());
+        assert!(LayerCache::to_bytes(&Tile::new(512), &[a]).is_err());
+        #[cfg(feature = "gzip")]
+        {
+            let mut tile = Tile::new(256);
+            tile.add_layer(tile.create_layer("c")).unwrap();
+            let data = LayerCache::to_bytes_gzip(&tile, &[a, b]).unwrap();
+            assert!(data.ends_with(b.as_gzip()));
+            let tile = Tile::from_compressed_bytes(&data).unwrap();
+            assert_eq!(tile.to_bytes().unwrap(), full.to_bytes().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_capacity() {
+        let tile = Tile::new(256);
+        let mut cache = LayerCache::default().with_capacity(2);
+        let a = cache.insert(tile.create_layer("a")).unwrap();
+        let b = cache.insert(tile.create_layer("b")).unwrap();
+        let c = cache.insert(tile.create_layer("c")).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&a).is_none());
+        assert!(cache.get(&b).is_some() && cache.get(&c).is_some());
+        let cache = cache.with_capacity(1);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&c).is_some());
+    }
+
+    #[test]
+    fn test_options() {
+        let tile = Tile::new(256);
+        let strict = EncodeOptions::default().with_max_string_len(2);
+        let mut cache = LayerCache::default().with_options(strict);
+        let mut feature = tile
+            .create_layer("a")
+            .into_feature(GeomData::new(GeomType::Point, vec![]));
+        feature.add_tag_string("name", "long");
+        assert!(matches!(
+            cache.insert(feature.into_layer()),
+            Err(Error::StringTooLong())
+        ));
+        assert!(cache.is_empty());
+    }
+}
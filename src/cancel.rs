@@ -0,0 +1,86 @@
+// cancel.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Cooperative cancellation of encoding.
+//!
+use crate::error::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Token for cancelling long-running encoding.
+///
+/// Clones share the same state, so one clone can be kept by a server (to
+/// cancel an abandoned tile request), while another is given to an
+/// encoder with `with_cancel`.  Encoding loops check the token, and return
+/// [Error::Cancelled] promptly once it has been cancelled.
+///
+/// # Example
+/// ```
+/// # use mvt::{CancelToken, Error, GeomEncoder, GeomType};
+/// # use pointy::Transform;
+/// let token = CancelToken::new();
+/// let mut enc = GeomEncoder::new(GeomType::Linestring, Transform::default())
+///     .with_cancel(token.clone());
+/// assert!(enc.add_point(0.0, 0.0).is_ok());
+/// token.cancel();
+/// assert!(matches!(enc.add_point(1.0, 1.0), Err(Error::Cancelled())));
+/// ```
+///
+/// [Error::Cancelled]: enum.Error.html#variant.Cancelled
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancelToken {}
+
+impl CancelToken {
+    /// Create a new token (not cancelled).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel encoding using this token (or any of its clones).
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Check whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Return an error if an optional token has been cancelled
+pub(crate) fn check_cancel(cancel: Option<&CancelToken>) -> Result<()> {
+    match cancel {
+        Some(token) if token.is_cancelled() => Err(Error::Cancelled()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cancel_token() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert_eq!(token, clone);
+        assert_ne!(token, CancelToken::new());
+        assert!(check_cancel(None).is_ok());
+        assert!(check_cancel(Some(&token)).is_ok());
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(
+            check_cancel(Some(&token)),
+            Err(Error::Cancelled())
+        ));
+    }
+}
@@ -0,0 +1,283 @@
+// chunk.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Chunked tile serialization.
+//!
+use crate::cache::LAYER_KEY;
+use crate::error::Result;
+use crate::vector_tile::tile::Layer as VtLayer;
+use crate::vector_tile::Tile as VecTile;
+use protobuf::rt::WireType;
+use protobuf::{CodedOutputStream, Message};
+
+/// Position of the next piece to write
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Piece {
+    /// Layer field header, version and name
+    Header(usize),
+    /// One feature of a layer
+    Feature(usize, usize),
+    /// One key of a layer
+    Key(usize, usize),
+    /// One value of a layer
+    Value(usize, usize),
+    /// Layer extent and unknown fields
+    Trailer(usize),
+    /// Unknown fields of the tile
+    Unknown,
+    /// A pre-encoded layer
+    Encoded(usize),
+    /// All pieces written
+    Done,
+}
+
+/// Iterator of encoded tile chunks.
+///
+/// Encoding is done one piece at a time (a feature, key or value of a
+/// layer, or an encoded layer), and a chunk is returned as soon as it
+/// reaches the maximum length.  So a chunk can exceed that length by at most
+/// one piece.  The chunks concatenated are identical to [Tile::to_bytes].
+///
+/// Between chunks, the caller can yield to other tasks (e.g. an async
+/// runtime), so that encoding a huge tile does not block a worker.
+///
+/// Created with [Tile::chunks].
+///
+/// [Tile::chunks]: struct.Tile.html#method.chunks
+/// [Tile::to_bytes]: struct.Tile.html#method.to_bytes
+pub struct TileChunks<'a> {
+    vec_tile: &'a VecTile,
+    encoded: Vec<&'a [u8]>,
+    max_len: usize,
+    piece: Piece,
+}
+
+impl<'a> TileChunks<'a> {
+    /// Create a new tile chunk iterator
+    pub(crate) fn new(
+        vec_tile: &'a VecTile,
+        encoded: Vec<&'a [u8]>,
+        max_len: usize,
+    ) -> Self {
+        TileChunks {
+            vec_tile,
+            encoded,
+            max_len: max_len.max(1),
+            piece: Piece::Header(0),
+        }
+    }
+
+    /// Get a layer
+    fn layer(&self, l: usize) -> &'a VtLayer {
+        &self.vec_tile.layers[l]
+    }
+
+    /// Get the piece following a layer header
+    fn after_header(&self, l: usize) -> Piece {
+        let layer = self.layer(l);
+        if !layer.features.is_empty() {
+            Piece::Feature(l, 0)
+        } else {
+            self.after_features(l)
+        }
+    }
+
+    /// Get the piece following the features of a layer
+    fn after_features(&self, l: usize) -> Piece {
+        let layer = self.layer(l);
+        if !layer.keys.is_empty() {
+            Piece::Key(l, 0)
+        } else {
+            self.after_keys(l)
+        }
+    }
+
+    /// Get the piece following the keys of a layer
+    fn after_keys(&self, l: usize) -> Piece {
+        let layer = self.layer(l);
+        if !layer.values.is_empty() {
+            Piece::Value(l, 0)
+        } else {
+            Piece::Trailer(l)
+        }
+    }
+
+    /// Get the piece following a layer
+    fn after_layer(&self, l: usize) -> Piece {
+        if l + 1 < self.vec_tile.layers.len() {
+            Piece::Header(l + 1)
+        } else {
+            Piece::Unknown
+        }
+    }
+
+    /// Get the first piece of the pre-encoded layers
+    fn first_encoded(&self) -> Piece {
+        if self.encoded.is_empty() {
+            Piece::Done
+        } else {
+            Piece::Encoded(0)
+        }
+    }
+
+    /// Write the next piece, and advance to the following one
+    fn write_piece(&mut self, os: &mut CodedOutputStream) -> Result<()> {
+        self.piece = match self.piece {
+            Piece::Header(l) if l >= self.vec_tile.layers.len() => {
+                Piece::Unknown
+            }
+            Piece::Header(l) => {
+                let layer = self.layer(l);
+                os.write_raw_byte(LAYER_KEY)?;
+                os.write_raw_varint64(layer.compute_size())?;
+                if let Some(v) = layer.version {
+                    os.write_uint32(15, v)?;
+                }
+                if let Some(v) = layer.name.as_ref() {
+                    os.write_string(1, v)?;
+                }
+                self.after_header(l)
+            }
+            Piece::Feature(l, f) => {
+                let layer = self.layer(l);
+                os.write_tag(2, WireType::LengthDelimited)?;
+                layer.features[f].write_length_delimited_to(os)?;
+                if f + 1 < layer.features.len() {
+                    Piece::Feature(l, f + 1)
+                } else {
+                    self.after_features(l)
+                }
+            }
+            Piece::Key(l, k) => {
+                let layer = self.layer(l);
+                os.write_string(3, &layer.keys[k])?;
+                if k + 1 < layer.keys.len() {
+                    Piece::Key(l, k + 1)
+                } else {
+                    self.after_keys(l)
+                }
+            }
+            Piece::Value(l, v) => {
+                let layer = self.layer(l);
+                os.write_tag(4, WireType::LengthDelimited)?;
+                layer.values[v].write_length_delimited_to(os)?;
+                if v + 1 < layer.values.len() {
+                    Piece::Value(l, v + 1)
+                } else {
+                    Piece::Trailer(l)
+                }
+            }
+            Piece::Trailer(l) => {
+                let layer = self.layer(l);
+                if let Some(v) = layer.extent {
+                    os.write_uint32(5, v)?;
+                }
+                os.write_unknown_fields(layer.special_fields.unknown_fields())?;
+                self.after_layer(l)
+            }
+            Piece::Unknown => {
+                os.write_unknown_fields(
+                    self.vec_tile.special_fields.unknown_fields(),
+                )?;
+                self.first_encoded()
+            }
+            Piece::Encoded(e) => {
+                os.write_raw_bytes(self.encoded[e])?;
+                if e + 1 < self.encoded.len() {
+                    Piece::Encoded(e + 1)
+                } else {
+                    Piece::Done
+                }
+            }
+            Piece::Done => Piece::Done,
+        };
+        Ok(())
+    }
+
+    /// Encode pieces until the chunk reaches the maximum length
+    fn next_chunk(&mut self) -> Result<Vec<u8>> {
+        let mut chunk = Vec::new();
+        while self.piece != Piece::Done && chunk.len() < self.max_len {
+            let mut os = CodedOutputStream::vec(&mut chunk);
+            self.write_piece(&mut os)?;
+            os.flush()?;
+        }
+        Ok(chunk)
+    }
+}
+
+impl Iterator for TileChunks<'_> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.piece == Piece::Done {
+            return None;
+        }
+        let chunk = self.next_chunk();
+        if chunk.is_err() {
+            self.piece = Piece::Done;
+        }
+        match chunk {
+            Ok(chunk) if chunk.is_empty() => None,
+            chunk => Some(chunk),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    fn make_tile() -> Tile {
+        let mut tile = Tile::new(4096);
+        for name in ["water", "roads"] {
+            let mut layer = tile.create_layer(name);
+            for i in 0..50 {
+                let geom =
+                    GeomEncoder::new(GeomType::Point, Transform::default())
+                        .point(f64::from(i), f64::from(i * 2))
+                        .unwrap()
+                        .encode()
+                        .unwrap();
+                let mut feature = layer.into_feature(geom);
+                feature.set_id(i as u64);
+                feature.add_tag_string("name", &format!("{name} {i}"));
+                feature.add_tag_uint("rank", i as u64 % 7);
+                layer = feature.into_layer();
+            }
+            tile.add_layer(layer).unwrap();
+        }
+        let mut other = Tile::new(4096);
+        other.add_layer(other.create_layer("empty")).unwrap();
+        let empty = other.layer_to_bytes("empty").unwrap().unwrap();
+        tile.add_encoded_layer(&empty).unwrap();
+        tile
+    }
+
+    #[test]
+    fn test_chunks() {
+        let tile = make_tile();
+        let data = tile.to_bytes().unwrap();
+        for max_len in [0, 1, 16, 100, 1000, data.len(), data.len() * 2] {
+            let chunks: Vec<Vec<u8>> =
+                tile.chunks(max_len).collect::<Result<_, _>>().unwrap();
+            assert_eq!(chunks.concat(), data);
+            assert!(chunks.iter().all(|c| !c.is_empty()));
+            if max_len >= data.len() {
+                assert_eq!(chunks.len(), 1);
+            }
+            if max_len == 100 {
+                assert!(chunks.len() >= data.len() / 200);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunks_empty() {
+        let tile = Tile::new(4096);
+        assert_eq!(tile.chunks(16).count(), 0);
+    }
+}
@@ -0,0 +1,344 @@
+// clip.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Polygon clipping.
+//!
+use pointy::Float;
+
+/// Axis-aligned clip rectangle
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Rect<F>
+where
+    F: Float,
+{
+    x_min: F,
+    y_min: F,
+    x_max: F,
+    y_max: F,
+}
+
+/// Edge of a clip rectangle
+#[derive(Clone, Copy, Debug)]
+enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Edge {
+    /// Check if a point is inside the edge
+    fn inside<F: Float>(self, r: &Rect<F>, p: (F, F)) -> bool {
+        match self {
+            Edge::Left => p.0 >= r.x_min,
+            Edge::Right => p.0 <= r.x_max,
+            Edge::Top => p.1 >= r.y_min,
+            Edge::Bottom => p.1 <= r.y_max,
+        }
+    }
+
+    /// Find intersection of a segment with the edge
+    fn intersect<F: Float>(self, r: &Rect<F>, a: (F, F), b: (F, F)) -> (F, F) {
+        match self {
+            Edge::Left => (r.x_min, lerp_y(a, b, r.x_min)),
+            Edge::Right => (r.x_max, lerp_y(a, b, r.x_max)),
+            Edge::Top => (lerp_x(a, b, r.y_min), r.y_min),
+            Edge::Bottom => (lerp_x(a, b, r.y_max), r.y_max),
+        }
+    }
+}
+
+/// Find Y of a segment at given X.
+///
+/// The result is clamped to the segment's Y range, so that rounding error
+/// on nearly parallel segments cannot produce points far outside it.
+pub(crate) fn lerp_y<F: Float>(a: (F, F), b: (F, F), x: F) -> F {
+    let y = a.1 + (b.1 - a.1) * (x - a.0) / (b.0 - a.0);
+    y.max(a.1.min(b.1)).min(a.1.max(b.1))
+}
+
+/// Find X of a segment at given Y (clamped to the segment's X range)
+pub(crate) fn lerp_x<F: Float>(a: (F, F), b: (F, F), y: F) -> F {
+    let x = a.0 + (b.0 - a.0) * (y - a.1) / (b.1 - a.1);
+    x.max(a.0.min(b.0)).min(a.0.max(b.0))
+}
+
+impl<F> Rect<F>
+where
+    F: Float,
+{
+    /// Create a new clip rectangle
+    pub(crate) fn new(x_min: F, y_min: F, x_max: F, y_max: F) -> Self {
+        Rect {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+        }
+    }
+
+    /// Check if a point is inside the rectangle
+    pub(crate) fn contains(&self, p: (F, F)) -> bool {
+        p.0 >= self.x_min
+            && p.0 <= self.x_max
+            && p.1 >= self.y_min
+            && p.1 <= self.y_max
+    }
+
+    /// Check if the rectangle overlaps another
+    pub(crate) fn overlaps(&self, other: &Self) -> bool {
+        self.x_min <= other.x_max
+            && other.x_min <= self.x_max
+            && self.y_min <= other.y_max
+            && other.y_min <= self.y_max
+    }
+
+    /// Get the bounding rectangle of a segment
+    pub(crate) fn of_segment(a: (F, F), b: (F, F)) -> Self {
+        Rect::new(a.0.min(b.0), a.1.min(b.1), a.0.max(b.0), a.1.max(b.1))
+    }
+
+    /// Get the center point
+    pub(crate) fn center(&self) -> (F, F) {
+        let two = F::one() + F::one();
+        (
+            (self.x_min + self.x_max) / two,
+            (self.y_min + self.y_max) / two,
+        )
+    }
+
+    /// Get the rectangle as a ring
+    pub(crate) fn ring(&self) -> Vec<(F, F)> {
+        vec![
+            (self.x_min, self.y_min),
+            (self.x_max, self.y_min),
+            (self.x_max, self.y_max),
+            (self.x_min, self.y_max),
+        ]
+    }
+
+    /// Get the position of a point along the edges.
+    ///
+    /// Positions increase counter-clockwise from the minimum corner.  The
+    /// point is projected to the nearest edge.
+    pub(crate) fn perimeter(&self, p: (F, F)) -> F {
+        let (w, h) = (self.x_max - self.x_min, self.y_max - self.y_min);
+        let x = p.0.max(self.x_min).min(self.x_max);
+        let y = p.1.max(self.y_min).min(self.y_max);
+        let dist = [
+            y - self.y_min,
+            self.x_max - x,
+            self.y_max - y,
+            x - self.x_min,
+        ];
+        let mut edge = 0;
+        for (i, d) in dist.iter().enumerate() {
+            if *d < dist[edge] {
+                edge = i;
+            }
+        }
+        match edge {
+            0 => x - self.x_min,
+            1 => w + y - self.y_min,
+            2 => w + h + self.x_max - x,
+            _ => w + w + h + self.y_max - y,
+        }
+    }
+
+    /// Get the corners, with their positions along the edges
+    pub(crate) fn corners(&self) -> [(F, (F, F)); 4] {
+        let (w, h) = (self.x_max - self.x_min, self.y_max - self.y_min);
+        [
+            (F::zero(), (self.x_min, self.y_min)),
+            (w, (self.x_max, self.y_min)),
+            (w + h, (self.x_max, self.y_max)),
+            (w + w + h, (self.x_min, self.y_max)),
+        ]
+    }
+
+    /// Get the distance walking along the edges between two positions
+    pub(crate) fn walk(&self, from: F, to: F, ccw: bool) -> F {
+        let (w, h) = (self.x_max - self.x_min, self.y_max - self.y_min);
+        let d = if ccw { to - from } else { from - to };
+        if d < F::zero() {
+            d + w + w + h + h
+        } else {
+            d
+        }
+    }
+
+    /// Clip a segment to the rectangle (Liang-Barsky).
+    ///
+    /// Returns the clipped segment, or `None` if it is outside.
+    pub(crate) fn clip_segment(
+        &self,
+        a: (F, F),
+        b: (F, F),
+    ) -> Option<((F, F), (F, F))> {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let mut t0 = F::zero();
+        let mut t1 = F::one();
+        for (p, q) in [
+            (-dx, a.0 - self.x_min),
+            (dx, self.x_max - a.0),
+            (-dy, a.1 - self.y_min),
+            (dy, self.y_max - a.1),
+        ] {
+            if p == F::zero() {
+                if q < F::zero() {
+                    return None;
+                }
+            } else {
+                let r = q / p;
+                if p < F::zero() {
+                    t0 = t0.max(r);
+                } else {
+                    t1 = t1.min(r);
+                }
+            }
+        }
+        if t0 > t1 {
+            return None;
+        }
+        let at = |t: F| {
+            if t == F::zero() {
+                a
+            } else if t == F::one() {
+                b
+            } else {
+                (a.0 + dx * t, a.1 + dy * t)
+            }
+        };
+        Some((at(t0), at(t1)))
+    }
+
+    /// Clip a linestring to the rectangle.
+    ///
+    /// Returns the pieces of the linestring inside the rectangle.
+    pub(crate) fn clip_line(&self, line: &[(F, F)]) -> Vec<Vec<(F, F)>> {
+        let mut pieces: Vec<Vec<(F, F)>> = vec![];
+        let mut joined = false;
+        for seg in line.windows(2) {
+            match self.clip_segment(seg[0], seg[1]) {
+                Some((a, b)) => {
+                    match pieces.last_mut() {
+                        Some(piece) if joined => piece.push(b),
+                        _ => pieces.push(vec![a, b]),
+                    }
+                    joined = b == seg[1];
+                }
+                None => joined = false,
+            }
+        }
+        pieces
+    }
+
+    /// Clip a polygon ring to the rectangle (Sutherland-Hodgman).
+    ///
+    /// The ring is implicitly closed; a repeated closing vertex is ignored.
+    /// Returns an empty ring if fewer than 3 vertices remain.  Parts of the
+    /// ring outside the rectangle are replaced by segments along its edges,
+    /// which is correct for filling.
+    pub(crate) fn clip_ring(&self, ring: &[(F, F)]) -> Vec<(F, F)> {
+        let ring = match ring.split_last() {
+            Some((last, rest)) if rest.first() == Some(last) => rest,
+            _ => ring,
+        };
+        if ring.iter().all(|p| self.contains(*p)) {
+            return if ring.len() >= 3 {
+                ring.to_vec()
+            } else {
+                vec![]
+            };
+        }
+        let mut pts = ring.to_vec();
+        for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+            let mut out = Vec::with_capacity(pts.len() + 4);
+            let mut prev = match pts.last() {
+                Some(p) => *p,
+                None => break,
+            };
+            for cur in pts.iter().copied() {
+                let (pin, cin) =
+                    (edge.inside(self, prev), edge.inside(self, cur));
+                if cin {
+                    if !pin {
+                        out.push(edge.intersect(self, prev, cur));
+                    }
+                    out.push(cur);
+                } else if pin {
+                    out.push(edge.intersect(self, prev, cur));
+                }
+                prev = cur;
+            }
+            pts = out;
+        }
+        if pts.len() >= 3 {
+            pts
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clip_ring() {
+        let r = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let ring = [(5.0, 5.0), (15.0, 5.0), (15.0, 15.0), (5.0, 15.0)];
+        let clipped = r.clip_ring(&ring);
+        assert_eq!(
+            clipped,
+            vec![(5.0, 10.0), (5.0, 5.0), (10.0, 5.0), (10.0, 10.0)]
+        );
+        let inside = [(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 1.0)];
+        assert_eq!(r.clip_ring(&inside).len(), 3);
+        let outside = [(11.0, 11.0), (12.0, 11.0), (12.0, 12.0)];
+        assert!(r.clip_ring(&outside).is_empty());
+        let covering = [(-1.0, -1.0), (11.0, -1.0), (11.0, 11.0), (-1.0, 11.0)];
+        assert_eq!(r.clip_ring(&covering).len(), 4);
+    }
+
+    #[test]
+    fn test_clip_line() {
+        let r = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let line = [(-5.0, 5.0), (5.0, 5.0), (5.0, 15.0), (8.0, 15.0)];
+        assert_eq!(
+            r.clip_line(&line),
+            vec![vec![(0.0, 5.0), (5.0, 5.0), (5.0, 10.0)]]
+        );
+        let line = [(5.0, 5.0), (15.0, 5.0), (15.0, 8.0), (5.0, 8.0)];
+        assert_eq!(
+            r.clip_line(&line),
+            vec![vec![(5.0, 5.0), (10.0, 5.0)], vec![(10.0, 8.0), (5.0, 8.0)]]
+        );
+        assert!(r.clip_line(&[(11.0, 0.0), (20.0, 0.0)]).is_empty());
+        assert!(r.clip_line(&[(5.0, 5.0)]).is_empty());
+    }
+
+    #[test]
+    fn test_lerp_clamped() {
+        let (a, b) = ((0.0, 1.0), (1e-300, 2.0));
+        assert_eq!(lerp_y(a, b, 1.0), 2.0);
+        assert_eq!(lerp_x(a, b, 3.0), 1e-300);
+    }
+
+    #[test]
+    fn test_clip_segment() {
+        let r = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(
+            r.clip_segment((-5.0, 5.0), (15.0, 5.0)),
+            Some(((0.0, 5.0), (10.0, 5.0)))
+        );
+        assert_eq!(
+            r.clip_segment((1.0, 1.0), (2.0, 3.0)),
+            Some(((1.0, 1.0), (2.0, 3.0)))
+        );
+        assert_eq!(r.clip_segment((-5.0, -1.0), (15.0, -1.0)), None);
+        assert_eq!(r.clip_segment((-5.0, 6.0), (6.0, 17.0)), None);
+    }
+}
@@ -0,0 +1,399 @@
+// contour.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Contour layer generation from elevation grids.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use crate::tile::{Layer, Tile};
+use pointy::Transform;
+use std::collections::BTreeMap;
+
+/// Generator for a contour layer, from an elevation grid (DEM).
+///
+/// The grid is a row-major array of elevation samples, with the first and
+/// last rows / columns on the tile edges.  Contour lines are traced with
+/// marching squares; cells with a `NaN` sample are skipped.
+///
+/// The layer has one multi-linestring feature per contour level, tagged with
+/// the level (`elevation` by default).  Closed contours repeat their first
+/// vertex.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{ContourLayer, Tile};
+///
+/// // a 3x3 grid with a peak in the center
+/// let grid = [0.0, 0.0, 0.0, 0.0, 25.0, 0.0, 0.0, 0.0, 0.0];
+/// let mut tile = Tile::new(4096);
+/// ContourLayer::new(10.0).add_to(&mut tile, &grid, 3)?;
+/// // levels 10 and 20
+/// assert_eq!(tile.layers()?[0].num_features(), 2);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContourLayer {
+    /// Layer name
+    name: String,
+
+    /// Tag key for contour level
+    key: String,
+
+    /// Interval between levels
+    interval: f64,
+
+    /// Base level
+    base: f64,
+}
+
+/// Grid edge crossed by a contour, as (horizontal, column, row)
+type Edge = (bool, usize, usize);
+
+/// Elevation grid
+struct Grid<'a> {
+    /// Samples (row-major)
+    samples: &'a [f64],
+
+    /// Number of columns
+    width: usize,
+
+    /// Number of rows
+    height: usize,
+}
+
+impl<'a> Grid<'a> {
+    /// Create a grid, checking dimensions
+    fn new(samples: &'a [f64], width: usize) -> Result<Self> {
+        if width < 2 || !samples.len().is_multiple_of(width) {
+            return Err(Error::InvalidGrid());
+        }
+        let height = samples.len() / width;
+        if height < 2 {
+            return Err(Error::InvalidGrid());
+        }
+        Ok(Grid {
+            samples,
+            width,
+            height,
+        })
+    }
+
+    /// Get one sample
+    fn sample(&self, col: usize, row: usize) -> f64 {
+        self.samples[row * self.width + col]
+    }
+
+    /// Get the point where a contour level crosses an edge
+    fn crossing(&self, (horiz, col, row): Edge, level: f64) -> (f64, f64) {
+        let (c1, r1) = if horiz {
+            (col + 1, row)
+        } else {
+            (col, row + 1)
+        };
+        let (a, b) = (self.sample(col, row), self.sample(c1, r1));
+        let t = (level - a) / (b - a);
+        if horiz {
+            (col as f64 + t, row as f64)
+        } else {
+            (col as f64, row as f64 + t)
+        }
+    }
+
+    /// Find segments of a contour level, as pairs of crossed edges
+    fn segments(&self, level: f64) -> Vec<(Edge, Edge)> {
+        let mut segments = vec![];
+        for row in 0..self.height - 1 {
+            for col in 0..self.width - 1 {
+                let tl = self.sample(col, row);
+                let tr = self.sample(col + 1, row);
+                let br = self.sample(col + 1, row + 1);
+                let bl = self.sample(col, row + 1);
+                let corners = [tl, tr, br, bl];
+                if corners.iter().any(|v| v.is_nan()) {
+                    continue;
+                }
+                let case = corners
+                    .iter()
+                    .fold(0, |case, v| case << 1 | u8::from(*v >= level));
+                let t = (true, col, row);
+                let b = (true, col, row + 1);
+                let l = (false, col, row);
+                let r = (false, col + 1, row);
+                // saddles are resolved by the cell center
+                let center = corners.iter().sum::<f64>() / 4.0 >= level;
+                match case {
+                    0b0001 | 0b1110 => segments.push((l, b)),
+                    0b0010 | 0b1101 => segments.push((b, r)),
+                    0b0011 | 0b1100 => segments.push((l, r)),
+                    0b0100 | 0b1011 => segments.push((t, r)),
+                    0b0110 | 0b1001 => segments.push((t, b)),
+                    0b0111 | 0b1000 => segments.push((l, t)),
+                    0b0101 if center => segments.extend([(l, t), (b, r)]),
+                    0b1010 if center => segments.extend([(t, r), (l, b)]),
+                    0b0101 => segments.extend([(t, r), (l, b)]),
+                    0b1010 => segments.extend([(l, t), (b, r)]),
+                    _ => (),
+                }
+            }
+        }
+        segments
+    }
+
+    /// Trace the lines of a contour level, in grid coördinates
+    fn lines(&self, level: f64) -> Vec<Vec<(f64, f64)>> {
+        let segments = self.segments(level);
+        let mut edges: BTreeMap<Edge, Vec<usize>> = BTreeMap::new();
+        for (i, (a, b)) in segments.iter().enumerate() {
+            edges.entry(*a).or_default().push(i);
+            edges.entry(*b).or_default().push(i);
+        }
+        let mut used = vec![false; segments.len()];
+        // open lines start at an edge crossed only once
+        let starts = edges
+            .iter()
+            .filter(|(_, segs)| segs.len() == 1)
+            .map(|(edge, segs)| (*edge, segs[0]))
+            .chain(segments.iter().enumerate().map(|(i, (a, _))| (*a, i)))
+            .collect::<Vec<_>>();
+        let mut lines = vec![];
+        for (start, seg) in starts {
+            if used[seg] {
+                continue;
+            }
+            let mut line = vec![self.crossing(start, level)];
+            let (mut edge, mut seg) = (start, seg);
+            loop {
+                used[seg] = true;
+                let (a, b) = segments[seg];
+                edge = if a == edge { b } else { a };
+                line.push(self.crossing(edge, level));
+                match edges[&edge].iter().find(|s| !used[**s]) {
+                    Some(s) => seg = *s,
+                    None => break,
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Get the range of (non-NaN) samples
+    fn range(&self) -> Option<(f64, f64)> {
+        self.samples
+            .iter()
+            .filter(|v| !v.is_nan())
+            .fold(None, |range, v| match range {
+                Some((lo, hi)) => Some((v.min(lo), v.max(hi))),
+                None => Some((*v, *v)),
+            })
+    }
+}
+
+impl ContourLayer {
+    /// Create a contour layer generator, named `contour`.
+    ///
+    /// * `interval` Interval between contour levels.
+    pub fn new(interval: f64) -> Self {
+        ContourLayer {
+            name: "contour".to_string(),
+            key: "elevation".to_string(),
+            interval,
+            base: 0.0,
+        }
+    }
+
+    /// Set the layer name.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Set the tag key for contour levels.
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = key.to_string();
+        self
+    }
+
+    /// Set the base level; levels are `base + n * interval`.
+    pub fn with_base(mut self, base: f64) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Get the contour levels within a range
+    fn levels(&self, (lo, hi): (f64, f64)) -> Result<Vec<f64>> {
+        if !(self.interval.is_finite() && self.interval > 0.0) {
+            return Err(Error::InvalidGrid());
+        }
+        let first = ((lo - self.base) / self.interval).ceil();
+        let last = ((hi - self.base) / self.interval).floor();
+        if !(first.is_finite() && last.is_finite()) {
+            return Err(Error::InvalidGrid());
+        }
+        let n = (last - first + 1.0).max(0.0) as usize;
+        Ok((0..n)
+            .map(|i| self.base + (first + i as f64) * self.interval)
+            .collect())
+    }
+
+    /// Trace contour lines of an elevation grid.
+    ///
+    /// * `grid` Elevation samples (row-major).
+    /// * `width` Number of columns in the grid.
+    ///
+    /// Returns lines for each level with any, in grid coördinates (column,
+    /// row).  Returns [Error::InvalidGrid] if the grid has fewer than two
+    /// rows or columns, or the interval is not positive.
+    ///
+    /// [Error::InvalidGrid]: enum.Error.html#variant.InvalidGrid
+    #[allow(clippy::type_complexity)]
+    pub fn lines(
+        &self,
+        grid: &[f64],
+        width: usize,
+    ) -> Result<Vec<(f64, Vec<Vec<(f64, f64)>>)>> {
+        let grid = Grid::new(grid, width)?;
+        let levels = match grid.range() {
+            Some(range) => self.levels(range)?,
+            None => vec![],
+        };
+        Ok(levels
+            .into_iter()
+            .map(|level| (level, grid.lines(level)))
+            .filter(|(_, lines)| !lines.is_empty())
+            .collect())
+    }
+
+    /// Encode contour lines, scaled from grid to tile coördinates
+    fn encode(lines: &[Vec<(f64, f64)>], sx: f64, sy: f64) -> Result<GeomData> {
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring, Transform::default());
+        for line in lines {
+            let mut pts: Vec<(f64, f64)> = line
+                .iter()
+                .map(|(x, y)| ((x * sx).round(), (y * sy).round()))
+                .collect();
+            pts.dedup();
+            if pts.len() < 2 {
+                continue;
+            }
+            for (x, y) in pts {
+                enc.add_point(x, y)?;
+            }
+            enc.complete_geom()?;
+        }
+        enc.encode()
+    }
+
+    /// Create a contour layer for a tile.
+    ///
+    /// * `tile` Tile to create the layer for.
+    /// * `grid` Elevation samples (row-major), covering the tile.
+    /// * `width` Number of columns in the grid.
+    pub fn layer(
+        &self,
+        tile: &Tile,
+        grid: &[f64],
+        width: usize,
+    ) -> Result<Layer> {
+        let extent = f64::from(tile.extent());
+        let height = grid.len() / width.max(1);
+        let sx = extent / (width.max(2) - 1) as f64;
+        let sy = extent / (height.max(2) - 1) as f64;
+        let mut layer = tile.create_layer(&self.name);
+        for (level, lines) in self.lines(grid, width)? {
+            let geom = Self::encode(&lines, sx, sy)?;
+            if geom.is_empty() {
+                continue;
+            }
+            let mut feature = layer.into_feature(geom);
+            feature.add_tag_double(&self.key, level);
+            layer = feature.into_layer();
+        }
+        Ok(layer)
+    }
+
+    /// Add a contour layer to a tile.
+    ///
+    /// * `tile` Tile to add the layer to.
+    /// * `grid` Elevation samples (row-major), covering the tile.
+    /// * `width` Number of columns in the grid.
+    pub fn add_to(
+        &self,
+        tile: &mut Tile,
+        grid: &[f64],
+        width: usize,
+    ) -> Result<()> {
+        let layer = self.layer(tile, grid, width)?;
+        tile.add_layer(layer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contour_lines() {
+        #[rustfmt::skip]
+        let grid = [
+            0.0, 0.0, 0.0, 0.0,
+            0.0, 20.0, 20.0, 0.0,
+            0.0, 0.0, 0.0, 0.0,
+        ];
+        let contour = ContourLayer::new(10.0).with_base(5.0);
+        let lines = contour.lines(&grid, 4).unwrap();
+        assert_eq!(lines.len(), 2);
+        let (level, rings) = &lines[0];
+        assert_eq!(*level, 5.0);
+        assert_eq!(rings.len(), 1);
+        let ring = &rings[0];
+        assert_eq!(ring.len(), 7);
+        assert_eq!(ring.first(), ring.last());
+        assert!(ring.contains(&(0.25, 1.0)));
+        assert!(ring.contains(&(2.75, 1.0)));
+        assert_eq!(lines[1].0, 15.0);
+        assert!(contour.lines(&grid, 5).is_err());
+        assert!(contour.lines(&grid[..4], 4).is_err());
+        assert!(ContourLayer::new(0.0).lines(&grid, 4).is_err());
+    }
+
+    #[test]
+    fn test_contour_open() {
+        // slope rising to the right, with a hole
+        #[rustfmt::skip]
+        let grid = [
+            0.0, 10.0, 20.0,
+            0.0, 10.0, 20.0,
+            0.0, f64::NAN, 20.0,
+        ];
+        let lines = ContourLayer::new(5.0).lines(&grid, 3).unwrap();
+        let levels: Vec<_> = lines.iter().map(|(level, _)| *level).collect();
+        assert_eq!(levels, [5.0, 10.0, 15.0, 20.0]);
+        assert_eq!(lines[0].1, [vec![(0.5, 0.0), (0.5, 1.0)]]);
+        assert_eq!(lines[2].1, [vec![(1.5, 0.0), (1.5, 1.0)]]);
+    }
+
+    #[test]
+    fn test_contour_layer() {
+        let grid = [0.0, 0.0, 0.0, 0.0, 30.0, 0.0, 0.0, 0.0, 0.0];
+        // level 30 collapses to a point, and is dropped
+        let mut tile = Tile::new(256);
+        ContourLayer::new(10.0)
+            .with_name("terrain")
+            .with_key("ele")
+            .add_to(&mut tile, &grid, 3)
+            .unwrap();
+        let layers = tile.layers().unwrap();
+        let layer = &layers[0];
+        assert_eq!(layer.name(), "terrain");
+        assert_eq!(layer.num_features(), 2);
+        let feature = layer.feature(0).unwrap().unwrap();
+        assert_eq!(feature.tag(layer, "ele").unwrap().as_f64(), Some(10.0));
+        let feature = layer.feature(1).unwrap().unwrap();
+        assert_eq!(feature.tag(layer, "ele").unwrap().as_f64(), Some(20.0));
+    }
+}
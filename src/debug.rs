@@ -0,0 +1,200 @@
+// debug.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Debug grid layer generation.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::Result;
+use crate::font::{text_geometry, text_width};
+use crate::mapgrid::TileId;
+use crate::tile::{Layer, Tile};
+use pointy::Transform;
+
+/// Generator for a debug layer, to visually check transforms and buffers.
+///
+/// The layer contains features tagged by `kind`:
+///
+/// * `outline` Tile boundary (linestring)
+/// * `buffer` Buffer boundary, if a buffer is set (linestring)
+/// * `grid` Coordinate grid lines (multi-linestring)
+/// * `label` Tile center, tagged with `tid` (`z/x/y`), `z`, `x` and `y`
+///   (point)
+/// * `text` Tile ID `z/x/y` rendered with a [stroke font], for clients
+///   without a symbol layer (multi-linestring)
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{DebugLayer, Tile, TileId};
+///
+/// let mut tile = Tile::new(4096);
+/// let tid = TileId::new(3, 5, 4)?;
+/// DebugLayer::new().with_buffer(64).add_to(&mut tile, tid)?;
+/// assert_eq!(tile.num_layers(), 1);
+/// # Ok(()) }
+/// ```
+///
+/// [stroke font]: fn.text_geometry.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DebugLayer {
+    /// Layer name
+    name: String,
+
+    /// Number of grid divisions on each axis
+    divisions: u32,
+
+    /// Buffer distance (tile units)
+    buffer: u32,
+}
+
+impl Default for DebugLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Encode a closed linestring around a square
+fn square(lo: f64, hi: f64) -> Result<GeomData> {
+    GeomEncoder::new(GeomType::Linestring, Transform::default())
+        .point(lo, lo)?
+        .point(hi, lo)?
+        .point(hi, hi)?
+        .point(lo, hi)?
+        .point(lo, lo)?
+        .encode()
+}
+
+impl DebugLayer {
+    /// Create a debug layer generator, named `debug` with an 8x8 grid.
+    pub fn new() -> Self {
+        DebugLayer {
+            name: "debug".to_string(),
+            divisions: 8,
+            buffer: 0,
+        }
+    }
+
+    /// Set the layer name.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Set the number of grid divisions on each axis.
+    ///
+    /// With 0 or 1, no grid feature is generated.
+    pub fn with_divisions(mut self, divisions: u32) -> Self {
+        self.divisions = divisions;
+        self
+    }
+
+    /// Set the buffer distance, to outline the buffered tile bounds.
+    pub fn with_buffer(mut self, buffer: u32) -> Self {
+        self.buffer = buffer;
+        self
+    }
+
+    /// Encode the grid lines
+    fn grid(&self, extent: f64) -> Result<GeomData> {
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring, Transform::default());
+        for i in 1..self.divisions {
+            let v = extent * f64::from(i) / f64::from(self.divisions);
+            enc = enc.point(v, 0.0)?.point(v, extent)?.complete()?;
+            enc = enc.point(0.0, v)?.point(extent, v)?.complete()?;
+        }
+        enc.encode()
+    }
+
+    /// Create a debug layer for a tile.
+    ///
+    /// * `tile` Tile to create the layer for.
+    /// * `tid` Tile ID, for the label feature.
+    pub fn layer(&self, tile: &Tile, tid: TileId) -> Result<Layer> {
+        let extent = f64::from(tile.extent());
+        let layer = tile.create_layer(&self.name);
+        let mut feature = layer.into_feature(square(0.0, extent)?);
+        feature.add_tag_string("kind", "outline");
+        let mut layer = feature.into_layer();
+        if self.buffer > 0 {
+            let b = f64::from(self.buffer);
+            let mut feature = layer.into_feature(square(-b, extent + b)?);
+            feature.add_tag_string("kind", "buffer");
+            layer = feature.into_layer();
+        }
+        if self.divisions > 1 {
+            let mut feature = layer.into_feature(self.grid(extent)?);
+            feature.add_tag_string("kind", "grid");
+            layer = feature.into_layer();
+        }
+        let center = extent / 2.0;
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(center, center)?
+            .encode()?;
+        let mut feature = layer.into_feature(geom);
+        feature
+            .add_tag_string("kind", "label")
+            .add_tag_string("tid", &tid.to_string())
+            .add_tag_uint("z", tid.z().into())
+            .add_tag_uint("x", tid.x().into())
+            .add_tag_uint("y", tid.y().into());
+        let text = tid.to_string();
+        let height = extent / 16.0;
+        let x = center - text_width(&text, height) / 2.0;
+        let geom = text_geometry(&text, x, center + height, height)?;
+        let mut feature = feature.into_layer().into_feature(geom);
+        feature.add_tag_string("kind", "text");
+        Ok(feature.into_layer())
+    }
+
+    /// Add a debug layer to a tile.
+    ///
+    /// * `tile` Tile to add the layer to.
+    /// * `tid` Tile ID, for the label feature.
+    pub fn add_to(&self, tile: &mut Tile, tid: TileId) -> Result<()> {
+        let layer = self.layer(tile, tid)?;
+        tile.add_layer(layer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_debug_layer() {
+        let tid = TileId::new(3, 5, 4).unwrap();
+        let mut tile = Tile::new(256);
+        DebugLayer::new()
+            .with_divisions(2)
+            .with_buffer(16)
+            .add_to(&mut tile, tid)
+            .unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let vt = &tile.vt_layers()[0];
+        assert_eq!(vt.name(), "debug");
+        assert_eq!(vt.features.len(), 5);
+        assert_eq!(
+            vt.features[0].geometry,
+            vec![9, 0, 0, 34, 512, 0, 0, 512, 511, 0, 0, 511]
+        );
+        assert_eq!(vt.features[1].geometry[..3], [9, 31, 31]);
+        assert_eq!(
+            vt.features[2].geometry,
+            vec![9, 256, 0, 10, 0, 512, 9, 255, 255, 10, 512, 0]
+        );
+        assert_eq!(vt.features[3].geometry, vec![9, 256, 256]);
+        assert_eq!(vt.features[3].tags.len(), 10);
+        assert!(!vt.features[4].geometry.is_empty());
+        assert!(vt.values.iter().any(|v| v.string_value() == "4/3/5"));
+        let mut tile = Tile::new(256);
+        DebugLayer::new()
+            .with_name("dbg")
+            .with_divisions(0)
+            .add_to(&mut tile, tid)
+            .unwrap();
+        assert_eq!(tile.vt_layers()[0].features.len(), 3);
+    }
+}
@@ -0,0 +1,194 @@
+// dedup.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Float value deduplication.
+//!
+use crate::vector_tile::tile::Value;
+
+/// Comparison of float / double tag values for layer value deduplication.
+///
+/// # Example
+/// ```
+/// use mvt::{FloatDedup, Tile};
+///
+/// let tile = Tile::new(4096);
+/// let layer = tile
+///     .create_layer("First Layer")
+///     .with_float_dedup(FloatDedup::Canonical);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum FloatDedup {
+    /// Values are stored as added, and only exactly equal values are shared.
+    #[default]
+    Exact,
+
+    /// Values are canonicalized before comparison:
+    /// * doubles which are exactly representable as floats are stored as
+    ///   floats (`1.0` double and `1.0` float share an entry)
+    /// * negative zero is stored as zero
+    /// * all NaN values share one entry
+    Canonical,
+
+    /// Values are canonicalized, and any value within the given tolerance of
+    /// an existing value shares its entry.
+    Tolerance(f64),
+}
+
+/// Get a float / double value as f64
+fn float_value(value: &Value) -> Option<f64> {
+    value.float_value.map(f64::from).or(value.double_value)
+}
+
+/// Canonicalize a double value
+fn canonical_double(v: f64) -> Value {
+    let mut value = Value::new();
+    if v.is_nan() {
+        value.set_float_value(f32::NAN);
+    } else if v == 0.0 {
+        value.set_float_value(0.0);
+    } else if f64::from(v as f32) == v {
+        value.set_float_value(v as f32);
+    } else {
+        value.set_double_value(v);
+    }
+    value
+}
+
+impl FloatDedup {
+    /// Canonicalize a value before adding it to a layer.
+    pub(crate) fn canonical(self, value: Value) -> Value {
+        if self == FloatDedup::Exact {
+            return value;
+        }
+        match float_value(&value) {
+            Some(v) => canonical_double(v),
+            None => value,
+        }
+    }
+
+    /// Check if a (canonical) value matches an existing layer value.
+    pub(crate) fn matches(self, existing: &Value, value: &Value) -> bool {
+        let floats = (float_value(existing), float_value(value));
+        match (self, floats) {
+            (FloatDedup::Exact, _) => existing == value,
+            (FloatDedup::Canonical, (Some(a), Some(b))) => {
+                a.to_bits() == b.to_bits()
+            }
+            (FloatDedup::Tolerance(tol), (Some(a), Some(b))) => {
+                (a.is_nan() && b.is_nan()) || (a - b).abs() <= tol
+            }
+            _ => existing == value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    fn num_values(dedup: FloatDedup) -> usize {
+        let tile = Tile::new(4096);
+        let mut layer = tile.create_layer("Layer").with_float_dedup(dedup);
+        for i in 0..2 {
+            let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(0.0, 0.0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.add_tag_double("a", 1.0);
+            feature.add_tag_float("b", 1.0);
+            feature.add_tag_double("c", -0.0);
+            feature.add_tag_double("d", 0.0);
+            feature.add_tag_double("e", f64::NAN);
+            feature.add_tag_float("f", f32::NAN);
+            feature.add_tag_double("g", 0.1 + 1e-12 * f64::from(i));
+            layer = feature.into_layer();
+        }
+        layer.into_vt().values.len()
+    }
+
+    #[test]
+    fn test_canonical() {
+        let v = FloatDedup::Canonical.canonical(canonical_double(-0.0));
+        assert_eq!(v.float_value, Some(0.0));
+        assert!(v.float_value.unwrap().is_sign_positive());
+        let v = FloatDedup::Canonical.canonical(canonical_double(0.1));
+        assert_eq!(v.double_value, Some(0.1));
+        let mut s = Value::new();
+        s.set_string_value("1.0".to_string());
+        assert_eq!(FloatDedup::Canonical.canonical(s.clone()), s);
+    }
+
+    fn canonical(v: Value) -> Value {
+        FloatDedup::Canonical.canonical(v)
+    }
+
+    fn double(v: f64) -> Value {
+        let mut value = Value::new();
+        value.set_double_value(v);
+        value
+    }
+
+    fn float(v: f32) -> Value {
+        let mut value = Value::new();
+        value.set_float_value(v);
+        value
+    }
+
+    #[test]
+    fn test_canonical_zero() {
+        assert_eq!(canonical(double(-0.0)), canonical(double(0.0)));
+        assert_eq!(canonical(float(-0.0)), canonical(double(0.0)));
+        assert!(FloatDedup::Canonical
+            .matches(&canonical(float(0.0)), &canonical(double(-0.0))));
+    }
+
+    #[test]
+    fn test_canonical_nan() {
+        let a = f64::from_bits(0x7FF8_0000_0000_0001);
+        let b = f64::from_bits(0xFFF8_0000_DEAD_BEEF);
+        let c = f32::from_bits(0x7FC0_1234);
+        let v = canonical(double(a));
+        assert_eq!(v.float_value.unwrap().to_bits(), f32::NAN.to_bits());
+        assert_eq!(
+            canonical(double(b)).float_value.unwrap().to_bits(),
+            f32::NAN.to_bits()
+        );
+        assert_eq!(
+            canonical(float(c)).float_value.unwrap().to_bits(),
+            f32::NAN.to_bits()
+        );
+        assert!(FloatDedup::Canonical.matches(&v, &canonical(float(c))));
+        assert!(!FloatDedup::Exact.matches(&double(a), &double(b)));
+    }
+
+    #[test]
+    fn test_canonical_float_double() {
+        assert_eq!(canonical(double(1.0)), canonical(float(1.0)));
+        assert_eq!(canonical(double(1.0)), float(1.0));
+        assert_eq!(canonical(double(0.5)), float(0.5));
+        // not representable as float
+        assert_eq!(canonical(double(0.1)), double(0.1));
+        assert_ne!(canonical(double(0.1)), canonical(float(0.1)));
+        assert!(!FloatDedup::Exact.matches(&double(1.0), &float(1.0)));
+        // integer values are not floats, so they are never merged
+        let mut i = Value::new();
+        i.set_int_value(1);
+        assert_eq!(canonical(i.clone()), i);
+        assert!(!FloatDedup::Canonical.matches(&canonical(double(1.0)), &i));
+    }
+
+    #[test]
+    fn test_dedup() {
+        // 1.0 (x2), 0.0 (x2), NaN (x4), 0.1, 0.1 + 1e-12
+        assert_eq!(num_values(FloatDedup::Exact), 9);
+        assert_eq!(num_values(FloatDedup::Canonical), 5);
+        assert_eq!(num_values(FloatDedup::Tolerance(1e-9)), 4);
+    }
+}
@@ -0,0 +1,465 @@
+// delta.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Experimental delta encoding between tile versions.
+//!
+use crate::error::{Error, Result};
+use crate::metadata::{metadata_feature, split_metadata};
+use crate::tile::{Layer, Tile};
+use crate::vector_tile::tile::{
+    Feature as VtFeature, GeomType as VtGeomType, Layer as VtLayer, Value,
+};
+use crate::vector_tile::Tile as VecTile;
+use protobuf::{CodedOutputStream, Message};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+/// Changes between two versions of a [Tile] (experimental).
+///
+/// Only added, changed and removed features are included, matched by feature
+/// ID.  Layers containing features without unique IDs are replaced in full.
+///
+/// The delta is encoded as a vector tile, where each layer holds the changes
+/// to the layer of the same name:
+/// * A feature with a geometry replaces the feature with the same ID, or is
+///   appended if no such feature exists.
+/// * A feature with `UNKNOWN` type, empty geometry and an ID removes the
+///   feature with that ID.
+/// * A feature with `UNKNOWN` type, empty geometry and no ID clears the layer.
+///
+/// A changed [metadata feature] is included first, replacing the layer's
+/// metadata; it is never treated as a removal marker.  Layers with removed
+/// metadata are replaced in full.
+///
+/// Unchanged layers are omitted, and empty layers are treated as absent.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, GeomEncoder, GeomType, TileDelta, Tile};
+/// # use pointy::Transform;
+/// # fn main() -> Result<(), Error> {
+/// let old = Tile::new(4096);
+/// let mut new = Tile::new(4096);
+/// let layer = new.create_layer("First Layer");
+/// let geom_data = GeomEncoder::new(GeomType::Point, Transform::default())
+///     .point(1.0, 2.0)?
+///     .encode()?;
+/// let mut feature = layer.into_feature(geom_data);
+/// feature.set_id(1);
+/// new.add_layer(feature.into_layer())?;
+/// let delta = TileDelta::diff(&old, &new)?;
+/// let data = delta.to_bytes()?;
+/// let delta = TileDelta::from_bytes(&data)?;
+/// let tile = delta.apply(&old)?;
+/// assert_eq!(tile.num_layers(), 1);
+/// # Ok(()) }
+/// ```
+///
+/// [metadata feature]: struct.Layer.html#method.with_metadata
+#[derive(Clone, Debug, Default)]
+pub struct TileDelta {
+    vec_tile: VecTile,
+}
+
+/// Create a removal marker feature
+fn marker(id: Option<u64>) -> VtFeature {
+    let mut feature = VtFeature::new();
+    feature.id = id;
+    feature.set_type(VtGeomType::UNKNOWN);
+    feature
+}
+
+/// Check if a feature is a removal marker
+fn is_marker(feature: &VtFeature) -> bool {
+    feature.type_() == VtGeomType::UNKNOWN && feature.geometry.is_empty()
+}
+
+/// Check if all features (except metadata) have unique IDs
+fn is_trackable(features: &[VtFeature]) -> bool {
+    let mut ids = HashSet::new();
+    features
+        .iter()
+        .all(|f| f.id.is_some_and(|id| ids.insert(id)))
+}
+
+/// Resolve the tags of a feature to key / value pairs
+fn tags<'a>(
+    layer: &'a VtLayer,
+    feature: &VtFeature,
+) -> Vec<(Option<&'a String>, Option<&'a Value>)> {
+    feature
+        .tags
+        .chunks_exact(2)
+        .map(|t| {
+            (
+                layer.keys.get(t[0] as usize),
+                layer.values.get(t[1] as usize),
+            )
+        })
+        .collect()
+}
+
+/// Check if two features are equivalent
+fn same_feature(
+    la: &VtLayer,
+    fa: &VtFeature,
+    lb: &VtLayer,
+    fb: &VtFeature,
+) -> bool {
+    fa.id == fb.id
+        && fa.type_() == fb.type_()
+        && fa.geometry == fb.geometry
+        && tags(la, fa) == tags(lb, fb)
+}
+
+/// Check if two layers are equivalent
+fn same_layer(a: &VtLayer, b: &VtLayer) -> bool {
+    a.features.len() == b.features.len()
+        && a.features
+            .iter()
+            .zip(&b.features)
+            .all(|(fa, fb)| same_feature(a, fa, b, fb))
+}
+
+/// Copy all features of a layer, including metadata
+fn copy_layer(mut layer: Layer, src: &VtLayer) -> Layer {
+    layer = layer.copy_metadata(src);
+    for f in split_metadata(src).1 {
+        layer = layer.copy_feature(src, f, f.geometry.clone());
+    }
+    layer
+}
+
+/// Make a delta layer between two versions of a layer
+fn diff_layer(old: &VtLayer, new: &VtLayer, extent: u32) -> Option<VtLayer> {
+    if same_layer(old, new) {
+        return None;
+    }
+    let (old_meta, old_features) = split_metadata(old);
+    let (new_meta, new_features) = split_metadata(new);
+    let mut layer = Layer::new(new.name(), extent);
+    // removed metadata has no marker, so the layer is replaced
+    if !is_trackable(old_features)
+        || !is_trackable(new_features)
+        || (old_meta.is_some() && new_meta.is_none())
+    {
+        layer.push_feature(marker(None));
+        return Some(copy_layer(layer, new).into_vt());
+    }
+    let same_meta = match (old_meta, new_meta) {
+        (Some(fold), Some(fnew)) => same_feature(old, fold, new, fnew),
+        _ => new_meta.is_none(),
+    };
+    if !same_meta {
+        layer = layer.copy_metadata(new);
+    }
+    // trackable layers have unique IDs
+    let old_ids: HashMap<Option<u64>, &VtFeature> =
+        old_features.iter().map(|f| (f.id, f)).collect();
+    let new_ids: HashSet<Option<u64>> =
+        new_features.iter().map(|f| f.id).collect();
+    for fold in old_features {
+        if !new_ids.contains(&fold.id) {
+            layer.push_feature(marker(fold.id));
+        }
+    }
+    for fnew in new_features {
+        let fold = old_ids.get(&fnew.id);
+        if !fold.is_some_and(|fold| same_feature(old, fold, new, fnew)) {
+            layer = layer.copy_feature(new, fnew, fnew.geometry.clone());
+        }
+    }
+    if layer.num_features() > 0 {
+        Some(layer.into_vt())
+    } else {
+        None
+    }
+}
+
+/// Apply a delta layer to a layer
+fn apply_layer(base: &VtLayer, delta: &VtLayer, extent: u32) -> Layer {
+    let changes = split_metadata(delta).1;
+    let cleared = changes.iter().any(|f| is_marker(f) && f.id.is_none());
+    let mut features: Vec<Option<(&VtLayer, &VtFeature)>> = if cleared {
+        vec![]
+    } else {
+        let base_features = split_metadata(base).1;
+        base_features.iter().map(|f| Some((base, f))).collect()
+    };
+    // positions of features with each ID, in order
+    let mut ids: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (pos, (_, f)) in features.iter().flatten().enumerate() {
+        if let Some(id) = f.id {
+            ids.entry(id).or_default().push(pos);
+        }
+    }
+    for f in changes {
+        if is_marker(f) {
+            if let Some(id) = f.id {
+                for pos in ids.remove(&id).unwrap_or_default() {
+                    features[pos] = None;
+                }
+            }
+            continue;
+        }
+        let pos = f.id.and_then(|id| ids.get(&id)).and_then(|p| p.first());
+        match pos {
+            Some(pos) => features[*pos] = Some((delta, f)),
+            None => {
+                if let Some(id) = f.id {
+                    ids.entry(id).or_default().push(features.len());
+                }
+                features.push(Some((delta, f)));
+            }
+        }
+    }
+    let meta = if cleared || metadata_feature(delta).is_some() {
+        delta
+    } else {
+        base
+    };
+    let mut layer = Layer::new(delta.name(), extent).copy_metadata(meta);
+    for (src, f) in features.into_iter().flatten() {
+        layer = layer.copy_feature(src, f, f.geometry.clone());
+    }
+    layer
+}
+
+/// Get layers of two tiles, paired by name
+fn pair_layers<'a>(
+    a: &'a [VtLayer],
+    b: &'a [VtLayer],
+) -> Vec<(Option<&'a VtLayer>, Option<&'a VtLayer>)> {
+    let mut pairs: Vec<_> = a
+        .iter()
+        .map(|la| (Some(la), b.iter().find(|lb| lb.name == la.name)))
+        .collect();
+    for lb in b {
+        if !a.iter().any(|la| la.name == lb.name) {
+            pairs.push((None, Some(lb)));
+        }
+    }
+    pairs
+}
+
+/// Make an empty layer with the same name as another
+fn empty_like(pair: (Option<&VtLayer>, Option<&VtLayer>)) -> VtLayer {
+    let mut layer = VtLayer::new();
+    layer.name = pair.0.or(pair.1).and_then(|l| l.name.clone());
+    layer
+}
+
+impl TileDelta {
+    /// Compute the changes between two versions of a tile.
+    ///
+    /// * `old` Earlier version of the tile.
+    /// * `new` Later version of the tile.
+    ///
+    /// Returns an error if the tile extents do not match.
+    pub fn diff(old: &Tile, new: &Tile) -> Result<Self> {
+        if old.extent() != new.extent() {
+            return Err(Error::WrongExtent());
+        }
+        let extent = new.extent();
+        let mut vec_tile = VecTile::new();
+        for pair in pair_layers(old.vt_layers(), new.vt_layers()) {
+            let empty = empty_like(pair);
+            let ol = pair.0.unwrap_or(&empty);
+            let nl = pair.1.unwrap_or(&empty);
+            if let Some(layer) = diff_layer(ol, nl, extent) {
+                vec_tile.layers.push(layer);
+            }
+        }
+        Ok(TileDelta { vec_tile })
+    }
+
+    /// Decode a delta from bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let vec_tile = VecTile::parse_from_bytes(data)?;
+        Ok(TileDelta { vec_tile })
+    }
+
+    /// Check if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.vec_tile.layers.is_empty()
+    }
+
+    /// Get the number of changed layers.
+    pub fn num_layers(&self) -> usize {
+        self.vec_tile.layers.len()
+    }
+
+    /// Apply the changes to a tile, creating a new tile.
+    ///
+    /// * `tile` Earlier version of the tile.
+    ///
+    /// Returns an error if the layer extents do not match the tile extent.
+    pub fn apply(&self, tile: &Tile) -> Result<Tile> {
+        let extent = tile.extent();
+        let deltas = &self.vec_tile.layers;
+        if deltas.iter().any(|l| l.extent() != extent) {
+            return Err(Error::WrongExtent());
+        }
+        let mut result = Tile::new(extent);
+        for pair in pair_layers(tile.vt_layers(), deltas) {
+            let empty = empty_like(pair);
+            let base = pair.0.unwrap_or(&empty);
+            let delta = pair.1.unwrap_or(&empty);
+            let layer = apply_layer(base, delta, extent);
+            if layer.num_features() > 0 {
+                result.add_layer(layer)?;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Write the delta.
+    ///
+    /// * `out` Writer to output the delta.
+    pub fn write_to(&self, mut out: &mut dyn Write) -> Result<()> {
+        let mut os = CodedOutputStream::new(&mut out);
+        self.vec_tile.write_to(&mut os)?;
+        os.flush()?;
+        Ok(())
+    }
+
+    /// Encode the delta and return the bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut v = Vec::with_capacity(self.compute_size());
+        self.write_to(&mut v)?;
+        Ok(v)
+    }
+
+    /// Compute the encoded size in bytes.
+    pub fn compute_size(&self) -> usize {
+        self.vec_tile.compute_size() as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use pointy::Transform;
+
+    fn add_point(layer: Layer, id: Option<u64>, x: f64, name: &str) -> Layer {
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(x, x)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        if let Some(id) = id {
+            feature.set_id(id);
+        }
+        feature.add_tag_string("name", name);
+        feature.into_layer()
+    }
+
+    type Points<'a> = &'a [(Option<u64>, f64, &'a str)];
+
+    fn tile(layers: &[(&str, Points)]) -> Tile {
+        let mut tile = Tile::new(4096);
+        for (name, points) in layers {
+            let mut layer = tile.create_layer(name);
+            for (id, x, name) in points.iter() {
+                layer = add_point(layer, *id, *x, name);
+            }
+            tile.add_layer(layer).unwrap();
+        }
+        tile
+    }
+
+    fn round_trip(old: &Tile, new: &Tile) -> TileDelta {
+        let delta = TileDelta::diff(old, new).unwrap();
+        let delta = TileDelta::from_bytes(&delta.to_bytes().unwrap()).unwrap();
+        let tile = delta.apply(old).unwrap();
+        assert!(TileDelta::diff(&tile, new).unwrap().is_empty());
+        delta
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let t = tile(&[("a", &[(Some(1), 1.0, "x"), (None, 2.0, "y")])]);
+        assert!(TileDelta::diff(&t, &t).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_features() {
+        let old = tile(&[(
+            "a",
+            &[
+                (Some(1), 1.0, "x"),
+                (Some(2), 2.0, "y"),
+                (Some(3), 3.0, "z"),
+            ],
+        )]);
+        let new = tile(&[(
+            "a",
+            &[
+                (Some(1), 1.0, "x"),
+                (Some(3), 5.0, "z"),
+                (Some(4), 4.0, "w"),
+            ],
+        )]);
+        let delta = round_trip(&old, &new);
+        // one removal marker, one changed and one added feature
+        assert_eq!(delta.vec_tile.layers[0].features.len(), 3);
+        assert!(delta.compute_size() < new.compute_size());
+    }
+
+    #[test]
+    fn test_untracked() {
+        let old = tile(&[("a", &[(None, 1.0, "x")])]);
+        let new = tile(&[("a", &[(None, 1.0, "x"), (None, 2.0, "y")])]);
+        round_trip(&old, &new);
+    }
+
+    #[test]
+    fn test_layers() {
+        let old = tile(&[("a", &[(Some(1), 1.0, "x")]), ("b", &[])]);
+        let new = tile(&[("b", &[]), ("c", &[(Some(1), 1.0, "x")])]);
+        let delta = round_trip(&old, &new);
+        assert_eq!(delta.num_layers(), 2);
+        let tile = delta.apply(&old).unwrap();
+        assert_eq!(tile.num_layers(), 1);
+    }
+
+    fn with_source(mut tile: Tile, source: &str) -> Tile {
+        let layer = tile.get_or_create_layer("a").unwrap();
+        tile.add_layer(layer.with_metadata("source", source))
+            .unwrap();
+        tile
+    }
+
+    #[test]
+    fn test_metadata() {
+        let points = [(Some(1), 1.0, "x"), (Some(2), 2.0, "y")];
+        let old = with_source(tile(&[("a", &points)]), "v1");
+        let points = [(Some(1), 1.0, "x"), (Some(2), 3.0, "y")];
+        let new = with_source(tile(&[("a", &points)]), "v1");
+        // metadata is neither a marker nor an untracked feature
+        let delta = round_trip(&old, &new);
+        assert_eq!(delta.vec_tile.layers[0].features.len(), 1);
+        let applied = delta.apply(&old).unwrap();
+        let meta = vec![("source".to_string(), "v1".to_string())];
+        assert_eq!(applied.layer_metadata("a").unwrap(), meta);
+        let newer = with_source(applied, "v2");
+        let delta = round_trip(&new, &newer);
+        assert_eq!(delta.vec_tile.layers[0].features.len(), 1);
+        let plain = tile(&[("a", &points)]);
+        round_trip(&plain, &new);
+        round_trip(&new, &plain);
+    }
+
+    #[test]
+    fn test_extent() {
+        let old = Tile::new(512);
+        let new = Tile::new(4096);
+        assert!(TileDelta::diff(&old, &new).is_err());
+        let new = tile(&[("a", &[(Some(1), 1.0, "x")])]);
+        let delta = TileDelta::diff(&Tile::new(4096), &new).unwrap();
+        assert!(delta.apply(&old).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+// determinism.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Determinism checks for content-addressed tile storage.
+//!
+use crate::duplicate::TileDigest;
+use crate::encoder::{GeomEncoder, GeomType};
+use crate::error::Result;
+use crate::sort::sort_layer;
+use crate::tile::Tile;
+use crate::watermark::mix;
+use pointy::Transform;
+
+/// Number of features per layer in a reference tile
+const NUM_FEATURES: u64 = 24;
+
+/// Put a tile into canonical form.
+///
+/// Layers are sorted by name, and key / value tables of each layer are
+/// sorted (see [Tile::sort_tables]).  Feature order is preserved, since it
+/// affects rendering.  Two tiles with the same content in canonical form
+/// encode to identical bytes.
+///
+/// [Tile::sort_tables]: struct.Tile.html#method.sort_tables
+pub fn canonicalize(tile: &mut Tile) {
+    let layers = tile.vt_layers_mut();
+    layers.sort_by(|a, b| a.name().cmp(b.name()));
+    for layer in layers.iter_mut() {
+        sort_layer(layer);
+    }
+}
+
+/// Pseudo-random number generator for reference tiles
+struct Rng(u64);
+
+impl Rng {
+    /// Get the next random number
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(1);
+        mix(self.0)
+    }
+
+    /// Get a random coördinate (exact in binary floating point)
+    fn coord(&mut self) -> f64 {
+        (self.next() % 40_000) as f64 * 0.5 - 2000.0
+    }
+}
+
+/// Build a reference tile from a seed.
+///
+/// The tile exercises points, linestrings, polygons, a scaling transform and
+/// every tag value type.  All float inputs are exactly representable, so the
+/// encoded bytes depend only on the encoder, not on the platform.
+pub fn reference_tile(seed: u64) -> Result<Tile> {
+    let mut rng = Rng(seed);
+    let mut tile = Tile::new(4096);
+    let transform = Transform::with_scale(0.25, 0.25).translate(16.0, -8.0);
+    for (name, geom_tp, n_pts) in [
+        ("points", GeomType::Point, 1),
+        ("lines", GeomType::Linestring, 5),
+        ("polygons", GeomType::Polygon, 4),
+    ] {
+        let mut layer = tile.create_layer(name);
+        for i in 0..NUM_FEATURES {
+            let mut enc = GeomEncoder::new(geom_tp, transform);
+            for _ in 0..n_pts {
+                enc.add_point(rng.coord(), rng.coord())?;
+            }
+            let mut feature = layer.into_feature(enc.encode()?);
+            feature.set_id(i + 1);
+            let r = rng.next();
+            feature.add_tag_string("class", ["a", "b", "c"][(r % 3) as usize]);
+            feature.add_tag_int("int", (r % 100) as i64 - 50);
+            feature.add_tag_uint("uint", r % 1000);
+            feature.add_tag_sint("sint", (r % 7) as i64 - 3);
+            feature.add_tag_float("float", (r % 64) as f32 * 0.25);
+            feature.add_tag_double("double", (r % 512) as f64 / 8.0);
+            feature.add_tag_bool("bool", r & 1 != 0);
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer)?;
+    }
+    Ok(tile)
+}
+
+/// Compute a digest of the encoded reference tiles for some seeds.
+pub fn determinism_digest<I>(seeds: I) -> Result<TileDigest>
+where
+    I: IntoIterator<Item = u64>,
+{
+    let mut data = vec![];
+    for seed in seeds {
+        let mut tile = reference_tile(seed)?;
+        data.extend(tile.to_bytes()?);
+        canonicalize(&mut tile);
+        data.extend(tile.to_bytes()?);
+    }
+    Ok(TileDigest::new(&data))
+}
+
+/// Check that reference tiles encode to the expected bytes.
+///
+/// * `seeds` Reference tile seeds.
+/// * `expected` Expected digest (hex), recorded on a reference platform.
+///
+/// Intended for CI across operating systems and architectures.
+///
+/// # Panics
+///
+/// Panics if encoding fails or the digest does not match.
+pub fn assert_deterministic<I>(seeds: I, expected: &str)
+where
+    I: IntoIterator<Item = u64>,
+{
+    let digest = determinism_digest(seeds).expect("reference tile encoding");
+    assert_eq!(digest.to_string(), expected, "encoder output changed");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digest_stable() {
+        assert_eq!(
+            TileDigest::new(b"abc").to_string(),
+            "d010dbac15598eb0e71fa2190541574b"
+        );
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut a = reference_tile(1).unwrap();
+        let mut b = Tile::new(4096);
+        for layer in a.vt_layers().iter().rev() {
+            b.vt_layers_mut().push(layer.clone());
+        }
+        assert_ne!(a.to_bytes().unwrap(), b.to_bytes().unwrap());
+        canonicalize(&mut a);
+        canonicalize(&mut b);
+        assert_eq!(a.to_bytes().unwrap(), b.to_bytes().unwrap());
+        assert_eq!(a.vt_layers()[0].name(), "lines");
+    }
+
+    #[test]
+    fn test_deterministic() {
+        assert_deterministic(0..4, "917587a1449d7c2a09927c74fc6e3251");
+    }
+}
@@ -0,0 +1,180 @@
+// duplicate.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Duplicate tile detection.
+//!
+use crate::archive::TileSource;
+use crate::error::Result;
+use crate::mapgrid::TileId;
+use crate::watermark::mix;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+
+/// 128-bit digest of encoded tile data.
+///
+/// This is not cryptographic, but collisions between distinct tiles are
+/// vanishingly unlikely for pyramid-sized tile sets.  Digests are stable
+/// across platforms and releases, so they can be used for content-addressed
+/// storage.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TileDigest(u128);
+
+/// Detector for identical tiles (common for ocean / empty tiles).
+///
+/// The first tile with given content is the *original*; later identical tiles
+/// are duplicates of it.
+///
+/// # Example
+/// ```
+/// # use mvt::{DuplicateTiles, Error, TileId};
+/// # fn main() -> Result<(), Error> {
+/// let mut dups = DuplicateTiles::default();
+/// let t0 = TileId::new(0, 0, 1)?;
+/// let t1 = TileId::new(1, 0, 1)?;
+/// assert_eq!(dups.add(t0, b"ocean"), None);
+/// assert_eq!(dups.add(t1, b"ocean"), Some(t0));
+/// assert_eq!(dups.num_duplicates(), 1);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DuplicateTiles {
+    /// Groups of identical tiles, original first
+    groups: HashMap<TileDigest, Vec<TileId>>,
+
+    /// Number of duplicate tiles
+    n_duplicates: usize,
+}
+
+/// Compute 64-bit word-wise hash (splitmix64 mixing)
+fn mix_hash(data: &[u8]) -> u64 {
+    let mut chunks = data.chunks_exact(8);
+    let mut h = data.len() as u64;
+    for chunk in &mut chunks {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        h = mix(h ^ u64::from_le_bytes(word));
+    }
+    let mut word = [0; 8];
+    word[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    mix(h ^ u64::from_le_bytes(word))
+}
+
+/// Compute 64-bit FNV-1a hash
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl TileDigest {
+    /// Compute the digest of encoded tile data.
+    pub fn new(data: &[u8]) -> Self {
+        let hi = u128::from(mix_hash(data)) << 64;
+        TileDigest(hi | u128::from(fnv1a(data)))
+    }
+}
+
+impl fmt::Display for TileDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl DuplicateTiles {
+    /// Scan every tile in a source.
+    pub fn scan<S>(source: &mut S) -> Result<Self>
+    where
+        S: TileSource + ?Sized,
+    {
+        let mut dups = DuplicateTiles::default();
+        while let Some((tid, data)) = source.next_tile()? {
+            dups.add(tid, &data);
+        }
+        Ok(dups)
+    }
+
+    /// Add a tile.
+    ///
+    /// * `tid` Tile ID.
+    /// * `data` Encoded tile data.
+    ///
+    /// Returns the ID of the original tile if this is a duplicate.
+    pub fn add(&mut self, tid: TileId, data: &[u8]) -> Option<TileId> {
+        match self.groups.entry(TileDigest::new(data)) {
+            Entry::Occupied(mut e) => {
+                let tids = e.get_mut();
+                tids.push(tid);
+                self.n_duplicates += 1;
+                Some(tids[0])
+            }
+            Entry::Vacant(e) => {
+                e.insert(vec![tid]);
+                None
+            }
+        }
+    }
+
+    /// Get the number of distinct tiles.
+    pub fn num_distinct(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Get the number of duplicate tiles.
+    pub fn num_duplicates(&self) -> usize {
+        self.n_duplicates
+    }
+
+    /// Get groups of identical tiles, with the original first.
+    ///
+    /// Only groups with duplicates are included, largest first.
+    pub fn groups(&self) -> Vec<(TileDigest, &[TileId])> {
+        let mut groups: Vec<_> = self
+            .groups
+            .iter()
+            .filter(|(_, tids)| tids.len() > 1)
+            .map(|(digest, tids)| (*digest, &tids[..]))
+            .collect();
+        groups.sort_by(|a, b| {
+            b.1.len().cmp(&a.1.len()).then(a.0 .0.cmp(&b.0 .0))
+        });
+        groups
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::archive::IterSource;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(TileDigest::new(b"abc"), TileDigest::new(b"abc"));
+        assert_ne!(TileDigest::new(b"abc"), TileDigest::new(b"abd"));
+        assert_eq!(TileDigest::new(b"").to_string().len(), 32);
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_scan() {
+        let tid = |x| TileId::new(x, 0, 3).unwrap();
+        let mut source = IterSource::new([
+            (tid(0), b"water".to_vec()),
+            (tid(1), b"land".to_vec()),
+            (tid(2), b"water".to_vec()),
+            (tid(3), b"water".to_vec()),
+            (tid(4), b"road".to_vec()),
+            (tid(5), b"road".to_vec()),
+        ]);
+        let dups = DuplicateTiles::scan(&mut source).unwrap();
+        assert_eq!(dups.num_distinct(), 3);
+        assert_eq!(dups.num_duplicates(), 3);
+        let groups = dups.groups();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, TileDigest::new(b"water"));
+        assert_eq!(groups[0].1, &[tid(0), tid(2), tid(3)]);
+        assert_eq!(groups[1].1, &[tid(4), tid(5)]);
+    }
+}
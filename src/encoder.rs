@@ -4,8 +4,24 @@
 //
 //! Encoder for Mapbox Vector Tile (MVT) geometry.
 //!
+use crate::cancel::{check_cancel, CancelToken};
+use crate::clip::Rect;
 use crate::error::{Error, Result};
-use pointy::{Float, Transform};
+use crate::geometry::{Geometry, Winding};
+use crate::options::{ring_area, EncodeOptions};
+use crate::watermark::Watermark;
+use pointy::{BBox, Float, Transform};
+use std::f64::consts::{PI, TAU};
+use std::fmt;
+
+/// Polygon rings: exterior, then interiors
+type Rings<F> = Vec<Vec<(F, F)>>;
+
+/// Minimum number of segments in an automatic circle
+const MIN_CIRCLE_SEGMENTS: u32 = 8;
+
+/// Maximum number of segments in an automatic circle
+const MAX_CIRCLE_SEGMENTS: u32 = 1024;
 
 #[derive(Copy, Clone, Debug)]
 enum Command {
@@ -25,8 +41,14 @@ struct ParamInt {
     value: i32,
 }
 
+/// Geometry command sequence, displayed as `M(3,6) L(5,6) L(12,22) Z`.
+///
+/// Parameters are relative (as encoded).
+struct Commands<'a>(&'a [u32]);
+
 /// Geometry types for [Features](struct.Feature.html).
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub enum GeomType {
     /// Point or multipoint
     Point,
@@ -40,7 +62,11 @@ pub enum GeomType {
 
 /// Encoder for [Feature](struct.Feature.html) geometry.
 ///
-/// This can consist of Point, Linestring or Polygon data.
+/// This can consist of Point, Linestring or Polygon data.  Adding more than
+/// one point to Point geometry makes a multipoint, encoded as a single
+/// `MoveTo` command; [complete_geom] has no effect on it.
+///
+/// [complete_geom]: struct.GeomEncoder.html#method.complete_geom
 ///
 /// # Example
 /// ```
@@ -59,11 +85,68 @@ where
 {
     geom_tp: GeomType,
     transform: Transform<F>,
+    watermark: Option<Watermark>,
+    options: EncodeOptions,
+    cancel: Option<CancelToken>,
+    clip: Option<Rect<F>>,
+    clip_pts: Vec<(F, F)>,
     x: i32,
     y: i32,
     cmd_offset: usize,
+    part_offset: usize,
+    part_start: (i32, i32),
+    part: Vec<(i32, i32)>,
     count: u32,
+    dropped_parts: usize,
+    skipped_points: usize,
+    data: Vec<u32>,
+}
+
+/// Decoded geometry command, with absolute (transformed) coördinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeomCommand<F>
+where
+    F: Float,
+{
+    /// Move to a point, starting a new part
+    MoveTo(F, F),
+
+    /// Draw a line to a point
+    LineTo(F, F),
+
+    /// Close the current ring
+    ClosePath,
+}
+
+/// Decoder for [Feature](struct.Feature.html) geometry.
+///
+/// This is an iterator of [GeomCommand]s, with coördinates transformed
+/// from tile units (for example, by the inverse of a tile transform).
+/// Malformed data yields one [Error::InvalidGeometryAt], with the position
+/// of the offending integer, then ends the iteration.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, GeomCommand, GeomDecoder};
+/// # fn main() -> Result<(), Error> {
+/// let data = vec![9, 4, 4, 18, 6, 0, 0, 2, 15];
+/// let commands = GeomDecoder::<f64>::new(data)
+///     .collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(commands[0], GeomCommand::MoveTo(2.0, 2.0));
+/// assert_eq!(commands[2], GeomCommand::LineTo(5.0, 3.0));
+/// assert_eq!(commands[3], GeomCommand::ClosePath);
+/// # Ok(()) }
+/// ```
+///
+/// [Error::InvalidGeometryAt]: enum.Error.html#variant.InvalidGeometryAt
+/// [GeomCommand]: enum.GeomCommand.html
+pub struct GeomDecoder<F>
+where
+    F: Float,
+{
+    transform: Transform<F>,
     data: Vec<u32>,
+    cursor: CommandCursor,
 }
 
 /// Validated geometry data for [Feature](struct.Feature.html)s.
@@ -81,9 +164,11 @@ where
 ///     .encode()?;
 /// # Ok(()) }
 /// ```
+#[derive(Clone)]
 pub struct GeomData {
     geom_tp: GeomType,
     data: Vec<u32>,
+    skipped_points: usize,
 }
 
 impl CommandInt {
@@ -104,6 +189,216 @@ impl ParamInt {
     fn encode(&self) -> u32 {
         ((self.value << 1) ^ (self.value >> 31)) as u32
     }
+
+    fn decode(v: u32) -> Self {
+        ParamInt::new(((v >> 1) as i32) ^ -((v & 1) as i32))
+    }
+}
+
+impl fmt::Display for Commands<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut it = self.0.iter();
+        let mut sep = "";
+        while let Some(cmd) = it.next() {
+            let c = match cmd & 0x7 {
+                1 => 'M',
+                2 => 'L',
+                7 => {
+                    write!(f, "{sep}Z")?;
+                    sep = " ";
+                    continue;
+                }
+                _ => {
+                    write!(f, "{sep}?{cmd}")?;
+                    sep = " ";
+                    continue;
+                }
+            };
+            for _ in 0..cmd >> 3 {
+                match (it.next(), it.next()) {
+                    (Some(dx), Some(dy)) => {
+                        let dx = ParamInt::decode(*dx).value;
+                        let dy = ParamInt::decode(*dy).value;
+                        write!(f, "{sep}{c}({dx},{dy})")?;
+                    }
+                    _ => return write!(f, "{sep}{c}(...)"),
+                }
+                sep = " ";
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Cursor for decoding geometry command integers.
+///
+/// Errors report the position of the offending integer: a command with an
+/// unknown ID or a count running past the end, or a parameter which
+/// overflows the coördinate range.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct CommandCursor {
+    pos: usize,
+    cmd: u32,
+    count: u32,
+    x: i32,
+    y: i32,
+}
+
+impl CommandCursor {
+    /// Decode the next command, with absolute coördinates
+    pub(crate) fn next(
+        &mut self,
+        data: &[u32],
+    ) -> Option<Result<(u32, i32, i32)>> {
+        if self.count == 0 && self.pos >= data.len() {
+            return None;
+        }
+        let cmd = self.decode(data);
+        if cmd.is_err() {
+            // stop after an error
+            self.count = 0;
+            self.pos = data.len();
+        }
+        Some(cmd)
+    }
+
+    /// Decode the next command
+    fn decode(&mut self, data: &[u32]) -> Result<(u32, i32, i32)> {
+        if self.count == 0 {
+            let at = self.pos;
+            let cmd = data[at];
+            self.pos += 1;
+            self.cmd = cmd & 0x7;
+            self.count = cmd >> 3;
+            let n_params = match self.cmd {
+                1 | 2 => 2 * self.count as usize,
+                7 => 0,
+                _ => return Err(Error::InvalidGeometryAt(at)),
+            };
+            if self.count == 0
+                || (self.cmd == 7 && self.count != 1)
+                || n_params > data.len() - self.pos
+            {
+                return Err(Error::InvalidGeometryAt(at));
+            }
+        }
+        self.count -= 1;
+        if self.cmd == 7 {
+            return Ok((7, self.x, self.y));
+        }
+        let at = self.pos;
+        let dx = ParamInt::decode(data[at]).value;
+        let dy = ParamInt::decode(data[at + 1]).value;
+        self.pos += 2;
+        self.x = self.x.checked_add(dx).ok_or(Error::InvalidGeometryAt(at))?;
+        self.y = self
+            .y
+            .checked_add(dy)
+            .ok_or(Error::InvalidGeometryAt(at + 1))?;
+        Ok((self.cmd, self.x, self.y))
+    }
+}
+
+/// Decode absolute coördinates of all vertices in geometry data.
+///
+/// Returns an error if the command integers are malformed.
+pub(crate) fn decode_vertices(data: &[u32]) -> Result<Vec<(i32, i32)>> {
+    let mut vertices = vec![];
+    let mut cursor = CommandCursor::default();
+    while let Some(cmd) = cursor.next(data) {
+        match cmd? {
+            (7, _, _) => (),
+            (_, x, y) => vertices.push((x, y)),
+        }
+    }
+    Ok(vertices)
+}
+
+/// Count vertices in geometry data, scanning command integers only.
+///
+/// Returns an error if the command integers are malformed.
+pub(crate) fn count_vertices(data: &[u32]) -> Result<usize> {
+    let mut vertices = 0;
+    let mut pos = 0;
+    while let Some(cmd) = data.get(pos) {
+        let (id, count) = (cmd & 0x7, (cmd >> 3) as usize);
+        let n_params = match id {
+            1 | 2 => 2 * count,
+            7 => 0,
+            _ => return Err(Error::InvalidGeometryAt(pos)),
+        };
+        if count == 0
+            || (id == 7 && count != 1)
+            || n_params > data.len() - pos - 1
+        {
+            return Err(Error::InvalidGeometryAt(pos));
+        }
+        if id != 7 {
+            vertices += count;
+        }
+        pos += 1 + n_params;
+    }
+    Ok(vertices)
+}
+
+/// Decode geometry data into parts (points, linestrings or rings).
+///
+/// Each `MoveTo` vertex starts a new part.  Returns an error if the command
+/// integers are malformed.
+pub(crate) fn decode_parts(data: &[u32]) -> Result<Vec<Vec<(i32, i32)>>> {
+    let mut parts: Vec<Vec<(i32, i32)>> = vec![];
+    let mut cursor = CommandCursor::default();
+    while let Some(cmd) = cursor.next(data) {
+        let at = cursor.pos;
+        match (cmd?, parts.last_mut()) {
+            ((7, _, _), _) => (),
+            ((2, x, y), Some(part)) => part.push((x, y)),
+            ((2, _, _), None) => return Err(Error::InvalidGeometryAt(at - 2)),
+            ((_, x, y), _) => parts.push(vec![(x, y)]),
+        }
+    }
+    Ok(parts)
+}
+
+/// Encode parts (points, linestrings or rings) into geometry data.
+///
+/// Rings must not repeat the first vertex.
+pub(crate) fn encode_parts(
+    geom_tp: GeomType,
+    parts: &[Vec<(i32, i32)>],
+) -> Vec<u32> {
+    let mut data = vec![];
+    let (mut x, mut y) = (0i32, 0i32);
+    let mut push = |data: &mut Vec<u32>, (px, py): (i32, i32)| {
+        data.push(ParamInt::new(px.wrapping_sub(x)).encode());
+        data.push(ParamInt::new(py.wrapping_sub(y)).encode());
+        (x, y) = (px, py);
+    };
+    if geom_tp == GeomType::Point {
+        let n_points = parts.iter().map(Vec::len).sum::<usize>() as u32;
+        if n_points > 0 {
+            data.push(CommandInt::new(Command::MoveTo, n_points).encode());
+            for pt in parts.iter().flatten() {
+                push(&mut data, *pt);
+            }
+        }
+        return data;
+    }
+    for part in parts.iter().filter(|part| !part.is_empty()) {
+        data.push(CommandInt::new(Command::MoveTo, 1).encode());
+        push(&mut data, part[0]);
+        if part.len() > 1 {
+            let count = part.len() as u32 - 1;
+            data.push(CommandInt::new(Command::LineTo, count).encode());
+            for pt in &part[1..] {
+                push(&mut data, *pt);
+            }
+        }
+        if geom_tp == GeomType::Polygon {
+            data.push(CommandInt::new(Command::ClosePath, 1).encode());
+        }
+    }
+    data
 }
 
 impl<F> GeomEncoder<F>
@@ -118,14 +413,83 @@ where
         GeomEncoder {
             geom_tp,
             transform,
+            watermark: None,
+            options: EncodeOptions::default(),
+            cancel: None,
+            clip: None,
+            clip_pts: vec![],
             x: 0,
             y: 0,
             count: 0,
             cmd_offset: 0,
+            part_offset: 0,
+            part_start: (0, 0),
+            part: vec![],
+            dropped_parts: 0,
+            skipped_points: 0,
             data: vec![],
         }
     }
 
+    /// Apply a watermark to all quantized points.
+    ///
+    /// * `watermark` Keyed watermark.
+    pub fn watermark(mut self, watermark: Watermark) -> Self {
+        self.watermark = Some(watermark);
+        self
+    }
+
+    /// Set validation options.
+    ///
+    /// In lenient mode, points which cannot be quantized (non-finite or out
+    /// of range) are skipped instead of returning an error.
+    pub fn with_options(mut self, options: EncodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set a token for cancelling encoding.
+    ///
+    /// Once cancelled, adding points or completing geometry returns
+    /// [Error::Cancelled].
+    ///
+    /// [Error::Cancelled]: enum.Error.html#variant.Cancelled
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Clip geometry to the tile extent, plus a buffer.
+    ///
+    /// * `extent` Height / width of tile bounds.
+    /// * `buffer` Buffer around the tile (tile units).
+    ///
+    /// Points are clipped after transforming, before quantizing.  Points
+    /// outside the buffered tile are dropped, linestrings are split where
+    /// they leave it, and polygon rings are clipped along its edges.
+    /// Linestrings and rings are clipped when completed.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let geom_data = GeomEncoder::new(GeomType::Linestring, Transform::default())
+    ///     .with_clip(256, 16)
+    ///     .point(-100.0, 10.0)?
+    ///     .point(100.0, 10.0)?
+    ///     .point(400.0, 10.0)?
+    ///     .encode()?;
+    /// assert_eq!(geom_data.to_string(), "M(-16,10) L(116,0) L(172,0)");
+    /// # Ok(()) }
+    /// ```
+    pub fn with_clip(mut self, extent: u32, buffer: u32) -> Self {
+        let min = -F::from(buffer).unwrap();
+        let max = F::from(u64::from(extent) + u64::from(buffer)).unwrap();
+        self.clip = Some(Rect::new(min, min, max, max));
+        self
+    }
+
     /// Add a Command
     fn command(&mut self, cmd: Command, count: u32) {
         self.cmd_offset = self.data.len();
@@ -139,11 +503,115 @@ where
         self.data[off] = CommandInt::new(cmd, count).encode();
     }
 
-    /// Push one point with relative coörindates.
-    fn push_point(&mut self, x: F, y: F) -> Result<()> {
+    /// Start a new part with a MoveTo command.
+    fn start_part(&mut self) {
+        self.part_offset = self.data.len();
+        self.part_start = (self.x, self.y);
+        self.part.clear();
+        self.command(Command::MoveTo, 1);
+    }
+
+    /// Drop the last point of a ring, if it repeats the first point.
+    fn drop_closing_point(&mut self) {
+        let n = self.part.len();
+        if n > 1 && self.part[n - 1] == self.part[0] {
+            self.data.truncate(self.data.len() - 2);
+            self.part.pop();
+            (self.x, self.y) = self.part[n - 2];
+            self.count -= 1;
+        }
+    }
+
+    /// Discard the current (degenerate) part.
+    fn discard_part(&mut self) -> Result<()> {
+        if self.options.is_degenerate() && !self.options.is_lenient() {
+            return Err(Error::InvalidGeometry());
+        }
+        debug!("discarding part with {} points", self.count);
+        self.data.truncate(self.part_offset);
+        (self.x, self.y) = self.part_start;
+        self.dropped_parts += 1;
+        Ok(())
+    }
+
+    /// Check whether the current part is degenerate after quantization.
+    ///
+    /// Linestrings with only one distinct point, and rings with zero area,
+    /// are degenerate.
+    fn is_degenerate_part(&self) -> bool {
+        if !self.options.is_degenerate() {
+            return false;
+        }
+        let vertices = &self.part;
+        match self.geom_tp {
+            GeomType::Point => false,
+            GeomType::Linestring => vertices.iter().all(|p| *p == vertices[0]),
+            GeomType::Polygon => ring_area(vertices) == 0,
+        }
+    }
+
+    /// Get the number of degenerate parts discarded so far.
+    ///
+    /// Linestrings with fewer than 2 points, and rings with fewer than 3, are
+    /// always discarded.  With the degenerate geometry check of
+    /// [EncodeOptions] enabled, linestrings with only one distinct point and
+    /// zero-area rings are also discarded in lenient mode; in strict mode,
+    /// [Error::InvalidGeometry] is returned instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{EncodeOptions, Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default())
+    ///     .with_options(EncodeOptions::lenient());
+    /// for (x, y) in [(0.0, 0.0), (10.0, 0.0), (20.0, 0.1)] {
+    ///     enc.add_point(x, y)?;
+    /// }
+    /// enc.complete_geom()?;
+    /// assert_eq!(enc.dropped_parts(), 1);
+    /// assert!(enc.encode()?.is_empty());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [EncodeOptions]: struct.EncodeOptions.html
+    /// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+    pub fn dropped_parts(&self) -> usize {
+        self.dropped_parts
+    }
+
+    /// Get the number of points skipped so far in lenient mode.
+    ///
+    /// Points with non-finite coördinates, or outside the `i32` range after
+    /// transform, are skipped in lenient mode instead of returning
+    /// [Error::InvalidValue].  The count is carried by the encoded
+    /// [GeomData], and included in the tile's [EncodeReport].
+    ///
+    /// [EncodeReport]: struct.EncodeReport.html
+    /// [Error::InvalidValue]: enum.Error.html#variant.InvalidValue
+    /// [GeomData]: struct.GeomData.html
+    pub fn skipped_points(&self) -> usize {
+        self.skipped_points
+    }
+
+    /// Transform and quantize a point.
+    fn quantize(&self, x: F, y: F) -> Result<(i32, i32)> {
         let p = self.transform * (x, y);
-        let x = p.x().round().to_i32().ok_or(Error::InvalidValue())?;
-        let y = p.y().round().to_i32().ok_or(Error::InvalidValue())?;
+        self.quantize_tile((p.x(), p.y()))
+    }
+
+    /// Quantize a point in tile coördinates.
+    fn quantize_tile(&self, (x, y): (F, F)) -> Result<(i32, i32)> {
+        let x = x.round().to_i32().ok_or(Error::InvalidValue())?;
+        let y = y.round().to_i32().ok_or(Error::InvalidValue())?;
+        Ok(match &self.watermark {
+            Some(wm) => wm.apply(x, y),
+            None => (x, y),
+        })
+    }
+
+    /// Push one point with relative coörindates.
+    fn push_point(&mut self, (x, y): (i32, i32)) {
         self.data
             .push(ParamInt::new(x.saturating_sub(self.x)).encode());
         self.data
@@ -151,31 +619,103 @@ where
         debug!("point: {},{}", x, y);
         self.x = x;
         self.y = y;
-        Ok(())
     }
 
     /// Add a point.
     pub fn add_point(&mut self, x: F, y: F) -> Result<()> {
+        check_cancel(self.cancel.as_ref())?;
+        if let Some(rect) = &self.clip {
+            let p = self.transform * (x, y);
+            let p = (p.x(), p.y());
+            if !(p.0.is_finite() && p.1.is_finite()) {
+                if self.options.is_lenient() {
+                    self.skipped_points += 1;
+                    return Ok(());
+                }
+                return Err(Error::InvalidValue());
+            }
+            match self.geom_tp {
+                GeomType::Point if rect.contains(p) => (),
+                GeomType::Point => return Ok(()),
+                _ => {
+                    self.clip_pts.push(p);
+                    return Ok(());
+                }
+            }
+            return self.push_tile_point(p);
+        }
+        let pt = match self.quantize(x, y) {
+            Err(_) if self.options.is_lenient() => {
+                self.skipped_points += 1;
+                return Ok(());
+            }
+            pt => pt?,
+        };
+        self.push_vertex(pt);
+        Ok(())
+    }
+
+    /// Quantize and push a point in tile coördinates.
+    fn push_tile_point(&mut self, p: (F, F)) -> Result<()> {
+        let pt = match self.quantize_tile(p) {
+            Err(_) if self.options.is_lenient() => {
+                self.skipped_points += 1;
+                return Ok(());
+            }
+            pt => pt?,
+        };
+        self.push_vertex(pt);
+        Ok(())
+    }
+
+    /// Clip points of the current linestring / ring, and push them.
+    fn clip_part(&mut self, rect: Rect<F>) -> Result<()> {
+        let pts = std::mem::take(&mut self.clip_pts);
         match self.geom_tp {
-            GeomType::Point => {
-                if self.count == 0 {
-                    self.command(Command::MoveTo, 1);
+            GeomType::Point => (),
+            GeomType::Linestring => {
+                for line in rect.clip_line(&pts) {
+                    for p in line {
+                        self.push_tile_point(p)?;
+                    }
+                    self.complete_part()?;
+                }
+            }
+            GeomType::Polygon => {
+                for p in rect.clip_ring(&pts) {
+                    self.push_tile_point(p)?;
                 }
             }
-            GeomType::Linestring => match self.count {
+        }
+        Ok(())
+    }
+
+    /// Push a quantized vertex, adding commands as needed.
+    fn push_vertex(&mut self, pt: (i32, i32)) {
+        if self.geom_tp != GeomType::Point
+            && self.count > 0
+            && pt == (self.x, self.y)
+            && self.options.is_dedup()
+        {
+            return;
+        }
+        match self.geom_tp {
+            // multipoint: a single MoveTo, with count of all points
+            GeomType::Point => match self.count {
                 0 => self.command(Command::MoveTo, 1),
-                1 => self.command(Command::LineTo, 1),
-                _ => (),
+                n => self.set_command(Command::MoveTo, n + 1),
             },
-            GeomType::Polygon => match self.count {
-                0 => self.command(Command::MoveTo, 1),
+            GeomType::Linestring | GeomType::Polygon => match self.count {
+                0 => self.start_part(),
                 1 => self.command(Command::LineTo, 1),
                 _ => (),
             },
         }
-        self.push_point(x, y)?;
+        self.push_point(pt);
+        if self.geom_tp != GeomType::Point {
+            self.part.push(pt);
+        }
         self.count += 1;
-        Ok(())
     }
 
     /// Add a point, taking ownership (for method chaining).
@@ -185,21 +725,61 @@ where
     }
 
     /// Complete the current geometry (for multilinestring / multipolygon).
+    ///
+    /// The next point starts a new part, with its own `MoveTo` / `LineTo`
+    /// commands.  Degenerate parts (linestrings with fewer than 2 points, or
+    /// rings with fewer than 3) are discarded; see [dropped_parts].
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let mut enc = GeomEncoder::new(GeomType::Linestring, Transform::default());
+    /// enc.add_point(0.0, 0.0)?;
+    /// enc.add_point(10.0, 0.0)?;
+    /// enc.complete_geom()?;
+    /// enc.add_point(0.0, 5.0)?;
+    /// enc.add_point(10.0, 5.0)?;
+    /// let geom_data = enc.encode()?;
+    /// assert_eq!(geom_data.to_string(), "M(0,0) L(10,0) M(-10,5) L(10,0)");
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [dropped_parts]: #method.dropped_parts
     pub fn complete_geom(&mut self) -> Result<()> {
-        // FIXME: return Error::InvalidGeometry
-        //        if "MUST" rules in the spec are violated
+        check_cancel(self.cancel.as_ref())?;
+        if let Some(rect) = self.clip {
+            self.clip_part(rect)?;
+        }
+        self.complete_part()
+    }
+
+    /// Complete the current part, discarding it if degenerate.
+    fn complete_part(&mut self) -> Result<()> {
         match self.geom_tp {
             GeomType::Point => (),
             GeomType::Linestring => {
-                if self.count > 1 {
-                    self.set_command(Command::LineTo, self.count - 1);
+                match self.count {
+                    0 => (),
+                    1 => self.discard_part()?,
+                    _ if self.is_degenerate_part() => self.discard_part()?,
+                    n => self.set_command(Command::LineTo, n - 1),
                 }
                 self.count = 0;
             }
             GeomType::Polygon => {
-                if self.count > 1 {
-                    self.set_command(Command::LineTo, self.count - 1);
-                    self.command(Command::ClosePath, 1);
+                if self.count > 1 && self.options.is_ring_closing() {
+                    self.drop_closing_point();
+                }
+                match self.count {
+                    0 => (),
+                    1 | 2 => self.discard_part()?,
+                    _ if self.is_degenerate_part() => self.discard_part()?,
+                    n => {
+                        self.set_command(Command::LineTo, n - 1);
+                        self.command(Command::ClosePath, 1);
+                    }
                 }
                 self.count = 0;
             }
@@ -213,87 +793,996 @@ where
         Ok(self)
     }
 
-    /// Encode the geometry data, consuming the encoder.
-    pub fn encode(mut self) -> Result<GeomData> {
-        // FIXME: return Error::InvalidGeometry
-        //        if "MUST" rules in the spec are violated
-        self = if let GeomType::Point = self.geom_tp {
-            if self.count > 1 {
-                self.set_command(Command::MoveTo, self.count);
-            }
-            self
-        } else {
-            self.complete()?
-        };
-        Ok(GeomData::new(self.geom_tp, self.data))
+    /// Get the determinant of the transform (tile area per unit area)
+    fn transform_det(&self) -> F {
+        let p0 = self.transform * (F::zero(), F::zero());
+        let px = self.transform * (F::one(), F::zero());
+        let py = self.transform * (F::zero(), F::one());
+        (px.x() - p0.x()) * (py.y() - p0.y())
+            - (px.y() - p0.y()) * (py.x() - p0.x())
     }
-}
 
-impl GeomData {
-    /// Create new geometry data.
+    /// Get the number of segments in a full circle
     ///
-    /// * `geom_tp` Geometry type.
-    /// * `data` Validated geometry.
-    pub fn new(geom_tp: GeomType, data: Vec<u32>) -> Self {
-        GeomData { geom_tp, data }
+    /// With 0 (automatic), the ring deviates from the circle by at most
+    /// half a tile unit.
+    pub(crate) fn circle_segments(&self, radius: F, segments: u32) -> u32 {
+        if segments > 0 {
+            return segments;
+        }
+        let radius = radius.abs() * self.transform_det().abs().sqrt();
+        let half = F::from(0.5).unwrap();
+        if radius.is_nan() || radius <= half {
+            return MIN_CIRCLE_SEGMENTS;
+        }
+        let step = (F::one() - half / radius).acos();
+        let pi = F::from(PI).unwrap();
+        (pi / step)
+            .ceil()
+            .to_u32()
+            .unwrap_or(MAX_CIRCLE_SEGMENTS)
+            .clamp(MIN_CIRCLE_SEGMENTS, MAX_CIRCLE_SEGMENTS)
     }
 
-    /// Get the geometry type
-    pub(crate) fn geom_type(&self) -> GeomType {
-        self.geom_tp
+    /// Add points along a circular arc, to the current part.
+    ///
+    /// * `center` Center of the arc.
+    /// * `radius` Radius (before transform).
+    /// * `start` Start angle (radians, counter-clockwise from +X).
+    /// * `end` End angle; the arc is clockwise if less than `start`.
+    /// * `segments` Segments in a full circle (0 for automatic, deviating
+    ///   by at most half a tile unit).
+    ///
+    /// Both end points are added.  Arcs can be combined with other points,
+    /// for example to make a sector polygon.  Arcs sweeping more than a full
+    /// circle are limited to one turn from `start`.  Returns
+    /// [Error::InvalidValue] if either angle is not finite.
+    ///
+    /// [Error::InvalidValue]: enum.Error.html#variant.InvalidValue
+    pub fn add_arc(
+        &mut self,
+        center: (F, F),
+        radius: F,
+        start: F,
+        end: F,
+        segments: u32,
+    ) -> Result<()> {
+        if !start.is_finite() || !end.is_finite() {
+            return Err(Error::InvalidValue());
+        }
+        let segments = F::from(self.circle_segments(radius, segments)).unwrap();
+        let tau = F::from(TAU).unwrap();
+        let sweep = (end - start).max(-tau).min(tau);
+        let n = (segments * sweep.abs() / tau).ceil().max(F::one());
+        let n = n.to_u32().ok_or(Error::InvalidValue())?;
+        for i in 0..=n {
+            let a = start + sweep * F::from(i).unwrap() / F::from(n).unwrap();
+            self.add_point(
+                center.0 + radius * a.cos(),
+                center.1 + radius * a.sin(),
+            )?;
+        }
+        Ok(())
     }
 
-    /// Check if data is empty
-    pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+    /// Add points along a circular arc, taking ownership (for method
+    /// chaining).
+    ///
+    /// See [add_arc](struct.GeomEncoder.html#method.add_arc).
+    pub fn arc(
+        mut self,
+        center: (F, F),
+        radius: F,
+        start: F,
+        end: F,
+        segments: u32,
+    ) -> Result<Self> {
+        self.add_arc(center, radius, start, end, segments)?;
+        Ok(self)
     }
 
-    /// Get length of data
-    pub fn len(&self) -> usize {
-        self.data.len()
+    /// Add a circle, as a complete polygon ring or closed linestring.
+    ///
+    /// * `center` Center of the circle.
+    /// * `radius` Radius (before transform).
+    /// * `segments` Number of segments (0 for automatic, deviating by at
+    ///   most half a tile unit).
+    ///
+    /// Polygon rings are wound as exterior rings (in tile coördinates).
+    /// Returns [Error::InvalidGeometry] for point geometry.
+    ///
+    /// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+    pub fn add_circle(
+        &mut self,
+        center: (F, F),
+        radius: F,
+        segments: u32,
+    ) -> Result<()> {
+        self.add_ellipse(center, (radius, radius), segments)
     }
 
-    /// Get the geometry data
-    pub(crate) fn into_vec(self) -> Vec<u32> {
-        self.data
+    /// Add a circle, taking ownership (for method chaining).
+    ///
+    /// See [add_circle](struct.GeomEncoder.html#method.add_circle).
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let geom_data = GeomEncoder::new(GeomType::Polygon, Transform::default())
+    ///     .circle((2048.0, 2048.0), 500.0, 0)?
+    ///     .encode()?;
+    /// geom_data.validate()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn circle(
+        mut self,
+        center: (F, F),
+        radius: F,
+        segments: u32,
+    ) -> Result<Self> {
+        self.add_circle(center, radius, segments)?;
+        Ok(self)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    /// Add an axis-aligned ellipse, as a complete polygon ring or closed
+    /// linestring.
+    ///
+    /// * `center` Center of the ellipse.
+    /// * `radii` X and Y radii (before transform).
+    /// * `segments` Number of segments (0 for automatic, deviating by at
+    ///   most half a tile unit).
+    ///
+    /// Polygon rings are wound as exterior rings (in tile coördinates).
+    /// Returns [Error::InvalidGeometry] for point geometry.
+    ///
+    /// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+    pub fn add_ellipse(
+        &mut self,
+        center: (F, F),
+        radii: (F, F),
+        segments: u32,
+    ) -> Result<()> {
+        if self.geom_tp == GeomType::Point {
+            return Err(Error::InvalidGeometry());
+        }
+        self.complete_geom()?;
+        let (rx, ry) = radii;
+        let n = self
+            .circle_segments(rx.abs().max(ry.abs()), segments)
+            .max(3);
+        let mut step = F::from(TAU / f64::from(n)).unwrap();
+        // exterior rings have positive area with Y down
+        if self.transform_det() * rx * ry < F::zero() {
+            step = -step;
+        }
+        let count = match self.geom_tp {
+            GeomType::Linestring => n + 1,
+            _ => n,
+        };
+        for i in 0..count {
+            let a = step * F::from(i % n).unwrap();
+            self.add_point(center.0 + rx * a.cos(), center.1 + ry * a.sin())?;
+        }
+        self.complete_geom()
+    }
 
-    // Examples from MVT spec:
-    #[test]
-    fn test_point() {
-        let v = GeomEncoder::new(GeomType::Point, Transform::default())
-            .point(25.0, 17.0)
-            .unwrap()
-            .encode()
-            .unwrap()
-            .into_vec();
-        assert_eq!(v, vec!(9, 50, 34));
+    /// Add an axis-aligned ellipse, taking ownership (for method chaining).
+    ///
+    /// See [add_ellipse](struct.GeomEncoder.html#method.add_ellipse).
+    pub fn ellipse(
+        mut self,
+        center: (F, F),
+        radii: (F, F),
+        segments: u32,
+    ) -> Result<Self> {
+        self.add_ellipse(center, radii, segments)?;
+        Ok(self)
     }
 
-    #[test]
-    fn test_multipoint() {
-        let v = GeomEncoder::new(GeomType::Point, Transform::default())
-            .point(5.0, 7.0)
-            .unwrap()
-            .point(3.0, 2.0)
-            .unwrap()
-            .encode()
-            .unwrap()
-            .into_vec();
-        assert_eq!(v, vec!(17, 10, 14, 3, 9));
+    /// Add a circular sector ("pie slice"), as a complete polygon ring or
+    /// closed linestring.
+    ///
+    /// * `center` Center of the circle.
+    /// * `radius` Radius (before transform).
+    /// * `start` Start angle (radians, counter-clockwise from +X).
+    /// * `end` End angle.
+    /// * `segments` Segments in a full circle (0 for automatic, deviating
+    ///   by at most half a tile unit).
+    ///
+    /// The sector is bounded by the radii at `start` and `end`, and the arc
+    /// between them (see [add_arc]).  Polygon rings are wound as exterior
+    /// rings (in tile coördinates).  Returns [Error::InvalidGeometry] for
+    /// point geometry.
+    ///
+    /// [add_arc]: struct.GeomEncoder.html#method.add_arc
+    /// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+    pub fn add_sector(
+        &mut self,
+        center: (F, F),
+        radius: F,
+        start: F,
+        end: F,
+        segments: u32,
+    ) -> Result<()> {
+        if self.geom_tp == GeomType::Point {
+            return Err(Error::InvalidGeometry());
+        }
+        self.complete_geom()?;
+        // exterior rings have positive area with Y down
+        let (start, end) = if (end - start) * self.transform_det() < F::zero() {
+            (end, start)
+        } else {
+            (start, end)
+        };
+        self.add_point(center.0, center.1)?;
+        self.add_arc(center, radius, start, end, segments)?;
+        if self.geom_tp == GeomType::Linestring {
+            self.add_point(center.0, center.1)?;
+        }
+        self.complete_geom()
     }
 
-    #[test]
-    fn test_linestring() {
-        let v = GeomEncoder::new(GeomType::Linestring, Transform::default())
-            .point(2.0, 2.0)
-            .unwrap()
-            .point(2.0, 10.0)
+    /// Add a circular sector, taking ownership (for method chaining).
+    ///
+    /// See [add_sector](struct.GeomEncoder.html#method.add_sector).
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # use std::f64::consts::FRAC_PI_4;
+    /// # fn main() -> Result<(), Error> {
+    /// // radar beam, 45° wide
+    /// let geom_data = GeomEncoder::new(GeomType::Polygon, Transform::default())
+    ///     .sector((2048.0, 2048.0), 1500.0, FRAC_PI_4, FRAC_PI_4 * 2.0, 0)?
+    ///     .encode()?;
+    /// geom_data.validate()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn sector(
+        mut self,
+        center: (F, F),
+        radius: F,
+        start: F,
+        end: F,
+        segments: u32,
+    ) -> Result<Self> {
+        self.add_sector(center, radius, start, end, segments)?;
+        Ok(self)
+    }
+
+    /// Add an axis-aligned rectangle, as a complete polygon ring or closed
+    /// linestring.
+    ///
+    /// * `bbox` Bounding box of the rectangle (before transform).
+    ///
+    /// Polygon rings are wound as exterior rings (in tile coördinates).
+    /// Returns [Error::InvalidGeometry] for point geometry.
+    ///
+    /// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+    pub fn add_rect(&mut self, bbox: BBox<F>) -> Result<()> {
+        if self.geom_tp == GeomType::Point {
+            return Err(Error::InvalidGeometry());
+        }
+        self.complete_geom()?;
+        let mut corners = [
+            (bbox.x_min(), bbox.y_min()),
+            (bbox.x_max(), bbox.y_min()),
+            (bbox.x_max(), bbox.y_max()),
+            (bbox.x_min(), bbox.y_max()),
+        ];
+        // exterior rings have positive area with Y down
+        if self.transform_det() < F::zero() {
+            corners.reverse();
+        }
+        for (x, y) in corners {
+            self.add_point(x, y)?;
+        }
+        if self.geom_tp == GeomType::Linestring {
+            self.add_point(corners[0].0, corners[0].1)?;
+        }
+        self.complete_geom()
+    }
+
+    /// Add an axis-aligned rectangle, taking ownership (for method
+    /// chaining).
+    ///
+    /// See [add_rect](struct.GeomEncoder.html#method.add_rect).
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType};
+    /// # use pointy::{BBox, Pt, Transform};
+    /// # fn main() -> Result<(), Error> {
+    /// let bbox = BBox::from((Pt::new(100.0, 100.0), Pt::new(400.0, 300.0)));
+    /// let geom_data = GeomEncoder::new(GeomType::Polygon, Transform::default())
+    ///     .rect(bbox)?
+    ///     .encode()?;
+    /// geom_data.validate()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn rect(mut self, bbox: BBox<F>) -> Result<Self> {
+        self.add_rect(bbox)?;
+        Ok(self)
+    }
+
+    /// Add a complete polygon ring, wound as exterior or interior.
+    fn add_ring(&mut self, ring: &[(F, F)], exterior: bool) -> Result<()> {
+        if self.geom_tp != GeomType::Polygon {
+            return Err(Error::InvalidGeometry());
+        }
+        self.complete_geom()?;
+        let mut pts = Vec::with_capacity(ring.len());
+        match self.clip {
+            Some(rect) => {
+                let tile_ring: Vec<(F, F)> = ring
+                    .iter()
+                    .map(|p| self.transform * *p)
+                    .map(|p| (p.x(), p.y()))
+                    .filter(|p| p.0.is_finite() && p.1.is_finite())
+                    .collect();
+                if tile_ring.len() < ring.len() && !self.options.is_lenient() {
+                    return Err(Error::InvalidValue());
+                }
+                self.skipped_points += ring.len() - tile_ring.len();
+                for p in rect.clip_ring(&tile_ring) {
+                    match self.quantize_tile(p) {
+                        Err(_) if self.options.is_lenient() => {
+                            self.skipped_points += 1;
+                        }
+                        pt => pts.push(pt?),
+                    }
+                }
+            }
+            None => {
+                for (x, y) in ring {
+                    match self.quantize(*x, *y) {
+                        Err(_) if self.options.is_lenient() => {
+                            self.skipped_points += 1;
+                        }
+                        pt => pts.push(pt?),
+                    }
+                }
+            }
+        }
+        pts.dedup();
+        // closing point is implied by ClosePath
+        if pts.len() > 1 && pts.first() == pts.last() {
+            pts.pop();
+        }
+        // exterior rings have positive area with Y down
+        let mut area = 0;
+        for (i, a) in pts.iter().enumerate() {
+            let b = pts[(i + 1) % pts.len()];
+            area += i64::from(a.0) * i64::from(b.1)
+                - i64::from(b.0) * i64::from(a.1);
+        }
+        if (area < 0) == exterior {
+            pts.reverse();
+        }
+        for pt in pts {
+            self.push_vertex(pt);
+        }
+        self.complete_geom()
+    }
+
+    /// Add an exterior polygon ring, starting a new polygon.
+    ///
+    /// * `ring` Points of the ring (before transform); the closing point may
+    ///   be included or omitted.
+    ///
+    /// The ring is completed with `ClosePath`, and reversed if needed to
+    /// have positive area in tile coördinates.  Any incomplete ring is
+    /// completed first.  Returns [Error::InvalidGeometry] unless the
+    /// geometry type is Polygon.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let outer = [(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0)];
+    /// let hole = [(25.0, 25.0), (75.0, 25.0), (75.0, 75.0), (25.0, 75.0)];
+    /// let geom_data = GeomEncoder::new(GeomType::Polygon, Transform::default())
+    ///     .exterior_ring(&outer)?
+    ///     .interior_ring(&hole)?
+    ///     .encode()?;
+    /// geom_data.validate()?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+    pub fn add_exterior_ring(&mut self, ring: &[(F, F)]) -> Result<()> {
+        self.add_ring(ring, true)
+    }
+
+    /// Add an interior polygon ring (hole), within the previous exterior
+    /// ring.
+    ///
+    /// * `ring` Points of the ring (before transform); the closing point may
+    ///   be included or omitted.
+    ///
+    /// The ring is reversed if needed to have negative area in tile
+    /// coördinates.  See [add_exterior_ring].
+    ///
+    /// [add_exterior_ring]: struct.GeomEncoder.html#method.add_exterior_ring
+    pub fn add_interior_ring(&mut self, ring: &[(F, F)]) -> Result<()> {
+        self.add_ring(ring, false)
+    }
+
+    /// Add an exterior polygon ring, taking ownership (for method chaining).
+    ///
+    /// See [add_exterior_ring](struct.GeomEncoder.html#method.add_exterior_ring).
+    pub fn exterior_ring(mut self, ring: &[(F, F)]) -> Result<Self> {
+        self.add_exterior_ring(ring)?;
+        Ok(self)
+    }
+
+    /// Add an interior polygon ring, taking ownership (for method chaining).
+    ///
+    /// See [add_interior_ring](struct.GeomEncoder.html#method.add_interior_ring).
+    pub fn interior_ring(mut self, ring: &[(F, F)]) -> Result<Self> {
+        self.add_interior_ring(ring)?;
+        Ok(self)
+    }
+
+    /// Add polygons with holes (multipolygon).
+    ///
+    /// * `polygons` Polygons, each an exterior ring followed by any interior
+    ///   rings, as with [Geometry::Polygons].
+    ///
+    /// Rings are wound so that decoders can tell new exteriors from holes;
+    /// see [add_exterior_ring].  If an exterior ring is dropped (degenerate
+    /// or clipped away), its interior rings are skipped too, and counted in
+    /// [dropped_parts].  Returns [Error::InvalidGeometry] unless the geometry
+    /// type is Polygon.
+    ///
+    /// [dropped_parts]: #method.dropped_parts
+    /// [add_exterior_ring]: struct.GeomEncoder.html#method.add_exterior_ring
+    /// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+    /// [Geometry::Polygons]: enum.Geometry.html#variant.Polygons
+    pub fn add_multipolygon(&mut self, polygons: &[Rings<F>]) -> Result<()> {
+        if self.geom_tp != GeomType::Polygon {
+            return Err(Error::InvalidGeometry());
+        }
+        for rings in polygons {
+            if let Some((exterior, interiors)) = rings.split_first() {
+                let len = self.data.len();
+                self.add_exterior_ring(exterior)?;
+                if self.data.len() == len {
+                    // holes without an exterior would become exteriors
+                    self.dropped_parts += interiors.len();
+                    continue;
+                }
+                for ring in interiors {
+                    self.add_interior_ring(ring)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add polygons with holes, taking ownership (for method chaining).
+    ///
+    /// See [add_multipolygon](struct.GeomEncoder.html#method.add_multipolygon).
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let polygons = vec![
+    ///     vec![
+    ///         vec![(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0)],
+    ///         vec![(25.0, 25.0), (75.0, 25.0), (75.0, 75.0), (25.0, 75.0)],
+    ///     ],
+    ///     vec![vec![(200.0, 0.0), (300.0, 0.0), (300.0, 100.0)]],
+    /// ];
+    /// let geom_data = GeomEncoder::new(GeomType::Polygon, Transform::default())
+    ///     .multipolygon(&polygons)?
+    ///     .encode()?;
+    /// geom_data.validate()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn multipolygon(mut self, polygons: &[Rings<F>]) -> Result<Self> {
+        self.add_multipolygon(polygons)?;
+        Ok(self)
+    }
+
+    /// Encode the geometry data, consuming the encoder.
+    pub fn encode(mut self) -> Result<GeomData> {
+        // degenerate parts were discarded by complete_geom; ring winding
+        // order is only checked (with EncodeOptions) by Tile::add_layer,
+        // and self-intersecting rings (a spec "MUST NOT") are not checked
+        self = self.complete()?;
+        let mut geom_data = GeomData::new(self.geom_tp, self.data);
+        geom_data.skipped_points = self.skipped_points;
+        Ok(geom_data)
+    }
+}
+
+#[cfg(feature = "geo-types")]
+impl<F> GeomEncoder<F>
+where
+    F: Float + geo_types::CoordNum,
+{
+    /// Add a `geo-types` multipolygon.
+    ///
+    /// See [add_multipolygon](struct.GeomEncoder.html#method.add_multipolygon).
+    pub fn add_geo_multipolygon(
+        &mut self,
+        polygons: &geo_types::MultiPolygon<F>,
+    ) -> Result<()> {
+        let ring = |line: &geo_types::LineString<F>| -> Vec<(F, F)> {
+            line.0.iter().map(|c| (c.x, c.y)).collect()
+        };
+        let polygons: Vec<Rings<F>> = polygons
+            .0
+            .iter()
+            .map(|polygon| {
+                let mut rings = vec![ring(polygon.exterior())];
+                rings.extend(polygon.interiors().iter().map(ring));
+                rings
+            })
+            .collect();
+        self.add_multipolygon(&polygons)
+    }
+}
+
+impl GeomData {
+    /// Create new geometry data.
+    ///
+    /// * `geom_tp` Geometry type.
+    /// * `data` Validated geometry.
+    pub fn new(geom_tp: GeomType, data: Vec<u32>) -> Self {
+        GeomData {
+            geom_tp,
+            data,
+            skipped_points: 0,
+        }
+    }
+
+    /// Get the geometry type
+    pub(crate) fn geom_type(&self) -> GeomType {
+        self.geom_tp
+    }
+
+    /// Check if data is empty
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Get length of data
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Get the number of points skipped in lenient mode
+    pub(crate) fn skipped_points(&self) -> usize {
+        self.skipped_points
+    }
+
+    /// Get the geometry data
+    pub(crate) fn into_vec(self) -> Vec<u32> {
+        self.data
+    }
+
+    /// Validate the geometry data against the specification.
+    ///
+    /// Checks that it is not empty and that the command sequence is valid
+    /// for the geometry type:
+    ///
+    /// * Point: one `MoveTo` with count > 0
+    /// * Linestring: `MoveTo` (1), `LineTo` (1+), repeated
+    /// * Polygon: `MoveTo` (1), `LineTo` (2+), `ClosePath` (1), repeated
+    pub fn validate(&self) -> Result<()> {
+        let mut it = self.data.iter();
+        let mut n_geoms = 0;
+        while let Some(count) = next_command(&mut it, Command::MoveTo)? {
+            let single = self.geom_tp != GeomType::Point;
+            if count == 0 || (single && count != 1) {
+                return Err(Error::InvalidGeometry());
+            }
+            skip_params(&mut it, count)?;
+            match self.geom_tp {
+                GeomType::Point => (),
+                GeomType::Linestring => {
+                    let count = next_command(&mut it, Command::LineTo)?;
+                    let count = count.ok_or(Error::InvalidGeometry())?;
+                    if count < 1 {
+                        return Err(Error::InvalidGeometry());
+                    }
+                    skip_params(&mut it, count)?;
+                }
+                GeomType::Polygon => {
+                    let count = next_command(&mut it, Command::LineTo)?;
+                    let count = count.ok_or(Error::InvalidGeometry())?;
+                    if count < 2 {
+                        return Err(Error::InvalidGeometry());
+                    }
+                    skip_params(&mut it, count)?;
+                    let close = next_command(&mut it, Command::ClosePath)?;
+                    if close != Some(1) {
+                        return Err(Error::InvalidGeometry());
+                    }
+                }
+            }
+            n_geoms += 1;
+        }
+        match (self.geom_tp, n_geoms) {
+            (_, 0) | (GeomType::Point, 2..) => Err(Error::InvalidGeometry()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Get the count of the next command, which must have the expected ID
+fn next_command(
+    it: &mut std::slice::Iter<u32>,
+    id: Command,
+) -> Result<Option<u32>> {
+    match it.next() {
+        Some(cmd) if cmd & 0x7 == id as u32 => Ok(Some(cmd >> 3)),
+        Some(_) => Err(Error::InvalidGeometry()),
+        None => Ok(None),
+    }
+}
+
+/// Skip parameters of a `MoveTo` / `LineTo` command
+fn skip_params(it: &mut std::slice::Iter<u32>, count: u32) -> Result<()> {
+    for _ in 0..count {
+        it.next().ok_or(Error::InvalidGeometry())?;
+        it.next().ok_or(Error::InvalidGeometry())?;
+    }
+    Ok(())
+}
+
+impl<F> GeomEncoder<F>
+where
+    F: Float,
+{
+    /// Get data with the count of an incomplete command filled in
+    fn pending_data(&self) -> Vec<u32> {
+        let mut data = self.data.clone();
+        if let Some(cmd) = data.get_mut(self.cmd_offset) {
+            if *cmd & 0x7 != Command::ClosePath as u32 {
+                let count = (self.data.len() - self.cmd_offset - 1) / 2;
+                *cmd = (*cmd & 0x7) | ((count as u32) << 3);
+            }
+        }
+        data
+    }
+}
+
+impl<F> fmt::Debug for GeomEncoder<F>
+where
+    F: Float,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GeomEncoder")
+            .field("geom_tp", &self.geom_tp)
+            .field("transform", &self.transform)
+            .field("commands", &format_args!("{self}"))
+            .finish()
+    }
+}
+
+impl<F> fmt::Display for GeomEncoder<F>
+where
+    F: Float,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Commands(&self.pending_data()))
+    }
+}
+
+impl fmt::Debug for GeomData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GeomData")
+            .field("geom_tp", &self.geom_tp)
+            .field("commands", &format_args!("{self}"))
+            .finish()
+    }
+}
+
+impl fmt::Display for GeomData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Commands(&self.data))
+    }
+}
+
+impl<F> GeomDecoder<F>
+where
+    F: Float,
+{
+    /// Create a new geometry decoder.
+    ///
+    /// * `data` Geometry command integers, as in an encoded feature.
+    pub fn new(data: Vec<u32>) -> Self {
+        GeomDecoder {
+            transform: Transform::default(),
+            data,
+            cursor: CommandCursor::default(),
+        }
+    }
+
+    /// Set the transform from tile units.
+    pub fn with_transform(mut self, transform: Transform<F>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Get an iterator of points, skipping `ClosePath` commands.
+    pub fn points(self) -> impl Iterator<Item = Result<(F, F)>> {
+        self.filter_map(|cmd| match cmd {
+            Ok(GeomCommand::MoveTo(x, y) | GeomCommand::LineTo(x, y)) => {
+                Some(Ok((x, y)))
+            }
+            Ok(GeomCommand::ClosePath) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Decode polygon geometry, grouping rings into polygons with holes.
+    ///
+    /// * `winding` Rule for grouping rings (`Version` is resolved for a
+    ///   version 2 layer).
+    ///
+    /// Each polygon is an exterior ring followed by its interior rings, as
+    /// with [Geometry::Polygons].  Rings are grouped by winding order in
+    /// tile units, then transformed; rings with zero area are dropped.  All
+    /// data is decoded, even if some commands have already been iterated.
+    ///
+    /// Returns an error if the command integers are malformed, or (with
+    /// [Winding::Area]) the first ring is not an exterior ring.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomDecoder, Winding};
+    /// # fn main() -> Result<(), Error> {
+    /// // exterior ring, then a hole
+    /// let data = vec![
+    ///     9, 0, 0, 26, 20, 0, 0, 20, 19, 0, 15,
+    ///     9, 4, 4, 26, 0, 4, 4, 0, 0, 3, 15,
+    /// ];
+    /// let polygons = GeomDecoder::<f64>::new(data).polygons(Winding::Area)?;
+    /// assert_eq!(polygons.len(), 1);
+    /// assert_eq!(polygons[0].len(), 2);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [Geometry::Polygons]: enum.Geometry.html#variant.Polygons
+    /// [Winding::Area]: enum.Winding.html#variant.Area
+    pub fn polygons(self, winding: Winding) -> Result<Vec<Rings<F>>> {
+        let transform = self.transform;
+        let geom =
+            Geometry::decode(GeomType::Polygon, &self.data, winding.rule(2))?;
+        match geom.map(|(x, y)| {
+            let p = transform * (F::from(x).unwrap(), F::from(y).unwrap());
+            (p.x(), p.y())
+        }) {
+            Geometry::Polygons(polygons) => Ok(polygons),
+            _ => Err(Error::InvalidGeometry()),
+        }
+    }
+}
+
+impl<F> Iterator for GeomDecoder<F>
+where
+    F: Float,
+{
+    type Item = Result<GeomCommand<F>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cmd = self.cursor.next(&self.data)?;
+        Some(cmd.map(|(cmd, x, y)| {
+            let x = F::from(x).unwrap();
+            let y = F::from(y).unwrap();
+            let p = self.transform * (x, y);
+            match cmd {
+                1 => GeomCommand::MoveTo(p.x(), p.y()),
+                2 => GeomCommand::LineTo(p.x(), p.y()),
+                _ => GeomCommand::ClosePath,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_param_decode() {
+        for v in [0, 1, -1, 25, -17, i32::MAX, i32::MIN] {
+            assert_eq!(ParamInt::decode(ParamInt::new(v).encode()).value, v);
+        }
+    }
+
+    #[test]
+    fn test_decode_vertices() {
+        let v = decode_vertices(&[9, 4, 4, 18, 0, 16, 16, 0]).unwrap();
+        assert_eq!(v, vec![(2, 2), (2, 10), (10, 10)]);
+        let v = decode_vertices(&[9, 6, 12, 18, 10, 12, 24, 44, 15]).unwrap();
+        assert_eq!(v, vec![(3, 6), (8, 12), (20, 34)]);
+        assert!(decode_vertices(&[9, 50]).is_err());
+        assert!(decode_vertices(&[3]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_position() {
+        let at = |data: &[u32]| match decode_vertices(data) {
+            Err(Error::InvalidGeometryAt(at)) => Some(at),
+            _ => None,
+        };
+        assert_eq!(at(&[9, 2, 2, 18, 2, 2]), Some(3));
+        assert_eq!(at(&[9, 2, 2, 12]), Some(3));
+        assert_eq!(at(&[9, 2, 2, 0xffff_fff2]), Some(3));
+        assert_eq!(at(&[9, 2, 2, 23]), Some(3));
+        assert_eq!(at(&[9, 0xffff_fffe, 0, 10, 2, 0]), Some(4));
+        assert_eq!(at(&[9, 0, 0, 10, 0, 0xffff_ffff, 10, 0, 1]), Some(8));
+        assert_eq!(at(&[9, 2, 2, 15]), None);
+        assert!(matches!(
+            decode_parts(&[15, 10, 2, 2]),
+            Err(Error::InvalidGeometryAt(2))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_points() {
+        let enc = GeomEncoder::new(GeomType::Point, Transform::default());
+        assert!(enc.point(f64::NAN, 1.0).is_err());
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .with_options(EncodeOptions::lenient())
+            .point(f64::NAN, 1.0)
+            .unwrap()
+            .point(1.0, 1e12)
+            .unwrap()
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.to_string(), "M(1,2)");
+    }
+
+    #[test]
+    fn test_parts() {
+        let data = vec![9, 6, 12, 18, 10, 12, 24, 44, 15];
+        let parts = decode_parts(&data).unwrap();
+        assert_eq!(parts, vec![vec![(3, 6), (8, 12), (20, 34)]]);
+        assert_eq!(encode_parts(GeomType::Polygon, &parts), data);
+        let data = vec![17, 10, 14, 3, 9];
+        let parts = decode_parts(&data).unwrap();
+        assert_eq!(parts, vec![vec![(5, 7)], vec![(3, 2)]]);
+        assert_eq!(encode_parts(GeomType::Point, &parts), data);
+        assert!(decode_parts(&[18, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid =
+            |tp, data: &[u32]| GeomData::new(tp, data.to_vec()).validate();
+        assert!(valid(GeomType::Point, &[9, 50, 34]).is_ok());
+        assert!(valid(GeomType::Point, &[17, 10, 14, 3, 9]).is_ok());
+        assert!(valid(GeomType::Point, &[]).is_err());
+        assert!(valid(GeomType::Point, &[9, 50, 34, 9, 2, 2]).is_err());
+        assert!(
+            valid(GeomType::Linestring, &[9, 4, 4, 18, 0, 16, 16, 0]).is_ok()
+        );
+        assert!(valid(GeomType::Linestring, &[9, 4, 4]).is_err());
+        assert!(valid(GeomType::Linestring, &[9, 4, 4, 18, 0, 16]).is_err());
+        assert!(
+            valid(GeomType::Polygon, &[9, 6, 12, 18, 10, 12, 24, 44, 15])
+                .is_ok()
+        );
+        assert!(
+            valid(GeomType::Polygon, &[9, 6, 12, 18, 10, 12, 24, 44]).is_err()
+        );
+        assert!(valid(GeomType::Polygon, &[9, 6, 12, 10, 10, 12, 15]).is_err());
+    }
+
+    #[test]
+    fn test_decoder() {
+        let data = vec![9, 4, 4, 18, 6, 0, 0, 2, 15, 9, 1, 1];
+        let t = Transform::with_scale(0.5, 2.0);
+        let cmds = GeomDecoder::new(data.clone())
+            .with_transform(t)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            cmds,
+            vec![
+                GeomCommand::MoveTo(1.0, 4.0),
+                GeomCommand::LineTo(2.5, 4.0),
+                GeomCommand::LineTo(2.5, 6.0),
+                GeomCommand::ClosePath,
+                GeomCommand::MoveTo(2.0, 4.0),
+            ]
+        );
+        let pts = GeomDecoder::<f64>::new(data)
+            .points()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(pts, vec![(2.0, 2.0), (5.0, 2.0), (5.0, 3.0), (4.0, 2.0)]);
+        for bad in [vec![9, 4], vec![3, 0, 0], vec![1], vec![23]] {
+            let mut dec = GeomDecoder::<f64>::new(bad);
+            assert!(dec.next().unwrap().is_err());
+            assert!(dec.next().is_none());
+        }
+        assert!(GeomDecoder::<f64>::new(vec![]).next().is_none());
+    }
+
+    #[test]
+    fn test_display() {
+        let data = GeomData::new(
+            GeomType::Polygon,
+            vec![9, 6, 12, 18, 10, 12, 24, 44, 15],
+        );
+        assert_eq!(data.to_string(), "M(3,6) L(5,6) L(12,22) Z");
+        assert_eq!(
+            format!("{data:?}"),
+            "GeomData { geom_tp: Polygon, commands: M(3,6) L(5,6) L(12,22) Z }"
+        );
+        let data = GeomData::new(GeomType::Point, vec![17, 10, 14, 3, 4, 4]);
+        assert_eq!(data.to_string(), "M(5,7) M(-2,2) ?4");
+        assert_eq!(
+            GeomData::new(GeomType::Point, vec![9, 4]).to_string(),
+            "M(...)"
+        );
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring, Transform::default());
+        enc.add_point(2.0, 2.0).unwrap();
+        enc.add_point(2.0, 10.0).unwrap();
+        enc.add_point(10.0, 10.0).unwrap();
+        assert_eq!(enc.to_string(), "M(2,2) L(0,8) L(8,0)");
+        assert_eq!(enc.encode().unwrap().to_string(), "M(2,2) L(0,8) L(8,0)");
+    }
+
+    // Examples from MVT spec:
+    #[test]
+    fn test_point() {
+        let v = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(25.0, 17.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 50, 34));
+    }
+
+    #[test]
+    fn test_multipoint() {
+        let v = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(5.0, 7.0)
+            .unwrap()
+            .point(3.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(17, 10, 14, 3, 9));
+    }
+
+    #[test]
+    fn test_multipoint_parts() {
+        let mut enc = GeomEncoder::new(GeomType::Point, Transform::default());
+        for i in 0..3 {
+            enc.add_point(f64::from(i), 1.0).unwrap();
+            enc.complete_geom().unwrap();
+        }
+        let geom = enc.encode().unwrap();
+        assert!(geom.validate().is_ok());
+        assert_eq!(geom.into_vec(), vec!(25, 0, 2, 2, 0, 2, 0));
+    }
+
+    #[test]
+    fn test_linestring() {
+        let v = GeomEncoder::new(GeomType::Linestring, Transform::default())
+            .point(2.0, 2.0)
+            .unwrap()
+            .point(2.0, 10.0)
             .unwrap()
             .point(10.0, 10.0)
             .unwrap()
@@ -339,6 +1828,499 @@ mod test {
         assert_eq!(v, vec!(9, 6, 12, 18, 10, 12, 24, 44, 15));
     }
 
+    #[test]
+    fn test_circle() {
+        use crate::options::ring_area;
+        let v = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .circle((0.0, 0.0), 10.0, 4)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 20, 0, 26, 19, 20, 19, 19, 20, 19, 15));
+        let flip = Transform::with_scale(10.0, -10.0);
+        for tp in [GeomType::Polygon, GeomType::Linestring] {
+            let data = GeomEncoder::new(tp, flip)
+                .circle((5.0, 5.0), 100.0, 0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            assert!(data.validate().is_ok());
+            let parts = decode_parts(&data.into_vec()).unwrap();
+            assert!(ring_area(&parts[0]) > 0);
+            // r = 1000 tile units => 100 segments
+            let n = if tp == GeomType::Polygon { 100 } else { 101 };
+            assert_eq!(parts[0].len(), n);
+        }
+        let enc = GeomEncoder::new(GeomType::Point, Transform::default());
+        assert!(enc.circle((0.0, 0.0), 1.0, 0).is_err());
+    }
+
+    #[test]
+    fn test_degenerate_parts() {
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring, Transform::default());
+        enc.add_point(0.0, 0.0).unwrap();
+        enc.add_point(10.0, 0.0).unwrap();
+        enc.complete_geom().unwrap();
+        enc.add_point(50.0, 50.0).unwrap();
+        enc.complete_geom().unwrap();
+        enc.add_point(0.0, 5.0).unwrap();
+        enc.add_point(10.0, 5.0).unwrap();
+        let geom = enc.encode().unwrap();
+        assert!(geom.validate().is_ok());
+        assert_eq!(geom.to_string(), "M(0,0) L(10,0) M(-10,5) L(10,0)");
+        let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default());
+        enc.add_point(0.0, 0.0).unwrap();
+        enc.add_point(10.0, 0.0).unwrap();
+        enc.complete_geom().unwrap();
+        enc.add_point(0.0, 0.0).unwrap();
+        enc.add_point(10.0, 0.0).unwrap();
+        enc.add_point(10.0, 10.0).unwrap();
+        let geom = enc.encode().unwrap();
+        assert!(geom.validate().is_ok());
+        assert_eq!(geom.to_string(), "M(0,0) L(10,0) L(0,10) Z");
+        let enc =
+            GeomEncoder::<f64>::new(GeomType::Linestring, Transform::default());
+        assert!(enc.point(1.0, 1.0).unwrap().encode().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_clip() {
+        let enc = |geom_tp| {
+            GeomEncoder::new(geom_tp, Transform::default()).with_clip(100, 10)
+        };
+        let geom = enc(GeomType::Point)
+            .point(50.0, 50.0)
+            .unwrap()
+            .point(-20.0, 50.0)
+            .unwrap()
+            .point(105.0, 105.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.to_string(), "M(50,50) M(55,55)");
+        // leaves and re-enters the buffered tile: two parts
+        let geom = enc(GeomType::Linestring)
+            .point(50.0, 50.0)
+            .unwrap()
+            .point(150.0, 50.0)
+            .unwrap()
+            .point(150.0, 60.0)
+            .unwrap()
+            .point(50.0, 60.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.to_string(), "M(50,50) L(60,0) M(0,10) L(-60,0)");
+        let square =
+            [(-50.0, -50.0), (50.0, -50.0), (50.0, 50.0), (-50.0, 50.0)];
+        let mut polygon = enc(GeomType::Polygon);
+        for (x, y) in square {
+            polygon.add_point(x, y).unwrap();
+        }
+        let geom = polygon.encode().unwrap();
+        assert_eq!(geom.to_string(), "M(-10,-10) L(60,0) L(0,60) L(-60,0) Z");
+        let geom = enc(GeomType::Polygon)
+            .exterior_ring(&square)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert_eq!(geom.to_string(), "M(-10,-10) L(60,0) L(0,60) L(-60,0) Z");
+        let far = [(200.0, 200.0), (300.0, 200.0), (300.0, 300.0)];
+        let geom = enc(GeomType::Polygon).exterior_ring(&far).unwrap();
+        assert!(geom.encode().unwrap().is_empty());
+        assert!(enc(GeomType::Linestring).point(f64::NAN, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_degenerate_quantized() {
+        fn encode(
+            geom_tp: GeomType,
+            options: EncodeOptions,
+            parts: &[&[(f64, f64)]],
+        ) -> Result<(String, usize)> {
+            let mut enc = GeomEncoder::new(geom_tp, Transform::default())
+                .with_options(options);
+            for part in parts {
+                for (x, y) in part.iter() {
+                    enc.add_point(*x, *y)?;
+                }
+                enc.complete_geom()?;
+            }
+            let dropped = enc.dropped_parts();
+            Ok((enc.encode()?.to_string(), dropped))
+        }
+        let short: &[(f64, f64)] = &[(0.0, 0.0), (0.3, 0.2)];
+        let line: &[(f64, f64)] = &[(0.0, 0.0), (5.0, 0.0)];
+        let flat: &[(f64, f64)] = &[(0.0, 0.0), (5.0, 0.0), (10.0, 0.2)];
+        let tri: &[(f64, f64)] = &[(0.0, 0.0), (5.0, 0.0), (5.0, 5.0)];
+        let lenient = EncodeOptions::lenient();
+        let r = encode(GeomType::Linestring, lenient, &[short, line]);
+        assert_eq!(r.unwrap(), ("M(0,0) L(5,0)".to_string(), 1));
+        let r = encode(GeomType::Polygon, lenient, &[flat, tri]);
+        assert_eq!(r.unwrap(), ("M(0,0) L(5,0) L(0,5) Z".to_string(), 1));
+        // without degenerate check, only the point count is checked
+        let default = EncodeOptions::default();
+        let r = encode(GeomType::Polygon, default, &[flat, tri]);
+        assert_eq!(r.unwrap().1, 0);
+        let r = encode(GeomType::Linestring, default, &[&line[..1], line]);
+        assert_eq!(r.unwrap().1, 1);
+        let strict = EncodeOptions::strict();
+        let r = encode(GeomType::Linestring, strict, &[short, line]);
+        assert!(matches!(r, Err(Error::InvalidGeometry())));
+        let r = encode(GeomType::Polygon, strict, &[tri, flat]);
+        assert!(matches!(r, Err(Error::InvalidGeometry())));
+    }
+
+    #[test]
+    fn test_dedup() {
+        let pts = [(0.0, 0.0), (0.4, 0.2), (5.0, 0.0), (5.0, 5.0), (5.2, 4.9)];
+        let encode = |geom_tp, options| {
+            let mut enc = GeomEncoder::new(geom_tp, Transform::default())
+                .with_options(options);
+            for (x, y) in pts {
+                enc.add_point(x, y).unwrap();
+            }
+            enc.encode().unwrap().to_string()
+        };
+        let dedup = EncodeOptions::default().with_dedup(true);
+        assert_eq!(encode(GeomType::Linestring, dedup), "M(0,0) L(5,0) L(0,5)");
+        assert_eq!(
+            encode(GeomType::Linestring, EncodeOptions::default()),
+            "M(0,0) L(0,0) L(5,0) L(0,5) L(0,0)"
+        );
+        assert_eq!(encode(GeomType::Polygon, dedup), "M(0,0) L(5,0) L(0,5) Z");
+        // multipoint keeps duplicate points
+        assert_eq!(
+            encode(GeomType::Point, dedup),
+            "M(0,0) M(0,0) M(5,0) M(0,5) M(0,0)"
+        );
+    }
+
+    #[test]
+    fn test_ring_closing() {
+        let ring = [(5.0, 5.0), (15.0, 5.0), (15.0, 15.0), (5.0, 5.0)];
+        let encode = |options: EncodeOptions, ring: &[(f64, f64)]| {
+            let mut enc =
+                GeomEncoder::new(GeomType::Polygon, Transform::default())
+                    .with_options(options);
+            for _ in 0..2 {
+                for (x, y) in ring {
+                    enc.add_point(*x, *y).unwrap();
+                }
+                enc.complete_geom().unwrap();
+            }
+            enc.encode().unwrap()
+        };
+        let closing = EncodeOptions::default().with_ring_closing(true);
+        let geom = encode(closing, &ring);
+        assert!(geom.validate().is_ok());
+        // cursor is restored, so the second ring starts at (5,5) too
+        assert_eq!(
+            geom.to_string(),
+            "M(5,5) L(10,0) L(0,10) Z M(-10,-10) L(10,0) L(0,10) Z"
+        );
+        assert_eq!(encode(closing, &ring[..3]).to_string(), geom.to_string());
+        let geom = encode(EncodeOptions::default(), &ring);
+        assert_eq!(
+            geom.to_string(),
+            "M(5,5) L(10,0) L(0,10) L(-10,-10) Z M(0,0) L(10,0) L(0,10) L(-10,-10) Z"
+        );
+        // triangle with closing point becomes degenerate
+        let geom = encode(closing, &[(5.0, 5.0), (15.0, 5.0), (5.0, 5.0)]);
+        assert!(geom.is_empty());
+    }
+
+    #[test]
+    fn test_ring_closing_saturated() {
+        // deltas between these points saturate
+        let (a, b) = (-2e9, 2e9);
+        let ring = [(a, 0.0), (b, 0.0), (b, b), (a, 0.0)];
+        let options = EncodeOptions::default()
+            .with_ring_closing(true)
+            .with_degenerate(true);
+        let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .with_options(options);
+        for (x, y) in ring {
+            enc.add_point(x, y).unwrap();
+        }
+        enc.complete_geom().unwrap();
+        assert_eq!(enc.count, 0);
+        assert_eq!(enc.part.len(), 3);
+        // cursor is restored to the previous vertex
+        assert_eq!((enc.x, enc.y), (2_000_000_000, 2_000_000_000));
+    }
+
+    #[test]
+    fn test_rings() {
+        // both rings wound the "wrong" way, with closing points
+        let outer = [
+            (0.0, 0.0),
+            (0.0, 10.0),
+            (10.0, 10.0),
+            (10.0, 0.0),
+            (0.0, 0.0),
+        ];
+        let inner = [(2.0, 2.0), (8.0, 2.0), (8.0, 8.0), (2.0, 8.0)];
+        let geom = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .exterior_ring(&outer)
+            .unwrap()
+            .interior_ring(&inner)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert!(geom.validate().is_ok());
+        assert_eq!(
+            geom.to_string(),
+            "M(10,0) L(0,10) L(-10,0) L(0,-10) Z M(2,8) L(6,0) L(0,-6) L(-6,0) Z"
+        );
+        let data = geom.into_vec();
+        let polygons = GeomDecoder::<f64>::new(data)
+            .polygons(Winding::Area)
+            .unwrap();
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0].len(), 2);
+        let enc = GeomEncoder::new(GeomType::Linestring, Transform::default());
+        assert!(enc.exterior_ring(&outer).is_err());
+    }
+
+    #[test]
+    fn test_add_multipolygon() {
+        let polygons = vec![
+            vec![
+                vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)],
+                vec![(2.0, 2.0), (8.0, 2.0), (8.0, 8.0), (2.0, 8.0)],
+            ],
+            vec![],
+            vec![vec![(20.0, 0.0), (20.0, 10.0), (30.0, 0.0)]],
+        ];
+        let geom = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .multipolygon(&polygons)
+            .unwrap()
+            .encode()
+            .unwrap();
+        assert!(geom.validate().is_ok());
+        let decoded = GeomDecoder::<f64>::new(geom.clone().into_vec())
+            .polygons(Winding::Area)
+            .unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].len(), 2);
+        assert_eq!(decoded[1].len(), 1);
+        #[cfg(feature = "geo-types")]
+        {
+            use geo_types::{LineString, MultiPolygon, Polygon};
+            let mp = MultiPolygon(
+                polygons
+                    .iter()
+                    .filter(|rings| !rings.is_empty())
+                    .map(|rings| {
+                        let mut rings =
+                            rings.iter().map(|r| LineString::from(r.clone()));
+                        let exterior = rings.next().unwrap();
+                        Polygon::new(exterior, rings.collect())
+                    })
+                    .collect(),
+            );
+            let mut enc =
+                GeomEncoder::new(GeomType::Polygon, Transform::default());
+            enc.add_geo_multipolygon(&mp).unwrap();
+            assert_eq!(enc.encode().unwrap().into_vec(), geom.into_vec());
+        }
+        let enc = GeomEncoder::new(GeomType::Point, Transform::default());
+        assert!(enc.multipolygon(&polygons).is_err());
+    }
+
+    #[test]
+    fn test_decoder_polygons() {
+        let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default());
+        // exterior, interior, exterior
+        let rings: [&[(f64, f64)]; 3] = [
+            &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)],
+            &[(2.0, 2.0), (2.0, 8.0), (8.0, 8.0), (8.0, 2.0)],
+            &[(20.0, 0.0), (30.0, 0.0), (30.0, 10.0)],
+        ];
+        for ring in rings {
+            for (x, y) in ring {
+                enc.add_point(*x, *y).unwrap();
+            }
+            enc.complete_geom().unwrap();
+        }
+        let data = enc.encode().unwrap().into_vec();
+        let polygons = GeomDecoder::new(data.clone())
+            .with_transform(Transform::with_scale(0.5, 0.5))
+            .polygons(Winding::Version)
+            .unwrap();
+        assert_eq!(polygons.len(), 2);
+        assert_eq!(polygons[0].len(), 2);
+        assert_eq!(polygons[0][1][2], (4.0, 4.0));
+        assert_eq!(
+            polygons[1],
+            vec![vec![(10.0, 0.0), (15.0, 0.0), (15.0, 5.0)]]
+        );
+        // first ring is an interior ring with the area rule
+        let data = [&data[11..], &data[..11]].concat();
+        assert!(GeomDecoder::<f64>::new(data.clone())
+            .polygons(Winding::Area)
+            .is_err());
+        let polygons = GeomDecoder::<f64>::new(data)
+            .polygons(Winding::FirstRing)
+            .unwrap();
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0].len(), 3);
+    }
+
+    #[test]
+    fn test_count_vertices() {
+        assert_eq!(count_vertices(&[]).unwrap(), 0);
+        let data = [9, 4, 4, 18, 6, 0, 0, 2, 15, 9, 2, 2, 10, 1, 1];
+        assert_eq!(count_vertices(&data).unwrap(), 5);
+        assert_eq!(
+            count_vertices(&data).unwrap(),
+            decode_vertices(&data).unwrap().len()
+        );
+        assert!(matches!(
+            count_vertices(&[9, 4, 4, 26, 6, 0]),
+            Err(Error::InvalidGeometryAt(3))
+        ));
+        assert!(count_vertices(&[9, 4, 4, 23]).is_err());
+    }
+
+    #[test]
+    fn test_ellipse() {
+        use crate::options::ring_area;
+        let v = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .ellipse((0.0, 0.0), (10.0, 5.0), 4)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 20, 0, 26, 19, 10, 19, 9, 20, 9, 15));
+        let data = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .ellipse((0.0, 0.0), (-10.0, 5.0), 0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let parts = decode_parts(&data.into_vec()).unwrap();
+        assert!(ring_area(&parts[0]) > 0);
+        // pending part is completed first
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring, Transform::default());
+        enc.add_point(0.0, 0.0).unwrap();
+        enc.add_point(20.0, 0.0).unwrap();
+        enc.add_ellipse((0.0, 0.0), (10.0, 5.0), 4).unwrap();
+        enc.add_point(30.0, 0.0).unwrap();
+        enc.add_point(40.0, 0.0).unwrap();
+        enc.add_sector((0.0, 0.0), 10.0, 0.0, 1.0, 4).unwrap();
+        let parts = decode_parts(&enc.encode().unwrap().into_vec()).unwrap();
+        let lens: Vec<usize> = parts.iter().map(|p| p.len()).collect();
+        assert_eq!(lens, vec![2, 5, 2, 4]);
+    }
+
+    #[test]
+    fn test_sector() {
+        use crate::options::ring_area;
+        use std::f64::consts::FRAC_PI_2;
+        let v = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .sector((0.0, 0.0), 10.0, 0.0, FRAC_PI_2, 4)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 0, 0, 18, 20, 0, 19, 20, 15));
+        let flip = Transform::with_scale(1.0, -1.0);
+        for tp in [GeomType::Polygon, GeomType::Linestring] {
+            let data = GeomEncoder::new(tp, flip)
+                .sector((0.0, 0.0), 100.0, 0.0, FRAC_PI_2, 0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            assert!(data.validate().is_ok());
+            let parts = decode_parts(&data.into_vec()).unwrap();
+            assert!(ring_area(&parts[0]) > 0);
+        }
+        let enc = GeomEncoder::new(GeomType::Point, Transform::default());
+        assert!(enc.sector((0.0, 0.0), 1.0, 0.0, 1.0, 0).is_err());
+    }
+
+    #[test]
+    fn test_rect() {
+        use crate::options::ring_area;
+        use pointy::Pt;
+        let bbox = BBox::from((Pt::new(1.0, 2.0), Pt::new(5.0, 4.0)));
+        let v = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .rect(bbox)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 2, 4, 26, 8, 0, 0, 4, 7, 0, 15));
+        let flip = Transform::with_scale(1.0, -1.0);
+        let data = GeomEncoder::new(GeomType::Polygon, flip)
+            .rect(bbox)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let parts = decode_parts(&data.into_vec()).unwrap();
+        assert!(ring_area(&parts[0]) > 0);
+        let v = GeomEncoder::new(GeomType::Linestring, Transform::default())
+            .rect(bbox)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 2, 4, 34, 8, 0, 0, 4, 7, 0, 0, 3));
+        // pending part is completed first
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring, Transform::default());
+        enc.add_point(0.0, 0.0).unwrap();
+        enc.add_point(9.0, 0.0).unwrap();
+        enc.add_rect(bbox).unwrap();
+        let parts = decode_parts(&enc.encode().unwrap().into_vec()).unwrap();
+        let lens: Vec<usize> = parts.iter().map(|p| p.len()).collect();
+        assert_eq!(lens, vec![2, 5]);
+        let enc = GeomEncoder::new(GeomType::Point, Transform::default());
+        assert!(enc.rect(bbox).is_err());
+    }
+
+    #[test]
+    fn test_arc() {
+        let v = GeomEncoder::new(GeomType::Linestring, Transform::default())
+            .arc((0.0, 0.0), 10.0, 0.0, std::f64::consts::PI, 4)
+            .unwrap()
+            .encode()
+            .unwrap()
+            .into_vec();
+        assert_eq!(v, vec!(9, 20, 0, 18, 19, 20, 19, 19));
+    }
+
+    #[test]
+    fn test_arc_limits() {
+        // sweep is limited to one turn
+        for end in [1e9, -1e9, f64::MAX] {
+            let data =
+                GeomEncoder::new(GeomType::Linestring, Transform::default())
+                    .arc((0.0, 0.0), 100.0, 0.0, end, 16)
+                    .unwrap()
+                    .encode()
+                    .unwrap();
+            let parts = decode_parts(&data.into_vec()).unwrap();
+            assert_eq!(parts[0].len(), 17);
+        }
+        for end in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut enc =
+                GeomEncoder::new(GeomType::Linestring, Transform::default());
+            assert!(matches!(
+                enc.add_arc((0.0, 0.0), 100.0, 0.0, end, 16),
+                Err(Error::InvalidValue())
+            ));
+            assert!(matches!(
+                enc.add_arc((0.0, 0.0), 100.0, end, 0.0, 0),
+                Err(Error::InvalidValue())
+            ));
+        }
+    }
+
     #[test]
     fn test_multipolygon() {
         let v = GeomEncoder::new(GeomType::Polygon, Transform::default())
@@ -384,4 +2366,20 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn test_multipolygon_dropped_exterior() {
+        let flat = vec![(0.0, 0.0), (5.0, 0.0), (10.0, 0.2)];
+        let hole = vec![(2.0, 2.0), (3.0, 2.0), (3.0, 3.0)];
+        let tri = vec![(0.0, 0.0), (5.0, 0.0), (5.0, 5.0)];
+        let polygons = vec![vec![flat, hole.clone()], vec![tri, hole]];
+        let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .with_options(EncodeOptions::lenient());
+        enc.add_multipolygon(&polygons).unwrap();
+        assert_eq!(enc.dropped_parts(), 2);
+        assert_eq!(
+            enc.encode().unwrap().to_string(),
+            "M(0,0) L(5,0) L(0,5) Z M(-2,-2) L(0,-1) L(-1,0) Z"
+        );
+    }
 }
@@ -3,6 +3,19 @@
 // Copyright (c) 2019-2022  Minnesota Department of Transportation
 //
 use protobuf::Error as ProtobufError;
+use std::fmt;
+
+/// Location of a decoding error in an encoded tile.
+///
+/// See [Error::Decode].
+///
+/// [Error::Decode]: enum.Error.html#variant.Decode
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecodeLocation {
+    layer: usize,
+    feature: Option<usize>,
+    offset: usize,
+}
 
 /// MVT Error types
 #[non_exhaustive]
@@ -24,10 +37,84 @@ pub enum Error {
     #[error("Invalid geometry data")]
     InvalidGeometry(),
 
+    /// Invalid geometry command integer, at a position in the geometry data.
+    #[error("Invalid geometry data at position {0}")]
+    InvalidGeometryAt(usize),
+
+    /// A GeoPackage geometry blob (or its WKB) is malformed or unsupported.
+    #[error("Invalid GeoPackage geometry")]
+    InvalidGpkgGeometry(),
+
+    /// A GeoPackage table is not a feature table, or has no primary key.
+    #[cfg(feature = "rusqlite")]
+    #[error("Invalid GeoPackage table")]
+    InvalidGpkgTable(),
+
+    /// An elevation grid has invalid dimensions or contour interval.
+    #[error("Invalid elevation grid")]
+    InvalidGrid(),
+
     /// Invalid float value
     #[error("Invalid float value")]
     InvalidValue(),
 
+    /// A string value is too long.
+    #[error("String too long")]
+    StringTooLong(),
+
+    /// An encoded layer is too large.
+    #[error("Layer too large")]
+    LayerTooLarge(),
+
+    /// An encoded layer is malformed.
+    #[error("Invalid encoded layer")]
+    InvalidLayer(),
+
+    /// A decode limit was exceeded.
+    #[error("Decode limit exceeded: {0}")]
+    LimitExceeded(&'static str),
+
+    /// A tile QA assertion failed.
+    #[error("Tile assertion failed: {0}")]
+    AssertionFailed(String),
+
+    /// Decoding failed at a location in an encoded tile.
+    #[error("Decode error at {0}: {1}")]
+    Decode(DecodeLocation, Box<Error>),
+
+    /// Deserializing feature properties failed.
+    #[error("Deserialize error: {0}")]
+    Deserialize(String),
+
+    /// Encoding was cancelled.
+    #[error("Encoding cancelled")]
+    Cancelled(),
+
+    /// Invalid stream frame.
+    #[error("Invalid frame")]
+    InvalidFrame(),
+
+    /// I/O error.
+    #[error("IO error {0}")]
+    Io(#[from] std::io::Error),
+
+    /// SQLite error.
+    #[cfg(feature = "rusqlite")]
+    #[error("SQLite error {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    /// Invalid tile archive.
+    #[error("Invalid tile archive")]
+    InvalidArchive(),
+
+    /// Unsupported compression in a tile archive.
+    #[error("Unsupported compression")]
+    UnsupportedCompression(),
+
+    /// Different content with the same digest.
+    #[error("Digest collision")]
+    DigestCollision(),
+
     /// Error while encoding protobuf data.
     #[error("Protobuf error {0}")]
     Protobuf(#[from] ProtobufError),
@@ -35,3 +122,54 @@ pub enum Error {
 
 /// MVT Result
 pub type Result<T> = std::result::Result<T, Error>;
+
+impl DecodeLocation {
+    /// Create a decode location
+    pub(crate) fn new(
+        layer: usize,
+        feature: Option<usize>,
+        offset: usize,
+    ) -> Self {
+        DecodeLocation {
+            layer,
+            feature,
+            offset,
+        }
+    }
+
+    /// Get the index of the layer being decoded.
+    pub fn layer(&self) -> usize {
+        self.layer
+    }
+
+    /// Get the index of the feature being decoded (within its layer).
+    pub fn feature(&self) -> Option<usize> {
+        self.feature
+    }
+
+    /// Get the byte offset (in the encoded tile) of the layer or feature
+    /// being decoded.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for DecodeLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "layer {}", self.layer)?;
+        if let Some(feature) = self.feature {
+            write!(f, ", feature {feature}")?;
+        }
+        write!(f, ", byte {}", self.offset)
+    }
+}
+
+impl Error {
+    /// Add a decode location, unless the error already has one
+    pub(crate) fn at(self, loc: DecodeLocation) -> Self {
+        match self {
+            Error::Decode(..) => self,
+            _ => Error::Decode(loc, Box::new(self)),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+// exactness.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Round-trip error of projecting points to tile units.
+//!
+use crate::error::Result;
+use crate::geometry::haversine_m;
+use crate::mapgrid::{MapConst, MapGrid, TileId};
+use crate::precise::PreciseTransform;
+use std::f64::consts::PI;
+
+/// Round-trip error of a point through tile units.
+///
+/// Compares each transform path against [PreciseTransform], to help choose
+/// the precision needed for a zoom level and latitude.
+///
+/// # Example
+/// ```
+/// # use mvt::{round_trip_error, Error};
+/// # fn main() -> Result<(), Error> {
+/// let err = round_trip_error((-93.2, 44.9), 14, 4096)?;
+/// assert!(!err.needs_precise());
+/// let err = round_trip_error((-93.2, 44.9), 20, 4096)?;
+/// assert!(err.needs_f64());
+/// # Ok(()) }
+/// ```
+///
+/// [PreciseTransform]: struct.PreciseTransform.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoundTripError {
+    /// Ground distance after quantizing (meters)
+    quantize_m: f64,
+
+    /// Error of `f64` tile transform (tile units)
+    f64_units: f64,
+
+    /// Error of `f32` tile transform (tile units)
+    f32_units: f64,
+}
+
+impl RoundTripError {
+    /// Get the ground distance (meters) between a point and the same point
+    /// after quantizing to integer tile units and back to lon/lat.
+    pub fn quantize_m(&self) -> f64 {
+        self.quantize_m
+    }
+
+    /// Get the error of [MapGrid::tile_transform] with `f64` (tile units).
+    ///
+    /// [MapGrid::tile_transform]: struct.MapGrid.html#method.tile_transform
+    pub fn f64_units(&self) -> f64 {
+        self.f64_units
+    }
+
+    /// Get the error of [MapGrid::tile_transform] with `f32` (tile units).
+    ///
+    /// [MapGrid::tile_transform]: struct.MapGrid.html#method.tile_transform
+    pub fn f32_units(&self) -> f64 {
+        self.f32_units
+    }
+
+    /// Check whether `f32` can round to the wrong tile unit.
+    pub fn needs_f64(&self) -> bool {
+        self.f32_units >= 0.5
+    }
+
+    /// Check whether `f64` can round to the wrong tile unit, so that
+    /// [PreciseTransform] is needed.
+    ///
+    /// [PreciseTransform]: struct.PreciseTransform.html
+    pub fn needs_precise(&self) -> bool {
+        self.f64_units >= 0.5
+    }
+}
+
+/// Project Web Mercator longitude / latitude (degrees) to meters
+fn mercator_m((lon, lat): (f64, f64)) -> (f64, f64) {
+    let r = f64::HALF_SIZE_M / PI;
+    let lat = lat.clamp(-85.051_128_779_806_6, 85.051_128_779_806_6);
+    (lon.to_radians() * r, lat.to_radians().tan().asinh() * r)
+}
+
+/// Find the tile containing a longitude / latitude (degrees)
+fn containing_tile(lonlat: (f64, f64), z: u32) -> Result<TileId> {
+    let (x, y) = TileId::new(0, 0, z)?.tile_xy(lonlat.0, lonlat.1);
+    let n = 1u32 << z;
+    let clamp = |v: f64| (v.floor().max(0.0) as u32).min(n - 1);
+    TileId::new(clamp(x), clamp(y), z)
+}
+
+/// Calculate the round-trip error of a point at a zoom level.
+///
+/// * `lonlat` Web Mercator longitude / latitude (degrees).
+/// * `z` Zoom level; the tile containing the point is used.
+/// * `extent` Tile extent.
+///
+/// Returns [Error::InvalidTid] if `z` is greater than 31.
+///
+/// [Error::InvalidTid]: enum.Error.html#variant.InvalidTid
+pub fn round_trip_error(
+    lonlat: (f64, f64),
+    z: u32,
+    extent: u32,
+) -> Result<RoundTripError> {
+    let tid = containing_tile(lonlat, z)?;
+    let (mx, my) = mercator_m(lonlat);
+    let precise = PreciseTransform::new(&MapGrid::default(), tid, extent);
+    let (px, py) = precise.transform(mx, my);
+    let ext = f64::from(extent);
+    let p = MapGrid::<f64>::default().tile_transform(tid) * (mx, my);
+    let f64_units = (p.x() * ext - px).hypot(p.y() * ext - py);
+    let p =
+        MapGrid::<f32>::default().tile_transform(tid) * (mx as f32, my as f32);
+    let f32_units =
+        (f64::from(p.x()) * ext - px).hypot(f64::from(p.y()) * ext - py);
+    let (qx, qy) = precise.quantize(mx, my)?;
+    let back = tid.lonlat(f64::from(qx) / ext, f64::from(qy) / ext);
+    let quantize_m = haversine_m(lonlat, back);
+    Ok(RoundTripError {
+        quantize_m,
+        f64_units,
+        f32_units,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_containing_tile() {
+        let tid = containing_tile((-93.2, 44.9), 10).unwrap();
+        assert_eq!((tid.x(), tid.y()), (246, 368));
+        let tid = containing_tile((180.0, -90.0), 2).unwrap();
+        assert_eq!((tid.x(), tid.y()), (3, 3));
+        assert!(containing_tile((0.0, 0.0), 32).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_error() {
+        let lonlat = (-93.2, 44.9);
+        let equator_m = f64::HALF_SIZE_M * 2.0;
+        for z in 0..=31 {
+            let err = round_trip_error(lonlat, z, 4096).unwrap();
+            // within one tile unit of the point
+            let unit_m = equator_m / f64::from(1u32 << z) / 4096.0;
+            assert!(err.quantize_m() < unit_m);
+            assert_eq!(err.needs_f64(), z >= 14);
+            assert!(!err.needs_precise());
+        }
+    }
+}
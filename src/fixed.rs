@@ -0,0 +1,516 @@
+// fixed.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Fixed-point (integer) geometry pipeline.
+//!
+use crate::cancel::{check_cancel, CancelToken};
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::{Error, Result};
+use crate::precise::PreciseTransform;
+use pointy::{Float, Transform};
+
+/// Integer point in tile units
+type IPt = (i32, i32);
+
+/// All-integer geometry pipeline.
+///
+/// Input is quantized to tile units first; clipping and simplification then
+/// happen entirely in integer arithmetic.  Unlike float processing, output is
+/// bit-reproducible across platforms and compilers.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, FixedEncoder};
+/// # use pointy::Transform;
+/// # fn main() -> Result<(), Error> {
+/// let enc = FixedEncoder::new(4096).with_buffer(64).with_tolerance(2);
+/// let line = enc.quantize(&Transform::default(), &[(-500.0, 10.0), (5000.0, 10.0)])?;
+/// let geom_data = enc.encode_lines(&[line])?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FixedEncoder {
+    extent: i32,
+    buffer: i32,
+    tolerance: i32,
+    cancel: Option<CancelToken>,
+}
+
+/// Integer clip rectangle
+#[derive(Clone, Copy, Debug)]
+struct IRect {
+    x_min: i32,
+    y_min: i32,
+    x_max: i32,
+    y_max: i32,
+}
+
+/// Divide, rounding half away from zero
+fn div_round(n: i64, d: i64) -> i64 {
+    let (n, d) = if d < 0 { (-n, -d) } else { (n, d) };
+    if n >= 0 {
+        (n + d / 2) / d
+    } else {
+        (n - d / 2) / d
+    }
+}
+
+/// Find Y of a segment at given X (clamped to segment range)
+fn ilerp_y(a: IPt, b: IPt, x: i32) -> i32 {
+    let dx = i64::from(b.0) - i64::from(a.0);
+    let dy = i64::from(b.1) - i64::from(a.1);
+    let y =
+        i64::from(a.1) + div_round(dy * (i64::from(x) - i64::from(a.0)), dx);
+    y.clamp(i64::from(a.1.min(b.1)), i64::from(a.1.max(b.1))) as i32
+}
+
+/// Find X of a segment at given Y (clamped to segment range)
+fn ilerp_x(a: IPt, b: IPt, y: i32) -> i32 {
+    ilerp_y((a.1, a.0), (b.1, b.0), y)
+}
+
+/// Calculate twice the signed area of a ring
+fn ring_area(ring: &[IPt]) -> i128 {
+    let mut area = 0;
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        area += i128::from(a.0) * i128::from(b.1)
+            - i128::from(b.0) * i128::from(a.1);
+    }
+    area
+}
+
+impl IRect {
+    /// Get outcode of a point (Cohen-Sutherland)
+    fn outcode(&self, p: IPt) -> u8 {
+        let mut code = 0;
+        if p.0 < self.x_min {
+            code |= 1;
+        } else if p.0 > self.x_max {
+            code |= 2;
+        }
+        if p.1 < self.y_min {
+            code |= 4;
+        } else if p.1 > self.y_max {
+            code |= 8;
+        }
+        code
+    }
+
+    /// Move an outside point onto the rectangle along a segment
+    fn clip_point(&self, p: IPt, q: IPt, code: u8) -> IPt {
+        if code & 1 != 0 {
+            (self.x_min, ilerp_y(p, q, self.x_min))
+        } else if code & 2 != 0 {
+            (self.x_max, ilerp_y(p, q, self.x_max))
+        } else if code & 4 != 0 {
+            (ilerp_x(p, q, self.y_min), self.y_min)
+        } else {
+            (ilerp_x(p, q, self.y_max), self.y_max)
+        }
+    }
+
+    /// Clip a segment (Cohen-Sutherland)
+    fn clip_segment(&self, mut a: IPt, mut b: IPt) -> Option<(IPt, IPt)> {
+        // each step moves an end point onto an edge, so 8 steps is enough
+        for _ in 0..8 {
+            let (ca, cb) = (self.outcode(a), self.outcode(b));
+            if ca | cb == 0 {
+                return Some((a, b));
+            }
+            if ca & cb != 0 {
+                return None;
+            }
+            if ca != 0 {
+                a = self.clip_point(a, b, ca);
+            } else {
+                b = self.clip_point(b, a, cb);
+            }
+        }
+        None
+    }
+
+    /// Clip a ring (Sutherland-Hodgman)
+    fn clip_ring(&self, ring: &[IPt]) -> Vec<IPt> {
+        let mut pts = ring.to_vec();
+        for edge in 0..4 {
+            let inside = |p: IPt| match edge {
+                0 => p.0 >= self.x_min,
+                1 => p.0 <= self.x_max,
+                2 => p.1 >= self.y_min,
+                _ => p.1 <= self.y_max,
+            };
+            let cross = |a: IPt, b: IPt| match edge {
+                0 => (self.x_min, ilerp_y(a, b, self.x_min)),
+                1 => (self.x_max, ilerp_y(a, b, self.x_max)),
+                2 => (ilerp_x(a, b, self.y_min), self.y_min),
+                _ => (ilerp_x(a, b, self.y_max), self.y_max),
+            };
+            let mut out = Vec::with_capacity(pts.len() + 4);
+            let mut prev = match pts.last() {
+                Some(p) => *p,
+                None => break,
+            };
+            for cur in pts.iter().copied() {
+                if inside(cur) {
+                    if !inside(prev) {
+                        out.push(cross(prev, cur));
+                    }
+                    out.push(cur);
+                } else if inside(prev) {
+                    out.push(cross(prev, cur));
+                }
+                prev = cur;
+            }
+            pts = out;
+        }
+        pts
+    }
+}
+
+/// Get distance of a point from a segment's line, as a fraction.
+///
+/// Returns (numerator, denominator) of the squared distance.
+fn distance2(p: IPt, a: IPt, b: IPt) -> (i128, i128) {
+    let d = |p: IPt, q: IPt| {
+        (
+            i128::from(q.0) - i128::from(p.0),
+            i128::from(q.1) - i128::from(p.1),
+        )
+    };
+    let ((dx, dy), (px, py)) = (d(a, b), d(a, p));
+    let len2 = dx * dx + dy * dy;
+    if len2 == 0 {
+        (px * px + py * py, 1)
+    } else {
+        let cross = dx * py - dy * px;
+        (cross * cross, len2)
+    }
+}
+
+/// Simplify a path (Douglas-Peucker), keeping end points
+fn simplify(
+    pts: &[IPt],
+    tolerance: i32,
+    cancel: Option<&CancelToken>,
+) -> Result<Vec<IPt>> {
+    if tolerance <= 0 || pts.len() < 3 {
+        return Ok(pts.to_vec());
+    }
+    let tol2 = i128::from(tolerance) * i128::from(tolerance);
+    let mut keep = vec![false; pts.len()];
+    keep[0] = true;
+    keep[pts.len() - 1] = true;
+    let mut stack = vec![(0, pts.len() - 1)];
+    while let Some((i0, i1)) = stack.pop() {
+        check_cancel(cancel)?;
+        let (a, b) = (pts[i0], pts[i1]);
+        let mut best: Option<(usize, i128)> = None;
+        let mut den = 1;
+        for (i, p) in pts.iter().enumerate().take(i1).skip(i0 + 1) {
+            let (num, d) = distance2(*p, a, b);
+            den = d;
+            if best.is_none_or(|(_, n)| num > n) {
+                best = Some((i, num));
+            }
+        }
+        if let Some((i, num)) = best {
+            if num > tol2 * den {
+                keep[i] = true;
+                stack.push((i0, i));
+                stack.push((i, i1));
+            }
+        }
+    }
+    Ok(pts
+        .iter()
+        .zip(keep)
+        .filter_map(|(p, k)| k.then_some(*p))
+        .collect())
+}
+
+impl FixedEncoder {
+    /// Create a new fixed-point encoder.
+    ///
+    /// * `extent` Height / width of tile bounds.
+    pub fn new(extent: u32) -> Self {
+        FixedEncoder {
+            extent: extent.min(i32::MAX as u32) as i32,
+            buffer: 0,
+            tolerance: 0,
+            cancel: None,
+        }
+    }
+
+    /// Set the buffer around the tile for clipping (tile units).
+    pub fn with_buffer(mut self, buffer: u32) -> Self {
+        self.buffer = buffer.min(i32::MAX as u32 / 4) as i32;
+        self
+    }
+
+    /// Set the simplification tolerance (tile units, 0 for none).
+    pub fn with_tolerance(mut self, tolerance: u32) -> Self {
+        self.tolerance = tolerance.min(i32::MAX as u32) as i32;
+        self
+    }
+
+    /// Set a token for cancelling encoding.
+    ///
+    /// Once cancelled, encoding returns [Error::Cancelled].
+    ///
+    /// [Error::Cancelled]: enum.Error.html#variant.Cancelled
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Create a geometry encoder
+    fn encoder(&self, geom_tp: GeomType) -> GeomEncoder<f64> {
+        let enc = GeomEncoder::new(geom_tp, Transform::default());
+        match &self.cancel {
+            Some(cancel) => enc.with_cancel(cancel.clone()),
+            None => enc,
+        }
+    }
+
+    /// Get the clip rectangle
+    fn rect(&self) -> IRect {
+        IRect {
+            x_min: -self.buffer,
+            y_min: -self.buffer,
+            x_max: self.extent.saturating_add(self.buffer),
+            y_max: self.extent.saturating_add(self.buffer),
+        }
+    }
+
+    /// Quantize points to tile units.
+    ///
+    /// * `transform` Transform to tile coördinates.
+    /// * `pts` Points (before transform).
+    ///
+    /// Consecutive duplicate points are removed.  Returns an error if a
+    /// point is not finite or out of `i32` range.
+    pub fn quantize<F>(
+        &self,
+        transform: &Transform<F>,
+        pts: &[(F, F)],
+    ) -> Result<Vec<(i32, i32)>>
+    where
+        F: Float,
+    {
+        let mut out = Vec::with_capacity(pts.len());
+        for p in pts {
+            let p = *transform * *p;
+            let x = p.x().round().to_i32().ok_or(Error::InvalidValue())?;
+            let y = p.y().round().to_i32().ok_or(Error::InvalidValue())?;
+            out.push((x, y));
+        }
+        out.dedup();
+        Ok(out)
+    }
+
+    /// Quantize points to tile units, with extended precision.
+    ///
+    /// * `transform` Precise transform to tile units.
+    /// * `pts` Points in map coördinates.
+    ///
+    /// Use this instead of [quantize] when plain `f64` arithmetic cannot
+    /// give sub-tile-unit accuracy (z23+).  Consecutive duplicate points are
+    /// removed.  Returns an error if a point is not finite or out of `i32`
+    /// range.
+    ///
+    /// [quantize]: #method.quantize
+    pub fn quantize_precise(
+        &self,
+        transform: &PreciseTransform,
+        pts: &[(f64, f64)],
+    ) -> Result<Vec<(i32, i32)>> {
+        let mut out = Vec::with_capacity(pts.len());
+        for (x, y) in pts {
+            out.push(transform.quantize(*x, *y)?);
+        }
+        out.dedup();
+        Ok(out)
+    }
+
+    /// Clip, simplify and encode points.
+    ///
+    /// Points outside the buffered tile are dropped.
+    pub fn encode_points(&self, pts: &[(i32, i32)]) -> Result<GeomData> {
+        let rect = self.rect();
+        let mut enc = self.encoder(GeomType::Point);
+        for p in pts.iter().filter(|p| rect.outcode(**p) == 0) {
+            enc.add_point(f64::from(p.0), f64::from(p.1))?;
+        }
+        enc.encode()
+    }
+
+    /// Clip, simplify and encode linestrings.
+    pub fn encode_lines(&self, lines: &[Vec<(i32, i32)>]) -> Result<GeomData> {
+        let rect = self.rect();
+        let mut enc = self.encoder(GeomType::Linestring);
+        for line in lines {
+            check_cancel(self.cancel.as_ref())?;
+            let mut pieces: Vec<Vec<IPt>> = vec![];
+            let mut joined = false;
+            for seg in line.windows(2) {
+                match rect.clip_segment(seg[0], seg[1]) {
+                    Some((a, b)) => {
+                        match pieces.last_mut() {
+                            Some(piece) if joined => piece.push(b),
+                            _ => pieces.push(vec![a, b]),
+                        }
+                        joined = b == seg[1];
+                    }
+                    None => joined = false,
+                }
+            }
+            for mut piece in pieces {
+                piece.dedup();
+                let piece =
+                    simplify(&piece, self.tolerance, self.cancel.as_ref())?;
+                if piece.len() >= 2 {
+                    for p in piece {
+                        enc.add_point(f64::from(p.0), f64::from(p.1))?;
+                    }
+                    enc.complete_geom()?;
+                }
+            }
+        }
+        enc.encode()
+    }
+
+    /// Clip, simplify and encode polygon rings.
+    ///
+    /// Rings which become degenerate (zero area) are dropped.
+    pub fn encode_polygon(
+        &self,
+        rings: &[Vec<(i32, i32)>],
+    ) -> Result<GeomData> {
+        let rect = self.rect();
+        let mut enc = self.encoder(GeomType::Polygon);
+        for ring in rings {
+            check_cancel(self.cancel.as_ref())?;
+            let mut ring = rect.clip_ring(ring);
+            ring.dedup();
+            while ring.len() > 1 && ring.first() == ring.last() {
+                ring.pop();
+            }
+            if let Some(first) = ring.first().copied() {
+                // simplify as a path closed back to the first point
+                ring.push(first);
+                ring = simplify(&ring, self.tolerance, self.cancel.as_ref())?;
+                ring.pop();
+            }
+            if ring.len() >= 3 && ring_area(&ring) != 0 {
+                for p in ring {
+                    enc.add_point(f64::from(p.0), f64::from(p.1))?;
+                }
+                enc.complete_geom()?;
+            }
+        }
+        enc.encode()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::decode_vertices;
+
+    #[test]
+    fn test_div_round() {
+        assert_eq!(div_round(5, 2), 3);
+        assert_eq!(div_round(-5, 2), -3);
+        assert_eq!(div_round(4, -3), -1);
+        assert_eq!(div_round(7, 3), 2);
+    }
+
+    #[test]
+    fn test_clip_segment() {
+        let r = FixedEncoder::new(100).rect();
+        assert_eq!(
+            r.clip_segment((-50, 0), (50, 100)),
+            Some(((0, 50), (50, 100)))
+        );
+        assert_eq!(r.clip_segment((-50, 0), (-1, 100)), None);
+        assert_eq!(
+            r.clip_segment((-10, 50), (110, 53)),
+            Some(((0, 50), (100, 53)))
+        );
+    }
+
+    #[test]
+    fn test_simplify() {
+        let pts = [(0, 0), (5, 1), (10, 0), (15, 8), (20, 0)];
+        let simp = |tol| simplify(&pts, tol, None).unwrap();
+        assert_eq!(simp(2), vec![(0, 0), (10, 0), (15, 8), (20, 0)]);
+        assert_eq!(simp(10), vec![(0, 0), (20, 0)]);
+        assert_eq!(simp(0), pts.to_vec());
+        let token = CancelToken::new();
+        token.cancel();
+        assert!(matches!(
+            simplify(&pts, 2, Some(&token)),
+            Err(Error::Cancelled())
+        ));
+    }
+
+    #[test]
+    fn test_encode_lines() {
+        let enc = FixedEncoder::new(4096).with_buffer(10);
+        let t = Transform::with_scale(0.5, 0.5);
+        let line = enc
+            .quantize(
+                &t,
+                &[(-100.0, 20.0), (0.0, 20.0), (0.4, 20.0), (9000.0, 20.0)],
+            )
+            .unwrap();
+        assert_eq!(line, vec![(-50, 10), (0, 10), (4500, 10)]);
+        let data = enc.encode_lines(&[line]).unwrap().into_vec();
+        let verts = decode_vertices(&data).unwrap();
+        assert_eq!(verts, vec![(-10, 10), (0, 10), (4106, 10)]);
+    }
+
+    #[test]
+    fn test_quantize_precise() {
+        let grid = crate::mapgrid::MapGrid::default();
+        let tid = crate::mapgrid::TileId::new(1, 1, 1).unwrap();
+        let t = PreciseTransform::new(&grid, tid, 4096);
+        let enc = FixedEncoder::new(4096);
+        let pts = [(0.0, 0.0), (1.0, -1.0), (10_018_754.171_394_624, 0.0)];
+        let line = enc.quantize_precise(&t, &pts).unwrap();
+        assert_eq!(line, vec![(0, 0), (2048, 0)]);
+        assert!(enc.quantize_precise(&t, &[(f64::INFINITY, 0.0)]).is_err());
+    }
+
+    #[test]
+    fn test_encode_polygon() {
+        let enc = FixedEncoder::new(100);
+        let ring = vec![(50, 50), (150, 50), (150, 150), (50, 150)];
+        let data = enc.encode_polygon(&[ring]).unwrap().into_vec();
+        let mut verts = decode_vertices(&data).unwrap();
+        verts.sort();
+        assert_eq!(verts, vec![(50, 50), (50, 100), (100, 50), (100, 100)]);
+        let flat = vec![(10, 10), (20, 10), (30, 10)];
+        assert!(enc.encode_polygon(&[flat]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancel() {
+        let token = CancelToken::new();
+        let enc = FixedEncoder::new(100).with_cancel(token.clone());
+        let lines = [vec![(0, 0), (50, 50), (100, 0)]];
+        assert!(enc.encode_lines(&lines).is_ok());
+        token.cancel();
+        assert!(matches!(enc.encode_lines(&lines), Err(Error::Cancelled())));
+        assert!(matches!(
+            enc.encode_polygon(&lines),
+            Err(Error::Cancelled())
+        ));
+        assert!(matches!(
+            enc.encode_points(&[(1, 1)]),
+            Err(Error::Cancelled())
+        ));
+    }
+}
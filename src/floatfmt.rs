@@ -0,0 +1,114 @@
+// floatfmt.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Float formatting for text exports.
+//!
+use std::fmt::Write;
+
+/// Formatting of float values for text exports (such as GeoJSON or SVG).
+///
+/// By default, the shortest text which parses back to the same value is
+/// written.  Limiting decimal places keeps exports compact, and rounding is
+/// exact (from the binary value), so output is identical on all platforms.
+/// Negative zero is written as `0`, and non-finite values as `null`.
+///
+/// # Example
+/// ```
+/// use mvt::FloatFormat;
+///
+/// let fmt = FloatFormat::default().with_decimals(3);
+/// assert_eq!(fmt.format(-93.2000001), "-93.200");
+/// let fmt = fmt.with_trim_zeros(true);
+/// assert_eq!(fmt.format(-93.2000001), "-93.2");
+/// assert_eq!(fmt.format(-0.0001), "0");
+/// assert_eq!(fmt.format(f64::NAN), "null");
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FloatFormat {
+    /// Number of decimal places (shortest round-trip if `None`)
+    decimals: Option<u8>,
+
+    /// Trim trailing zeros after the decimal point
+    trim_zeros: bool,
+}
+
+impl FloatFormat {
+    /// Set the number of decimal places.
+    pub fn with_decimals(mut self, decimals: u8) -> Self {
+        self.decimals = Some(decimals);
+        self
+    }
+
+    /// Enable or disable trimming trailing zeros after the decimal point.
+    ///
+    /// If all decimal places are trimmed, the decimal point is also removed.
+    pub fn with_trim_zeros(mut self, trim_zeros: bool) -> Self {
+        self.trim_zeros = trim_zeros;
+        self
+    }
+
+    /// Format a value.
+    pub fn format(&self, v: f64) -> String {
+        let mut out = String::new();
+        self.write_to(&mut out, v);
+        out
+    }
+
+    /// Append a formatted value to a string.
+    pub fn write_to(&self, out: &mut String, v: f64) {
+        if !v.is_finite() {
+            out.push_str("null");
+            return;
+        }
+        let start = out.len();
+        let _ = match self.decimals {
+            Some(d) => write!(out, "{:.*}", usize::from(d), v),
+            None => write!(out, "{v}"),
+        };
+        if self.trim_zeros && out[start..].contains('.') {
+            let len = out.trim_end_matches('0').trim_end_matches('.').len();
+            out.truncate(len);
+        }
+        // rounding can leave negative zero ("-0" or "-0.00")
+        if out[start..].starts_with('-')
+            && out[start + 1..].bytes().all(|b| b == b'0' || b == b'.')
+        {
+            out.remove(start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        let fmt = FloatFormat::default();
+        assert_eq!(fmt.format(1.0), "1");
+        assert_eq!(fmt.format(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(fmt.format(-0.0), "0");
+        assert_eq!(fmt.format(1e21), "1000000000000000000000");
+        assert_eq!(fmt.format(f64::INFINITY), "null");
+    }
+
+    #[test]
+    fn test_decimals() {
+        let fmt = FloatFormat::default().with_decimals(2);
+        assert_eq!(fmt.format(1.0), "1.00");
+        assert_eq!(fmt.format(2.675), "2.67");
+        assert_eq!(fmt.format(-0.004), "0.00");
+        assert_eq!(fmt.with_decimals(0).format(44.9), "45");
+        let fmt = fmt.with_trim_zeros(true);
+        assert_eq!(fmt.format(1.0), "1");
+        assert_eq!(fmt.format(10.0), "10");
+        assert_eq!(fmt.format(0.5), "0.5");
+        assert_eq!(fmt.format(-0.004), "0");
+        let mut out = String::from("[");
+        fmt.write_to(&mut out, -93.25);
+        out.push(',');
+        fmt.write_to(&mut out, 44.9);
+        assert_eq!(out, "[-93.25,44.9");
+    }
+}
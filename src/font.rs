@@ -0,0 +1,138 @@
+// font.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Tiny stroke font, for rendering text as linestrings.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::Result;
+use pointy::Transform;
+
+/// Glyph cell width
+const CELL_WIDTH: f64 = 4.0;
+
+/// Glyph cell height
+const CELL_HEIGHT: f64 = 6.0;
+
+/// Horizontal advance between glyphs
+const ADVANCE: f64 = 6.0;
+
+/// Glyph strokes, in a 4x6 cell with Y down
+type Glyph = &'static [&'static [(u8, u8)]];
+
+/// Get the strokes for a character
+fn glyph(c: char) -> Glyph {
+    match c.to_ascii_uppercase() {
+        '0' => &[&[(0, 0), (4, 0), (4, 6), (0, 6), (0, 0)], &[(4, 0), (0, 6)]],
+        '1' => &[&[(1, 1), (2, 0), (2, 6)], &[(1, 6), (3, 6)]],
+        '2' => &[&[(0, 0), (4, 0), (4, 3), (0, 3), (0, 6), (4, 6)]],
+        '3' => &[&[(0, 0), (4, 0), (4, 6), (0, 6)], &[(0, 3), (4, 3)]],
+        '4' => &[&[(0, 0), (0, 3), (4, 3)], &[(4, 0), (4, 6)]],
+        '5' => &[&[(4, 0), (0, 0), (0, 3), (4, 3), (4, 6), (0, 6)]],
+        '6' => &[&[(4, 0), (0, 0), (0, 6), (4, 6), (4, 3), (0, 3)]],
+        '7' => &[&[(0, 0), (4, 0), (4, 6)]],
+        '8' => &[&[(0, 0), (4, 0), (4, 6), (0, 6), (0, 0)], &[(0, 3), (4, 3)]],
+        '9' => &[&[(4, 3), (0, 3), (0, 0), (4, 0), (4, 6), (0, 6)]],
+        'A' => &[&[(0, 6), (0, 0), (4, 0), (4, 6)], &[(0, 3), (4, 3)]],
+        'B' => &[
+            &[(0, 0), (0, 6), (4, 6), (4, 3), (0, 3)],
+            &[(0, 0), (3, 0), (3, 3)],
+        ],
+        'C' => &[&[(4, 0), (0, 0), (0, 6), (4, 6)]],
+        'D' => &[&[(0, 0), (3, 0), (4, 1), (4, 5), (3, 6), (0, 6), (0, 0)]],
+        'E' => &[&[(4, 0), (0, 0), (0, 6), (4, 6)], &[(0, 3), (3, 3)]],
+        'F' => &[&[(4, 0), (0, 0), (0, 6)], &[(0, 3), (3, 3)]],
+        '/' => &[&[(0, 6), (4, 0)]],
+        '-' => &[&[(1, 3), (3, 3)]],
+        '.' => &[&[(2, 5), (2, 6)]],
+        ':' => &[&[(2, 1), (2, 2)], &[(2, 4), (2, 5)]],
+        _ => &[],
+    }
+}
+
+/// Get the width of rendered text.
+///
+/// * `text` Text to measure.
+/// * `height` Glyph height (tile units).
+pub fn text_width(text: &str, height: f64) -> f64 {
+    let scale = height / CELL_HEIGHT;
+    match text.chars().count() {
+        0 => 0.0,
+        n => ((n - 1) as f64 * ADVANCE + CELL_WIDTH) * scale,
+    }
+}
+
+/// Render text as multi-linestring geometry, using a tiny stroke font.
+///
+/// Supported characters are digits, hex letters `A`-`F` (either case),
+/// `/`, `-`, `.` and `:`; others are rendered as blank space.  This is
+/// enough for tile IDs and coordinates, for clients without a symbol layer.
+///
+/// * `text` Text to render.
+/// * `x` Left edge (tile units).
+/// * `y` Top edge (tile units).
+/// * `height` Glyph height (tile units).
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{text_geometry, text_width, Tile};
+///
+/// let tile = Tile::new(4096);
+/// let x = 2048.0 - text_width("12/34/56", 96.0) / 2.0;
+/// let geom = text_geometry("12/34/56", x, 2000.0, 96.0)?;
+/// let mut feature = tile.create_layer("debug").into_feature(geom);
+/// feature.add_tag_string("kind", "text");
+/// let layer = feature.into_layer();
+/// # Ok(()) }
+/// ```
+pub fn text_geometry(
+    text: &str,
+    x: f64,
+    y: f64,
+    height: f64,
+) -> Result<GeomData> {
+    let scale = height / CELL_HEIGHT;
+    let mut enc = GeomEncoder::new(GeomType::Linestring, Transform::default());
+    for (i, c) in text.chars().enumerate() {
+        let t = Transform::with_scale(scale, scale)
+            .translate(x + i as f64 * ADVANCE * scale, y);
+        for stroke in glyph(c) {
+            for (px, py) in stroke.iter() {
+                let p = t * (f64::from(*px), f64::from(*py));
+                enc.add_point(p.x(), p.y())?;
+            }
+            enc.complete_geom()?;
+        }
+    }
+    enc.encode()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::decode_parts;
+
+    #[test]
+    fn test_text() {
+        assert_eq!(text_width("", 6.0), 0.0);
+        assert_eq!(text_width("1", 6.0), 4.0);
+        assert_eq!(text_width("4/3/5", 12.0), 56.0);
+        let geom = text_geometry("1-", 10.0, 20.0, 12.0).unwrap();
+        let parts = decode_parts(&geom.into_vec()).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                vec![(12, 22), (14, 20), (14, 32)],
+                vec![(12, 32), (16, 32)],
+                vec![(24, 26), (28, 26)],
+            ]
+        );
+        let geom = text_geometry("x?", 0.0, 0.0, 6.0).unwrap();
+        assert!(geom.is_empty());
+        for c in "0123456789abcdef/-.:".chars() {
+            assert!(!glyph(c).is_empty());
+        }
+    }
+}
@@ -0,0 +1,384 @@
+// geometry.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Structured geometry, decoded from command integers.
+//!
+use crate::encoder::{decode_parts, GeomType};
+use crate::error::{Error, Result};
+use crate::options::ring_area;
+
+/// Rule for grouping polygon rings by winding order, when decoding.
+///
+/// | Rule        | Exterior rings                                    |
+/// |-------------|---------------------------------------------------|
+/// | `Version`   | `Area` for version 2 layers, else `FirstRing`     |
+/// | `Area`      | positive area (version 2 spec)                    |
+/// | `FirstRing` | same winding as the first ring (version 1 spec)   |
+///
+/// Areas are in tile coördinates, with Y down.  Interior rings belong to
+/// the preceding exterior ring.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Winding {
+    /// Rule depends on layer spec version
+    #[default]
+    Version,
+
+    /// Exterior rings have positive area
+    Area,
+
+    /// Exterior rings have the same winding as the first ring
+    FirstRing,
+}
+
+/// Decoded feature geometry, in tile coördinates (or lon/lat with `f64`).
+///
+/// Rings are not closed; the first vertex is not repeated at the end.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Geometry<T = i32> {
+    /// Point or multipoint
+    Points(Vec<(T, T)>),
+
+    /// Linestring or multilinestring
+    Linestrings(Vec<Vec<(T, T)>>),
+
+    /// Polygon or multipolygon; each polygon is an exterior ring followed by
+    /// its interior rings
+    Polygons(Vec<Vec<Vec<(T, T)>>>),
+}
+
+impl Winding {
+    /// Resolve the rule for a layer spec version
+    pub(crate) fn rule(self, version: u32) -> Self {
+        match (self, version) {
+            (Winding::Version, 2..) => Winding::Area,
+            (Winding::Version, _) => Winding::FirstRing,
+            _ => self,
+        }
+    }
+}
+
+impl Geometry {
+    /// Decode geometry command integers.
+    ///
+    /// Polygon rings are grouped by `winding` (resolved for a version): each
+    /// exterior ring starts a polygon, and interior rings belong to the
+    /// preceding exterior ring.  Rings with zero area are dropped.
+    ///
+    /// Returns an error if the command integers are malformed, or (with
+    /// [Winding::Area]) the first polygon ring is not an exterior ring.
+    pub(crate) fn decode(
+        geom_tp: GeomType,
+        data: &[u32],
+        winding: Winding,
+    ) -> Result<Self> {
+        let parts = decode_parts(data)?;
+        match geom_tp {
+            GeomType::Point => {
+                Ok(Geometry::Points(parts.into_iter().flatten().collect()))
+            }
+            GeomType::Linestring => Ok(Geometry::Linestrings(parts)),
+            GeomType::Polygon => {
+                let mut polygons: Vec<Vec<Vec<(i32, i32)>>> = vec![];
+                let mut exterior = None;
+                for ring in parts {
+                    let area = ring_area(&ring);
+                    if area == 0 {
+                        continue;
+                    }
+                    let positive = area > 0;
+                    let exterior = *exterior.get_or_insert(match winding {
+                        Winding::FirstRing => positive,
+                        _ => true,
+                    });
+                    if positive == exterior {
+                        polygons.push(vec![ring]);
+                    } else {
+                        polygons
+                            .last_mut()
+                            .ok_or(Error::InvalidGeometry())?
+                            .push(ring);
+                    }
+                }
+                Ok(Geometry::Polygons(polygons))
+            }
+        }
+    }
+}
+
+impl<T> Geometry<T> {
+    /// Get the geometry type.
+    pub fn geom_type(&self) -> GeomType {
+        match self {
+            Geometry::Points(_) => GeomType::Point,
+            Geometry::Linestrings(_) => GeomType::Linestring,
+            Geometry::Polygons(_) => GeomType::Polygon,
+        }
+    }
+
+    /// Check if the geometry is empty.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Geometry::Points(points) => points.is_empty(),
+            Geometry::Linestrings(lines) => lines.is_empty(),
+            Geometry::Polygons(polygons) => polygons.is_empty(),
+        }
+    }
+
+    /// Map all vertices to another coördinate type.
+    ///
+    /// * `f` Function to map a vertex.
+    pub fn map<U, M>(self, mut f: M) -> Geometry<U>
+    where
+        M: FnMut((T, T)) -> (U, U),
+    {
+        let mut line =
+            |line: Vec<(T, T)>| line.into_iter().map(&mut f).collect();
+        match self {
+            Geometry::Points(points) => Geometry::Points(line(points)),
+            Geometry::Linestrings(lines) => {
+                Geometry::Linestrings(lines.into_iter().map(line).collect())
+            }
+            Geometry::Polygons(polygons) => Geometry::Polygons(
+                polygons
+                    .into_iter()
+                    .map(|rings| rings.into_iter().map(&mut line).collect())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Earth radius (meters), as used by Web Mercator
+pub(crate) const EARTH_RADIUS_M: f64 = 6_378_137.0;
+
+/// Calculate the great-circle distance (meters) between two lon/lat points
+pub(crate) fn haversine_m(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat_a, lat_b) = (a.1.to_radians(), b.1.to_radians());
+    let dlat = lat_b - lat_a;
+    let dlon = (b.0 - a.0).to_radians();
+    let h = (dlat / 2.0).sin().powi(2)
+        + lat_a.cos() * lat_b.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Calculate the length (meters) of a lon/lat line
+fn line_length_m(line: &[(f64, f64)]) -> f64 {
+    line.windows(2).map(|w| haversine_m(w[0], w[1])).sum()
+}
+
+/// Calculate the unsigned spherical area (square meters) of a lon/lat ring
+fn ring_area_m2(ring: &[(f64, f64)]) -> f64 {
+    let mut area = 0.0;
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        area += (b.0 - a.0).to_radians()
+            * (2.0 + a.1.to_radians().sin() + b.1.to_radians().sin());
+    }
+    (area * EARTH_RADIUS_M * EARTH_RADIUS_M / 2.0).abs()
+}
+
+impl Geometry<f64> {
+    /// Calculate the length (meters) of lon/lat geometry.
+    ///
+    /// This is the total length of linestrings, or perimeter of polygon
+    /// rings, on a sphere.  Points have zero length.
+    pub fn length_m(&self) -> f64 {
+        match self {
+            Geometry::Points(_) => 0.0,
+            Geometry::Linestrings(lines) => {
+                lines.iter().map(|line| line_length_m(line)).sum()
+            }
+            Geometry::Polygons(polygons) => polygons
+                .iter()
+                .flatten()
+                .map(|ring| match (ring.first(), ring.last()) {
+                    (Some(a), Some(b)) => {
+                        line_length_m(ring) + haversine_m(*b, *a)
+                    }
+                    _ => 0.0,
+                })
+                .sum(),
+        }
+    }
+
+    /// Calculate the area (square meters) of lon/lat geometry.
+    ///
+    /// Interior ring areas are subtracted from exterior rings, on a sphere.
+    /// Points and linestrings have zero area.
+    pub fn area_m2(&self) -> f64 {
+        match self {
+            Geometry::Polygons(polygons) => polygons
+                .iter()
+                .filter_map(|rings| {
+                    let (exterior, interiors) = rings.split_first()?;
+                    let interior: f64 =
+                        interiors.iter().map(|r| ring_area_m2(r)).sum();
+                    Some(ring_area_m2(exterior) - interior)
+                })
+                .sum(),
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(feature = "geo-types")]
+impl From<Geometry<f64>> for geo_types::Geometry<f64> {
+    /// Convert to `geo-types` geometry.
+    ///
+    /// Single points, linestrings and polygons are converted to their
+    /// simple types, others to multi types.  Polygon rings are closed.
+    fn from(geom: Geometry<f64>) -> Self {
+        use geo_types::{
+            LineString, MultiLineString, MultiPoint, MultiPolygon, Point,
+            Polygon,
+        };
+        let polygon = |rings: Vec<Vec<(f64, f64)>>| {
+            let mut rings = rings.into_iter().map(LineString::from);
+            let exterior = rings.next().unwrap_or(LineString(vec![]));
+            Polygon::new(exterior, rings.collect())
+        };
+        match geom {
+            Geometry::Points(mut points) if points.len() == 1 => {
+                Point::from(points.remove(0)).into()
+            }
+            Geometry::Points(points) => {
+                MultiPoint(points.into_iter().map(Point::from).collect()).into()
+            }
+            Geometry::Linestrings(mut lines) if lines.len() == 1 => {
+                LineString::from(lines.remove(0)).into()
+            }
+            Geometry::Linestrings(lines) => MultiLineString(
+                lines.into_iter().map(LineString::from).collect(),
+            )
+            .into(),
+            Geometry::Polygons(mut polygons) if polygons.len() == 1 => {
+                polygon(polygons.remove(0)).into()
+            }
+            Geometry::Polygons(polygons) => {
+                MultiPolygon(polygons.into_iter().map(polygon).collect()).into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomEncoder;
+    use pointy::Transform;
+
+    const AREA: Winding = Winding::Area;
+
+    fn polygons() -> Vec<u32> {
+        let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default());
+        // exterior, interior, exterior
+        let rings: [&[(f64, f64)]; 3] = [
+            &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)],
+            &[(2.0, 2.0), (2.0, 4.0), (4.0, 4.0), (4.0, 2.0)],
+            &[(20.0, 0.0), (30.0, 0.0), (30.0, 10.0)],
+        ];
+        for ring in rings {
+            for (x, y) in ring {
+                enc.add_point(*x, *y).unwrap();
+            }
+            enc.complete_geom().unwrap();
+        }
+        enc.encode().unwrap().into_vec()
+    }
+
+    #[test]
+    fn test_geometry() {
+        let geom =
+            Geometry::decode(GeomType::Point, &[17, 2, 4, 2, 2], AREA).unwrap();
+        assert_eq!(geom, Geometry::Points(vec![(1, 2), (2, 3)]));
+        let geom =
+            Geometry::decode(GeomType::Linestring, &[9, 2, 4, 10, 4, 0], AREA)
+                .unwrap();
+        assert_eq!(geom, Geometry::Linestrings(vec![vec![(1, 2), (3, 2)]]));
+        let geom =
+            Geometry::decode(GeomType::Polygon, &polygons(), AREA).unwrap();
+        match &geom {
+            Geometry::Polygons(polygons) => {
+                assert_eq!(polygons.len(), 2);
+                assert_eq!(polygons[0].len(), 2);
+                assert_eq!(polygons[0][1][0], (2, 2));
+                assert_eq!(polygons[1].len(), 1);
+            }
+            _ => panic!("not polygons"),
+        }
+        #[cfg(feature = "geo-types")]
+        match geo_types::Geometry::from(
+            geom.clone().map(|(x, y)| (f64::from(x), f64::from(y))),
+        ) {
+            geo_types::Geometry::MultiPolygon(polygons) => {
+                assert_eq!(polygons.0.len(), 2);
+                assert_eq!(polygons.0[0].interiors().len(), 1);
+                assert_eq!(polygons.0[1].exterior().0.len(), 4);
+            }
+            _ => panic!("not multipolygon"),
+        }
+        assert_eq!(geom.geom_type(), GeomType::Polygon);
+        assert!(!geom.is_empty());
+        let half = geom.clone().map(|(x, y)| (x as f64 / 2.0, y as f64 / 2.0));
+        match half {
+            Geometry::Polygons(polygons) => {
+                assert_eq!(polygons[0][1][0], (1.0, 1.0));
+            }
+            _ => panic!("not polygons"),
+        }
+        // interior ring first
+        let data = [9, 4, 4, 26, 0, 4, 4, 0, 0, 3, 15];
+        assert!(Geometry::decode(GeomType::Polygon, &data, AREA).is_err());
+        assert!(Geometry::decode(GeomType::Point, &[17, 2], AREA).is_err());
+    }
+
+    #[test]
+    fn test_winding() {
+        assert_eq!(Winding::Version.rule(2), Winding::Area);
+        assert_eq!(Winding::Version.rule(1), Winding::FirstRing);
+        assert_eq!(Winding::Area.rule(1), Winding::Area);
+        // negative area ring, then positive area ring
+        let data = [
+            9, 4, 4, 26, 0, 4, 4, 0, 0, 3, 15, 9, 32, 3, 18, 20, 0, 0, 20, 15,
+        ];
+        let geom =
+            Geometry::decode(GeomType::Polygon, &data, Winding::FirstRing)
+                .unwrap();
+        match geom {
+            Geometry::Polygons(polygons) => {
+                assert_eq!(polygons.len(), 1);
+                assert_eq!(polygons[0][1][0], (20, 0));
+            }
+            _ => panic!("not polygons"),
+        }
+        assert!(Geometry::decode(GeomType::Polygon, &data, AREA).is_err());
+        let geom = Geometry::decode(
+            GeomType::Polygon,
+            &polygons(),
+            Winding::FirstRing,
+        )
+        .unwrap();
+        assert_eq!(
+            geom,
+            Geometry::decode(GeomType::Polygon, &polygons(), AREA).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_measure() {
+        let line = Geometry::Linestrings(vec![vec![(0.0, 0.0), (1.0, 0.0)]]);
+        let m = line.length_m();
+        assert!((m - 111_319.49).abs() < 0.01);
+        assert_eq!(line.area_m2(), 0.0);
+        let square = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let hole = vec![(0.25, 0.25), (0.25, 0.75), (0.75, 0.75), (0.75, 0.25)];
+        let polygon = Geometry::Polygons(vec![vec![square.clone()]]);
+        let area = polygon.area_m2();
+        assert!((area / 1.239e10 - 1.0).abs() < 0.001);
+        assert!((polygon.length_m() / (4.0 * m) - 1.0).abs() < 0.001);
+        let polygon = Geometry::Polygons(vec![vec![square, hole]]);
+        assert!((polygon.area_m2() / (area * 0.75) - 1.0).abs() < 0.001);
+        assert_eq!(Geometry::Points(vec![(0.0, 0.0)]).length_m(), 0.0);
+    }
+}
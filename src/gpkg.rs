@@ -0,0 +1,718 @@
+// gpkg.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! GeoPackage geometry blobs (binary header + WKB), and feature tables.
+//!
+use crate::encoder::{GeomData, GeomEncoder};
+use crate::error::{Error, Result};
+use crate::geometry::Geometry;
+#[cfg(feature = "rusqlite")]
+use crate::source::{geometry_bbox, FeatureSource, SourceFeature, SourceValue};
+use pointy::{BBox, Pt, Transform};
+#[cfg(feature = "rusqlite")]
+use rusqlite::types::ValueRef;
+#[cfg(feature = "rusqlite")]
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Row};
+#[cfg(feature = "rusqlite")]
+use std::path::Path;
+
+/// Geometry read from a GeoPackage feature table.
+///
+/// The geometry column of a GeoPackage table is a blob with a small header
+/// (spatial reference ID and optional envelope) followed by Well-Known
+/// Binary.  Z and M values are ignored.  Whole tables can be read with
+/// [GpkgReader] (with `rusqlite` feature), or rows with any SQLite library.
+///
+/// [GpkgReader]: struct.GpkgReader.html
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{Geometry, GpkgGeometry};
+///
+/// // header: "GP", version 0, little-endian, no envelope, SRS 4326
+/// let mut blob = vec![b'G', b'P', 0, 0x01, 0xE6, 0x10, 0, 0];
+/// // WKB: little-endian point (1, 2)
+/// blob.extend([1, 1, 0, 0, 0]);
+/// blob.extend(1.0f64.to_le_bytes());
+/// blob.extend(2.0f64.to_le_bytes());
+/// let geom = GpkgGeometry::from_blob(&blob)?;
+/// assert_eq!(geom.srs_id(), 4326);
+/// assert_eq!(geom.geometry(), &Geometry::Points(vec![(1.0, 2.0)]));
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpkgGeometry {
+    /// Spatial reference system ID
+    srs_id: i32,
+
+    /// Envelope from header
+    envelope: Option<BBox<f64>>,
+
+    /// Decoded geometry
+    geometry: Geometry<f64>,
+}
+
+/// Reader for binary data with a byte order
+struct Reader<'a> {
+    /// Remaining data
+    data: &'a [u8],
+
+    /// Little-endian byte order
+    le: bool,
+}
+
+impl<'a> Reader<'a> {
+    /// Take a fixed number of bytes
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.data.len() < N {
+            return Err(Error::InvalidGpkgGeometry());
+        }
+        let (head, tail) = self.data.split_at(N);
+        self.data = tail;
+        Ok(head.try_into().unwrap())
+    }
+
+    /// Read one byte
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    /// Read an unsigned 32-bit integer
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.take()?;
+        Ok(if self.le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    /// Read a double
+    fn f64(&mut self) -> Result<f64> {
+        let b = self.take()?;
+        Ok(if self.le {
+            f64::from_le_bytes(b)
+        } else {
+            f64::from_be_bytes(b)
+        })
+    }
+
+    /// Read a count, checking it against the remaining data
+    fn count(&mut self, min_size: usize) -> Result<usize> {
+        let n = self.u32()? as usize;
+        if n.saturating_mul(min_size) > self.data.len() {
+            return Err(Error::InvalidGpkgGeometry());
+        }
+        Ok(n)
+    }
+}
+
+/// WKB geometry header
+struct WkbHeader {
+    /// Base type (1 - 6)
+    base: u32,
+
+    /// Number of ordinates per point
+    dims: usize,
+}
+
+impl WkbHeader {
+    /// Read a WKB header, setting the reader's byte order
+    fn read(rd: &mut Reader) -> Result<Self> {
+        rd.le = match rd.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(Error::InvalidGpkgGeometry()),
+        };
+        let tp = rd.u32()?;
+        let (base, dims) = match tp / 1000 {
+            0 => (tp, 2),
+            1 | 2 => (tp % 1000, 3),
+            3 => (tp % 1000, 4),
+            _ => return Err(Error::InvalidGpkgGeometry()),
+        };
+        Ok(WkbHeader { base, dims })
+    }
+
+    /// Read a header, checking its base type
+    fn expect(rd: &mut Reader, base: u32) -> Result<usize> {
+        let hdr = WkbHeader::read(rd)?;
+        if hdr.base != base {
+            return Err(Error::InvalidGpkgGeometry());
+        }
+        Ok(hdr.dims)
+    }
+}
+
+/// Read a point (extra ordinates are skipped)
+fn read_point(rd: &mut Reader, dims: usize) -> Result<(f64, f64)> {
+    let x = rd.f64()?;
+    let y = rd.f64()?;
+    for _ in 2..dims {
+        rd.f64()?;
+    }
+    Ok((x, y))
+}
+
+/// Read a sequence of points
+fn read_line(rd: &mut Reader, dims: usize) -> Result<Vec<(f64, f64)>> {
+    let n = rd.count(dims * 8)?;
+    (0..n).map(|_| read_point(rd, dims)).collect()
+}
+
+/// Read polygon rings, without repeating the first vertex
+fn read_rings(rd: &mut Reader, dims: usize) -> Result<Vec<Vec<(f64, f64)>>> {
+    let n = rd.count(4)?;
+    let mut rings = Vec::with_capacity(n);
+    for _ in 0..n {
+        let mut ring = read_line(rd, dims)?;
+        if ring.len() > 1 && ring.first() == ring.last() {
+            ring.pop();
+        }
+        if !ring.is_empty() {
+            rings.push(ring);
+        }
+    }
+    Ok(rings)
+}
+
+/// Read a WKB geometry
+fn read_wkb(rd: &mut Reader) -> Result<Geometry<f64>> {
+    let WkbHeader { base, dims } = WkbHeader::read(rd)?;
+    // empty points are encoded with NaN coördinates
+    let non_empty = |pt: &(f64, f64)| !(pt.0.is_nan() && pt.1.is_nan());
+    Ok(match base {
+        1 => {
+            let pt = read_point(rd, dims)?;
+            Geometry::Points(Some(pt).into_iter().filter(non_empty).collect())
+        }
+        2 => {
+            let line = read_line(rd, dims)?;
+            Geometry::Linestrings(Some(line).into_iter().collect())
+        }
+        3 => {
+            let rings = read_rings(rd, dims)?;
+            let polygons = Some(rings).filter(|r| !r.is_empty());
+            Geometry::Polygons(polygons.into_iter().collect())
+        }
+        4 => {
+            let n = rd.count(5)?;
+            let mut points = Vec::with_capacity(n);
+            for _ in 0..n {
+                let dims = WkbHeader::expect(rd, 1)?;
+                points.push(read_point(rd, dims)?);
+            }
+            Geometry::Points(points.into_iter().filter(non_empty).collect())
+        }
+        5 => {
+            let n = rd.count(9)?;
+            let mut lines = Vec::with_capacity(n);
+            for _ in 0..n {
+                let dims = WkbHeader::expect(rd, 2)?;
+                lines.push(read_line(rd, dims)?);
+            }
+            Geometry::Linestrings(lines)
+        }
+        6 => {
+            let n = rd.count(9)?;
+            let mut polygons = Vec::with_capacity(n);
+            for _ in 0..n {
+                let dims = WkbHeader::expect(rd, 3)?;
+                let rings = read_rings(rd, dims)?;
+                if !rings.is_empty() {
+                    polygons.push(rings);
+                }
+            }
+            Geometry::Polygons(polygons)
+        }
+        // geometry collections cannot be mapped to one geometry type
+        _ => return Err(Error::InvalidGpkgGeometry()),
+    })
+}
+
+impl GpkgGeometry {
+    /// Decode a GeoPackage geometry blob.
+    ///
+    /// Returns an error if the header or WKB is malformed, or the geometry
+    /// is a collection (or another unsupported type).
+    pub fn from_blob(blob: &[u8]) -> Result<Self> {
+        let mut rd = Reader {
+            data: blob,
+            le: false,
+        };
+        if rd.take::<2>()? != *b"GP" {
+            return Err(Error::InvalidGpkgGeometry());
+        }
+        let _version = rd.u8()?;
+        let flags = rd.u8()?;
+        if flags & 0b10_0000 != 0 {
+            // extended geometry types are not supported
+            return Err(Error::InvalidGpkgGeometry());
+        }
+        rd.le = flags & 0b1 != 0;
+        let srs_id = rd.u32()? as i32;
+        let n_env = match (flags >> 1) & 0b111 {
+            0 => 0,
+            1 => 4,
+            2 | 3 => 6,
+            4 => 8,
+            _ => return Err(Error::InvalidGpkgGeometry()),
+        };
+        let mut env = [0.0; 8];
+        for e in env.iter_mut().take(n_env) {
+            *e = rd.f64()?;
+        }
+        let envelope = (n_env > 0).then(|| {
+            BBox::from((Pt::new(env[0], env[2]), Pt::new(env[1], env[3])))
+        });
+        let geometry = read_wkb(&mut rd)?;
+        Ok(GpkgGeometry {
+            srs_id,
+            envelope,
+            geometry,
+        })
+    }
+
+    /// Get the spatial reference system ID.
+    pub fn srs_id(&self) -> i32 {
+        self.srs_id
+    }
+
+    /// Get the envelope (X/Y bounds) from the header, if present.
+    pub fn envelope(&self) -> Option<BBox<f64>> {
+        self.envelope
+    }
+
+    /// Get the geometry.
+    pub fn geometry(&self) -> &Geometry<f64> {
+        &self.geometry
+    }
+
+    /// Take the geometry, consuming self.
+    pub fn into_geometry(self) -> Geometry<f64> {
+        self.geometry
+    }
+
+    /// Encode the geometry for a tile.
+    ///
+    /// * `transform` Transform to tile coördinates (see
+    ///   [MapGrid::tile_transform]).
+    ///
+    /// [MapGrid::tile_transform]: struct.MapGrid.html#method.tile_transform
+    pub fn encode(&self, transform: Transform<f64>) -> Result<GeomData> {
+        let mut enc = GeomEncoder::new(self.geometry.geom_type(), transform);
+        let line = |enc: &mut GeomEncoder<f64>, pts: &[(f64, f64)]| {
+            for (x, y) in pts {
+                enc.add_point(*x, *y)?;
+            }
+            enc.complete_geom()
+        };
+        match &self.geometry {
+            Geometry::Points(points) => line(&mut enc, points)?,
+            Geometry::Linestrings(lines) => {
+                for pts in lines {
+                    line(&mut enc, pts)?;
+                }
+            }
+            Geometry::Polygons(polygons) => {
+                for pts in polygons.iter().flatten() {
+                    line(&mut enc, pts)?;
+                }
+            }
+        }
+        enc.encode()
+    }
+}
+
+impl From<GpkgGeometry> for Geometry<f64> {
+    fn from(geom: GpkgGeometry) -> Self {
+        geom.geometry
+    }
+}
+
+/// Reader for a GeoPackage feature table (with `rusqlite` feature).
+///
+/// The geometry column is found in `gpkg_geometry_columns`, and the
+/// integer primary key is used for feature IDs.  Other columns become tags;
+/// `NULL` and blob values are skipped.  If the table has an RTree spatial
+/// index (`rtree_<table>_<column>`), it is used for [FeatureSource] queries;
+/// otherwise, all rows are scanned.
+///
+/// # Example
+/// ```no_run
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{FeatureSource, GpkgReader};
+/// use pointy::{BBox, Pt};
+///
+/// let mut reader = GpkgReader::open("roads.gpkg", "roads")?;
+/// let bbox = BBox::from((Pt::new(-94.0, 44.0), Pt::new(-93.0, 45.0)));
+/// for feature in reader.features_in(bbox)? {
+///     println!("{:?}: {:?}", feature.id(), feature.tags());
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// [FeatureSource]: trait.FeatureSource.html
+#[cfg(feature = "rusqlite")]
+pub struct GpkgReader {
+    /// SQLite connection
+    conn: Connection,
+
+    /// Quoted table name
+    table: String,
+
+    /// Quoted primary key column
+    fid_column: String,
+
+    /// Quoted geometry column
+    geom_column: String,
+
+    /// Tag column names
+    columns: Vec<String>,
+
+    /// Quoted RTree index table name
+    rtree: Option<String>,
+}
+
+/// Quote an SQL identifier
+#[cfg(feature = "rusqlite")]
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(feature = "rusqlite")]
+impl GpkgReader {
+    /// Open a feature table of a GeoPackage file.
+    ///
+    /// * `path` GeoPackage file path.
+    /// * `table` Feature table name.
+    ///
+    /// The file is opened read-only; it is an error if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P, table: &str) -> Result<Self> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY;
+        Self::from_connection(Connection::open_with_flags(path, flags)?, table)
+    }
+
+    /// Open a feature table from a SQLite connection.
+    ///
+    /// Returns [Error::InvalidGpkgTable] if the table is not in
+    /// `gpkg_geometry_columns`, or has no primary key.
+    ///
+    /// [Error::InvalidGpkgTable]: enum.Error.html#variant.InvalidGpkgTable
+    pub fn from_connection(conn: Connection, table: &str) -> Result<Self> {
+        let geom_column: String = conn
+            .query_row(
+                "SELECT column_name FROM gpkg_geometry_columns \
+                 WHERE table_name = ?1",
+                [table],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(Error::InvalidGpkgTable())?;
+        let mut fid_column = None;
+        let mut columns = vec![];
+        {
+            let sql = format!("PRAGMA table_info({})", quote(table));
+            let mut stmt = conn.prepare(&sql)?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let name: String = row.get(1)?;
+                let pk: i64 = row.get(5)?;
+                if pk > 0 && fid_column.is_none() {
+                    fid_column = Some(name);
+                } else if name != geom_column {
+                    columns.push(name);
+                }
+            }
+        }
+        let fid_column = fid_column.ok_or(Error::InvalidGpkgTable())?;
+        let rtree = format!("rtree_{table}_{geom_column}");
+        let rtree: Option<String> = conn
+            .query_row(
+                "SELECT name FROM sqlite_master \
+                 WHERE type = 'table' AND name = ?1",
+                [&rtree],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(GpkgReader {
+            conn,
+            table: quote(table),
+            fid_column: quote(&fid_column),
+            geom_column: quote(&geom_column),
+            columns,
+            rtree: rtree.map(|r| quote(&r)),
+        })
+    }
+
+    /// Check whether an RTree spatial index is used for queries.
+    pub fn has_rtree(&self) -> bool {
+        self.rtree.is_some()
+    }
+
+    /// Get the SQL to select features (`t` is the table alias)
+    fn select(&self) -> String {
+        let mut sql =
+            format!("SELECT t.{}, t.{}", self.fid_column, self.geom_column);
+        for column in &self.columns {
+            sql.push_str(", t.");
+            sql.push_str(&quote(column));
+        }
+        sql.push_str(" FROM ");
+        sql.push_str(&self.table);
+        sql.push_str(" t");
+        sql
+    }
+
+    /// Read a feature from a row; `None` if the geometry is `NULL`
+    fn read_feature(
+        &self,
+        row: &Row,
+    ) -> Result<Option<(SourceFeature, Option<BBox<f64>>)>> {
+        let fid: i64 = row.get(0)?;
+        let geom = match row.get_ref(1)? {
+            ValueRef::Blob(blob) => GpkgGeometry::from_blob(blob)?,
+            _ => return Ok(None),
+        };
+        let envelope = geom.envelope();
+        let mut feature = SourceFeature::new(geom.into_geometry());
+        if let Ok(fid) = u64::try_from(fid) {
+            feature = feature.with_id(fid);
+        }
+        for (i, column) in self.columns.iter().enumerate() {
+            let value = match row.get_ref(i + 2)? {
+                ValueRef::Integer(v) => SourceValue::Int(v),
+                ValueRef::Real(v) => SourceValue::Double(v),
+                ValueRef::Text(v) => {
+                    SourceValue::String(String::from_utf8_lossy(v).into_owned())
+                }
+                ValueRef::Null | ValueRef::Blob(_) => continue,
+            };
+            feature = feature.with_tag(column, value);
+        }
+        Ok(Some((feature, envelope)))
+    }
+
+    /// Read all features of the table.
+    pub fn features(&self) -> Result<Vec<SourceFeature>> {
+        let mut stmt = self.conn.prepare(&self.select())?;
+        let mut rows = stmt.query([])?;
+        let mut features = vec![];
+        while let Some(row) = rows.next()? {
+            if let Some((feature, _)) = self.read_feature(row)? {
+                features.push(feature);
+            }
+        }
+        Ok(features)
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl FeatureSource for GpkgReader {
+    fn features_in(&mut self, bbox: BBox<f64>) -> Result<Vec<SourceFeature>> {
+        let mut sql = self.select();
+        if let Some(rtree) = &self.rtree {
+            sql.push_str(&format!(
+                " JOIN {rtree} r ON t.{} = r.id \
+                 WHERE r.minx <= ?3 AND r.maxx >= ?1 \
+                 AND r.miny <= ?4 AND r.maxy >= ?2",
+                self.fid_column
+            ));
+        }
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut rows = if self.rtree.is_some() {
+            stmt.query([
+                bbox.x_min(),
+                bbox.y_min(),
+                bbox.x_max(),
+                bbox.y_max(),
+            ])?
+        } else {
+            stmt.query([])?
+        };
+        let mut features = vec![];
+        while let Some(row) = rows.next()? {
+            if let Some((feature, envelope)) = self.read_feature(row)? {
+                let bounds =
+                    envelope.or_else(|| geometry_bbox(feature.geometry()));
+                if bounds.is_some_and(|b| b.intersects(bbox)) {
+                    features.push(feature);
+                }
+            }
+        }
+        Ok(features)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomType;
+
+    fn header(flags: u8, env: &[f64]) -> Vec<u8> {
+        let mut blob = vec![b'G', b'P', 0, flags];
+        blob.extend(3857i32.to_le_bytes());
+        for e in env {
+            blob.extend(e.to_le_bytes());
+        }
+        blob
+    }
+
+    fn wkb_be(tp: u32, pts: &[(f64, f64)]) -> Vec<u8> {
+        let mut wkb = vec![0];
+        wkb.extend(tp.to_be_bytes());
+        if tp != 1 {
+            wkb.extend((pts.len() as u32).to_be_bytes());
+        }
+        for (x, y) in pts {
+            wkb.extend(x.to_be_bytes());
+            wkb.extend(y.to_be_bytes());
+        }
+        wkb
+    }
+
+    #[test]
+    fn test_gpkg_linestring() {
+        let env = [0.0, 10.0, 0.0, 20.0];
+        let mut blob = header(0b011, &env);
+        blob.extend(wkb_be(2, &[(0.0, 0.0), (10.0, 20.0)]));
+        let geom = GpkgGeometry::from_blob(&blob).unwrap();
+        assert_eq!(geom.srs_id(), 3857);
+        let bbox = geom.envelope().unwrap();
+        assert_eq!((bbox.x_max(), bbox.y_max()), (10.0, 20.0));
+        assert_eq!(
+            geom.geometry(),
+            &Geometry::Linestrings(vec![vec![(0.0, 0.0), (10.0, 20.0)]])
+        );
+        let data = geom.encode(Transform::default()).unwrap();
+        assert_eq!(data.geom_type(), GeomType::Linestring);
+        assert_eq!(data.into_vec(), [9, 0, 0, 10, 20, 40]);
+        assert!(GpkgGeometry::from_blob(&blob[..blob.len() - 1]).is_err());
+        assert!(GpkgGeometry::from_blob(&blob[1..]).is_err());
+    }
+
+    #[test]
+    fn test_gpkg_multipolygon() {
+        let ring = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 0.0)];
+        let mut polygon = vec![0];
+        polygon.extend(3u32.to_be_bytes());
+        polygon.extend(1u32.to_be_bytes());
+        polygon.extend(&wkb_be(2, &ring)[5..]);
+        let mut blob = header(0b001, &[]);
+        blob.extend([0, 0, 0, 0, 6, 0, 0, 0, 2]);
+        blob.extend(&polygon);
+        blob.extend(&polygon);
+        let geom = GpkgGeometry::from_blob(&blob).unwrap();
+        assert_eq!(geom.envelope(), None);
+        let rings = vec![vec![(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)]];
+        assert_eq!(
+            geom.into_geometry(),
+            Geometry::Polygons(vec![rings.clone(), rings])
+        );
+        // multipoint containing a linestring
+        let mut blob = header(0b001, &[]);
+        blob.extend([0, 0, 0, 0, 4, 0, 0, 0, 1]);
+        blob.extend(wkb_be(2, &ring));
+        assert!(GpkgGeometry::from_blob(&blob).is_err());
+    }
+
+    #[test]
+    fn test_gpkg_point_z() {
+        let mut blob = header(0b001, &[]);
+        blob.extend([1]);
+        blob.extend(1001u32.to_le_bytes());
+        for v in [1.5, 2.5, 99.0] {
+            blob.extend(f64::to_le_bytes(v));
+        }
+        let geom = GpkgGeometry::from_blob(&blob).unwrap();
+        assert_eq!(geom.geometry(), &Geometry::Points(vec![(1.5, 2.5)]));
+        let mut blob = header(0b001, &[]);
+        blob.extend(wkb_be(1, &[(f64::NAN, f64::NAN)]));
+        let geom = GpkgGeometry::from_blob(&blob).unwrap();
+        assert!(geom.geometry().is_empty());
+    }
+
+    #[cfg(feature = "rusqlite")]
+    fn point_blob(x: f64, y: f64) -> Vec<u8> {
+        let mut blob = header(0b001, &[]);
+        blob.extend(wkb_be(1, &[(x, y)]));
+        blob
+    }
+
+    #[cfg(feature = "rusqlite")]
+    fn make_gpkg(rtree: bool) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE gpkg_geometry_columns \
+             (table_name TEXT, column_name TEXT); \
+             INSERT INTO gpkg_geometry_columns VALUES ('poi', 'geom'); \
+             CREATE TABLE poi (fid INTEGER PRIMARY KEY, geom BLOB, \
+             name TEXT, rank INTEGER, height REAL);",
+        )
+        .unwrap();
+        if rtree {
+            conn.execute_batch(
+                "CREATE TABLE rtree_poi_geom \
+                 (id INTEGER, minx REAL, maxx REAL, miny REAL, maxy REAL); \
+                 INSERT INTO rtree_poi_geom VALUES (1, 1, 1, 1, 1); \
+                 INSERT INTO rtree_poi_geom VALUES (2, 9, 9, 9, 9);",
+            )
+            .unwrap();
+        }
+        for (fid, x, name) in [(1, 1.0, "one"), (2, 9.0, "two")] {
+            conn.execute(
+                "INSERT INTO poi VALUES (?1, ?2, ?3, ?4, NULL)",
+                (fid, point_blob(x, x), name, fid * 10),
+            )
+            .unwrap();
+        }
+        conn.execute("INSERT INTO poi (fid) VALUES (3)", [])
+            .unwrap();
+        conn
+    }
+
+    // Needs `cargo test --features rusqlite`
+    #[cfg(feature = "rusqlite")]
+    #[test]
+    fn test_gpkg_missing() {
+        let path = std::env::temp_dir().join("mvt-missing-test.gpkg");
+        assert!(GpkgReader::open(&path, "roads").is_err());
+        assert!(!path.exists());
+    }
+
+    // Needs `cargo test --features rusqlite`
+    #[cfg(feature = "rusqlite")]
+    #[test]
+    fn test_gpkg_reader() {
+        let bbox = BBox::from((Pt::new(0.0, 0.0), Pt::new(5.0, 5.0)));
+        for rtree in [false, true] {
+            let mut reader =
+                GpkgReader::from_connection(make_gpkg(rtree), "poi").unwrap();
+            assert_eq!(reader.has_rtree(), rtree);
+            assert_eq!(reader.features().unwrap().len(), 2);
+            let features = reader.features_in(bbox).unwrap();
+            assert_eq!(features.len(), 1);
+            assert_eq!(features[0].id(), Some(1));
+            assert_eq!(
+                features[0].geometry(),
+                &Geometry::Points(vec![(1.0, 1.0)])
+            );
+            assert_eq!(
+                features[0].tags(),
+                [
+                    ("name".to_string(), SourceValue::String("one".into())),
+                    ("rank".to_string(), SourceValue::Int(10)),
+                ]
+            );
+        }
+        let conn = make_gpkg(false);
+        assert!(matches!(
+            GpkgReader::from_connection(conn, "roads"),
+            Err(Error::InvalidGpkgTable())
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+// hooks.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Per-tile callback hooks.
+//!
+use crate::error::Result;
+use crate::mapgrid::TileId;
+use crate::tile::Tile;
+
+/// Tile hook function
+type Hook = Box<dyn Fn(&TileId, &mut Tile) -> Result<()> + Send + Sync>;
+
+/// Callback hooks run on each tile of a pipeline.
+///
+/// *Pre* hooks run before the tile function, and *post* hooks after it,
+/// just before encoding.  They can inject layers (debug grids, watermarks)
+/// or mutate tiles without changing the pipeline itself.  Hooks run in the
+/// order they were added.
+///
+/// # Example
+/// ```no_run
+/// # use mvt::{DirSink, DirSource, Error, TileHooks, for_each_tile};
+/// # fn main() -> Result<(), Error> {
+/// let hooks = TileHooks::new().post_encode(|tid, tile| {
+///     let layer = tile
+///         .create_layer("debug")
+///         .with_metadata("tid", &tid.to_string());
+///     tile.add_layer(layer)
+/// });
+/// let mut source = DirSource::open("tiles")?;
+/// let mut sink = DirSink::new("debug");
+/// let f = hooks.around(|_tid, tile| Ok(Some(tile)));
+/// for_each_tile(&mut source, 4, &mut sink, f)?;
+/// # Ok(()) }
+/// ```
+#[derive(Default)]
+pub struct TileHooks {
+    pre: Vec<Hook>,
+    post: Vec<Hook>,
+}
+
+impl TileHooks {
+    /// Create an empty set of hooks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a hook to run before the tile function.
+    pub fn pre_encode<H>(mut self, hook: H) -> Self
+    where
+        H: Fn(&TileId, &mut Tile) -> Result<()> + Send + Sync + 'static,
+    {
+        self.pre.push(Box::new(hook));
+        self
+    }
+
+    /// Add a hook to run after the tile function, before encoding.
+    pub fn post_encode<H>(mut self, hook: H) -> Self
+    where
+        H: Fn(&TileId, &mut Tile) -> Result<()> + Send + Sync + 'static,
+    {
+        self.post.push(Box::new(hook));
+        self
+    }
+
+    /// Run pre hooks on a tile.
+    pub fn run_pre(&self, tid: &TileId, tile: &mut Tile) -> Result<()> {
+        self.pre.iter().try_for_each(|hook| hook(tid, tile))
+    }
+
+    /// Run post hooks on a tile.
+    pub fn run_post(&self, tid: &TileId, tile: &mut Tile) -> Result<()> {
+        self.post.iter().try_for_each(|hook| hook(tid, tile))
+    }
+
+    /// Wrap a tile function (for [for_each_tile]) with the hooks.
+    ///
+    /// Post hooks are not run on dropped tiles.
+    ///
+    /// [for_each_tile]: fn.for_each_tile.html
+    pub fn around<'a, F>(
+        &'a self,
+        f: F,
+    ) -> impl Fn(TileId, Tile) -> Result<Option<Tile>> + Sync + 'a
+    where
+        F: Fn(TileId, Tile) -> Result<Option<Tile>> + Sync + 'a,
+    {
+        move |tid, mut tile| {
+            self.run_pre(&tid, &mut tile)?;
+            match f(tid, tile)? {
+                Some(mut tile) => {
+                    self.run_post(&tid, &mut tile)?;
+                    Ok(Some(tile))
+                }
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::archive::{for_each_tile, IterSource};
+    use crate::error::Error;
+
+    #[test]
+    fn test_hooks() {
+        let tid = |x| TileId::new(x, 0, 2).unwrap();
+        let mut source = IterSource::new(
+            (0..4).map(|x| (tid(x), Tile::new(256).to_bytes().unwrap())),
+        );
+        let hooks = TileHooks::new()
+            .pre_encode(|_tid, tile| tile.add_layer(tile.create_layer("pre")))
+            .post_encode(|tid, tile| {
+                let name = tid.to_string();
+                tile.add_layer(tile.create_layer(&name))
+            });
+        let mut sink = vec![];
+        let f = hooks.around(|tid, tile| {
+            assert_eq!(tile.num_layers(), 1);
+            Ok((tid.x() % 2 == 0).then_some(tile))
+        });
+        assert_eq!(for_each_tile(&mut source, 2, &mut sink, f).unwrap(), 2);
+        let tile = Tile::from_bytes(&sink[1].1).unwrap();
+        assert!(tile.layer_metadata("2/2/0").is_some());
+        assert!(tile.layer_metadata("pre").is_some());
+        let hooks =
+            TileHooks::new().pre_encode(|_, _| Err(Error::InvalidTid()));
+        let mut tile = Tile::new(256);
+        assert!(hooks.run_pre(&tid(0), &mut tile).is_err());
+    }
+}
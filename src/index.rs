@@ -0,0 +1,182 @@
+// index.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Tile inspection index.
+//!
+use crate::archive::TileSource;
+use crate::error::Result;
+use crate::mapgrid::TileId;
+use crate::tile::Tile;
+use crate::vector_tile::tile::Value;
+use std::collections::HashMap;
+
+/// Lookup from feature ID / tag value to the tiles containing it.
+///
+/// Useful for finding where a feature appears, or which tiles must be
+/// invalidated when it changes.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, GeomEncoder, GeomType, Tile, TileId, TileIndex};
+/// # use pointy::Transform;
+/// # fn main() -> Result<(), Error> {
+/// let mut tile = Tile::new(4096);
+/// let layer = tile.create_layer("Roads");
+/// let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+///     .point(1.0, 2.0)?
+///     .encode()?;
+/// let mut feature = layer.into_feature(geom);
+/// feature.set_id(7);
+/// feature.add_tag_string("name", "Main St");
+/// tile.add_layer(feature.into_layer())?;
+/// let tid = TileId::new(0, 0, 0)?;
+/// let mut index = TileIndex::default();
+/// index.add_tile(tid, &tile);
+/// assert_eq!(index.tiles_with_id("Roads", 7), &[tid]);
+/// assert_eq!(index.tiles_with_tag("name", "Main St"), &[tid]);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TileIndex {
+    /// Tiles by layer name and feature ID
+    ids: HashMap<(String, u64), Vec<TileId>>,
+
+    /// Tiles by tag key and value
+    tags: HashMap<(String, String), Vec<TileId>>,
+}
+
+/// Format a tag value for lookup
+fn value_string(value: &Value) -> Option<String> {
+    if let Some(v) = &value.string_value {
+        Some(v.clone())
+    } else if let Some(v) = value.float_value {
+        Some(v.to_string())
+    } else if let Some(v) = value.double_value {
+        Some(v.to_string())
+    } else if let Some(v) = value.int_value {
+        Some(v.to_string())
+    } else if let Some(v) = value.uint_value {
+        Some(v.to_string())
+    } else if let Some(v) = value.sint_value {
+        Some(v.to_string())
+    } else {
+        value.bool_value.map(|v| v.to_string())
+    }
+}
+
+/// Add a tile ID to a list, unless it was just added
+fn push_tid(tids: &mut Vec<TileId>, tid: TileId) {
+    if tids.last() != Some(&tid) {
+        tids.push(tid);
+    }
+}
+
+impl TileIndex {
+    /// Build an index of every tile in a source.
+    ///
+    /// Returns an error if any tile cannot be read or decoded.
+    pub fn build<S>(source: &mut S) -> Result<Self>
+    where
+        S: TileSource + ?Sized,
+    {
+        let mut index = TileIndex::default();
+        while let Some((tid, data)) = source.next_tile()? {
+            index.add_tile(tid, &Tile::from_bytes(&data)?);
+        }
+        Ok(index)
+    }
+
+    /// Add all features of a tile to the index.
+    ///
+    /// Tags referring to missing keys or values are skipped.
+    pub fn add_tile(&mut self, tid: TileId, tile: &Tile) {
+        for layer in tile.vt_layers() {
+            for feature in &layer.features {
+                if let Some(id) = feature.id {
+                    let key = (layer.name().to_string(), id);
+                    push_tid(self.ids.entry(key).or_default(), tid);
+                }
+                for tag in feature.tags.chunks_exact(2) {
+                    let key = layer.keys.get(tag[0] as usize);
+                    let value = layer.values.get(tag[1] as usize);
+                    if let (Some(key), Some(value)) = (key, value) {
+                        if let Some(value) = value_string(value) {
+                            let tids = self
+                                .tags
+                                .entry((key.clone(), value))
+                                .or_default();
+                            push_tid(tids, tid);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get tiles containing a feature.
+    ///
+    /// * `layer` Layer name.
+    /// * `id` Feature ID.
+    pub fn tiles_with_id(&self, layer: &str, id: u64) -> &[TileId] {
+        self.ids
+            .get(&(layer.to_string(), id))
+            .map_or(&[], |tids| &tids[..])
+    }
+
+    /// Get tiles containing a tag value, in any layer.
+    ///
+    /// * `key` Tag key.
+    /// * `value` Tag value, formatted as a string (`"3"`, `"true"`, etc).
+    pub fn tiles_with_tag(&self, key: &str, value: &str) -> &[TileId] {
+        self.tags
+            .get(&(key.to_string(), value.to_string()))
+            .map_or(&[], |tids| &tids[..])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::archive::IterSource;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use pointy::Transform;
+
+    fn tile(ids: &[u64]) -> Vec<u8> {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("Points");
+        for id in ids {
+            let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(0.0, 0.0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.set_id(*id);
+            feature.add_tag_uint("even", id % 2);
+            feature.add_tag_bool("big", *id > 10);
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        tile.to_bytes().unwrap()
+    }
+
+    #[test]
+    fn test_build() {
+        let t0 = TileId::new(0, 0, 1).unwrap();
+        let t1 = TileId::new(1, 0, 1).unwrap();
+        let mut source = IterSource::new([
+            (t0, tile(&[1, 2, 3])),
+            (t1, tile(&[3, 20])),
+            (t1, vec![]),
+        ]);
+        let index = TileIndex::build(&mut source).unwrap();
+        assert_eq!(index.tiles_with_id("Points", 1), &[t0]);
+        assert_eq!(index.tiles_with_id("Points", 3), &[t0, t1]);
+        assert!(index.tiles_with_id("Lines", 3).is_empty());
+        assert!(index.tiles_with_id("Points", 4).is_empty());
+        assert_eq!(index.tiles_with_tag("even", "0"), &[t0, t1]);
+        assert_eq!(index.tiles_with_tag("big", "true"), &[t1]);
+        assert!(index.tiles_with_tag("big", "1").is_empty());
+    }
+}
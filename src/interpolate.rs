@@ -0,0 +1,191 @@
+// interpolate.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Point feature interpolation between tile versions.
+//!
+use crate::encoder::{decode_vertices, GeomEncoder, GeomType};
+use crate::error::Result;
+use crate::metadata::split_metadata;
+use crate::tile::Layer;
+use crate::vector_tile::tile::{
+    Feature as VtFeature, GeomType as VtGeomType, Layer as VtLayer,
+};
+use pointy::Transform;
+use std::collections::{HashMap, VecDeque};
+
+/// Interpolate between two versions of a layer.
+///
+/// * `old` Earlier version of the layer.
+/// * `new` Later version of the layer.
+/// * `t` Interpolation factor, from 0 (old) to 1 (new).
+///
+/// Features and metadata are taken from the nearer version, in its order.
+/// Features with duplicate IDs are matched in order of appearance.
+pub(crate) fn interpolate_layer(
+    old: &VtLayer,
+    new: &VtLayer,
+    t: f64,
+) -> Result<Layer> {
+    let near_old = t < 0.5;
+    let (near, far) = if near_old { (old, new) } else { (new, old) };
+    let mut layer = Layer::new(near.name(), near.extent()).copy_metadata(near);
+    let mut far_ids: HashMap<u64, VecDeque<&VtFeature>> = HashMap::new();
+    for f in split_metadata(far).1 {
+        if let Some(id) = f.id {
+            far_ids.entry(id).or_default().push_back(f);
+        }
+    }
+    for f in split_metadata(near).1 {
+        let other =
+            f.id.and_then(|id| far_ids.get_mut(&id))
+                .and_then(|features| features.pop_front());
+        let geometry = match other {
+            Some(other) => {
+                let (f0, f1) = if near_old { (f, other) } else { (other, f) };
+                interpolate_points(f0, f1, t)?
+            }
+            None => None,
+        };
+        let geometry = geometry.unwrap_or_else(|| f.geometry.clone());
+        layer = layer.copy_feature(near, f, geometry);
+    }
+    Ok(layer)
+}
+
+/// Interpolate point geometry of two features.
+///
+/// Returns `None` unless both are points with the same number of vertices.
+fn interpolate_points(
+    f0: &VtFeature,
+    f1: &VtFeature,
+    t: f64,
+) -> Result<Option<Vec<u32>>> {
+    if f0.type_() != VtGeomType::POINT || f1.type_() != VtGeomType::POINT {
+        return Ok(None);
+    }
+    let v0 = decode_vertices(&f0.geometry)?;
+    let v1 = decode_vertices(&f1.geometry)?;
+    if v0.len() != v1.len() {
+        return Ok(None);
+    }
+    let lerp = |a: i32, b: i32| {
+        let a = f64::from(a);
+        a + (f64::from(b) - a) * t
+    };
+    let mut enc = GeomEncoder::new(GeomType::Point, Transform::default());
+    for ((x0, y0), (x1, y1)) in v0.into_iter().zip(v1) {
+        enc.add_point(lerp(x0, x1), lerp(y0, y1))?;
+    }
+    Ok(Some(enc.encode()?.into_vec()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::{Layer, Tile};
+    use pointy::Transform;
+
+    fn add_point(layer: Layer, id: u64, x: f64, y: f64, name: &str) -> Layer {
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(x, y)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.set_id(id);
+        feature.add_tag_string("name", name);
+        feature.into_layer()
+    }
+
+    fn tile(points: &[(u64, f64, f64, &str)]) -> Tile {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("vehicles");
+        for (id, x, y, name) in points {
+            layer = add_point(layer, *id, *x, *y, name);
+        }
+        tile.add_layer(layer).unwrap();
+        tile
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let old = tile(&[(1, 0.0, 0.0, "a"), (2, 5.0, 5.0, "b")]);
+        let new = tile(&[(1, 10.0, 20.0, "A"), (3, 7.0, 7.0, "c")]);
+        let mid = old.interpolate(&new, 0.5).unwrap();
+        let expected = tile(&[(1, 5.0, 10.0, "A"), (3, 7.0, 7.0, "c")]);
+        assert_eq!(mid.to_bytes().unwrap(), expected.to_bytes().unwrap());
+        let early = old.interpolate(&new, 0.25).unwrap();
+        let expected = tile(&[(1, 2.5, 5.0, "a"), (2, 5.0, 5.0, "b")]);
+        assert_eq!(early.to_bytes().unwrap(), expected.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_ends() {
+        let old = tile(&[(1, 0.0, 0.0, "a")]);
+        let new = tile(&[(1, 10.0, 20.0, "b")]);
+        let t0 = old.interpolate(&new, 0.0).unwrap();
+        assert_eq!(t0.to_bytes().unwrap(), old.to_bytes().unwrap());
+        let t1 = old.interpolate(&new, 1.0).unwrap();
+        assert_eq!(t1.to_bytes().unwrap(), new.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_order() {
+        let old = tile(&[(1, 0.0, 0.0, "a"), (2, 0.0, 0.0, "b")]);
+        let new = tile(&[(2, 10.0, 10.0, "b"), (1, 20.0, 20.0, "a")]);
+        let t1 = old.interpolate(&new, 1.0).unwrap();
+        assert_eq!(t1.to_bytes().unwrap(), new.to_bytes().unwrap());
+        let mut old = tile(&[]);
+        let mut layer = old.create_layer("other");
+        layer = add_point(layer, 1, 0.0, 0.0, "x");
+        old.add_layer(layer).unwrap();
+        let mut new = Tile::new(4096);
+        let mut layer = new.create_layer("other");
+        layer = add_point(layer, 1, 8.0, 8.0, "x");
+        new.add_layer(layer).unwrap();
+        new.add_layer(new.create_layer("vehicles")).unwrap();
+        let t1 = old.interpolate(&new, 1.0).unwrap();
+        assert_eq!(t1.to_bytes().unwrap(), new.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let old = tile(&[(1, 0.0, 0.0, "a"), (1, 10.0, 0.0, "b")]);
+        let new = tile(&[(1, 0.0, 10.0, "a"), (1, 10.0, 10.0, "b")]);
+        let mid = old.interpolate(&new, 0.5).unwrap();
+        let expected = tile(&[(1, 0.0, 5.0, "a"), (1, 10.0, 5.0, "b")]);
+        assert_eq!(mid.to_bytes().unwrap(), expected.to_bytes().unwrap());
+    }
+
+    fn with_source(points: &[(u64, f64, f64, &str)], source: &str) -> Tile {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile
+            .create_layer("vehicles")
+            .with_metadata("source", source);
+        for (id, x, y, name) in points {
+            layer = add_point(layer, *id, *x, *y, name);
+        }
+        tile.add_layer(layer).unwrap();
+        tile
+    }
+
+    #[test]
+    fn test_metadata() {
+        let old = with_source(&[(1, 0.0, 0.0, "a")], "v1");
+        let new = with_source(&[(1, 10.0, 20.0, "a")], "v2");
+        let mid = old.interpolate(&new, 0.5).unwrap();
+        let expected = with_source(&[(1, 5.0, 10.0, "a")], "v2");
+        assert_eq!(mid.to_bytes().unwrap(), expected.to_bytes().unwrap());
+        let early = old.interpolate(&new, 0.25).unwrap();
+        let expected = with_source(&[(1, 2.5, 5.0, "a")], "v1");
+        assert_eq!(early.to_bytes().unwrap(), expected.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_extent() {
+        let old = tile(&[]);
+        let new = Tile::new(512);
+        assert!(old.interpolate(&new, 0.5).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+// label.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Label collision filtering.
+//!
+use num_traits::FromPrimitive;
+use pointy::Float;
+use std::collections::HashMap;
+
+/// Label candidate box, centered on an anchor point
+#[derive(Clone, Copy, Debug)]
+struct LabelBox<F>
+where
+    F: Float,
+{
+    x: F,
+    y: F,
+    half_width: F,
+    half_height: F,
+    priority: i32,
+}
+
+/// Greedy collision filter for point / label layers.
+///
+/// Label boxes are approximated from text length and glyph size.  Candidates
+/// are accepted in priority order, dropping any which overlap a previously
+/// accepted box.  All coördinates are in tile units.
+///
+/// # Example
+/// ```
+/// use mvt::LabelFilter;
+///
+/// let mut filter = LabelFilter::new(12.0, 16.0).with_padding(2.0);
+/// filter.add_label(100.0, 100.0, "Minneapolis", 10);
+/// filter.add_label(110.0, 104.0, "Saint Paul", 5);
+/// filter.add_label(900.0, 100.0, "Duluth", 1);
+/// assert_eq!(filter.filter(), vec![0, 2]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LabelFilter<F>
+where
+    F: Float,
+{
+    glyph_width: F,
+    glyph_height: F,
+    padding: F,
+    boxes: Vec<LabelBox<F>>,
+}
+
+impl<F> LabelBox<F>
+where
+    F: Float,
+{
+    /// Check if two boxes overlap, with padding
+    fn overlaps(&self, other: &Self, padding: F) -> bool {
+        (self.x - other.x).abs() < self.half_width + other.half_width + padding
+            && (self.y - other.y).abs()
+                < self.half_height + other.half_height + padding
+    }
+}
+
+impl<F> LabelFilter<F>
+where
+    F: Float + FromPrimitive,
+{
+    /// Create a new label filter.
+    ///
+    /// * `glyph_width` Approximate width of one glyph (tile units).
+    /// * `glyph_height` Approximate height of one line of text (tile units).
+    pub fn new(glyph_width: F, glyph_height: F) -> Self {
+        LabelFilter {
+            glyph_width,
+            glyph_height,
+            padding: F::zero(),
+            boxes: vec![],
+        }
+    }
+
+    /// Set minimum padding between labels (tile units).
+    pub fn with_padding(mut self, padding: F) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Get the number of label candidates.
+    pub fn len(&self) -> usize {
+        self.boxes.len()
+    }
+
+    /// Check if there are no label candidates.
+    pub fn is_empty(&self) -> bool {
+        self.boxes.is_empty()
+    }
+
+    /// Add a text label candidate.
+    ///
+    /// * `x` X coördinate of label anchor.
+    /// * `y` Y coördinate of label anchor.
+    /// * `text` Label text, used to estimate box width.
+    /// * `priority` Higher priority labels are placed first.
+    ///
+    /// Returns the candidate index.
+    pub fn add_label(
+        &mut self,
+        x: F,
+        y: F,
+        text: &str,
+        priority: i32,
+    ) -> usize {
+        let glyphs = F::from_usize(text.chars().count()).unwrap_or(F::zero());
+        let width = glyphs * self.glyph_width;
+        self.add_box(x, y, width, self.glyph_height, priority)
+    }
+
+    /// Add a label candidate with explicit box size.
+    ///
+    /// * `x` X coördinate of box center.
+    /// * `y` Y coördinate of box center.
+    /// * `width` Box width.
+    /// * `height` Box height.
+    /// * `priority` Higher priority labels are placed first.
+    ///
+    /// Returns the candidate index.
+    pub fn add_box(
+        &mut self,
+        x: F,
+        y: F,
+        width: F,
+        height: F,
+        priority: i32,
+    ) -> usize {
+        let two = F::one() + F::one();
+        self.boxes.push(LabelBox {
+            x,
+            y,
+            half_width: width.abs() / two,
+            half_height: height.abs() / two,
+            priority,
+        });
+        self.boxes.len() - 1
+    }
+
+    /// Filter candidates, greedily placing labels by priority.
+    ///
+    /// Ties are broken by candidate order.  Returns indices of accepted
+    /// candidates, in ascending order.
+    pub fn filter(&self) -> Vec<usize> {
+        let two = F::one() + F::one();
+        // cell size is large enough that a box covers at most 2x2 cells
+        let cell = self.boxes.iter().fold(F::one(), |c, b| {
+            c.max(two * b.half_width.max(b.half_height) + self.padding)
+        });
+        let mut order: Vec<usize> = (0..self.boxes.len()).collect();
+        order.sort_by_key(|i| std::cmp::Reverse(self.boxes[*i].priority));
+        let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        let mut accepted = vec![];
+        for i in order {
+            let b = &self.boxes[i];
+            let collides = self
+                .cells(b, cell, self.padding)
+                .iter()
+                .filter_map(|c| grid.get(c))
+                .flatten()
+                .any(|j| b.overlaps(&self.boxes[*j], self.padding));
+            if !collides {
+                for c in self.cells(b, cell, F::zero()) {
+                    grid.entry(c).or_default().push(i);
+                }
+                accepted.push(i);
+            }
+        }
+        accepted.sort_unstable();
+        accepted
+    }
+
+    /// Get grid cells covered by a box, expanded by padding
+    fn cells(&self, b: &LabelBox<F>, cell: F, pad: F) -> Vec<(i64, i64)> {
+        let cx = |v: F| (v / cell).floor().to_i64().unwrap_or(0);
+        let x0 = cx(b.x - b.half_width - pad);
+        let x1 = cx(b.x + b.half_width + pad);
+        let y0 = cx(b.y - b.half_height - pad);
+        let y1 = cx(b.y + b.half_height + pad);
+        let mut cells = vec![];
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                cells.push((x, y));
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_priority() {
+        let mut f = LabelFilter::new(10.0, 10.0);
+        f.add_label(0.0, 0.0, "low", 1);
+        f.add_label(5.0, 5.0, "high", 2);
+        assert_eq!(f.filter(), vec![1]);
+    }
+
+    #[test]
+    fn test_ties() {
+        let mut f = LabelFilter::new(10.0, 10.0);
+        f.add_label(0.0, 0.0, "first", 0);
+        f.add_label(5.0, 5.0, "second", 0);
+        assert_eq!(f.filter(), vec![0]);
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut f = LabelFilter::new(10.0, 10.0);
+        f.add_box(0.0, 0.0, 10.0, 10.0, 0);
+        f.add_box(12.0, 0.0, 10.0, 10.0, 0);
+        assert_eq!(f.filter(), vec![0, 1]);
+        let f = f.with_padding(4.0);
+        assert_eq!(f.filter(), vec![0]);
+    }
+
+    #[test]
+    fn test_cell_edges() {
+        let mut f = LabelFilter::new(1.0, 1.0);
+        f.add_box(-1.0, -1.0, 100.0, 1.0, 0);
+        f.add_box(48.0, -0.5, 1.0, 1.0, 1);
+        f.add_box(-49.0, -1.2, 1.0, 1.0, 2);
+        f.add_box(-500.0, 500.0, 1.0, 1.0, 0);
+        assert_eq!(f.filter(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_padding_across_cells() {
+        let mut f = LabelFilter::new(1.0, 1.0).with_padding(4.0);
+        f.add_box(8.0, 0.0, 10.0, 10.0, 0);
+        f.add_box(16.0, 0.0, 2.0, 2.0, 0);
+        assert_eq!(f.filter(), vec![0]);
+    }
+}
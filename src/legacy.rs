@@ -0,0 +1,82 @@
+// legacy.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Decoding of version 1 layers.
+//!
+use crate::error::Result;
+use crate::vector_tile::tile::Layer as VtLayer;
+use crate::vector_tile::Tile as VecTile;
+use protobuf::rt::read_unknown_or_skip_group;
+use protobuf::{CodedInputStream, Message};
+
+/// Set the version of a layer which has none.
+///
+/// Version 1 encoders often omitted the field, which is required by the
+/// version 2 schema (with a default of 1).
+fn default_version(layer: &mut VtLayer) {
+    if layer.version.is_none() {
+        layer.set_version(1);
+    }
+}
+
+/// Parse a tile, accepting layers without a version
+pub(crate) fn parse_tile(is: &mut CodedInputStream) -> Result<VecTile> {
+    let mut vec_tile = VecTile::new();
+    while let Some(tag) = is.read_raw_tag_or_eof()? {
+        match tag {
+            // Tile.layers (field 3, length-delimited)
+            26 => {
+                let mut layer = VtLayer::new();
+                is.merge_message(&mut layer)?;
+                default_version(&mut layer);
+                layer.check_initialized()?;
+                vec_tile.layers.push(layer);
+            }
+            tag => read_unknown_or_skip_group(
+                tag,
+                is,
+                vec_tile.special_fields.mut_unknown_fields(),
+            )?,
+        }
+    }
+    Ok(vec_tile)
+}
+
+/// Parse a tile from bytes, accepting layers without a version
+pub(crate) fn parse_tile_bytes(data: &[u8]) -> Result<VecTile> {
+    let mut is = CodedInputStream::from_bytes(data);
+    let vec_tile = parse_tile(&mut is)?;
+    is.check_eof()?;
+    Ok(vec_tile)
+}
+
+/// Parse a layer message body, accepting a layer without a version
+pub(crate) fn parse_layer(body: &[u8]) -> Result<VtLayer> {
+    let mut layer = VtLayer::new();
+    layer.merge_from_bytes(body)?;
+    default_version(&mut layer);
+    layer.check_initialized()?;
+    Ok(layer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_legacy() {
+        // layer { name: "a", features: [{ type: POINT, geometry: [9, 2, 4] }] }
+        let body = [0x0a, 0x01, b'a', 0x12, 0x07, 0x18, 1, 0x22, 3, 9, 2, 4];
+        assert!(VtLayer::parse_from_bytes(&body).is_err());
+        let layer = parse_layer(&body).unwrap();
+        assert_eq!(layer.version, Some(1));
+        assert_eq!(layer.features[0].geometry, [9, 2, 4]);
+        let data = [&[0x1a, body.len() as u8][..], &body].concat();
+        let vec_tile = parse_tile_bytes(&data).unwrap();
+        assert_eq!(vec_tile.layers[0].version, Some(1));
+        // name is still required
+        assert!(parse_layer(&[0x78, 1]).is_err());
+        assert!(parse_tile_bytes(&data[..4]).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+// lenient.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Checked decoding, strict or lenient.
+//!
+use crate::encoder::decode_parts;
+use crate::error::{DecodeLocation, Error, Result};
+use crate::legacy::parse_layer;
+use crate::vector_tile::tile::{Feature as VtFeature, Layer as VtLayer};
+use crate::vector_tile::Tile as VecTile;
+use crate::wire::{keep_unknown, offset_of, Field, Fields, WireValue};
+use std::fmt;
+
+/// Invalid data skipped while decoding in lenient mode.
+///
+/// See [Tile::from_bytes_lenient].
+///
+/// [Tile::from_bytes_lenient]: struct.Tile.html#method.from_bytes_lenient
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DecodeReport {
+    dropped_layers: usize,
+    dropped_features: usize,
+}
+
+impl DecodeReport {
+    /// Get the number of dropped layers (malformed or wrong extent).
+    pub fn dropped_layers(&self) -> usize {
+        self.dropped_layers
+    }
+
+    /// Get the number of dropped features (invalid tags or geometry).
+    pub fn dropped_features(&self) -> usize {
+        self.dropped_features
+    }
+
+    /// Check if nothing was dropped.
+    pub fn is_empty(&self) -> bool {
+        self.dropped_layers == 0 && self.dropped_features == 0
+    }
+}
+
+impl fmt::Display for DecodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "dropped {} layers, {} features",
+            self.dropped_layers, self.dropped_features
+        )
+    }
+}
+
+/// Check that a feature's tags and geometry are valid
+fn check_feature(layer: &VtLayer, feature: &VtFeature) -> Result<()> {
+    let (n_keys, n_values) = (layer.keys.len(), layer.values.len());
+    if !feature.tags.len().is_multiple_of(2)
+        || feature
+            .tags
+            .chunks_exact(2)
+            .any(|kv| kv[0] as usize >= n_keys || kv[1] as usize >= n_values)
+    {
+        return Err(Error::InvalidLayer());
+    }
+    decode_parts(&feature.geometry)?;
+    Ok(())
+}
+
+/// Find the byte offset of a feature within an encoded tile
+fn feature_offset(data: &[u8], body: &[u8], n_feature: usize) -> usize {
+    Fields::new(body)
+        .filter_map(|field| match field {
+            Ok(Field {
+                number: 2,
+                value: WireValue::Bytes(f),
+                ..
+            }) => Some(offset_of(data, f)),
+            _ => None,
+        })
+        .nth(n_feature)
+        .unwrap_or_else(|| offset_of(data, body))
+}
+
+/// Decode and check all layers of an encoded tile.
+///
+/// In lenient mode, invalid layers and features are dropped and counted;
+/// otherwise the first one is an error.  Returns the layers with their
+/// (common) extent.  Errors are located with [Error::Decode].
+///
+/// [Error::Decode]: enum.Error.html#variant.Decode
+pub(crate) fn decode_checked(
+    data: &[u8],
+    lenient: bool,
+) -> Result<(VecTile, u32, DecodeReport)> {
+    let fail = |err: Error, at| {
+        if lenient {
+            Ok(())
+        } else {
+            Err(err.at(at))
+        }
+    };
+    let mut vec_tile = VecTile::new();
+    let mut extent = None;
+    let mut report = DecodeReport::default();
+    let mut fields = Fields::new(data);
+    let mut n_layer = 0;
+    loop {
+        let at = DecodeLocation::new(n_layer, None, fields.offset());
+        let body = match fields.next() {
+            None => break,
+            Some(Ok(Field {
+                number: 3,
+                value: WireValue::Bytes(body),
+                ..
+            })) => body,
+            Some(Ok(Field { number: 3, .. })) => {
+                // layer with wrong wire type
+                n_layer += 1;
+                fail(Error::InvalidLayer(), at)?;
+                report.dropped_layers += 1;
+                continue;
+            }
+            Some(Ok(field)) => {
+                let unknown = vec_tile.special_fields.mut_unknown_fields();
+                keep_unknown(unknown, &field);
+                continue;
+            }
+            Some(Err(err)) => {
+                // framing is lost; nothing after this can be decoded
+                fail(err, at)?;
+                report.dropped_layers += 1;
+                break;
+            }
+        };
+        n_layer += 1;
+        let mut layer = match parse_layer(body) {
+            Ok(layer) => layer,
+            Err(err) => {
+                fail(err, at)?;
+                report.dropped_layers += 1;
+                continue;
+            }
+        };
+        if *extent.get_or_insert(layer.extent()) != layer.extent() {
+            fail(Error::WrongExtent(), at)?;
+            report.dropped_layers += 1;
+            continue;
+        }
+        let features = std::mem::take(&mut layer.features);
+        for (n_feature, feature) in features.into_iter().enumerate() {
+            match check_feature(&layer, &feature) {
+                Ok(()) => layer.features.push(feature),
+                Err(err) => {
+                    let offset = feature_offset(data, body, n_feature);
+                    let at = DecodeLocation::new(
+                        at.layer(),
+                        Some(n_feature),
+                        offset,
+                    );
+                    fail(err, at)?;
+                    report.dropped_features += 1;
+                }
+            }
+        }
+        vec_tile.layers.push(layer);
+    }
+    if !report.is_empty() {
+        warn!("Decoding {report}");
+    }
+    Ok((vec_tile, extent.unwrap_or(4096), report))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use protobuf::Message;
+
+    #[test]
+    fn test_decode_checked() {
+        // layer { version: 2, name: "a", keys: ["k"],
+        //   features: [{ tags: [0] }] }
+        let bad_tags = [
+            0x1a, 0x0d, 0x78, 2, 0x0a, 0x01, b'a', 0x1a, 0x01, b'k', 0x12,
+            0x03, 0x12, 0x01, 0x00,
+        ];
+        // layer { version: 2, name: "b", features: [{ geometry: [9, 2] }] }
+        let bad_geom = [
+            0x1a, 0x0b, 0x78, 2, 0x0a, 0x01, b'b', 0x12, 0x04, 0x22, 0x02, 9, 2,
+        ];
+        // layer { version: 2, name: "c", features: [{}] }
+        let good = [0x1a, 0x07, 0x78, 2, 0x0a, 0x01, b'c', 0x12, 0x00];
+        // layer { version: 2, name: "d", extent: 256 }
+        let extent = [0x1a, 0x08, 0x78, 2, 0x0a, 0x01, b'd', 0x28, 0x80, 0x02];
+        let data = [&good[..], &bad_tags, &bad_geom, &extent, &good[..4]];
+        let data = data.concat();
+        match decode_checked(&data, false) {
+            Err(Error::Decode(loc, err)) => {
+                assert_eq!(loc, DecodeLocation::new(1, Some(0), 21));
+                assert!(matches!(*err, Error::InvalidLayer()));
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+        let data2 = [&good[..], &extent].concat();
+        match decode_checked(&data2, false) {
+            Err(Error::Decode(loc, _)) => {
+                assert_eq!(loc.to_string(), "layer 1, byte 9");
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+        assert!(decode_checked(&extent, false).is_ok());
+        let (vec_tile, extent, report) = decode_checked(&data, true).unwrap();
+        assert_eq!(extent, 4096);
+        assert_eq!(vec_tile.layers.len(), 3);
+        assert_eq!(vec_tile.layers[0].features.len(), 1);
+        assert_eq!(vec_tile.layers[1].features.len(), 0);
+        assert_eq!(vec_tile.layers[2].features.len(), 0);
+        assert_eq!(
+            (report.dropped_layers(), report.dropped_features()),
+            (2, 2)
+        );
+        assert_eq!(report.to_string(), "dropped 2 layers, 2 features");
+        let (_, _, report) = decode_checked(&good, false).unwrap();
+        assert!(report.is_empty());
+        // extension field 16: 7
+        let data = [&good[..], &[0x80, 0x01, 0x07]].concat();
+        let (vec_tile, _, _) = decode_checked(&data, false).unwrap();
+        assert_eq!(vec_tile.write_to_bytes().unwrap(), data);
+    }
+
+    #[test]
+    fn test_layer_wire_type() {
+        // layer { version: 2, name: "c", features: [{}] }
+        let good = [0x1a, 0x07, 0x78, 2, 0x0a, 0x01, b'c', 0x12, 0x00];
+        // field 3 (layers) as varint: 5
+        let data = [&good[..], &[0x18, 0x05], &good[..]].concat();
+        match decode_checked(&data, false) {
+            Err(Error::Decode(loc, err)) => {
+                assert_eq!(loc.to_string(), "layer 1, byte 9");
+                assert!(matches!(*err, Error::InvalidLayer()));
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+        let (vec_tile, _, report) = decode_checked(&data, true).unwrap();
+        assert_eq!(vec_tile.layers.len(), 2);
+        assert_eq!(report.dropped_layers(), 1);
+        assert_eq!(report.to_string(), "dropped 1 layers, 0 features");
+    }
+}
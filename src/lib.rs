@@ -2,7 +2,7 @@
 //
 // Copyright (c) 2019-2021  Minnesota Department of Transportation
 //
-//! A library for encoding [mapbox vector tiles].
+//! A library for encoding and decoding [mapbox vector tiles].
 //!
 //! A [tile] is composed of one or more [layer]s.  Each layer can have any number
 //! of [feature]s, which contain the geometry to be rendered.  They can also have
@@ -36,22 +36,168 @@
 //! }
 //! ```
 //!
+//! ## Decoding
+//!
+//! Encoded tiles can be decoded with [Tile::from_bytes], or inspected with a
+//! zero-copy [TileView].  A view borrows layer names, keys and string values
+//! from the encoded bytes, allocating only for each layer's tables.
+//!
+//! ```rust
+//! use mvt::{Error, Tile, TileView};
+//!
+//! fn main() -> Result<(), Error> {
+//!     let mut tile = Tile::new(4096);
+//!     tile.add_layer(tile.create_layer("First Layer"))?;
+//!     let data = tile.to_bytes()?;
+//!     let view = TileView::new(&data)?;
+//!     for layer in view.layers() {
+//!         println!("{}: {} features", layer.name(), layer.num_features());
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
 //! [feature]: struct.Feature.html
 //! [layer]: struct.Layer.html
 //! [mapbox vector tiles]: https://github.com/mapbox/vector-tile-spec
 //! [tile]: struct.Tile.html
+//! [Tile::from_bytes]: struct.Tile.html#method.from_bytes
+//! [TileView]: struct.TileView.html
 #![forbid(unsafe_code)]
 
 #[macro_use]
 extern crate log;
 
+mod archive;
+mod assertions;
+mod bearing;
+mod buffer;
+mod builder;
+mod cache;
+mod cancel;
+mod chunk;
+mod clip;
+mod contour;
+mod debug;
+mod dedup;
+mod delta;
+mod determinism;
+mod duplicate;
 mod encoder;
 mod error;
+mod exactness;
+mod fixed;
+mod floatfmt;
+mod font;
+mod geometry;
+mod gpkg;
+mod hooks;
+mod index;
+mod interpolate;
+mod label;
+mod legacy;
+mod lenient;
+mod limits;
 mod mapgrid;
+#[cfg(feature = "rusqlite")]
+mod mbtiles;
+mod metadata;
+mod options;
+mod pmtiles;
+mod precise;
+mod predicates;
+#[cfg(feature = "serde")]
+mod props;
+mod provenance;
+mod segindex;
+#[cfg(feature = "serde")]
+mod serial;
+mod size;
+mod smooth;
+mod sort;
+mod source;
+mod spacing;
+mod split;
+#[cfg(feature = "stream")]
+mod stream;
+mod summary;
 mod tile;
+mod tilebuilder;
+mod transform;
+mod uniform;
 mod vector_tile;
+mod view;
+mod visitor;
+mod watermark;
+mod wire;
+#[cfg(feature = "zstd")]
+mod zdict;
 
-pub use crate::encoder::{GeomData, GeomEncoder, GeomType};
-pub use crate::error::Error;
+pub use crate::archive::{
+    for_each_tile, DirSink, DirSource, IterSource, TileSink, TileSource,
+};
+pub use crate::assertions::TileAssertions;
+pub use crate::bearing::{bearing, line_bearings};
+pub use crate::buffer::point_buffer_m;
+pub use crate::builder::{
+    BuilderState, FeatureBuilder, NoGeometry, NoTags, TagState, Tagged,
+};
+pub use crate::cache::{CachedLayer, LayerCache};
+pub use crate::cancel::CancelToken;
+pub use crate::chunk::TileChunks;
+pub use crate::contour::ContourLayer;
+pub use crate::debug::DebugLayer;
+pub use crate::dedup::FloatDedup;
+pub use crate::delta::TileDelta;
+pub use crate::determinism::{
+    assert_deterministic, canonicalize, determinism_digest, reference_tile,
+};
+pub use crate::duplicate::{DuplicateTiles, TileDigest};
+pub use crate::encoder::{
+    GeomCommand, GeomData, GeomDecoder, GeomEncoder, GeomType,
+};
+pub use crate::error::{DecodeLocation, Error};
+pub use crate::exactness::{round_trip_error, RoundTripError};
+pub use crate::fixed::FixedEncoder;
+pub use crate::floatfmt::FloatFormat;
+pub use crate::font::{text_geometry, text_width};
+pub use crate::geometry::{Geometry, Winding};
+pub use crate::gpkg::GpkgGeometry;
+#[cfg(feature = "rusqlite")]
+pub use crate::gpkg::GpkgReader;
+pub use crate::hooks::TileHooks;
+pub use crate::index::TileIndex;
+pub use crate::label::LabelFilter;
+pub use crate::lenient::DecodeReport;
+pub use crate::limits::DecodeLimits;
 pub use crate::mapgrid::{MapGrid, TileId};
+#[cfg(feature = "rusqlite")]
+pub use crate::mbtiles::{MbtilesSink, MbtilesSource};
+pub use crate::metadata::METADATA_PREFIX;
+pub use crate::options::{EncodeOptions, EncodeReport};
+pub use crate::pmtiles::PmtilesReader;
+pub use crate::precise::PreciseTransform;
+pub use crate::provenance::{Provenance, PROVENANCE_LAYER};
+pub use crate::segindex::SegmentIndex;
+#[cfg(feature = "serde")]
+pub use crate::serial::{bbox as serde_bbox, transform as serde_transform};
+pub use crate::size::{GeomStats, LayerSize};
+pub use crate::smooth::Smoother;
+pub use crate::sort::TableSort;
+pub use crate::source::{FeatureSource, SourceFeature, SourceValue};
+pub use crate::spacing::LineSpacer;
+pub use crate::split::PolygonSplitter;
+#[cfg(feature = "stream")]
+pub use crate::stream::{
+    write_tileset_stream, Frame, FrameKind, StreamSink, StreamSource,
+};
+pub use crate::summary::{LayerSummary, TileSummary};
 pub use crate::tile::{Feature, Layer, Tile};
+pub use crate::tilebuilder::{BudgetReport, TileBuilder};
+pub use crate::transform::{TransformDisplay, TransformParams};
+pub use crate::uniform::{full_extent_polygon, UniformTile, EMPTY_TILE};
+pub use crate::view::{FeatureView, Features, LayerView, TileView, ValueView};
+pub use crate::visitor::{decode_with, TileVisitor};
+pub use crate::watermark::{Watermark, WatermarkScore};
+#[cfg(feature = "zstd")]
+pub use crate::zdict::ZstdDict;
@@ -0,0 +1,250 @@
+// limits.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Decode limits, for untrusted tiles.
+//!
+use crate::error::{Error, Result};
+use crate::wire::{Field, Fields, WireValue};
+
+/// Limits on decoded tile contents, for tiles from untrusted sources.
+///
+/// Limits are checked by scanning the encoded data before anything is
+/// decoded, so exceeding one costs no more memory than the data itself.
+/// The default is no limits; [untrusted] is a conservative preset.
+///
+/// | Limit         | Counted                                        |
+/// |---------------|------------------------------------------------|
+/// | Layers        | layers in the tile                             |
+/// | Features      | features in all layers                         |
+/// | String length | bytes in each layer name, key or string value  |
+/// | Geometry      | command integers in each feature               |
+/// | Nesting       | message depth (2 for a layer with features)    |
+///
+/// Exceeding a limit returns [Error::LimitExceeded].
+///
+/// # Example
+/// ```
+/// # use mvt::{DecodeLimits, Error, Tile};
+/// # fn main() -> Result<(), Error> {
+/// let mut tile = Tile::new(4096);
+/// tile.add_layer(tile.create_layer("water"))?;
+/// tile.add_layer(tile.create_layer("roads"))?;
+/// let data = tile.to_bytes()?;
+/// let limits = DecodeLimits::untrusted().with_max_layers(1);
+/// assert!(Tile::from_bytes_with_limits(&data, &limits).is_err());
+/// # Ok(()) }
+/// ```
+///
+/// [Error::LimitExceeded]: enum.Error.html#variant.LimitExceeded
+/// [untrusted]: struct.DecodeLimits.html#method.untrusted
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DecodeLimits {
+    /// Maximum number of layers
+    max_layers: Option<usize>,
+
+    /// Maximum number of features (all layers)
+    max_features: Option<usize>,
+
+    /// Maximum string length (bytes)
+    max_string_len: Option<usize>,
+
+    /// Maximum geometry command integers per feature
+    max_geometry: Option<usize>,
+
+    /// Maximum message nesting depth
+    max_nesting: Option<u32>,
+}
+
+impl DecodeLimits {
+    /// Create conservative limits for untrusted tiles.
+    ///
+    /// These allow 256 layers, 1M features, 64 KiB strings, 4M geometry
+    /// integers per feature and nesting depth of 8.
+    pub fn untrusted() -> Self {
+        DecodeLimits {
+            max_layers: Some(256),
+            max_features: Some(1 << 20),
+            max_string_len: Some(1 << 16),
+            max_geometry: Some(1 << 22),
+            max_nesting: Some(8),
+        }
+    }
+
+    /// Set the maximum number of layers.
+    pub fn with_max_layers(mut self, max_layers: usize) -> Self {
+        self.max_layers = Some(max_layers);
+        self
+    }
+
+    /// Set the maximum number of features, in all layers.
+    pub fn with_max_features(mut self, max_features: usize) -> Self {
+        self.max_features = Some(max_features);
+        self
+    }
+
+    /// Set the maximum length (bytes) of layer names, keys and string
+    /// values.
+    pub fn with_max_string_len(mut self, max_len: usize) -> Self {
+        self.max_string_len = Some(max_len);
+        self
+    }
+
+    /// Set the maximum number of geometry command integers per feature.
+    pub fn with_max_geometry(mut self, max_geometry: usize) -> Self {
+        self.max_geometry = Some(max_geometry);
+        self
+    }
+
+    /// Set the maximum message nesting depth.
+    pub fn with_max_nesting(mut self, max_nesting: u32) -> Self {
+        self.max_nesting = Some(max_nesting);
+        self
+    }
+
+    /// Get the maximum message nesting depth.
+    pub(crate) fn max_nesting(&self) -> Option<u32> {
+        self.max_nesting
+    }
+
+    /// Check a count against a limit
+    fn check(
+        limit: Option<usize>,
+        count: usize,
+        name: &'static str,
+    ) -> Result<()> {
+        match limit {
+            Some(max) if count > max => Err(Error::LimitExceeded(name)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check message nesting depth
+    fn check_depth(&self, depth: u32) -> Result<()> {
+        match self.max_nesting {
+            Some(max) if depth > max => Err(Error::LimitExceeded("nesting")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a string length
+    fn check_string(&self, s: &[u8]) -> Result<()> {
+        Self::check(self.max_string_len, s.len(), "string length")
+    }
+
+    /// Check an encoded tile against the limits.
+    pub(crate) fn check_tile(&self, data: &[u8]) -> Result<()> {
+        let mut n_layers = 0;
+        let mut n_features = 0;
+        for field in Fields::new(data) {
+            if let Field {
+                number: 3,
+                value: WireValue::Bytes(body),
+                ..
+            } = field?
+            {
+                n_layers += 1;
+                Self::check(self.max_layers, n_layers, "layers")?;
+                self.check_depth(1)?;
+                self.check_layer(body, &mut n_features)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check an encoded layer
+    fn check_layer(&self, body: &[u8], n_features: &mut usize) -> Result<()> {
+        for field in Fields::new(body) {
+            let field = field?;
+            match (field.number, field.value) {
+                (1 | 3, WireValue::Bytes(s)) => self.check_string(s)?,
+                (2, WireValue::Bytes(feature)) => {
+                    *n_features += 1;
+                    Self::check(self.max_features, *n_features, "features")?;
+                    self.check_depth(2)?;
+                    self.check_feature(feature)?;
+                }
+                (4, WireValue::Bytes(value)) => {
+                    self.check_depth(2)?;
+                    for field in Fields::new(value) {
+                        let field = field?;
+                        if let (1, WireValue::Bytes(s)) =
+                            (field.number, field.value)
+                        {
+                            self.check_string(s)?;
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Check an encoded feature
+    fn check_feature(&self, body: &[u8]) -> Result<()> {
+        let mut n_geometry = 0;
+        for field in Fields::new(body) {
+            let field = field?;
+            match (field.number, field.value) {
+                (4, WireValue::Bytes(packed)) => {
+                    // count final bytes of varints
+                    n_geometry +=
+                        packed.iter().filter(|b| *b & 0x80 == 0).count();
+                }
+                (4, WireValue::Varint(_)) => n_geometry += 1,
+                _ => continue,
+            }
+            Self::check(self.max_geometry, n_geometry, "geometry")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    fn tile() -> Vec<u8> {
+        let mut tile = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Linestring, Transform::default())
+            .point(0.0, 0.0)
+            .unwrap()
+            .point(300.0, 0.0)
+            .unwrap()
+            .point(300.0, 300.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("roads").into_feature(geom);
+        feature.add_tag_string("name", "Main Street");
+        tile.add_layer(feature.into_layer()).unwrap();
+        tile.add_layer(tile.create_layer("water")).unwrap();
+        tile.to_bytes().unwrap()
+    }
+
+    fn err(limits: DecodeLimits) -> Option<&'static str> {
+        match limits.check_tile(&tile()) {
+            Err(Error::LimitExceeded(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        assert!(DecodeLimits::default().check_tile(&tile()).is_ok());
+        assert!(DecodeLimits::untrusted().check_tile(&tile()).is_ok());
+        let limits = DecodeLimits::default();
+        assert_eq!(err(limits.with_max_layers(1)), Some("layers"));
+        assert_eq!(err(limits.with_max_features(0)), Some("features"));
+        assert_eq!(err(limits.with_max_string_len(8)), Some("string length"));
+        assert_eq!(err(limits.with_max_string_len(11)), None);
+        assert_eq!(err(limits.with_max_geometry(7)), Some("geometry"));
+        assert_eq!(err(limits.with_max_geometry(8)), None);
+        assert_eq!(err(limits.with_max_nesting(1)), Some("nesting"));
+        assert_eq!(err(limits.with_max_nesting(2)), None);
+    }
+}
@@ -5,8 +5,10 @@
 //! TileId and MapGrid structs.
 //!
 use crate::error::{Error, Result};
+use crate::geometry::EARTH_RADIUS_M;
 use num_traits::FromPrimitive;
 use pointy::{BBox, Float, Pt, Transform};
+use std::f64::consts::TAU;
 use std::fmt;
 
 /// Web Mercator map constants
@@ -23,12 +25,17 @@ impl MapConst for f64 {
     const HALF_SIZE_M: Self = 20_037_508.342_789_248;
 }
 
+/// Maximum Web Mercator latitude (degrees)
+const MAX_LATITUDE: f64 = 85.051_128_779_806_6;
+
 /// A tile ID identifies a tile on a map grid at a specific zoom level.
 ///
 /// It uses XYZ addressing, with X increasing from west to east and Y increasing
 /// from north to south.  The X and Y values can range from 0 to
 /// 2<sup>Z</sup>-1.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "crate::serial::RawTileId"))]
 pub struct TileId {
     x: u32, // not public to prevent invalid values being created
     y: u32,
@@ -43,6 +50,18 @@ pub struct TileId {
 /// [tile]: struct.Tile.html
 /// [Web Mercator]: https://en.wikipedia.org/wiki/Web_Mercator_projection
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(
+        from = "crate::serial::RawMapGrid<F>",
+        into = "crate::serial::RawMapGrid<F>",
+        bound(
+            serialize = "F: serde::Serialize",
+            deserialize = "F: serde::Deserialize<'de>"
+        )
+    )
+)]
 pub struct MapGrid<F>
 where
     F: Float,
@@ -54,6 +73,28 @@ where
     bbox: BBox<F>,
 }
 
+#[cfg(feature = "serde")]
+impl<F> From<MapGrid<F>> for crate::serial::RawMapGrid<F>
+where
+    F: Float,
+{
+    fn from(grid: MapGrid<F>) -> Self {
+        let MapGrid { srid, bbox } = grid;
+        crate::serial::RawMapGrid { srid, bbox }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<F> From<crate::serial::RawMapGrid<F>> for MapGrid<F>
+where
+    F: Float,
+{
+    fn from(raw: crate::serial::RawMapGrid<F>) -> Self {
+        let crate::serial::RawMapGrid { srid, bbox } = raw;
+        MapGrid { srid, bbox }
+    }
+}
+
 impl TileId {
     /// Get the X value.
     pub fn x(&self) -> u32 {
@@ -69,6 +110,62 @@ impl TileId {
     pub fn z(&self) -> u32 {
         self.z
     }
+
+    /// Convert a ground distance to tile units, at the tile's latitude.
+    ///
+    /// * `meters` Distance on the ground (meters).
+    /// * `extent` Tile extent.
+    ///
+    /// Web Mercator scale increases toward the poles, by the secant of the
+    /// latitude; the scale at the center of the tile is used.  This is
+    /// suitable for widths of features within the tile, such as roads.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, TileId};
+    /// # fn main() -> Result<(), Error> {
+    /// let equator = TileId::new(8, 7, 4)?;
+    /// let north = TileId::new(8, 2, 4)?;
+    /// let w0 = equator.meters_to_tile_units(20.0, 4096);
+    /// let w1 = north.meters_to_tile_units(20.0, 4096);
+    /// assert!(w1 > w0 * 2.0);
+    /// # Ok(()) }
+    /// ```
+    pub fn meters_to_tile_units(&self, meters: f64, extent: u32) -> f64 {
+        let (_, lat) = self.lonlat(0.5, 0.5);
+        let circumference = TAU * EARTH_RADIUS_M * lat.to_radians().cos();
+        meters * f64::from(extent) * f64::from(1u32 << self.z) / circumference
+    }
+}
+
+impl TileId {
+    /// Get the Web Mercator longitude / latitude (degrees) of a point.
+    ///
+    /// * `x` Point X, in 0 to 1 range across the tile (west to east).
+    /// * `y` Point Y, in 0 to 1 range across the tile (north to south).
+    pub(crate) fn lonlat(&self, x: f64, y: f64) -> (f64, f64) {
+        let n = f64::from(1u32 << self.z);
+        let x = (f64::from(self.x) + x) / n;
+        let y = (f64::from(self.y) + y) / n;
+        let lon = x * 360.0 - 180.0;
+        let lat = (std::f64::consts::PI * (1.0 - 2.0 * y))
+            .sinh()
+            .atan()
+            .to_degrees();
+        (lon, lat)
+    }
+
+    /// Get the position of a Web Mercator longitude / latitude (degrees).
+    ///
+    /// Returns X and Y, in 0 to 1 range across the tile (inverse of
+    /// `lonlat`); latitude is clamped to the Web Mercator limit.
+    pub(crate) fn tile_xy(&self, lon: f64, lat: f64) -> (f64, f64) {
+        let n = f64::from(1u32 << self.z);
+        let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+        let x = (lon + 180.0) / 360.0;
+        let y = (1.0 - lat.tan().asinh() / std::f64::consts::PI) / 2.0;
+        (x * n - f64::from(self.x), y * n - f64::from(self.y))
+    }
 }
 
 impl fmt::Display for TileId {
@@ -152,17 +249,42 @@ where
     }
 
     /// Get the transform to coördinates in 0 to 1 range.
+    ///
+    /// The transform is a single affine matrix, so the (scaled) tile corner
+    /// is subtracted after scaling map coördinates.  At high zoom levels
+    /// (z20+), [tile_point] is more precise.
+    ///
+    /// [tile_point]: struct.MapGrid.html#method.tile_point
     pub fn tile_transform(&self, tid: TileId) -> Transform<F> {
-        let tx = self.bbox.x_min(); // west edge
-        let ty = self.bbox.y_max(); // north edge
+        let (tx, ty, sx, sy) = self.tile_origin(tid);
+        Transform::with_translate(-tx, -ty).scale(sx, -sy)
+    }
+
+    /// Transform a point to coördinates in 0 to 1 range.
+    ///
+    /// * `tid` Tile ID.
+    /// * `x` Map X coördinate.
+    /// * `y` Map Y coördinate.
+    ///
+    /// Unlike [tile_transform], the tile's north-west corner is subtracted
+    /// before scaling, so high zoom tiles (z20+) are not quantized by the
+    /// magnitude of scaled map coördinates.
+    ///
+    /// [tile_transform]: struct.MapGrid.html#method.tile_transform
+    pub fn tile_point(&self, tid: TileId, x: F, y: F) -> Pt<F> {
+        let (tx, ty, sx, sy) = self.tile_origin(tid);
+        Pt::new((x - tx) * sx, (ty - y) * sy)
+    }
+
+    /// Get the north-west corner and scales of a tile
+    fn tile_origin(&self, tid: TileId) -> (F, F, F, F) {
+        let bbox = self.tile_bbox(tid);
+        let tx = bbox.x_min(); // west edge of tile
+        let ty = bbox.y_max(); // north edge of tile
         let tz = F::from_u32(1 << tid.z).unwrap();
         let sx = tz / self.bbox.x_span();
         let sy = tz / self.bbox.y_span();
-        let tidx = F::from_u32(tid.x).unwrap();
-        let tidy = F::from_u32(tid.y).unwrap();
-        Transform::with_translate(-tx, -ty)
-            .scale(sx, -sy)
-            .translate(-tidx, -tidy)
+        (tx, ty, sx, sy)
     }
 }
 
@@ -177,6 +299,7 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::precise::PreciseTransform;
 
     #[test]
     fn test_tile_bbox() {
@@ -210,6 +333,36 @@ mod test {
         assert_eq!(b.y_max(), 5635549.221409475);
     }
 
+    #[test]
+    fn test_lonlat() {
+        let tid = TileId::new(0, 0, 0).unwrap();
+        assert_eq!(tid.lonlat(0.5, 0.5), (0.0, 0.0));
+        let (lon, lat) = tid.lonlat(0.0, 0.0);
+        assert_eq!(lon, -180.0);
+        assert!((lat - 85.051_128_779_806_6).abs() < 1e-9);
+        let tid = TileId::new(1, 0, 1).unwrap();
+        assert_eq!(tid.lonlat(1.0, 1.0), (180.0, 0.0));
+        let (x, y) = tid.tile_xy(-90.0, 45.0);
+        let (lon, lat) = tid.lonlat(x, y);
+        assert!((lon + 90.0).abs() < 1e-9 && (lat - 45.0).abs() < 1e-9);
+        assert!(tid.tile_xy(0.0, 90.0).1.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_meters_to_tile_units() {
+        let tid = TileId::new(0, 0, 0).unwrap();
+        let equator = TAU * EARTH_RADIUS_M;
+        assert!(
+            (tid.meters_to_tile_units(equator, 4096) - 4096.0).abs() < 1e-9
+        );
+        // tile center at 60° north
+        let tid = TileId::new(0, 0, 1).unwrap();
+        let (_, lat) = tid.lonlat(0.5, 0.5);
+        let w = tid.meters_to_tile_units(100.0, 4096);
+        let w0 = 100.0 * 4096.0 * 2.0 / equator;
+        assert!((w * lat.to_radians().cos() - w0).abs() < 1e-9);
+    }
+
     #[test]
     fn test_tile_transform() {
         let g = MapGrid::default();
@@ -251,4 +404,48 @@ mod test {
             t * Pt::new(-10370975.997732716, 5596413.462927466)
         );
     }
+
+    #[test]
+    fn test_tile_point() {
+        let g = MapGrid::<f64>::default();
+        let tid = TileId::new(246, 368, 10).unwrap();
+        let b = g.tile_bbox(tid);
+        let p = g.tile_point(tid, b.x_min(), b.y_max());
+        assert_eq!(p, Pt::new(0.0, 0.0));
+        // subtracting the corner first is more precise at high zoom
+        let tid = TileId::new(5_033_164, 5_033_164, 24).unwrap();
+        let b = g.tile_bbox(tid);
+        let t = g.tile_transform(tid);
+        let precise = PreciseTransform::new(&g, tid, 4096);
+        let (mut err_t, mut err_p) = (0.0f64, 0.0f64);
+        for i in 0..16 {
+            for j in 0..16 {
+                let x = b.x_min() + b.x_span() * f64::from(i) / 16.0;
+                let y = b.y_max() - b.y_span() * f64::from(j) / 16.0;
+                let (px, py) = precise.transform(x, y);
+                let err = |p: Pt<f64>| {
+                    (p.x() * 4096.0 - px).abs().max((p.y() * 4096.0 - py).abs())
+                };
+                err_t = err_t.max(err(t * Pt::new(x, y)));
+                err_p = err_p.max(err(g.tile_point(tid, x, y)));
+            }
+        }
+        assert!(err_p < 5e-7, "{err_p}");
+        assert!(err_p < err_t, "{err_p} {err_t}");
+    }
+
+    #[test]
+    fn test_tile_transform_high_zoom() {
+        let g = MapGrid::<f64>::default();
+        let tid = TileId::new(263_123, 385_456, 20).unwrap();
+        let t = g.tile_transform(tid);
+        let b = g.tile_bbox(tid);
+        assert_eq!(Pt::new(0.0, 0.0), t * Pt::new(b.x_min(), b.y_max()));
+        // one tile unit at extent 4096 is less than 1 cm
+        let p = t * Pt::new(b.x_min() + b.x_span() / 4096.0, b.y_max());
+        assert!((p.x() * 4096.0 - 1.0).abs() < 1e-6);
+        assert!(p.y().abs() < 1e-12);
+        let p = t * Pt::new(b.x_max(), b.y_min());
+        assert!((p.x() - 1.0).abs() < 1e-9 && (p.y() - 1.0).abs() < 1e-9);
+    }
 }
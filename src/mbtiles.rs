@@ -0,0 +1,384 @@
+// mbtiles.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! MBTiles (SQLite) tile archives.
+//!
+use crate::archive::{TileSink, TileSource};
+use crate::duplicate::{DuplicateTiles, TileDigest};
+use crate::error::{Error, Result};
+use crate::mapgrid::TileId;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// Maximum size of memory-mapped file (bytes)
+const MMAP_SIZE: i64 = 1 << 30;
+
+/// Number of tiles read per query
+const BATCH_LEN: i64 = 64;
+
+/// Schema with deduplicated tile images
+const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS metadata (name TEXT PRIMARY KEY, value TEXT);
+CREATE TABLE IF NOT EXISTS map (
+    zoom_level INTEGER,
+    tile_column INTEGER,
+    tile_row INTEGER,
+    tile_id TEXT,
+    PRIMARY KEY (zoom_level, tile_column, tile_row)
+);
+CREATE TABLE IF NOT EXISTS images (tile_id TEXT PRIMARY KEY, tile_data BLOB);
+CREATE VIEW IF NOT EXISTS tiles AS
+    SELECT map.zoom_level AS zoom_level,
+           map.tile_column AS tile_column,
+           map.tile_row AS tile_row,
+           images.tile_data AS tile_data
+    FROM map JOIN images ON images.tile_id = map.tile_id;";
+
+/// Tile sink for an [MBTiles] file.
+///
+/// Identical tiles (common for ocean / empty tiles) are stored once, in the
+/// `images` table, keyed by [TileDigest].  The `map` table links tile IDs to
+/// images, and the `tiles` view joins them as the spec requires.  Tile rows
+/// use the TMS scheme (`y` flipped).
+///
+/// [MBTiles]: https://github.com/mapbox/mbtiles-spec
+/// [TileDigest]: struct.TileDigest.html
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, MbtilesSink, Tile, TileId, TileSink};
+/// # fn main() -> Result<(), Error> {
+/// let conn = rusqlite::Connection::open_in_memory()?;
+/// let mut sink = MbtilesSink::from_connection(conn)?;
+/// sink.set_metadata("format", "pbf")?;
+/// let data = Tile::new(4096).to_bytes()?;
+/// sink.put_tile(TileId::new(0, 0, 1)?, &data)?;
+/// sink.put_tile(TileId::new(1, 0, 1)?, &data)?;
+/// assert_eq!(sink.duplicates().num_duplicates(), 1);
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct MbtilesSink {
+    /// SQLite connection
+    conn: Connection,
+
+    /// Duplicate tiles written
+    dups: DuplicateTiles,
+}
+
+/// Tile source for an [MBTiles] file.
+///
+/// Tiles are read from the `tiles` table (or view), ordered by zoom level,
+/// column and row; rows are converted from the TMS scheme.  SQLite
+/// memory-maps the file (up to 1 GiB), so tile data is not copied through
+/// its page cache.
+///
+/// [MBTiles]: https://github.com/mapbox/mbtiles-spec
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, MbtilesSource, Tile, TileId, TileSource};
+/// # fn main() -> Result<(), Error> {
+/// let conn = rusqlite::Connection::open_in_memory()?;
+/// conn.execute_batch(
+///     "CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, \
+///      tile_row INTEGER, tile_data BLOB)",
+/// )?;
+/// let data = Tile::new(4096).to_bytes()?;
+/// conn.execute(
+///     "INSERT INTO tiles VALUES (1, 0, 0, ?1)",
+///     rusqlite::params![data],
+/// )?;
+/// let mut source = MbtilesSource::from_connection(conn)?;
+/// let (tid, _data) = source.next_tile()?.unwrap();
+/// assert_eq!(tid, TileId::new(0, 1, 1)?);
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct MbtilesSource {
+    /// SQLite connection
+    conn: Connection,
+
+    /// Zoom level, column and TMS row of last tile read
+    last: (i64, i64, i64),
+
+    /// Tiles read, but not yet returned
+    batch: VecDeque<(TileId, Vec<u8>)>,
+}
+
+/// Make a tile ID from zoom level, column and TMS row
+fn tms_tile(z: i64, x: i64, tms: i64) -> Result<TileId> {
+    let (Ok(z), Ok(x), Ok(tms)) =
+        (u32::try_from(z), u32::try_from(x), u32::try_from(tms))
+    else {
+        return Err(Error::InvalidTid());
+    };
+    if z > 31 || tms >= 1 << z {
+        return Err(Error::InvalidTid());
+    }
+    TileId::new(x, (1 << z) - 1 - tms, z)
+}
+
+impl MbtilesSink {
+    /// Create an MBTiles file (or open an existing one for writing).
+    ///
+    /// * `path` MBTiles file path.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Create MBTiles tables on a SQLite connection.
+    pub fn from_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(MbtilesSink {
+            conn,
+            dups: DuplicateTiles::default(),
+        })
+    }
+
+    /// Set a metadata value.
+    ///
+    /// * `name` Metadata name, such as `name`, `format` or `bounds`.
+    /// * `value` Metadata value.
+    pub fn set_metadata(&mut self, name: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO metadata (name, value) VALUES (?1, ?2)",
+            [name, value],
+        )?;
+        Ok(())
+    }
+
+    /// Get the duplicate tiles written.
+    pub fn duplicates(&self) -> &DuplicateTiles {
+        &self.dups
+    }
+
+    /// Store tile data as an image, returning its ID
+    fn put_image(&mut self, data: &[u8]) -> Result<String> {
+        let tile_id = TileDigest::new(data).to_string();
+        let stored: Option<Vec<u8>> = self
+            .conn
+            .prepare_cached("SELECT tile_data FROM images WHERE tile_id = ?1")?
+            .query_row([&tile_id], |row| row.get(0))
+            .optional()?;
+        match stored {
+            // digests can collide, so only share identical images
+            Some(stored) if stored != data => {
+                return Err(Error::DigestCollision());
+            }
+            Some(_) => (),
+            None => {
+                self.conn
+                    .prepare_cached(
+                        "INSERT INTO images (tile_id, tile_data) \
+                         VALUES (?1, ?2)",
+                    )?
+                    .execute(params![tile_id, data])?;
+            }
+        }
+        Ok(tile_id)
+    }
+}
+
+impl TileSink for MbtilesSink {
+    fn put_tile(&mut self, tid: TileId, data: &[u8]) -> Result<()> {
+        let tile_id = self.put_image(data)?;
+        let row = (1u32 << tid.z()) - 1 - tid.y();
+        self.conn
+            .prepare_cached(
+                "INSERT OR REPLACE INTO map \
+                 (zoom_level, tile_column, tile_row, tile_id) \
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?
+            .execute(params![tid.z(), tid.x(), row, tile_id])?;
+        self.dups.add(tid, data);
+        Ok(())
+    }
+}
+
+impl MbtilesSource {
+    /// Open an MBTiles file for reading.
+    ///
+    /// * `path` MBTiles file path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY;
+        Self::from_connection(Connection::open_with_flags(path, flags)?)
+    }
+
+    /// Read MBTiles tables on a SQLite connection.
+    pub fn from_connection(conn: Connection) -> Result<Self> {
+        conn.pragma_update_and_check(None, "mmap_size", MMAP_SIZE, |row| {
+            row.get::<_, i64>(0)
+        })?;
+        Ok(MbtilesSource {
+            conn,
+            last: (-1, 0, 0),
+            batch: VecDeque::new(),
+        })
+    }
+
+    /// Get a metadata value.
+    ///
+    /// * `name` Metadata name, such as `name`, `format` or `bounds`.
+    pub fn metadata(&self, name: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .prepare_cached("SELECT value FROM metadata WHERE name = ?1")?
+            .query_row([name], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// Read one tile.
+    ///
+    /// * `tid` Tile ID.
+    ///
+    /// Returns `None` if there is no such tile.
+    pub fn get_tile(&self, tid: TileId) -> Result<Option<Vec<u8>>> {
+        let tms = (1u32 << tid.z()) - 1 - tid.y();
+        Ok(self
+            .conn
+            .prepare_cached(
+                "SELECT tile_data FROM tiles \
+                 WHERE zoom_level = ?1 AND tile_column = ?2 \
+                 AND tile_row = ?3",
+            )?
+            .query_row(params![tid.z(), tid.x(), tms], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// Read the next batch of tiles
+    fn read_batch(&mut self) -> Result<()> {
+        let (z, x, tms) = self.last;
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles \
+             WHERE (zoom_level, tile_column, tile_row) > (?1, ?2, ?3) \
+             ORDER BY zoom_level, tile_column, tile_row LIMIT ?4",
+        )?;
+        let mut rows = stmt.query(params![z, x, tms, BATCH_LEN])?;
+        while let Some(row) = rows.next()? {
+            self.last = (row.get(0)?, row.get(1)?, row.get(2)?);
+            let (z, x, tms) = self.last;
+            let tid = tms_tile(z, x, tms)?;
+            self.batch.push_back((tid, row.get(3)?));
+        }
+        Ok(())
+    }
+}
+
+impl TileSource for MbtilesSource {
+    fn next_tile(&mut self) -> Result<Option<(TileId, Vec<u8>)>> {
+        if self.batch.is_empty() {
+            self.read_batch()?;
+        }
+        Ok(self.batch.pop_front())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn count(sink: &MbtilesSink, table: &str) -> i64 {
+        let sql = format!("SELECT COUNT(*) FROM {table}");
+        sink.conn.query_row(&sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_dedup() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut sink = MbtilesSink::from_connection(conn).unwrap();
+        sink.set_metadata("name", "test").unwrap();
+        let tid = |x, y| TileId::new(x, y, 2).unwrap();
+        sink.put_tile(tid(0, 0), b"water").unwrap();
+        sink.put_tile(tid(1, 0), b"land").unwrap();
+        sink.put_tile(tid(2, 0), b"water").unwrap();
+        sink.put_tile(tid(3, 1), b"water").unwrap();
+        assert_eq!(count(&sink, "images"), 2);
+        assert_eq!(count(&sink, "tiles"), 4);
+        assert_eq!(sink.duplicates().num_duplicates(), 2);
+        let data: Vec<u8> = sink
+            .conn
+            .query_row(
+                "SELECT tile_data FROM tiles \
+                 WHERE zoom_level = 2 AND tile_column = 3 AND tile_row = 2",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(data, b"water");
+        let name: String = sink
+            .conn
+            .query_row(
+                "SELECT value FROM metadata WHERE name = 'name'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(name, "test");
+    }
+
+    #[test]
+    fn test_collision() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut sink = MbtilesSink::from_connection(conn).unwrap();
+        let digest = TileDigest::new(b"water").to_string();
+        sink.conn
+            .execute(
+                "INSERT INTO images (tile_id, tile_data) VALUES (?1, ?2)",
+                params![digest, b"land".to_vec()],
+            )
+            .unwrap();
+        let tid = TileId::new(0, 0, 0).unwrap();
+        assert!(matches!(
+            sink.put_tile(tid, b"water"),
+            Err(Error::DigestCollision())
+        ));
+        assert_eq!(count(&sink, "map"), 0);
+    }
+
+    #[test]
+    fn test_source() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut sink = MbtilesSink::from_connection(conn).unwrap();
+        sink.set_metadata("format", "pbf").unwrap();
+        let tid = |x, y, z| TileId::new(x, y, z).unwrap();
+        sink.put_tile(tid(1, 0, 1), b"a").unwrap();
+        sink.put_tile(tid(0, 1, 1), b"b").unwrap();
+        sink.put_tile(tid(0, 0, 0), b"c").unwrap();
+        for x in 0..100 {
+            sink.put_tile(tid(x, 0, 7), b"d").unwrap();
+        }
+        let mut source = MbtilesSource::from_connection(sink.conn).unwrap();
+        assert_eq!(source.metadata("format").unwrap().unwrap(), "pbf");
+        assert!(source.metadata("bounds").unwrap().is_none());
+        let data = source.get_tile(tid(0, 1, 1)).unwrap().unwrap();
+        assert_eq!(data, b"b");
+        assert!(source.get_tile(tid(1, 1, 1)).unwrap().is_none());
+        let mut tiles = vec![];
+        while let Some((tid, data)) = source.next_tile().unwrap() {
+            tiles.push((tid.to_string(), data));
+        }
+        assert_eq!(tiles.len(), 103);
+        // rows are in TMS order
+        assert_eq!(tiles[0], ("0/0/0".to_string(), b"c".to_vec()));
+        assert_eq!(tiles[1], ("1/0/1".to_string(), b"b".to_vec()));
+        assert_eq!(tiles[2], ("1/1/0".to_string(), b"a".to_vec()));
+        assert_eq!(tiles[102], ("7/99/0".to_string(), b"d".to_vec()));
+    }
+
+    #[test]
+    fn test_source_invalid() {
+        let conn = Connection::open_in_memory().unwrap();
+        let sink = MbtilesSink::from_connection(conn).unwrap();
+        sink.conn
+            .execute_batch(
+                "INSERT INTO map VALUES (1, 0, 2, 'x'); \
+                 INSERT INTO images VALUES ('x', x'00')",
+            )
+            .unwrap();
+        let mut source = MbtilesSource::from_connection(sink.conn).unwrap();
+        assert!(matches!(source.next_tile(), Err(Error::InvalidTid())));
+    }
+}
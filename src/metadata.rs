@@ -0,0 +1,109 @@
+// metadata.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Layer metadata convention.
+//!
+//! The MVT layer message has no slot for metadata, so it is stored in a
+//! *metadata feature*:
+//!
+//! * geometry type `UNKNOWN`, with no geometry (ignored by renderers)
+//! * first feature in the layer
+//! * string tags only, with keys prefixed by [METADATA_PREFIX]
+//!
+//! [METADATA_PREFIX]: constant.METADATA_PREFIX.html
+use crate::vector_tile::tile::{
+    Feature as VtFeature, GeomType as VtGeomType, Layer as VtLayer,
+};
+
+/// Key prefix for layer metadata tags
+pub const METADATA_PREFIX: &str = "_meta:";
+
+/// Check if a feature follows the metadata convention
+fn is_metadata(layer: &VtLayer, feature: &VtFeature) -> bool {
+    feature.type_() == VtGeomType::UNKNOWN
+        && feature.geometry.is_empty()
+        && !feature.tags.is_empty()
+        && feature.tags.chunks(2).all(|tag| {
+            layer
+                .keys
+                .get(tag[0] as usize)
+                .is_some_and(|k| k.starts_with(METADATA_PREFIX))
+        })
+}
+
+/// Get the index of the metadata feature of a layer
+pub(crate) fn metadata_feature(layer: &VtLayer) -> Option<usize> {
+    layer
+        .features
+        .first()
+        .filter(|f| is_metadata(layer, f))
+        .map(|_| 0)
+}
+
+/// Split a layer's features into its metadata feature and the rest
+pub(crate) fn split_metadata(
+    layer: &VtLayer,
+) -> (Option<&VtFeature>, &[VtFeature]) {
+    match metadata_feature(layer) {
+        Some(idx) => (Some(&layer.features[idx]), &layer.features[idx + 1..]),
+        None => (None, &layer.features),
+    }
+}
+
+/// Read metadata key / value pairs of a layer (without key prefix)
+pub(crate) fn read_metadata(layer: &VtLayer) -> Vec<(String, String)> {
+    let Some(idx) = metadata_feature(layer) else {
+        return vec![];
+    };
+    layer.features[idx]
+        .tags
+        .chunks_exact(2)
+        .filter_map(|tag| {
+            let key = layer.keys.get(tag[0] as usize)?;
+            let value = layer.values.get(tag[1] as usize)?;
+            let key = key.strip_prefix(METADATA_PREFIX)?;
+            value
+                .has_string_value()
+                .then(|| (key.to_string(), value.string_value().to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    #[test]
+    fn test_metadata() {
+        let mut tile = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(1.0, 1.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("roads").into_feature(geom);
+        feature.add_tag_string("_meta:name", "not metadata");
+        let layer = feature
+            .into_layer()
+            .with_metadata("source", "v1")
+            .with_metadata("attribution", "me")
+            .with_metadata("source", "v2");
+        assert_eq!(layer.num_features(), 2);
+        tile.add_layer(layer).unwrap();
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            tile.layer_metadata("roads").unwrap(),
+            vec![
+                ("source".to_string(), "v2".to_string()),
+                ("attribution".to_string(), "me".to_string()),
+            ]
+        );
+        assert!(tile.layer_metadata("rivers").is_none());
+        let mut tile = Tile::new(4096);
+        tile.add_layer(tile.create_layer("empty")).unwrap();
+        assert!(tile.layer_metadata("empty").unwrap().is_empty());
+    }
+}
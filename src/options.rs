@@ -0,0 +1,796 @@
+// options.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Encoding validation options.
+//!
+use crate::encoder::{decode_parts, encode_parts, GeomType};
+use crate::error::{Error, Result};
+use crate::vector_tile::tile::{
+    Feature as VtFeature, GeomType as VtGeomType, Layer as VtLayer,
+};
+use protobuf::Message;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Validation behavior for encoding, in one place.
+///
+/// Each check can be enabled separately.  In *strict* mode, a failed check
+/// is an error; in *lenient* mode, the problem is fixed or the offending
+/// data is dropped:
+///
+/// | Check           | Strict                   | Lenient                     |
+/// |-----------------|--------------------------|-----------------------------|
+/// | Winding         | `InvalidGeometry`        | polygon rings reversed      |
+/// | Degenerate      | `InvalidGeometry`        | parts / features dropped    |
+/// | Non-finite      | `InvalidValue`           | points skipped              |
+/// | String length   | `StringTooLong`          | strings truncated           |
+/// | Layer size      | `LayerTooLarge`          | trailing features dropped   |
+///
+/// The default performs no checks (other than rejecting non-finite points),
+/// matching the behavior without options.  Layer checks are applied by
+/// [Tile::add_layer]; point checks by [GeomEncoder::with_options].
+///
+/// # Example
+/// ```
+/// use mvt::{EncodeOptions, Tile};
+///
+/// let tile = Tile::new(4096).with_options(
+///     EncodeOptions::lenient().with_max_string_len(255),
+/// );
+/// ```
+///
+/// [Tile::add_layer]: struct.Tile.html#method.add_layer
+/// [GeomEncoder::with_options]: struct.GeomEncoder.html#method.with_options
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EncodeOptions {
+    /// Fix / drop instead of returning errors
+    lenient: bool,
+
+    /// Check polygon winding order
+    winding: bool,
+
+    /// Check winding order of each ring (exterior or hole)
+    ring_winding: bool,
+
+    /// Check for degenerate geometry
+    degenerate: bool,
+
+    /// Drop repeated closing points of polygon rings
+    ring_closing: bool,
+
+    /// Skip consecutive duplicate points
+    dedup: bool,
+
+    /// Maximum string value length (bytes)
+    max_string_len: Option<usize>,
+
+    /// Maximum encoded layer size (bytes)
+    max_layer_size: Option<usize>,
+}
+
+/// Fixes made to one layer in lenient mode.
+///
+/// See [Tile::encode_report].
+///
+/// [Tile::encode_report]: struct.Tile.html#method.encode_report
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EncodeReport {
+    name: String,
+    dropped_features: usize,
+    dropped_parts: usize,
+    fixed_rings: usize,
+    truncated_strings: usize,
+    skipped_points: usize,
+}
+
+impl EncodeReport {
+    /// Get the layer name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the number of dropped features (degenerate or over size limit).
+    pub fn dropped_features(&self) -> usize {
+        self.dropped_features
+    }
+
+    /// Get the number of dropped degenerate linestrings / rings.
+    pub fn dropped_parts(&self) -> usize {
+        self.dropped_parts
+    }
+
+    /// Get the number of polygon rings with reversed winding order.
+    pub fn fixed_rings(&self) -> usize {
+        self.fixed_rings
+    }
+
+    /// Get the number of truncated string values.
+    pub fn truncated_strings(&self) -> usize {
+        self.truncated_strings
+    }
+
+    /// Get the number of points skipped while encoding geometry.
+    ///
+    /// See [GeomEncoder::skipped_points].
+    ///
+    /// [GeomEncoder::skipped_points]: struct.GeomEncoder.html#method.skipped_points
+    pub fn skipped_points(&self) -> usize {
+        self.skipped_points
+    }
+
+    /// Add to the number of skipped points
+    pub(crate) fn add_skipped_points(&mut self, n: usize) {
+        self.skipped_points += n;
+    }
+
+    /// Add counts from another report (of the same layer)
+    pub(crate) fn merge(&mut self, other: &EncodeReport) {
+        self.dropped_features += other.dropped_features;
+        self.dropped_parts += other.dropped_parts;
+        self.fixed_rings += other.fixed_rings;
+        self.truncated_strings += other.truncated_strings;
+        self.skipped_points += other.skipped_points;
+    }
+
+    /// Check if nothing was fixed.
+    pub fn is_empty(&self) -> bool {
+        self.dropped_features == 0
+            && self.dropped_parts == 0
+            && self.fixed_rings == 0
+            && self.truncated_strings == 0
+            && self.skipped_points == 0
+    }
+}
+
+impl fmt::Display for EncodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: dropped {} features, {} parts; fixed {} rings; \
+             truncated {} strings; skipped {} points",
+            self.name,
+            self.dropped_features,
+            self.dropped_parts,
+            self.fixed_rings,
+            self.truncated_strings,
+            self.skipped_points
+        )
+    }
+}
+
+impl EncodeOptions {
+    /// Create strict options, with winding and degenerate geometry checks.
+    pub fn strict() -> Self {
+        EncodeOptions {
+            winding: true,
+            degenerate: true,
+            ..Default::default()
+        }
+    }
+
+    /// Create lenient options, with winding and degenerate geometry checks.
+    pub fn lenient() -> Self {
+        EncodeOptions {
+            lenient: true,
+            ..Self::strict()
+        }
+    }
+
+    /// Enable or disable polygon winding order check.
+    pub fn with_winding(mut self, winding: bool) -> Self {
+        self.winding = winding;
+        self
+    }
+
+    /// Enable or disable winding order check of each polygon ring.
+    ///
+    /// Each ring is classified by containment: a ring within the previous
+    /// exterior ring is a hole, and should have negative area; others are
+    /// exterior rings, with positive area.  In lenient mode, rings are
+    /// reversed as needed, so input with any winding convention (such as
+    /// GeoJSON) is corrected.  When enabled, this replaces the winding
+    /// check, which only considers the first ring of each feature.
+    ///
+    /// # Example
+    /// ```
+    /// use mvt::{EncodeOptions, Tile};
+    ///
+    /// let tile = Tile::new(4096)
+    ///     .with_options(EncodeOptions::lenient().with_ring_winding(true));
+    /// ```
+    pub fn with_ring_winding(mut self, ring_winding: bool) -> Self {
+        self.ring_winding = ring_winding;
+        self
+    }
+
+    /// Enable or disable degenerate geometry check.
+    pub fn with_degenerate(mut self, degenerate: bool) -> Self {
+        self.degenerate = degenerate;
+        self
+    }
+
+    /// Enable or disable dropping repeated closing points of polygon rings.
+    ///
+    /// Rings from sources such as GeoJSON repeat the first point at the end.
+    /// Since `ClosePath` closes every ring, [GeomEncoder] drops the repeated
+    /// point when a ring is completed.  Unclosed rings are unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{EncodeOptions, Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let options = EncodeOptions::default().with_ring_closing(true);
+    /// let geom_data = GeomEncoder::new(GeomType::Polygon, Transform::default())
+    ///     .with_options(options)
+    ///     .point(0.0, 0.0)?
+    ///     .point(10.0, 0.0)?
+    ///     .point(10.0, 10.0)?
+    ///     .point(0.0, 0.0)?
+    ///     .encode()?;
+    /// assert_eq!(geom_data.to_string(), "M(0,0) L(10,0) L(0,10) Z");
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [GeomEncoder]: struct.GeomEncoder.html
+    pub fn with_ring_closing(mut self, ring_closing: bool) -> Self {
+        self.ring_closing = ring_closing;
+        self
+    }
+
+    /// Enable or disable skipping consecutive duplicate points.
+    ///
+    /// After quantizing to tile units, consecutive points often land on the
+    /// same unit.  When enabled, [GeomEncoder] skips them in linestrings and
+    /// polygon rings, instead of encoding zero-length `LineTo` segments.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{EncodeOptions, Error, GeomEncoder, GeomType};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let options = EncodeOptions::default().with_dedup(true);
+    /// let geom_data = GeomEncoder::new(GeomType::Linestring, Transform::default())
+    ///     .with_options(options)
+    ///     .point(0.0, 0.0)?
+    ///     .point(10.2, 0.0)?
+    ///     .point(9.8, 0.1)?
+    ///     .point(10.0, 10.0)?
+    ///     .encode()?;
+    /// assert_eq!(geom_data.to_string(), "M(0,0) L(10,0) L(0,10)");
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [GeomEncoder]: struct.GeomEncoder.html
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Limit the length of string values (bytes).
+    pub fn with_max_string_len(mut self, max_len: usize) -> Self {
+        self.max_string_len = Some(max_len);
+        self
+    }
+
+    /// Limit the encoded size of each layer (bytes).
+    pub fn with_max_layer_size(mut self, max_size: usize) -> Self {
+        self.max_layer_size = Some(max_size);
+        self
+    }
+
+    /// Check if lenient (fixing instead of returning errors).
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Check if repeated closing points are dropped
+    pub(crate) fn is_ring_closing(&self) -> bool {
+        self.ring_closing
+    }
+
+    /// Check if degenerate geometry is checked
+    pub(crate) fn is_degenerate(&self) -> bool {
+        self.degenerate
+    }
+
+    /// Check if consecutive duplicate points are skipped
+    pub(crate) fn is_dedup(&self) -> bool {
+        self.dedup
+    }
+
+    /// Return an error in strict mode
+    fn fail(&self, err: Error) -> Result<()> {
+        if self.lenient {
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Apply layer checks, reporting any fixes
+    ///
+    /// Features and values before `first` (feature, value) were already
+    /// checked, and are not checked again.
+    pub(crate) fn check_layer(
+        &self,
+        layer: &mut VtLayer,
+        first: (usize, usize),
+    ) -> Result<EncodeReport> {
+        let mut report = EncodeReport {
+            name: layer.name().to_string(),
+            ..Default::default()
+        };
+        if self.max_string_len.is_some() {
+            self.check_strings(layer, first.1, &mut report)?;
+        }
+        if self.winding || self.ring_winding || self.degenerate {
+            let first = first.0.min(layer.features.len());
+            let features = layer.features.split_off(first);
+            for mut feature in features {
+                if self.check_feature(&mut feature, &mut report)? {
+                    layer.features.push(feature);
+                } else {
+                    report.dropped_features += 1;
+                }
+            }
+        }
+        if let Some(max_size) = self.max_layer_size {
+            self.check_size(layer, max_size, &mut report)?;
+        }
+        if !report.is_empty() {
+            warn!("Encoding fixes in layer {report}");
+        }
+        Ok(report)
+    }
+
+    /// Truncate a string value if it is too long (lenient mode).
+    ///
+    /// Returns `true` if the value was truncated.
+    fn truncate_string(&self, val: &mut String) -> Result<bool> {
+        match self.max_string_len {
+            Some(max_len) if val.len() > max_len => {
+                self.fail(Error::StringTooLong())?;
+                let mut end = max_len;
+                while !val.is_char_boundary(end) {
+                    end -= 1;
+                }
+                val.truncate(end);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Check string value lengths
+    fn check_strings(
+        &self,
+        layer: &mut VtLayer,
+        first: usize,
+        report: &mut EncodeReport,
+    ) -> Result<()> {
+        let mut truncated = false;
+        for value in layer.values.iter_mut().skip(first) {
+            if let Some(val) = &mut value.string_value {
+                if self.truncate_string(val)? {
+                    report.truncated_strings += 1;
+                    truncated = true;
+                }
+            }
+        }
+        if truncated {
+            dedup_strings(layer);
+        }
+        Ok(())
+    }
+
+    /// Check feature geometry; returns `false` if it should be dropped
+    fn check_feature(
+        &self,
+        feature: &mut VtFeature,
+        report: &mut EncodeReport,
+    ) -> Result<bool> {
+        let geom_tp = match feature.type_() {
+            VtGeomType::POINT => GeomType::Point,
+            VtGeomType::LINESTRING => GeomType::Linestring,
+            VtGeomType::POLYGON => GeomType::Polygon,
+            VtGeomType::UNKNOWN => return Ok(true),
+        };
+        let mut parts = decode_parts(&feature.geometry)?;
+        let n_parts = parts.len();
+        let mut reversed = false;
+        if self.degenerate {
+            match geom_tp {
+                GeomType::Point => (),
+                GeomType::Linestring => {
+                    parts.retain(|line| line.iter().any(|pt| *pt != line[0]))
+                }
+                GeomType::Polygon => parts.retain(|ring| ring_area(ring) != 0),
+            }
+            if parts.len() != n_parts || parts.is_empty() {
+                self.fail(Error::InvalidGeometry())?;
+                // a feature with no parts left is counted as dropped
+                if !parts.is_empty() {
+                    report.dropped_parts += n_parts - parts.len();
+                }
+            }
+        }
+        if self.ring_winding && geom_tp == GeomType::Polygon {
+            let wrong = wrong_rings(&parts);
+            if !wrong.is_empty() {
+                self.fail(Error::InvalidGeometry())?;
+                for i in &wrong {
+                    parts[*i].reverse();
+                }
+                report.fixed_rings += wrong.len();
+                reversed = true;
+            }
+        } else if self.winding && geom_tp == GeomType::Polygon {
+            let first = parts.iter().map(|r| ring_area(r)).find(|a| *a != 0);
+            if first.is_some_and(|area| area < 0) {
+                self.fail(Error::InvalidGeometry())?;
+                parts.iter_mut().for_each(|ring| ring.reverse());
+                report.fixed_rings += parts.len();
+                reversed = true;
+            }
+        }
+        if parts.is_empty() {
+            return Ok(!self.degenerate);
+        }
+        if reversed || parts.len() != n_parts {
+            feature.geometry = encode_parts(geom_tp, &parts);
+        }
+        Ok(true)
+    }
+
+    /// Check encoded layer size
+    fn check_size(
+        &self,
+        layer: &mut VtLayer,
+        max_size: usize,
+        report: &mut EncodeReport,
+    ) -> Result<()> {
+        let mut size = layer.compute_size() as usize;
+        while size > max_size {
+            self.fail(Error::LayerTooLarge())?;
+            match layer.features.pop() {
+                Some(feature) => {
+                    size -= field_size(feature.compute_size());
+                    report.dropped_features += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Merge string values which are equal (after truncation)
+fn dedup_strings(layer: &mut VtLayer) {
+    let mut first = HashMap::new();
+    let mut remap = Vec::with_capacity(layer.values.len());
+    let mut values = Vec::with_capacity(layer.values.len());
+    for value in std::mem::take(&mut layer.values) {
+        let pos = match &value.string_value {
+            Some(val) => first.get(val).copied(),
+            None => None,
+        };
+        match pos {
+            Some(pos) => remap.push(pos),
+            None => {
+                if let Some(val) = &value.string_value {
+                    first.insert(val.clone(), values.len());
+                }
+                remap.push(values.len());
+                values.push(value);
+            }
+        }
+    }
+    layer.values = values;
+    for feature in layer.features.iter_mut() {
+        for v in feature.tags.iter_mut().skip(1).step_by(2) {
+            if let Some(pos) = remap.get(*v as usize) {
+                *v = *pos as u32;
+            }
+        }
+    }
+}
+
+/// Calculate twice the signed area of a ring (positive for exterior rings)
+pub(crate) fn ring_area(ring: &[(i32, i32)]) -> i64 {
+    let mut area = 0;
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        area +=
+            i64::from(a.0) * i64::from(b.1) - i64::from(b.0) * i64::from(a.1);
+    }
+    area
+}
+
+/// Check if a point is inside a ring (even-odd rule)
+fn point_in_ring(p: (i32, i32), ring: &[(i32, i32)]) -> bool {
+    let (px, py) = (i64::from(p.0), i64::from(p.1));
+    let mut inside = false;
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        let (ax, ay) = (i64::from(a.0), i64::from(a.1));
+        let (bx, by) = (i64::from(b.0), i64::from(b.1));
+        if (ay > py) != (by > py) {
+            // is the point left of the edge crossing?
+            let lhs = (px - ax) * (by - ay);
+            let rhs = (py - ay) * (bx - ax);
+            if (by > ay && lhs < rhs) || (by < ay && lhs > rhs) {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Check if a ring is within an exterior ring
+fn ring_in_ring(ring: &[(i32, i32)], exterior: &[(i32, i32)]) -> bool {
+    // first vertex which is not shared with the exterior
+    ring.iter()
+        .find(|p| !exterior.contains(p))
+        .is_some_and(|p| point_in_ring(*p, exterior))
+}
+
+/// Find indices of polygon rings with wrong winding order
+fn wrong_rings(rings: &[Vec<(i32, i32)>]) -> Vec<usize> {
+    let mut wrong = vec![];
+    let mut exterior: Option<usize> = None;
+    for (i, ring) in rings.iter().enumerate() {
+        let area = ring_area(ring);
+        if area == 0 {
+            continue;
+        }
+        let hole = exterior.is_some_and(|e| ring_in_ring(ring, &rings[e]));
+        if (area < 0) != hole {
+            wrong.push(i);
+        }
+        if !hole {
+            exterior = Some(i);
+        }
+    }
+    wrong
+}
+
+/// Calculate the encoded size of an embedded message field
+fn field_size(len: u64) -> usize {
+    let varint_len = (64 - len.leading_zeros()).max(1).div_ceil(7);
+    1 + varint_len as usize + len as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dedup::FloatDedup;
+    use crate::encoder::GeomEncoder;
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    /// Make a tile with one layer of polygons
+    fn tile(options: EncodeOptions, rings: &[&[(f64, f64)]]) -> Result<Tile> {
+        let mut tile = Tile::new(256).with_options(options);
+        let mut layer = tile.create_layer("polygons");
+        for ring in rings {
+            let mut enc =
+                GeomEncoder::new(GeomType::Polygon, Transform::default());
+            for (x, y) in ring.iter() {
+                enc.add_point(*x, *y)?;
+            }
+            let mut feature = layer.into_feature(enc.encode()?);
+            feature.add_tag_string("name", "a long name");
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer)?;
+        Ok(tile)
+    }
+
+    const CW: &[(f64, f64)] = &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+    const CCW: &[(f64, f64)] = &[(0.0, 0.0), (10.0, 10.0), (10.0, 0.0)];
+    const FLAT: &[(f64, f64)] = &[(0.0, 0.0), (5.0, 5.0), (10.0, 10.0)];
+
+    #[test]
+    fn test_default() {
+        let tile = tile(EncodeOptions::default(), &[CW, CCW, FLAT]).unwrap();
+        assert_eq!(tile.vt_layers()[0].features.len(), 3);
+        assert!(tile.encode_report().is_empty());
+    }
+
+    #[test]
+    fn test_strict() {
+        let strict = EncodeOptions::strict();
+        assert!(tile(strict, &[CW]).is_ok());
+        assert!(matches!(
+            tile(strict, &[CCW]),
+            Err(Error::InvalidGeometry())
+        ));
+        assert!(matches!(
+            tile(strict, &[FLAT]),
+            Err(Error::InvalidGeometry())
+        ));
+        let strict = strict.with_max_string_len(4);
+        assert!(matches!(tile(strict, &[CW]), Err(Error::StringTooLong())));
+        let strict = EncodeOptions::strict().with_max_layer_size(20);
+        assert!(matches!(tile(strict, &[CW]), Err(Error::LayerTooLarge())));
+    }
+
+    #[test]
+    fn test_ring_winding() {
+        let outer = [(0, 0), (100, 0), (100, 100), (0, 100)];
+        let hole = [(20, 20), (20, 80), (80, 80), (80, 20)];
+        assert!(ring_area(&outer) > 0 && ring_area(&hole) < 0);
+        assert!(point_in_ring((50, 50), &outer));
+        assert!(!point_in_ring((150, 50), &outer));
+        assert!(ring_in_ring(&hole, &outer));
+        let rev =
+            |r: &[(i32, i32)]| r.iter().rev().copied().collect::<Vec<_>>();
+        let other: Vec<_> = outer.iter().map(|(x, y)| (x + 200, *y)).collect();
+        // correct: exterior, hole, exterior
+        let rings = vec![outer.to_vec(), hole.to_vec(), other.clone()];
+        assert!(wrong_rings(&rings).is_empty());
+        // GeoJSON-style (all reversed)
+        let rings = vec![rev(&outer), rev(&hole), rev(&other)];
+        assert_eq!(wrong_rings(&rings), vec![0, 1, 2]);
+        // only the hole wound like an exterior
+        let rings = vec![outer.to_vec(), rev(&hole), other];
+        assert_eq!(wrong_rings(&rings), vec![1]);
+        // strict option fails, lenient fixes
+        let rings = [rev(&outer), rev(&hole)];
+        let encode = |options: EncodeOptions| -> Result<Tile> {
+            let mut tile = Tile::new(256).with_options(options);
+            let mut enc =
+                GeomEncoder::new(GeomType::Polygon, Transform::default());
+            for ring in &rings {
+                for (x, y) in ring {
+                    enc.add_point(f64::from(*x), f64::from(*y))?;
+                }
+                enc.complete_geom()?;
+            }
+            let feature = tile.create_layer("a").into_feature(enc.encode()?);
+            tile.add_layer(feature.into_layer())?;
+            Ok(tile)
+        };
+        let strict = EncodeOptions::default().with_ring_winding(true);
+        assert!(matches!(encode(strict), Err(Error::InvalidGeometry())));
+        let tile =
+            encode(EncodeOptions::lenient().with_ring_winding(true)).unwrap();
+        assert_eq!(tile.encode_report()[0].fixed_rings(), 2);
+        let geometry = &tile.vt_layers()[0].features[0].geometry;
+        let parts = decode_parts(geometry).unwrap();
+        assert!(ring_area(&parts[0]) > 0 && ring_area(&parts[1]) < 0);
+    }
+
+    #[test]
+    fn test_lenient() {
+        let lenient = EncodeOptions::lenient().with_max_string_len(6);
+        let tile = tile(lenient, &[CW, CCW, FLAT]).unwrap();
+        let layer = &tile.vt_layers()[0];
+        assert_eq!(layer.features.len(), 2);
+        let parts = decode_parts(&layer.features[1].geometry).unwrap();
+        assert!(ring_area(&parts[0]) > 0);
+        let report = &tile.encode_report()[0];
+        assert_eq!(report.name(), "polygons");
+        assert_eq!(report.dropped_features(), 1);
+        assert_eq!(report.dropped_parts(), 0);
+        assert_eq!(report.fixed_rings(), 1);
+        assert_eq!(report.truncated_strings(), 1);
+        assert_eq!(layer.values[0].string_value(), "a long");
+        let lenient = EncodeOptions::lenient().with_max_layer_size(80);
+        let tile = self::tile(lenient, &[CW, CW, CW]).unwrap();
+        let layer = &tile.vt_layers()[0];
+        assert!(layer.compute_size() <= 80);
+        assert!(!layer.features.is_empty() && layer.features.len() < 3);
+    }
+
+    #[test]
+    fn test_truncated_dedup() {
+        let lenient = EncodeOptions::lenient().with_max_string_len(4);
+        let mut tile = Tile::new(256).with_options(lenient);
+        let mut layer = tile.create_layer("names");
+        for name in ["abcdef", "abcdxy", "other"] {
+            let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(1.0, 1.0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.add_tag_string("name", name);
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        assert_eq!(tile.encode_report()[0].truncated_strings(), 3);
+        let layer = &tile.vt_layers()[0];
+        assert_eq!(layer.values.len(), 2);
+        assert_eq!(layer.values[0].string_value(), "abcd");
+        assert_eq!(layer.values[1].string_value(), "othe");
+        let tags: Vec<_> = layer.features.iter().map(|f| f.tags[1]).collect();
+        assert_eq!(tags, vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn test_skipped_points() {
+        let mut tile = Tile::new(256).with_options(EncodeOptions::lenient());
+        let mut enc = GeomEncoder::new(GeomType::Point, Transform::default())
+            .with_options(EncodeOptions::lenient());
+        for (x, y) in [(1.0, 1.0), (f64::NAN, 1.0), (1e12, 1.0)] {
+            enc.add_point(x, y).unwrap();
+        }
+        assert_eq!(enc.skipped_points(), 2);
+        let layer = tile
+            .create_layer("points")
+            .into_feature(enc.encode().unwrap());
+        tile.add_layer(layer.into_layer()).unwrap();
+        let report = &tile.encode_report()[0];
+        assert_eq!(report.skipped_points(), 2);
+        assert_eq!(report.dropped_features(), 0);
+    }
+
+    #[test]
+    fn test_get_or_create_layer() {
+        let point = || {
+            GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(1.0, 1.0)
+                .unwrap()
+                .encode()
+                .unwrap()
+        };
+        let mut tile = tile(EncodeOptions::lenient(), &[CCW]).unwrap();
+        let mut feature = tile
+            .create_layer("floats")
+            .with_float_dedup(FloatDedup::Canonical)
+            .into_feature(point());
+        feature.add_tag_double("v", 1.0);
+        tile.add_layer(feature.into_layer()).unwrap();
+        assert_eq!(tile.encode_report().len(), 1);
+        let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default());
+        for (x, y) in CCW {
+            enc.add_point(*x, *y).unwrap();
+        }
+        let layer = tile.get_or_create_layer("polygons").unwrap();
+        let feature = layer.into_feature(enc.encode().unwrap());
+        tile.add_layer(feature.into_layer()).unwrap();
+        let layer = tile.get_or_create_layer("floats").unwrap();
+        let mut feature = layer.into_feature(point());
+        feature.add_tag_double("v", 1.0);
+        tile.add_layer(feature.into_layer()).unwrap();
+        // only the appended feature is checked, merged into one report
+        let report = tile.encode_report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].name(), "polygons");
+        assert_eq!(report[0].fixed_rings(), 2);
+        assert_eq!(report[0].truncated_strings(), 0);
+        let layers = tile.vt_layers();
+        assert_eq!(layers[0].name(), "polygons");
+        assert_eq!(layers[0].features.len(), 2);
+        assert_eq!(layers[1].name(), "floats");
+        assert_eq!(layers[1].values.len(), 1);
+    }
+
+    #[test]
+    fn test_taken_layer_dropped() {
+        let mut tile = tile(EncodeOptions::lenient(), &[CCW]).unwrap();
+        tile.add_layer(tile.create_layer("other")).unwrap();
+        // taken, then dropped without being added back
+        let _ = tile.get_or_create_layer("polygons").unwrap();
+        let strict = EncodeOptions::default().with_ring_winding(true);
+        let mut tile = tile.with_options(strict);
+        let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default());
+        for (x, y) in CCW {
+            enc.add_point(*x, *y).unwrap();
+        }
+        let layer = tile.create_layer("polygons");
+        let layer = layer.into_feature(enc.encode().unwrap()).into_layer();
+        // a fresh layer is checked from its first feature
+        assert!(matches!(
+            tile.add_layer(layer),
+            Err(Error::InvalidGeometry())
+        ));
+        tile.add_layer(tile.create_layer("polygons")).unwrap();
+        let layers = tile.vt_layers();
+        assert_eq!(layers[0].name(), "other");
+        assert_eq!(layers[1].name(), "polygons");
+    }
+}
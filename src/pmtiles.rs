@@ -0,0 +1,504 @@
+// pmtiles.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! PMTiles (version 3) tile archives.
+//!
+use crate::archive::TileSource;
+use crate::error::{Error, Result};
+use crate::mapgrid::TileId;
+use crate::view::TileView;
+use crate::wire::read_varint;
+use std::borrow::Cow;
+#[cfg(any(feature = "gzip", feature = "brotli"))]
+use std::io::Read;
+
+/// Length of archive header
+const HEADER_LEN: usize = 127;
+
+/// Maximum depth of leaf directories
+const MAX_DEPTH: usize = 4;
+
+/// Directory entry
+#[derive(Clone, Copy, Debug, Default)]
+struct Entry {
+    /// First tile ID
+    tile_id: u64,
+
+    /// Number of tiles with the same data (0 for leaf directory)
+    run_length: u64,
+
+    /// Offset of data, in tile data or leaf directories section
+    offset: u64,
+
+    /// Length of data
+    length: u64,
+}
+
+/// Reader for a [PMTiles] archive (version 3).
+///
+/// The archive is borrowed, so it can be a memory-mapped file.  When tiles
+/// are not compressed, [get_tile] and [tile_view] borrow tile data from the
+/// archive without copying it.
+///
+/// Directories compressed with gzip, brotli or zstd require the crate
+/// feature of the same name.  As a [TileSource], tiles are read in tile ID
+/// (Hilbert curve) order, and decompressed.
+///
+/// [PMTiles]: https://github.com/protomaps/PMTiles
+/// [get_tile]: struct.PmtilesReader.html#method.get_tile
+/// [tile_view]: struct.PmtilesReader.html#method.tile_view
+/// [TileSource]: trait.TileSource.html
+#[derive(Clone, Debug)]
+pub struct PmtilesReader<'a> {
+    /// Archive data
+    data: &'a [u8],
+
+    /// Offset of leaf directories section
+    leaf_offset: u64,
+
+    /// Offset of tile data section
+    tile_offset: u64,
+
+    /// Compression of directories
+    internal_compression: u8,
+
+    /// Compression of tiles
+    tile_compression: u8,
+
+    /// Root directory entries
+    root: Vec<Entry>,
+
+    /// Directories being read as a tile source, with next entry positions
+    stack: Vec<(Vec<Entry>, usize)>,
+
+    /// Tile entry being read as a tile source, with next tile in its run
+    run: Option<(Entry, u64)>,
+}
+
+/// Get the PMTiles ID of a tile (position on Hilbert curve)
+fn tile_id(tid: TileId) -> u64 {
+    let z = tid.z();
+    let n = 1u64 << z;
+    // number of tiles at lower zoom levels
+    let mut id = ((1u64 << (2 * z)) - 1) / 3;
+    let (mut x, mut y) = (u64::from(tid.x()), u64::from(tid.y()));
+    let mut s = n >> 1;
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+        id += s * s * ((3 * rx) ^ ry);
+        rotate(n, &mut x, &mut y, rx, ry);
+        s >>= 1;
+    }
+    id
+}
+
+/// Get the tile with a PMTiles ID
+fn tile_of(id: u64) -> Result<TileId> {
+    let mut first = 0;
+    for z in 0..32 {
+        let num_tiles = 1u64 << (2 * z);
+        if id - first < num_tiles {
+            let mut pos = id - first;
+            let (mut x, mut y) = (0, 0);
+            let mut s = 1;
+            while s < 1u64 << z {
+                let rx = 1 & (pos / 2);
+                let ry = 1 & (pos ^ rx);
+                rotate(s, &mut x, &mut y, rx, ry);
+                x += s * rx;
+                y += s * ry;
+                pos /= 4;
+                s *= 2;
+            }
+            return TileId::new(x as u32, y as u32, z);
+        }
+        first += num_tiles;
+    }
+    Err(Error::InvalidTid())
+}
+
+/// Rotate a Hilbert curve quadrant
+fn rotate(n: u64, x: &mut u64, y: &mut u64, rx: u64, ry: u64) {
+    if ry == 0 {
+        if rx == 1 {
+            *x = n - 1 - *x;
+            *y = n - 1 - *y;
+        }
+        std::mem::swap(x, y);
+    }
+}
+
+/// Read a little-endian `u64` from the header
+fn header_u64(header: &[u8], pos: usize) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&header[pos..pos + 8]);
+    u64::from_le_bytes(buf)
+}
+
+/// Read a varint from a directory
+fn varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    read_varint(data, pos).map_err(|_| Error::InvalidArchive())
+}
+
+/// Decompress directory or tile data
+fn decompress(data: &[u8], compression: u8) -> Result<Cow<'_, [u8]>> {
+    match compression {
+        // unknown compression is read as none
+        0 | 1 => Ok(Cow::Borrowed(data)),
+        #[cfg(feature = "gzip")]
+        2 => {
+            let mut out = vec![];
+            flate2::read::GzDecoder::new(data).read_to_end(&mut out)?;
+            Ok(Cow::Owned(out))
+        }
+        #[cfg(feature = "brotli")]
+        3 => {
+            let mut out = vec![];
+            brotli::Decompressor::new(data, 4096).read_to_end(&mut out)?;
+            Ok(Cow::Owned(out))
+        }
+        #[cfg(feature = "zstd")]
+        4 => Ok(Cow::Owned(zstd::decode_all(data)?)),
+        _ => Err(Error::UnsupportedCompression()),
+    }
+}
+
+/// Parse directory entries
+fn parse_directory(data: &[u8]) -> Result<Vec<Entry>> {
+    let mut pos = 0;
+    let n_entries = varint(data, &mut pos)?;
+    // each entry takes at least 4 bytes
+    if n_entries > data.len() as u64 {
+        return Err(Error::InvalidArchive());
+    }
+    let mut entries = vec![Entry::default(); n_entries as usize];
+    let mut tile_id = 0u64;
+    for entry in entries.iter_mut() {
+        tile_id = tile_id
+            .checked_add(varint(data, &mut pos)?)
+            .ok_or(Error::InvalidArchive())?;
+        entry.tile_id = tile_id;
+    }
+    for entry in entries.iter_mut() {
+        entry.run_length = varint(data, &mut pos)?;
+    }
+    for entry in entries.iter_mut() {
+        entry.length = varint(data, &mut pos)?;
+    }
+    for i in 0..entries.len() {
+        entries[i].offset = match (varint(data, &mut pos)?, i) {
+            (0, 0) => return Err(Error::InvalidArchive()),
+            // zero means directly after the previous entry
+            (0, _) => entries[i - 1]
+                .offset
+                .checked_add(entries[i - 1].length)
+                .ok_or(Error::InvalidArchive())?,
+            (offset, _) => offset - 1,
+        };
+    }
+    Ok(entries)
+}
+
+impl<'a> PmtilesReader<'a> {
+    /// Read the header and root directory of an archive.
+    ///
+    /// * `data` Archive data (can be memory-mapped).
+    ///
+    /// Returns [Error::InvalidArchive] if the data is not a version 3
+    /// archive of vector tiles.
+    ///
+    /// [Error::InvalidArchive]: enum.Error.html#variant.InvalidArchive
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let header = data.get(..HEADER_LEN).ok_or(Error::InvalidArchive())?;
+        // tile type 1 is MVT
+        if !header.starts_with(b"PMTiles") || header[7] != 3 || header[99] > 1 {
+            return Err(Error::InvalidArchive());
+        }
+        let mut reader = PmtilesReader {
+            data,
+            leaf_offset: header_u64(header, 40),
+            tile_offset: header_u64(header, 56),
+            internal_compression: header[97],
+            tile_compression: header[98],
+            root: vec![],
+            stack: vec![],
+            run: None,
+        };
+        let root =
+            reader.section(header_u64(header, 8), header_u64(header, 16))?;
+        reader.root = reader.directory(root)?;
+        reader.stack.push((reader.root.clone(), 0));
+        Ok(reader)
+    }
+
+    /// Get a section of the archive
+    fn section(&self, offset: u64, length: u64) -> Result<&'a [u8]> {
+        let start =
+            usize::try_from(offset).map_err(|_| Error::InvalidArchive())?;
+        let len =
+            usize::try_from(length).map_err(|_| Error::InvalidArchive())?;
+        let end = start.checked_add(len).ok_or(Error::InvalidArchive())?;
+        self.data.get(start..end).ok_or(Error::InvalidArchive())
+    }
+
+    /// Decompress and parse a directory
+    fn directory(&self, data: &[u8]) -> Result<Vec<Entry>> {
+        parse_directory(&decompress(data, self.internal_compression)?)
+    }
+
+    /// Read the leaf directory of an entry
+    fn leaf_directory(&self, entry: &Entry) -> Result<Vec<Entry>> {
+        let offset = self
+            .leaf_offset
+            .checked_add(entry.offset)
+            .ok_or(Error::InvalidArchive())?;
+        self.directory(self.section(offset, entry.length)?)
+    }
+
+    /// Get the (possibly compressed) data of a tile entry
+    fn tile_data(&self, entry: &Entry) -> Result<&'a [u8]> {
+        let offset = self
+            .tile_offset
+            .checked_add(entry.offset)
+            .ok_or(Error::InvalidArchive())?;
+        self.section(offset, entry.length)
+    }
+
+    /// Find the tile entry containing a tile ID
+    fn find_entry(&self, id: u64) -> Result<Option<Entry>> {
+        let mut entries = Cow::Borrowed(&self.root[..]);
+        for _ in 0..MAX_DEPTH {
+            let pos = entries.partition_point(|e| e.tile_id <= id);
+            let Some(entry) = pos.checked_sub(1).map(|p| entries[p]) else {
+                return Ok(None);
+            };
+            if entry.run_length == 0 {
+                entries = Cow::Owned(self.leaf_directory(&entry)?);
+            } else if id - entry.tile_id < entry.run_length {
+                return Ok(Some(entry));
+            } else {
+                return Ok(None);
+            }
+        }
+        Err(Error::InvalidArchive())
+    }
+
+    /// Check if tiles are stored without compression.
+    pub fn is_uncompressed(&self) -> bool {
+        matches!(self.tile_compression, 0 | 1)
+    }
+
+    /// Get the data of one tile.
+    ///
+    /// * `tid` Tile ID.
+    ///
+    /// The data is borrowed from the archive, unless the tiles are
+    /// compressed.  Returns `None` if the archive has no such tile.
+    pub fn get_tile(&self, tid: TileId) -> Result<Option<Cow<'a, [u8]>>> {
+        match self.find_entry(tile_id(tid))? {
+            Some(entry) => {
+                let data = self.tile_data(&entry)?;
+                Ok(Some(decompress(data, self.tile_compression)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Get a borrowed view of one tile.
+    ///
+    /// * `tid` Tile ID.
+    ///
+    /// Returns `None` if the archive has no such tile, or
+    /// [Error::UnsupportedCompression] if tiles are compressed (use
+    /// [get_tile] instead).
+    ///
+    /// [Error::UnsupportedCompression]: enum.Error.html#variant.UnsupportedCompression
+    /// [get_tile]: struct.PmtilesReader.html#method.get_tile
+    pub fn tile_view(&self, tid: TileId) -> Result<Option<TileView<'a>>> {
+        if !self.is_uncompressed() {
+            return Err(Error::UnsupportedCompression());
+        }
+        match self.find_entry(tile_id(tid))? {
+            Some(entry) => Ok(Some(TileView::new(self.tile_data(&entry)?)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl TileSource for PmtilesReader<'_> {
+    fn next_tile(&mut self) -> Result<Option<(TileId, Vec<u8>)>> {
+        loop {
+            if let Some((entry, pos)) = self.run {
+                if pos < entry.run_length {
+                    self.run = Some((entry, pos + 1));
+                    let tid = tile_of(entry.tile_id + pos)?;
+                    let data = self.tile_data(&entry)?;
+                    let data = decompress(data, self.tile_compression)?;
+                    return Ok(Some((tid, data.into_owned())));
+                }
+                self.run = None;
+            }
+            let Some((entries, pos)) = self.stack.last_mut() else {
+                return Ok(None);
+            };
+            match entries.get(*pos).copied() {
+                Some(entry) => {
+                    *pos += 1;
+                    if entry.run_length > 0 {
+                        self.run = Some((entry, 0));
+                    } else if self.stack.len() < MAX_DEPTH {
+                        let entries = self.leaf_directory(&entry)?;
+                        self.stack.push((entries, 0));
+                    } else {
+                        return Err(Error::InvalidArchive());
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tile::Tile;
+
+    fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    /// Encode a directory of (tile_id, run_length, offset, length) entries
+    fn directory(entries: &[(u64, u64, u64, u64)]) -> Vec<u8> {
+        let mut out = vec![];
+        push_varint(&mut out, entries.len() as u64);
+        let mut last = 0;
+        for (tile_id, _, _, _) in entries {
+            push_varint(&mut out, tile_id - last);
+            last = *tile_id;
+        }
+        for (_, run_length, _, _) in entries {
+            push_varint(&mut out, *run_length);
+        }
+        for (_, _, _, length) in entries {
+            push_varint(&mut out, *length);
+        }
+        for (_, _, offset, _) in entries {
+            push_varint(&mut out, offset + 1);
+        }
+        out
+    }
+
+    fn archive(root: &[u8], leaves: &[u8], tiles: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; HEADER_LEN];
+        data[..7].copy_from_slice(b"PMTiles");
+        data[7] = 3;
+        let root_offset = HEADER_LEN as u64;
+        let leaf_offset = root_offset + root.len() as u64;
+        let tile_offset = leaf_offset + leaves.len() as u64;
+        for (pos, value) in [
+            (8, root_offset),
+            (16, root.len() as u64),
+            (24, tile_offset),
+            (40, leaf_offset),
+            (48, leaves.len() as u64),
+            (56, tile_offset),
+            (64, tiles.len() as u64),
+        ] {
+            data[pos..pos + 8].copy_from_slice(&u64::to_le_bytes(value));
+        }
+        data[97] = 1;
+        data[98] = 1;
+        data[99] = 1;
+        data.extend_from_slice(root);
+        data.extend_from_slice(leaves);
+        data.extend_from_slice(tiles);
+        data
+    }
+
+    fn tile(n_layers: usize) -> Vec<u8> {
+        let mut tile = Tile::new(4096);
+        for i in 0..n_layers {
+            tile.add_layer(tile.create_layer(&format!("Layer {i}")))
+                .unwrap();
+        }
+        tile.to_bytes().unwrap()
+    }
+
+    #[test]
+    fn test_tile_id() {
+        let tid = |x, y, z| TileId::new(x, y, z).unwrap();
+        assert_eq!(tile_id(tid(0, 0, 0)), 0);
+        assert_eq!(tile_id(tid(0, 0, 1)), 1);
+        assert_eq!(tile_id(tid(0, 1, 1)), 2);
+        assert_eq!(tile_id(tid(1, 1, 1)), 3);
+        assert_eq!(tile_id(tid(1, 0, 1)), 4);
+        assert_eq!(tile_id(tid(0, 0, 2)), 5);
+        for z in 0..5 {
+            for x in 0..1 << z {
+                for y in 0..1 << z {
+                    let t = tid(x, y, z);
+                    assert_eq!(tile_of(tile_id(t)).unwrap(), t);
+                }
+            }
+        }
+        let t = tid(1 << 30, 5, 31);
+        assert_eq!(tile_of(tile_id(t)).unwrap(), t);
+    }
+
+    #[test]
+    fn test_reader() {
+        let (one, two) = (tile(1), tile(2));
+        let mut tiles = one.clone();
+        tiles.extend_from_slice(&two);
+        let (len1, len2) = (one.len() as u64, two.len() as u64);
+        // tile 3 is in a leaf directory, sharing data with tile 0
+        let leaves = directory(&[(3, 1, 0, len1)]);
+        let root = directory(&[
+            (0, 1, 0, len1),
+            (1, 2, len1, len2),
+            (3, 0, 0, leaves.len() as u64),
+        ]);
+        let data = archive(&root, &leaves, &tiles);
+        let mut reader = PmtilesReader::new(&data).unwrap();
+        let tid = |x, y, z| TileId::new(x, y, z).unwrap();
+        let found = reader.get_tile(tid(0, 1, 1)).unwrap().unwrap();
+        assert!(matches!(found, Cow::Borrowed(_)));
+        assert_eq!(&found[..], &two[..]);
+        let view = reader.tile_view(tid(1, 1, 1)).unwrap().unwrap();
+        assert_eq!(view.layers().len(), 1);
+        assert!(reader.get_tile(tid(1, 0, 1)).unwrap().is_none());
+        assert!(reader.get_tile(tid(0, 0, 2)).unwrap().is_none());
+        let mut read = vec![];
+        while let Some((tid, data)) = reader.next_tile().unwrap() {
+            read.push((tid.to_string(), Tile::from_bytes(&data).unwrap()));
+        }
+        let read: Vec<_> = read
+            .iter()
+            .map(|(t, tile)| (&t[..], tile.num_layers()))
+            .collect();
+        assert_eq!(
+            read,
+            [("0/0/0", 1), ("1/0/0", 2), ("1/0/1", 2), ("1/1/1", 1)]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(PmtilesReader::new(b"PMTiles").is_err());
+        let root = directory(&[(0, 1, 0, 100)]);
+        let mut data = archive(&root, &[], &tile(1));
+        let reader = PmtilesReader::new(&data).unwrap();
+        assert!(reader.get_tile(TileId::new(0, 0, 0).unwrap()).is_err());
+        data[7] = 2;
+        assert!(PmtilesReader::new(&data).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+// precise.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Extended precision (double-double) tile transform.
+//!
+use crate::error::{Error, Result};
+use crate::mapgrid::{MapGrid, TileId};
+
+/// Double-double number: unevaluated sum of two non-overlapping `f64`s
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DoubleDouble(f64, f64);
+
+/// Sum of two values, with exact rounding error
+fn two_sum(a: f64, b: f64) -> DoubleDouble {
+    let s = a + b;
+    let bb = s - a;
+    DoubleDouble(s, (a - (s - bb)) + (b - bb))
+}
+
+/// Sum of two values where `|a| >= |b|`, with exact rounding error
+fn quick_two_sum(a: f64, b: f64) -> DoubleDouble {
+    let s = a + b;
+    DoubleDouble(s, b - (s - a))
+}
+
+/// Product of two values, with exact rounding error
+fn two_prod(a: f64, b: f64) -> DoubleDouble {
+    let p = a * b;
+    DoubleDouble(p, a.mul_add(b, -p))
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(v: f64) -> Self {
+        DoubleDouble(v, 0.0)
+    }
+}
+
+impl DoubleDouble {
+    /// Add another value
+    fn add(self, rhs: Self) -> Self {
+        let DoubleDouble(s, e) = two_sum(self.0, rhs.0);
+        quick_two_sum(s, e + self.1 + rhs.1)
+    }
+
+    /// Subtract another value
+    fn sub(self, rhs: Self) -> Self {
+        self.add(DoubleDouble(-rhs.0, -rhs.1))
+    }
+
+    /// Multiply by another value
+    fn mul(self, rhs: Self) -> Self {
+        let DoubleDouble(p, e) = two_prod(self.0, rhs.0);
+        quick_two_sum(p, e + self.0 * rhs.1 + self.1 * rhs.0)
+    }
+
+    /// Divide an exact value by an `f64`
+    fn div(n: f64, d: f64) -> Self {
+        let q = n / d;
+        let r = (-q).mul_add(d, n);
+        quick_two_sum(q, r / d)
+    }
+
+    /// Scale by a power of two (exact)
+    fn scale(self, s: f64) -> Self {
+        DoubleDouble(self.0 * s, self.1 * s)
+    }
+
+    /// Round to nearest integer, half away from zero
+    fn round(self) -> f64 {
+        let r = self.0.round();
+        let frac = (self.0 - r) + self.1;
+        if frac > 0.5 || (frac == 0.5 && r >= 0.0) {
+            r + 1.0
+        } else if frac < -0.5 || (frac == -0.5 && r <= 0.0) {
+            r - 1.0
+        } else {
+            r
+        }
+    }
+}
+
+/// Extended precision transform from map to tile coördinates.
+///
+/// Plain `f64` arithmetic loses sub-tile-unit accuracy at extreme zoom levels
+/// (z23+ with large extents), because the tile origin cannot be represented
+/// exactly.  This transform keeps the origin and scale as double-double
+/// values, so results are accurate to the precision of the input points.
+///
+/// Unlike [MapGrid::tile_transform], the result is in tile units (`0` to
+/// `extent`).
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, MapGrid, PreciseTransform, TileId};
+/// # fn main() -> Result<(), Error> {
+/// let grid = MapGrid::default();
+/// let tid = TileId::new(0, 0, 1)?;
+/// let t = PreciseTransform::new(&grid, tid, 4096);
+/// assert_eq!(t.transform(0.0, 0.0), (4096.0, 4096.0));
+/// assert_eq!(t.quantize(-10_018_754.171_394_624, 0.0)?, (2048, 4096));
+/// # Ok(()) }
+/// ```
+///
+/// [MapGrid::tile_transform]: struct.MapGrid.html#method.tile_transform
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PreciseTransform {
+    /// West edge of tile
+    x0: DoubleDouble,
+
+    /// North edge of tile
+    y0: DoubleDouble,
+
+    /// X scale (tile units per map unit)
+    sx: DoubleDouble,
+
+    /// Y scale (tile units per map unit)
+    sy: DoubleDouble,
+}
+
+impl PreciseTransform {
+    /// Create a new precise transform.
+    ///
+    /// * `grid` Map grid.
+    /// * `tid` Tile ID.
+    /// * `extent` Height / width of tile bounds.
+    pub fn new(grid: &MapGrid<f64>, tid: TileId, extent: u32) -> Self {
+        let bbox = grid.bbox();
+        let tz = f64::from(1u32 << tid.z());
+        let x_span = bbox.x_span();
+        let y_span = bbox.y_span();
+        // tile index / 2^z is exact, so only spans and edges are rounded
+        let x0 = DoubleDouble::from(bbox.x_min())
+            .add(two_prod(x_span, f64::from(tid.x())).scale(1.0 / tz));
+        let y0 = DoubleDouble::from(bbox.y_max())
+            .sub(two_prod(y_span, f64::from(tid.y())).scale(1.0 / tz));
+        let units = f64::from(extent) * tz;
+        let sx = DoubleDouble::div(units, x_span);
+        let sy = DoubleDouble::div(units, y_span);
+        PreciseTransform { x0, y0, sx, sy }
+    }
+
+    /// Transform a point to tile units (double-double)
+    fn apply(&self, x: f64, y: f64) -> (DoubleDouble, DoubleDouble) {
+        let tx = DoubleDouble::from(x).sub(self.x0).mul(self.sx);
+        let ty = self.y0.sub(DoubleDouble::from(y)).mul(self.sy);
+        (tx, ty)
+    }
+
+    /// Transform a point to tile units.
+    pub fn transform(&self, x: f64, y: f64) -> (f64, f64) {
+        let (tx, ty) = self.apply(x, y);
+        (tx.0 + tx.1, ty.0 + ty.1)
+    }
+
+    /// Transform and round a point to integer tile units.
+    ///
+    /// Rounding is half away from zero, using the full precision of the
+    /// transformed value.  Returns an error if a point is not finite or out
+    /// of `i32` range.
+    pub fn quantize(&self, x: f64, y: f64) -> Result<(i32, i32)> {
+        let (tx, ty) = self.apply(x, y);
+        Ok((to_i32(tx.round())?, to_i32(ty.round())?))
+    }
+}
+
+/// Convert a rounded value to `i32`
+fn to_i32(v: f64) -> Result<i32> {
+    if v.is_finite() && v >= f64::from(i32::MIN) && v <= f64::from(i32::MAX) {
+        Ok(v as i32)
+    } else {
+        Err(Error::InvalidValue())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_double_double() {
+        let a = two_sum(1.0, 1e-20);
+        assert_eq!(a, DoubleDouble(1.0, 1e-20));
+        let b = a.sub(DoubleDouble::from(1.0));
+        assert_eq!(b.0, 1e-20);
+        let third = DoubleDouble::div(1.0, 3.0);
+        let one = third.mul(DoubleDouble::from(3.0));
+        assert_eq!(one.0 + one.1, 1.0);
+        assert_eq!(DoubleDouble(2.0, 0.5).round(), 3.0);
+        assert_eq!(DoubleDouble(3.0, -0.5).round(), 3.0);
+        assert_eq!(DoubleDouble(-2.0, -0.5).round(), -3.0);
+        assert_eq!(DoubleDouble(2.0, 0.4999).round(), 2.0);
+        assert_eq!(DoubleDouble(2.0, 1e-30).round(), 2.0);
+    }
+
+    #[test]
+    fn test_matches_tile_transform() {
+        let grid = MapGrid::default();
+        let tid = TileId::new(246, 368, 10).unwrap();
+        let t = PreciseTransform::new(&grid, tid, 4096);
+        let plain = grid.tile_transform(tid);
+        let (x, y) = (-10_400_000.0, 5_600_000.0);
+        let p = plain * (x, y);
+        let (px, py) = t.transform(x, y);
+        assert!((px - p.x() * 4096.0).abs() < 1e-6);
+        assert!((py - p.y() * 4096.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_adjacent_tiles() {
+        let grid = MapGrid::default();
+        // z30 tiles: one tile unit is about 9 picometers
+        let west = TileId::new(800_000_000, 500_000_000, 30).unwrap();
+        let east = TileId::new(800_000_001, 500_000_000, 30).unwrap();
+        let tw = PreciseTransform::new(&grid, west, 4096);
+        let te = PreciseTransform::new(&grid, east, 4096);
+        let bbox = grid.tile_bbox(east);
+        for i in 0..16 {
+            let x = bbox.x_min() + f64::from(i) * 1e-9;
+            let y = bbox.y_max();
+            let (xw, _) = tw.transform(x, y);
+            let (xe, _) = te.transform(x, y);
+            assert!((xw - xe - 4096.0).abs() < 1e-6);
+            let qw = tw.quantize(x, y).unwrap();
+            let qe = te.quantize(x, y).unwrap();
+            assert_eq!(qw.0 - 4096, qe.0);
+        }
+        assert!(tw.quantize(f64::NAN, 0.0).is_err());
+        assert!(tw.quantize(0.0, 0.0).is_err());
+    }
+}
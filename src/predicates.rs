@@ -0,0 +1,118 @@
+// predicates.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Robust geometric predicates.
+//!
+use pointy::Float;
+use std::cmp::Ordering;
+
+/// Relative error bound of the fast orientation test (Shewchuk)
+const ORIENT_ERR_BOUND: f64 = (3.0 + 16.0 * f64::EPSILON) * f64::EPSILON / 2.0;
+
+/// Compute a product and its rounding error exactly
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    (p, a.mul_add(b, -p))
+}
+
+/// Add a value to a nonoverlapping expansion (Shewchuk's Grow-Expansion)
+fn grow_expansion(e: &mut Vec<f64>, b: f64) {
+    let mut q = b;
+    for c in e.iter_mut() {
+        let s = q + *c;
+        let bv = s - q;
+        let av = s - bv;
+        let err = (q - av) + (*c - bv);
+        *c = err;
+        q = s;
+    }
+    e.push(q);
+}
+
+/// Compute exact orientation of three points
+fn orient_exact(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> Ordering {
+    let mut e = Vec::with_capacity(13);
+    for (x, y, sign) in [
+        (a.0, b.1, 1.0),
+        (a.0, c.1, -1.0),
+        (a.1, b.0, -1.0),
+        (a.1, c.0, 1.0),
+        (b.0, c.1, 1.0),
+        (b.1, c.0, -1.0),
+    ] {
+        let (p, err) = two_product(x * sign, y);
+        grow_expansion(&mut e, err);
+        grow_expansion(&mut e, p);
+    }
+    // largest nonzero component determines the sign
+    match e.iter().rev().find(|v| **v != 0.0) {
+        Some(v) => v.partial_cmp(&0.0).unwrap_or(Ordering::Equal),
+        None => Ordering::Equal,
+    }
+}
+
+/// Get the orientation of three points exactly.
+///
+/// Returns `Greater` if `c` is to the left of the line from `a` to `b`
+/// (counter-clockwise with Y up), `Less` if to the right, or `Equal` if the
+/// points are collinear.  A fast floating-point test is used when its result
+/// is certain, falling back to exact arithmetic for near-degenerate input.
+pub(crate) fn orient2d<F: Float>(a: (F, F), b: (F, F), c: (F, F)) -> Ordering {
+    let f = |p: (F, F)| (p.0.to_f64().unwrap(), p.1.to_f64().unwrap());
+    let (a, b, c) = (f(a), f(b), f(c));
+    let left = (a.0 - c.0) * (b.1 - c.1);
+    let right = (a.1 - c.1) * (b.0 - c.0);
+    let det = left - right;
+    let bound = ORIENT_ERR_BOUND * (left.abs() + right.abs());
+    if det > bound {
+        Ordering::Greater
+    } else if -det > bound {
+        Ordering::Less
+    } else {
+        orient_exact(a, b, c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Exact orientation of integer points
+    fn orient_int(a: (i64, i64), b: (i64, i64), c: (i64, i64)) -> Ordering {
+        let det = i128::from(b.0 - a.0) * i128::from(c.1 - a.1)
+            - i128::from(b.1 - a.1) * i128::from(c.0 - a.0);
+        det.cmp(&0)
+    }
+
+    #[test]
+    fn test_orient2d() {
+        assert_eq!(
+            orient2d((0.0, 0.0), (1.0, 0.0), (0.0, 1.0)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            orient2d((0.0, 0.0), (1.0, 0.0), (0.0, -1.0)),
+            Ordering::Less
+        );
+        assert_eq!(
+            orient2d((0.5, 0.5), (12.0, 12.0), (24.0, 24.0)),
+            Ordering::Equal
+        );
+        // nearly collinear points, scaled so that doubles are exact integers
+        // divided by a power of two
+        let scale = 2f64.powi(-40);
+        let mut seed: u64 = 12345;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 12) as i64 & ((1 << 50) - 1)
+        };
+        for _ in 0..1000 {
+            let a = (next(), next());
+            let b = (next(), next());
+            let c = ((a.0 + b.0) / 2 + next() % 3 - 1, (a.1 + b.1) / 2);
+            let f = |p: (i64, i64)| (p.0 as f64 * scale, p.1 as f64 * scale);
+            assert_eq!(orient2d(f(a), f(b), f(c)), orient_int(a, b, c));
+        }
+    }
+}
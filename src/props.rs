@@ -0,0 +1,162 @@
+// props.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Deserializing feature properties with serde.
+//!
+use crate::error::{Error, Result};
+use crate::view::{FeatureView, LayerView, ValueView};
+use serde::de::value::MapDeserializer;
+use serde::de::{self, DeserializeOwned, Deserializer, IntoDeserializer};
+use serde::forward_to_deserialize_any;
+use std::fmt;
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Deserialize(msg.to_string())
+    }
+}
+
+/// Deserializer for one tag value
+struct ValueDeserializer<'v, 'a>(&'v ValueView<'a>);
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'_, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.0 {
+            ValueView::String(v) => visitor.visit_str(v),
+            ValueView::Float(v) => visitor.visit_f32(*v),
+            ValueView::Double(v) => visitor.visit_f64(*v),
+            ValueView::Int(v) | ValueView::Sint(v) => visitor.visit_i64(*v),
+            ValueView::Uint(v) => visitor.visit_u64(*v),
+            ValueView::Bool(v) => visitor.visit_bool(*v),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        // a tag which is present is never "none"
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.0 {
+            ValueView::String(v) => {
+                visitor.visit_enum(v.as_ref().into_deserializer())
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer<'_, '_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Deserialize the tags of a feature, as a map from keys to values
+pub(crate) fn properties_as<T>(
+    feature: &FeatureView,
+    layer: &LayerView,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let tags = feature
+        .tags(layer)
+        .map(|(key, value)| (key, ValueDeserializer(value)));
+    T::deserialize(MapDeserializer::<_, Error>::new(tags))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::error::Error;
+    use crate::tile::Tile;
+    use pointy::Transform;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Surface {
+        Paved,
+        Gravel,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct RoadProps {
+        name: String,
+        lanes: u8,
+        speed: f64,
+        oneway: bool,
+        surface: Surface,
+        bridge: Option<bool>,
+    }
+
+    #[test]
+    fn test_properties_as() {
+        let mut tile = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Linestring, Transform::default())
+            .point(0.0, 0.0)
+            .unwrap()
+            .point(10.0, 0.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("roads").into_feature(geom);
+        feature
+            .add_tag_string("name", "Main St")
+            .add_tag_uint("lanes", 2)
+            .add_tag_sint("speed", 45)
+            .add_tag_bool("oneway", false)
+            .add_tag_string("surface", "paved")
+            .add_tag_string("ref", "US 52");
+        tile.add_layer(feature.into_layer()).unwrap();
+        let layers = tile.layers().unwrap();
+        let feature = layers[0].feature(0).unwrap().unwrap();
+        let props: RoadProps = feature.properties_as(&layers[0]).unwrap();
+        assert_eq!(
+            props,
+            RoadProps {
+                name: "Main St".into(),
+                lanes: 2,
+                speed: 45.0,
+                oneway: false,
+                surface: Surface::Paved,
+                bridge: None,
+            }
+        );
+        #[derive(Debug, Deserialize)]
+        struct Wrong {
+            #[allow(dead_code)]
+            name: bool,
+        }
+        assert!(matches!(
+            feature.properties_as::<Wrong>(&layers[0]),
+            Err(Error::Deserialize(_))
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+// provenance.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Tile provenance stamping.
+//!
+use crate::duplicate::TileDigest;
+use crate::error::Result;
+use crate::tile::Tile;
+
+/// Name of the hidden provenance layer
+pub const PROVENANCE_LAYER: &str = "_provenance";
+
+/// Generator name and version
+const GENERATOR: &str = concat!("mvt ", env!("CARGO_PKG_VERSION"));
+
+/// Provenance of a tile: which pipeline built it, and when.
+///
+/// It is stored as [layer metadata] in a layer named [PROVENANCE_LAYER],
+/// containing no visible features.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{Provenance, Tile};
+///
+/// let mut tile = Tile::new(4096);
+/// Provenance::new()
+///     .with_config(b"zoom = 0..14")
+///     .with_timestamp(1_700_000_000)
+///     .stamp(&mut tile)?;
+/// let tile = Tile::from_bytes(&tile.to_bytes()?)?;
+/// let prov = Provenance::read(&tile).unwrap();
+/// assert!(prov.generator().starts_with("mvt "));
+/// assert_eq!(prov.timestamp(), Some(1_700_000_000));
+/// # Ok(()) }
+/// ```
+///
+/// [layer metadata]: struct.Layer.html#method.with_metadata
+/// [PROVENANCE_LAYER]: constant.PROVENANCE_LAYER.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Provenance {
+    /// Generator name and version
+    generator: String,
+
+    /// Configuration digest (hex)
+    config: Option<String>,
+
+    /// Build time (seconds since Unix epoch)
+    timestamp: Option<u64>,
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Provenance {
+    /// Create provenance for this crate version.
+    pub fn new() -> Self {
+        Provenance {
+            generator: GENERATOR.to_string(),
+            config: None,
+            timestamp: None,
+        }
+    }
+
+    /// Set the generator name and version (default: `mvt` version).
+    pub fn with_generator(mut self, generator: &str) -> Self {
+        self.generator = generator.to_string();
+        self
+    }
+
+    /// Record a digest of the pipeline configuration.
+    ///
+    /// * `config` Serialized configuration.
+    pub fn with_config(mut self, config: &[u8]) -> Self {
+        self.config = Some(TileDigest::new(config).to_string());
+        self
+    }
+
+    /// Record the build time.
+    ///
+    /// * `timestamp` Seconds since Unix epoch.
+    ///
+    /// Omit this for reproducible (content-addressable) tiles.
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Get the generator name and version.
+    pub fn generator(&self) -> &str {
+        &self.generator
+    }
+
+    /// Get the configuration digest (hex).
+    pub fn config(&self) -> Option<&str> {
+        self.config.as_deref()
+    }
+
+    /// Get the build time (seconds since Unix epoch).
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    /// Stamp a tile with a provenance layer.
+    ///
+    /// Returns [Error::DuplicateName] if the tile is already stamped.
+    ///
+    /// [Error::DuplicateName]: enum.Error.html#variant.DuplicateName
+    pub fn stamp(&self, tile: &mut Tile) -> Result<()> {
+        let mut layer = tile
+            .create_layer(PROVENANCE_LAYER)
+            .with_metadata("generator", &self.generator);
+        if let Some(config) = &self.config {
+            layer = layer.with_metadata("config", config);
+        }
+        if let Some(timestamp) = self.timestamp {
+            layer = layer.with_metadata("timestamp", &timestamp.to_string());
+        }
+        tile.add_layer(layer)
+    }
+
+    /// Read provenance from a tile.
+    ///
+    /// Returns `None` if the tile has no provenance layer.
+    pub fn read(tile: &Tile) -> Option<Self> {
+        let mut prov = Provenance {
+            generator: String::new(),
+            config: None,
+            timestamp: None,
+        };
+        for (key, value) in tile.layer_metadata(PROVENANCE_LAYER)? {
+            match key.as_str() {
+                "generator" => prov.generator = value,
+                "config" => prov.config = Some(value),
+                "timestamp" => prov.timestamp = value.parse().ok(),
+                _ => (),
+            }
+        }
+        Some(prov)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn test_provenance() {
+        let mut tile = Tile::new(256);
+        assert_eq!(Provenance::read(&tile), None);
+        let prov = Provenance::new()
+            .with_generator("tiler 1.2")
+            .with_config(b"x");
+        prov.stamp(&mut tile).unwrap();
+        assert!(matches!(prov.stamp(&mut tile), Err(Error::DuplicateName())));
+        let tile = Tile::from_bytes(&tile.to_bytes().unwrap()).unwrap();
+        let read = Provenance::read(&tile).unwrap();
+        assert_eq!(read, prov);
+        assert_eq!(read.config().unwrap().len(), 32);
+        assert_eq!(read.timestamp(), None);
+    }
+}
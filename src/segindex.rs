@@ -0,0 +1,549 @@
+// segindex.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Segment index for clipping huge geometries.
+//!
+use crate::clip::{lerp_x, lerp_y, Rect};
+use crate::predicates::orient2d;
+use pointy::{BBox, Float};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Segment reference: (path number, segment number)
+type SegRef = (u32, u32);
+
+/// Grid index of the segments of huge linestrings or polygon rings.
+///
+/// Clipping a geometry with millions of vertices to each tile would visit
+/// every vertex per tile.  With this index, only segments in grid cells near
+/// the tile bounds are visited.  The cell size should be about the size of
+/// the tiles being clipped.
+///
+/// # Example
+/// ```
+/// # use mvt::SegmentIndex;
+/// # use pointy::{BBox, Pt};
+/// let trace: Vec<(f64, f64)> = (0..1000).map(|i| (i as f64, 5.0)).collect();
+/// let index = SegmentIndex::new(vec![trace], 10.0);
+/// let bbox = BBox::from((Pt::new(100.0, 0.0), Pt::new(110.0, 10.0)));
+/// let lines = index.clip_lines(bbox);
+/// assert_eq!(lines.len(), 1);
+/// assert_eq!(lines[0].len(), 11);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SegmentIndex<F>
+where
+    F: Float,
+{
+    /// Indexed paths (linestrings or rings)
+    paths: Vec<Vec<(F, F)>>,
+
+    /// Orientation of each path as a ring (positive signed area)
+    ccw: Vec<bool>,
+
+    /// Grid cell size
+    cell_size: F,
+
+    /// Segments in each grid cell
+    cells: HashMap<(i64, i64), Vec<SegRef>>,
+
+    /// Maximum column of any cell
+    max_col: i64,
+}
+
+impl<F> SegmentIndex<F>
+where
+    F: Float,
+{
+    /// Create a new segment index.
+    ///
+    /// * `paths` Linestrings or polygon rings.
+    /// * `cell_size` Width / height of grid cells.
+    ///
+    /// Polygon rings are implicitly closed.
+    pub fn new(paths: Vec<Vec<(F, F)>>, cell_size: F) -> Self {
+        let mut index = SegmentIndex {
+            paths: vec![],
+            ccw: paths.iter().map(|p| signed_area(p) > F::zero()).collect(),
+            cell_size,
+            cells: HashMap::new(),
+            max_col: i64::MIN,
+        };
+        for (p, path) in paths.iter().enumerate() {
+            for s in 0..path.len() {
+                let (a, b) = index.segment_of(path, s);
+                index.add_segment(a, b, (p as u32, s as u32));
+            }
+        }
+        index.paths = paths;
+        index
+    }
+
+    /// Add a segment to the cells it passes through.
+    ///
+    /// The segment is walked along its major axis, one row or column of
+    /// cells at a time.  The span on the minor axis is padded by one cell
+    /// (within the segment bounds), so rounding cannot miss a cell.
+    fn add_segment(&mut self, a: (F, F), b: (F, F), seg: SegRef) {
+        let (c0, r0) = self.cell(a.0.min(b.0), a.1.min(b.1));
+        let (c1, r1) = self.cell(a.0.max(b.0), a.1.max(b.1));
+        self.max_col = self.max_col.max(c1);
+        if c1 - c0 >= r1 - r0 {
+            for c in c0..=c1 {
+                let (x0, x1) = self.span(c, a.0, b.0);
+                let (ya, yb) = if a.0 == b.0 {
+                    (a.1, b.1)
+                } else {
+                    (lerp_y(a, b, x0), lerp_y(a, b, x1))
+                };
+                let (_, ra) = self.cell(x0, ya.min(yb));
+                let (_, rb) = self.cell(x0, ya.max(yb));
+                for r in (ra - 1).max(r0)..=(rb + 1).min(r1) {
+                    self.cells.entry((c, r)).or_default().push(seg);
+                }
+            }
+        } else {
+            for r in r0..=r1 {
+                let (y0, y1) = self.span(r, a.1, b.1);
+                let (xa, xb) = if a.1 == b.1 {
+                    (a.0, b.0)
+                } else {
+                    (lerp_x(a, b, y0), lerp_x(a, b, y1))
+                };
+                let (ca, _) = self.cell(xa.min(xb), y0);
+                let (cb, _) = self.cell(xa.max(xb), y0);
+                for c in (ca - 1).max(c0)..=(cb + 1).min(c1) {
+                    self.cells.entry((c, r)).or_default().push(seg);
+                }
+            }
+        }
+    }
+
+    /// Get the span of a segment (on one axis) within a row / column
+    fn span(&self, i: i64, a: F, b: F) -> (F, F) {
+        let lo = F::from(i).unwrap_or_else(F::zero) * self.cell_size;
+        let hi = lo + self.cell_size;
+        (a.min(b).max(lo), a.max(b).min(hi))
+    }
+
+    /// Get a segment, wrapping around to the first vertex
+    fn segment_of(&self, path: &[(F, F)], s: usize) -> ((F, F), (F, F)) {
+        (path[s], path[(s + 1) % path.len()])
+    }
+
+    /// Get a segment of an indexed path
+    fn segment(&self, seg: SegRef) -> ((F, F), (F, F)) {
+        self.segment_of(&self.paths[seg.0 as usize], seg.1 as usize)
+    }
+
+    /// Get the grid cell containing a point
+    fn cell(&self, x: F, y: F) -> (i64, i64) {
+        let c = (x / self.cell_size).floor().to_i64().unwrap_or(0);
+        let r = (y / self.cell_size).floor().to_i64().unwrap_or(0);
+        (c, r)
+    }
+
+    /// Find segments overlapping a rectangle, in path order
+    fn query(&self, rect: &Rect<F>, bbox: &BBox<F>) -> Vec<SegRef> {
+        let (c0, r0) = self.cell(bbox.x_min(), bbox.y_min());
+        let (c1, r1) = self.cell(bbox.x_max(), bbox.y_max());
+        let mut segs = vec![];
+        for r in r0..=r1 {
+            for c in c0..=c1.min(self.max_col) {
+                if let Some(cell) = self.cells.get(&(c, r)) {
+                    segs.extend(cell.iter().filter(|seg| {
+                        let (a, b) = self.segment(**seg);
+                        Rect::of_segment(a, b).overlaps(rect)
+                    }));
+                }
+            }
+        }
+        segs.sort_unstable();
+        segs.dedup();
+        segs
+    }
+
+    /// Check if a point is inside a ring (crossing number)
+    fn ring_contains(&self, ring: u32, pt: (F, F)) -> bool {
+        let (c0, r) = self.cell(pt.0, pt.1);
+        let mut segs = vec![];
+        for c in c0..=self.max_col {
+            if let Some(cell) = self.cells.get(&(c, r)) {
+                segs.extend(cell.iter().filter(|seg| seg.0 == ring));
+            }
+        }
+        segs.sort_unstable();
+        segs.dedup();
+        let mut inside = false;
+        for seg in segs {
+            let (a, b) = self.segment(seg);
+            let crossing = if a.1 <= pt.1 && b.1 > pt.1 {
+                orient2d(a, b, pt) == Ordering::Greater
+            } else if b.1 <= pt.1 && a.1 > pt.1 {
+                orient2d(a, b, pt) == Ordering::Less
+            } else {
+                false
+            };
+            if crossing {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Clip indexed linestrings to a bounding box.
+    ///
+    /// Returns the pieces of each linestring inside the box, in order.
+    pub fn clip_lines(&self, bbox: BBox<F>) -> Vec<Vec<(F, F)>> {
+        self.clip_lines_indexed(bbox)
+            .into_iter()
+            .map(|(_, line)| line)
+            .collect()
+    }
+
+    /// Clip indexed linestrings to a bounding box, with path numbers.
+    ///
+    /// Returns the pieces of each linestring inside the box, in order, with
+    /// the index of its source path (to correlate with caller records).
+    pub fn clip_lines_indexed(
+        &self,
+        bbox: BBox<F>,
+    ) -> Vec<(usize, Vec<(F, F)>)> {
+        let rect = rect_of(&bbox);
+        let mut lines: Vec<(usize, Vec<(F, F)>)> = vec![];
+        let mut prev: Option<SegRef> = None;
+        for seg in self.query(&rect, &bbox) {
+            // rings are closed, but linestrings have no closing segment
+            if seg.1 as usize + 1 >= self.paths[seg.0 as usize].len() {
+                continue;
+            }
+            let (a, b) = self.segment(seg);
+            if let Some((a, b)) = rect.clip_segment(a, b) {
+                // skip segments only touching the box
+                if a == b {
+                    continue;
+                }
+                let joined = prev == Some((seg.0, seg.1.wrapping_sub(1)))
+                    && lines.last().and_then(|(_, l)| l.last()) == Some(&a);
+                match lines.last_mut() {
+                    Some((_, line)) if joined => line.push(b),
+                    _ => lines.push((seg.0 as usize, vec![a, b])),
+                }
+                prev = Some(seg);
+            }
+        }
+        lines
+    }
+
+    /// Clip indexed polygon rings to a bounding box.
+    ///
+    /// Rings with no segments near the box are either outside it, or cover
+    /// it completely; that is checked using only segments to the right of
+    /// the box center.  Rings crossing the box are built from their segments
+    /// inside it, connected along the box edges.  A ring can be split into
+    /// several rings when it enters the box more than once.
+    ///
+    /// Returns clipped rings, with orientation preserved.
+    pub fn clip_rings(&self, bbox: BBox<F>) -> Vec<Vec<(F, F)>> {
+        self.clip_rings_indexed(bbox)
+            .into_iter()
+            .map(|(_, ring)| ring)
+            .collect()
+    }
+
+    /// Clip indexed polygon rings to a bounding box, with path numbers.
+    ///
+    /// Returns clipped rings as in [clip_rings], with the index of each
+    /// source ring.
+    ///
+    /// [clip_rings]: struct.SegmentIndex.html#method.clip_rings
+    pub fn clip_rings_indexed(
+        &self,
+        bbox: BBox<F>,
+    ) -> Vec<(usize, Vec<(F, F)>)> {
+        let rect = rect_of(&bbox);
+        let segs = self.query(&rect, &bbox);
+        let center = rect.center();
+        let mut rings = vec![];
+        for (r, ring) in self.paths.iter().enumerate() {
+            let r = r as u32;
+            let start = segs.partition_point(|seg| seg.0 < r);
+            let end = segs.partition_point(|seg| seg.0 <= r);
+            let chains = self.ring_chains(&rect, &segs[start..end]);
+            if !chains.is_empty() {
+                for clipped in self.link_chains(&rect, r, chains) {
+                    rings.push((r as usize, clipped));
+                }
+            } else if ring.len() >= 3 && self.ring_contains(r, center) {
+                let mut covered = rect.ring();
+                if !self.ccw[r as usize] {
+                    covered.reverse();
+                }
+                rings.push((r as usize, covered));
+            }
+        }
+        rings
+    }
+
+    /// Clip the segments of one ring, joining them into chains.
+    ///
+    /// A ring entirely inside the rectangle is one closed chain (with the
+    /// first point repeated at the end); otherwise, each chain enters and
+    /// leaves through the rectangle edges.
+    fn ring_chains(&self, rect: &Rect<F>, segs: &[SegRef]) -> Vec<Chain<F>> {
+        let mut chains: Vec<Chain<F>> = vec![];
+        for seg in segs {
+            let (a, b) = self.segment(*seg);
+            let Some((a, b)) = rect.clip_segment(a, b) else {
+                continue;
+            };
+            let joined = match chains.last() {
+                Some(chain) => {
+                    chain.last + 1 == seg.1 && chain.pts.last() == Some(&a)
+                }
+                None => false,
+            };
+            match chains.last_mut() {
+                Some(chain) if joined => {
+                    chain.last = seg.1;
+                    if a != b {
+                        chain.pts.push(b);
+                    }
+                }
+                // skip segments only touching the rectangle
+                _ if a == b => (),
+                _ => chains.push(Chain {
+                    first: seg.1,
+                    last: seg.1,
+                    pts: vec![a, b],
+                    closed: false,
+                }),
+            }
+        }
+        // join the last chain to the first across the ring's closing vertex
+        if let (Some(first), Some(last)) = (chains.first(), chains.last()) {
+            let len = segs
+                .first()
+                .map_or(0, |seg| self.paths[seg.0 as usize].len() as u32);
+            if first.first == 0
+                && last.last + 1 == len
+                && first.pts.first() == last.pts.last()
+            {
+                let last = chains.pop().unwrap();
+                match chains.first_mut() {
+                    Some(first) => {
+                        let mut pts = last.pts;
+                        pts.extend_from_slice(&first.pts[1..]);
+                        first.pts = pts;
+                        first.first = last.first;
+                    }
+                    None => chains.push(Chain {
+                        closed: true,
+                        ..last
+                    }),
+                }
+            }
+        }
+        chains
+    }
+
+    /// Link chains of one ring into clipped rings.
+    ///
+    /// From where each chain leaves the rectangle, its edges are followed
+    /// (in the same orientation as the ring) to the nearest chain entering
+    /// it, as in Weiler-Atherton clipping.
+    fn link_chains(
+        &self,
+        rect: &Rect<F>,
+        r: u32,
+        chains: Vec<Chain<F>>,
+    ) -> Vec<Vec<(F, F)>> {
+        let ccw = self.ccw[r as usize];
+        let mut rings = vec![];
+        let mut used = vec![false; chains.len()];
+        let entries: Vec<F> = chains
+            .iter()
+            .map(|chain| rect.perimeter(chain.pts[0]))
+            .collect();
+        for i in 0..chains.len() {
+            if used[i] {
+                continue;
+            }
+            let mut ring = vec![];
+            let mut j = i;
+            loop {
+                used[j] = true;
+                let chain = &chains[j];
+                ring.extend_from_slice(&chain.pts);
+                if chain.closed {
+                    break;
+                }
+                let exit = rect.perimeter(*chain.pts.last().unwrap());
+                let dist = |to: F| rect.walk(exit, to, ccw);
+                let next = (0..chains.len())
+                    .filter(|k| *k == i || !used[*k])
+                    .min_by(|a, b| {
+                        let (da, db) = (dist(entries[*a]), dist(entries[*b]));
+                        da.partial_cmp(&db).unwrap_or(Ordering::Equal)
+                    })
+                    .unwrap_or(i);
+                let target = dist(entries[next]);
+                let mut corners: Vec<(F, (F, F))> = rect
+                    .corners()
+                    .into_iter()
+                    .map(|(pos, pt)| (dist(pos), pt))
+                    .filter(|(d, _)| *d > F::zero() && *d < target)
+                    .collect();
+                corners.sort_by(|a, b| {
+                    a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal)
+                });
+                ring.extend(corners.into_iter().map(|(_, pt)| pt));
+                if next == i {
+                    break;
+                }
+                j = next;
+            }
+            ring.dedup();
+            if ring.len() > 1 && ring.first() == ring.last() {
+                ring.pop();
+            }
+            if ring.len() >= 3 {
+                rings.push(ring);
+            }
+        }
+        rings
+    }
+}
+
+/// Clipped piece of a ring, from consecutive segments
+struct Chain<F: Float> {
+    /// First segment number
+    first: u32,
+
+    /// Last segment number
+    last: u32,
+
+    /// Clipped points
+    pts: Vec<(F, F)>,
+
+    /// Ring entirely inside the rectangle
+    closed: bool,
+}
+
+/// Make a clip rectangle from a bounding box
+fn rect_of<F: Float>(bbox: &BBox<F>) -> Rect<F> {
+    Rect::new(bbox.x_min(), bbox.y_min(), bbox.x_max(), bbox.y_max())
+}
+
+/// Calculate twice the signed area of a ring
+fn signed_area<F: Float>(ring: &[(F, F)]) -> F {
+    let mut area = F::zero();
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        area = area + a.0 * b.1 - b.0 * a.1;
+    }
+    area
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pointy::Pt;
+
+    fn bbox(x0: f64, y0: f64, x1: f64, y1: f64) -> BBox<f64> {
+        BBox::from((Pt::new(x0, y0), Pt::new(x1, y1)))
+    }
+
+    #[test]
+    fn test_clip_lines() {
+        let zigzag = vec![(0.0, 0.0), (20.0, 0.0), (20.0, 5.0), (0.0, 5.0)];
+        let index = SegmentIndex::new(vec![zigzag], 4.0);
+        let lines = index.clip_lines(bbox(8.0, -1.0, 12.0, 6.0));
+        assert_eq!(
+            lines,
+            vec![vec![(8.0, 0.0), (12.0, 0.0)], vec![(12.0, 5.0), (8.0, 5.0)]]
+        );
+        let lines = index.clip_lines(bbox(15.0, -1.0, 25.0, 6.0));
+        assert_eq!(
+            lines,
+            vec![vec![(15.0, 0.0), (20.0, 0.0), (20.0, 5.0), (15.0, 5.0)]]
+        );
+        assert!(index.clip_lines(bbox(30.0, 0.0, 40.0, 5.0)).is_empty());
+        let other = vec![(10.0, -5.0), (10.0, 10.0)];
+        let index =
+            SegmentIndex::new(vec![vec![(0.0, 0.0), (20.0, 0.0)], other], 4.0);
+        let lines = index.clip_lines_indexed(bbox(8.0, -1.0, 12.0, 6.0));
+        assert_eq!(
+            lines,
+            vec![
+                (0, vec![(8.0, 0.0), (12.0, 0.0)]),
+                (1, vec![(10.0, -1.0), (10.0, 6.0)])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clip_rings() {
+        let outer =
+            vec![(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0)];
+        let hole = vec![(40.0, 40.0), (40.0, 60.0), (60.0, 60.0), (60.0, 40.0)];
+        let index = SegmentIndex::new(vec![outer, hole], 10.0);
+        // interior: covered by outer ring
+        let rings = index.clip_rings(bbox(10.0, 10.0, 20.0, 20.0));
+        assert_eq!(rings.len(), 1);
+        assert!(signed_area(&rings[0]) > 0.0);
+        // inside hole: covered by both rings (cancelling out)
+        let rings = index.clip_rings(bbox(45.0, 45.0, 55.0, 55.0));
+        assert_eq!(rings.len(), 2);
+        let indexed = index.clip_rings_indexed(bbox(45.0, 45.0, 55.0, 55.0));
+        assert_eq!((indexed[0].0, indexed[1].0), (0, 1));
+        assert!(signed_area(&rings[1]) < 0.0);
+        // crossing outer edge
+        let rings = index.clip_rings(bbox(90.0, 10.0, 110.0, 20.0));
+        assert_eq!(
+            rings,
+            vec![vec![
+                (100.0, 10.0),
+                (100.0, 20.0),
+                (90.0, 20.0),
+                (90.0, 10.0)
+            ]]
+        );
+        // outside
+        assert!(index.clip_rings(bbox(110.0, 10.0, 120.0, 20.0)).is_empty());
+    }
+
+    #[test]
+    fn test_diagonal_cells() {
+        let diagonal = vec![(0.0, 0.0), (1000.0, 1000.0)];
+        let index = SegmentIndex::new(vec![diagonal], 10.0);
+        // only cells along the diagonal, not its whole bounding box
+        assert!(index.cells.len() < 500);
+        let lines = index.clip_lines(bbox(500.0, 500.0, 510.0, 510.0));
+        assert_eq!(lines, vec![vec![(500.0, 500.0), (510.0, 510.0)]]);
+    }
+
+    #[test]
+    fn test_clip_rings_split() {
+        let u = vec![
+            (0.0, 0.0),
+            (30.0, 0.0),
+            (30.0, 30.0),
+            (20.0, 30.0),
+            (20.0, 10.0),
+            (10.0, 10.0),
+            (10.0, 30.0),
+            (0.0, 30.0),
+        ];
+        let index = SegmentIndex::new(vec![u], 10.0);
+        let rings = index.clip_rings(bbox(-5.0, 20.0, 35.0, 25.0));
+        assert_eq!(
+            rings,
+            vec![
+                vec![(30.0, 20.0), (30.0, 25.0), (20.0, 25.0), (20.0, 20.0)],
+                vec![(10.0, 20.0), (10.0, 25.0), (0.0, 25.0), (0.0, 20.0)],
+            ]
+        );
+    }
+}
@@ -0,0 +1,220 @@
+// serial.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Serde support for configuration types.
+//!
+use crate::error::Error;
+use crate::mapgrid::TileId;
+use pointy::{BBox, Float, Pt};
+use serde::{Deserialize, Serialize};
+
+/// Serialized tile ID (validated on deserialize)
+#[derive(Deserialize)]
+pub(crate) struct RawTileId {
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+impl TryFrom<RawTileId> for TileId {
+    type Error = Error;
+
+    fn try_from(raw: RawTileId) -> Result<Self, Self::Error> {
+        TileId::new(raw.x, raw.y, raw.z)
+    }
+}
+
+/// Serialized map grid
+#[derive(Deserialize, Serialize)]
+#[serde(bound(
+    serialize = "F: Serialize",
+    deserialize = "F: Deserialize<'de>"
+))]
+pub(crate) struct RawMapGrid<F>
+where
+    F: Float,
+{
+    pub(crate) srid: i32,
+    #[serde(with = "bbox")]
+    pub(crate) bbox: BBox<F>,
+}
+
+/// Serde `with` module for [Transform]s.
+///
+/// Transforms are stored as coefficients `[a, b, c, d, e, f]` (see
+/// [TransformParams]).  Only axis-aligned transforms (scale and translate)
+/// are supported; others are an error when serializing or deserializing.
+///
+/// # Example
+/// ```
+/// # fn main() -> Result<(), serde::de::value::Error> {
+/// use mvt::TransformParams;
+/// use pointy::Transform;
+/// use serde::de::value::{Error, SeqDeserializer};
+/// use serde::de::IntoDeserializer;
+///
+/// #[derive(serde::Deserialize, serde::Serialize)]
+/// struct Job {
+///     #[serde(with = "mvt::serde_transform")]
+///     transform: Transform<f64>,
+/// }
+///
+/// let c = [0.25, 0.0, 16.0, 0.0, -0.5, 8.0];
+/// let d: SeqDeserializer<_, Error> = c.to_vec().into_deserializer();
+/// let job = Job {
+///     transform: mvt::serde_transform::deserialize(d)?,
+/// };
+/// assert_eq!(job.transform.coefficients(), c);
+/// # Ok(()) }
+/// ```
+///
+/// [Transform]: https://docs.rs/pointy/latest/pointy/struct.Transform.html
+/// [TransformParams]: trait.TransformParams.html
+pub mod transform {
+    use crate::transform::{
+        check_coefficients, from_coefficients, TransformParams,
+    };
+    use pointy::{Float, Transform};
+    use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize a transform
+    pub fn serialize<F, S>(t: &Transform<F>, s: S) -> Result<S::Ok, S::Error>
+    where
+        F: Float + Serialize,
+        S: Serializer,
+    {
+        let c = t.coefficients();
+        check_coefficients(&c).map_err(ser::Error::custom)?;
+        c.serialize(s)
+    }
+
+    /// Deserialize a transform
+    pub fn deserialize<'de, F, D>(d: D) -> Result<Transform<F>, D::Error>
+    where
+        F: Float + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let c = <[F; 6]>::deserialize(d)?;
+        from_coefficients(c).map_err(de::Error::custom)
+    }
+}
+
+/// Serde `with` module for [BBox]es.
+///
+/// Bounding boxes are stored as `[x_min, y_min, x_max, y_max]`.
+///
+/// [BBox]: https://docs.rs/pointy/latest/pointy/struct.BBox.html
+pub mod bbox {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize a bounding box
+    pub fn serialize<F, S>(b: &BBox<F>, s: S) -> Result<S::Ok, S::Error>
+    where
+        F: Float + Serialize,
+        S: Serializer,
+    {
+        [b.x_min(), b.y_min(), b.x_max(), b.y_max()].serialize(s)
+    }
+
+    /// Deserialize a bounding box
+    pub fn deserialize<'de, F, D>(d: D) -> Result<BBox<F>, D::Error>
+    where
+        F: Float + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let [x0, y0, x1, y1] = <[F; 4]>::deserialize(d)?;
+        Ok(BBox::from((Pt::new(x0, y0), Pt::new(x1, y1))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomType;
+    use crate::mapgrid::MapGrid;
+    use crate::transform::TransformParams;
+    use pointy::Transform;
+
+    #[derive(Deserialize, Serialize)]
+    struct Job {
+        #[serde(with = "transform")]
+        transform: Transform<f64>,
+        #[serde(with = "bbox")]
+        bbox: BBox<f64>,
+    }
+
+    #[test]
+    fn test_tile_id() {
+        let tid = TileId::new(3, 5, 4).unwrap();
+        let json = serde_json::to_string(&tid).unwrap();
+        assert_eq!(json, r#"{"x":3,"y":5,"z":4}"#);
+        assert_eq!(serde_json::from_str::<TileId>(&json).unwrap(), tid);
+        let tid = |json| serde_json::from_str::<TileId>(json);
+        assert!(tid(r#"{"x":15,"y":15,"z":4}"#).is_ok());
+        assert!(tid(r#"{"x":16,"y":0,"z":4}"#).is_err());
+        assert!(tid(r#"{"x":0,"y":16,"z":4}"#).is_err());
+        assert!(tid(r#"{"x":1,"y":0,"z":0}"#).is_err());
+        assert!(tid(r#"{"x":0,"y":0,"z":32}"#).is_err());
+    }
+
+    #[test]
+    fn test_map_grid() {
+        let bbox = BBox::from((Pt::new(0.0, -10.0), Pt::new(100.0, 90.0)));
+        let grid = MapGrid::new(3857, bbox);
+        let json = serde_json::to_string(&grid).unwrap();
+        assert_eq!(json, r#"{"srid":3857,"bbox":[0.0,-10.0,100.0,90.0]}"#);
+        let grid: MapGrid<f64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(grid.srid(), 3857);
+        assert_eq!(serde_json::to_string(&grid).unwrap(), json);
+    }
+
+    #[test]
+    fn test_geom_type() {
+        let json = serde_json::to_string(&GeomType::Polygon).unwrap();
+        assert_eq!(json, r#""Polygon""#);
+        let tp: GeomType = serde_json::from_str(&json).unwrap();
+        assert_eq!(tp, GeomType::Polygon);
+        assert!(serde_json::from_str::<GeomType>(r#""Circle""#).is_err());
+    }
+
+    #[test]
+    fn test_transform_bbox() {
+        let job = Job {
+            transform: Transform::with_scale(0.25, -0.5).translate(16.0, 8.0),
+            bbox: BBox::from((Pt::new(1.0, 2.0), Pt::new(3.0, 4.0))),
+        };
+        let json = serde_json::to_string(&job).unwrap();
+        assert_eq!(
+            json,
+            r#"{"transform":[0.25,0.0,16.0,0.0,-0.5,8.0],"bbox":[1.0,2.0,3.0,4.0]}"#
+        );
+        let job: Job = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            job.transform.coefficients(),
+            [0.25, 0.0, 16.0, 0.0, -0.5, 8.0]
+        );
+        assert_eq!(serde_json::to_string(&job).unwrap(), json);
+    }
+
+    #[test]
+    fn test_transform_rejected() {
+        let job = Job {
+            transform: Transform::with_rotate(0.5),
+            bbox: BBox::from((Pt::new(0.0, 0.0), Pt::new(1.0, 1.0))),
+        };
+        assert!(serde_json::to_string(&job).is_err());
+        let job = Job {
+            transform: Transform::with_scale(f64::INFINITY, 1.0),
+            bbox: BBox::from((Pt::new(0.0, 0.0), Pt::new(1.0, 1.0))),
+        };
+        assert!(serde_json::to_string(&job).is_err());
+        let skewed =
+            r#"{"transform":[1.0,0.5,0.0,0.0,1.0,0.0],"bbox":[0,0,1,1]}"#;
+        assert!(serde_json::from_str::<Job>(skewed).is_err());
+        let rotated =
+            r#"{"transform":[1.0,0.0,0.0,0.5,1.0,0.0],"bbox":[0,0,1,1]}"#;
+        assert!(serde_json::from_str::<Job>(rotated).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+// size.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Encoded size breakdown and geometry statistics.
+//!
+use crate::encoder::decode_vertices;
+use crate::error::Result;
+use crate::vector_tile::tile::Layer as VtLayer;
+use protobuf::Message;
+use std::fmt;
+
+/// Encoded size of one layer, in bytes, broken down by field group.
+///
+/// The groups add up to the total.  See [Tile::size_breakdown].
+///
+/// [Tile::size_breakdown]: struct.Tile.html#method.size_breakdown
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LayerSize {
+    name: String,
+    geometry: usize,
+    tags: usize,
+    keys: usize,
+    values: usize,
+    other: usize,
+}
+
+/// Geometry compression statistics of one layer.
+///
+/// Shows whether simplification / quantization settings are paying off.
+/// See [Tile::geom_stats].
+///
+/// [Tile::geom_stats]: struct.Tile.html#method.geom_stats
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GeomStats {
+    name: String,
+    vertices: usize,
+    commands: usize,
+    bytes: usize,
+    histogram: [usize; 33],
+}
+
+/// Get the encoded length of a varint
+fn varint_len(v: u64) -> usize {
+    let bits = 64 - (v | 1).leading_zeros() as usize;
+    bits.div_ceil(7)
+}
+
+/// Get the encoded length of a length-delimited field
+fn field_len(len: usize) -> usize {
+    1 + varint_len(len as u64) + len
+}
+
+/// Get the encoded length of a packed repeated field
+fn packed_len(data: &[u32]) -> usize {
+    if data.is_empty() {
+        0
+    } else {
+        field_len(data.iter().map(|v| varint_len(u64::from(*v))).sum())
+    }
+}
+
+impl LayerSize {
+    /// Compute encoded size of a layer, including its tile field header.
+    pub(crate) fn new(layer: &VtLayer) -> Self {
+        let total = field_len(layer.compute_size() as usize);
+        let geometry = layer.features.iter().map(|f| packed_len(&f.geometry));
+        let tags = layer.features.iter().map(|f| packed_len(&f.tags));
+        let keys = layer.keys.iter().map(|k| field_len(k.len()));
+        let values = layer
+            .values
+            .iter()
+            .map(|v| field_len(v.compute_size() as usize));
+        let mut size = LayerSize {
+            name: layer.name().to_string(),
+            geometry: geometry.sum(),
+            tags: tags.sum(),
+            keys: keys.sum(),
+            values: values.sum(),
+            other: 0,
+        };
+        size.other =
+            total - size.geometry - size.tags - size.keys - size.values;
+        size
+    }
+
+    /// Get the layer name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get total encoded size of the layer.
+    pub fn total(&self) -> usize {
+        self.geometry + self.tags + self.keys + self.values + self.other
+    }
+
+    /// Get size of feature geometry.
+    pub fn geometry(&self) -> usize {
+        self.geometry
+    }
+
+    /// Get size of feature tags (key / value indices).
+    pub fn tags(&self) -> usize {
+        self.tags
+    }
+
+    /// Get size of the key table.
+    pub fn keys(&self) -> usize {
+        self.keys
+    }
+
+    /// Get size of the value table.
+    pub fn values(&self) -> usize {
+        self.values
+    }
+
+    /// Get size of everything else (IDs, types, headers, name, extent).
+    pub fn other(&self) -> usize {
+        self.other
+    }
+}
+
+impl GeomStats {
+    /// Compute geometry statistics of a layer.
+    pub(crate) fn new(layer: &VtLayer) -> Result<Self> {
+        let mut stats = GeomStats {
+            name: layer.name().to_string(),
+            vertices: 0,
+            commands: 0,
+            bytes: 0,
+            histogram: [0; 33],
+        };
+        for feature in &layer.features {
+            let vertices = decode_vertices(&feature.geometry)?;
+            stats.bytes += packed_len(&feature.geometry);
+            stats.vertices += vertices.len();
+            stats.commands += feature.geometry.len() - vertices.len() * 2;
+            let mut prev = (0, 0);
+            for v in vertices {
+                let dx = v.0.wrapping_sub(prev.0).unsigned_abs();
+                let dy = v.1.wrapping_sub(prev.1).unsigned_abs();
+                let bits = 32 - dx.max(dy).leading_zeros();
+                stats.histogram[bits as usize] += 1;
+                prev = v;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Get the layer name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get number of vertices.
+    pub fn vertices(&self) -> usize {
+        self.vertices
+    }
+
+    /// Get number of command integers.
+    pub fn commands(&self) -> usize {
+        self.commands
+    }
+
+    /// Get encoded size of geometry (bytes).
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Get average encoded bytes per vertex.
+    pub fn bytes_per_vertex(&self) -> f64 {
+        if self.vertices > 0 {
+            self.bytes as f64 / self.vertices as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Get histogram of vertex delta magnitudes.
+    ///
+    /// Element `n` is the number of vertices whose larger delta (in X or Y)
+    /// needs `n` bits, so element 0 counts repeated points, element 1 counts
+    /// deltas of 1, element 2 counts 2 to 3, and so on.
+    pub fn histogram(&self) -> &[usize] {
+        &self.histogram
+    }
+}
+
+impl fmt::Display for GeomStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} vertices, {} bytes ({:.2} bytes/vertex)",
+            self.name,
+            self.vertices,
+            self.bytes,
+            self.bytes_per_vertex()
+        )?;
+        let last = self.histogram.iter().rposition(|n| *n > 0).unwrap_or(0);
+        for (bits, n) in self.histogram[..=last].iter().enumerate() {
+            write!(f, "{}{}", if bits == 0 { " [" } else { " " }, n)?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl fmt::Display for LayerSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} bytes (geometry {}, tags {}, keys {}, values {}, other {})",
+            self.name,
+            self.total(),
+            self.geometry,
+            self.tags,
+            self.keys,
+            self.values,
+            self.other
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    #[test]
+    fn test_varint_len() {
+        assert_eq!(varint_len(0), 1);
+        assert_eq!(varint_len(127), 1);
+        assert_eq!(varint_len(128), 2);
+        assert_eq!(varint_len(16_383), 2);
+        assert_eq!(varint_len(16_384), 3);
+        assert_eq!(varint_len(u64::MAX), 10);
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("First Layer");
+        for i in 0..100 {
+            let geom =
+                GeomEncoder::new(GeomType::Linestring, Transform::default())
+                    .point(0.0, 0.0)
+                    .unwrap()
+                    .point(i as f64 * 40.0, 2000.0)
+                    .unwrap()
+                    .encode()
+                    .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.set_id(i);
+            feature.add_tag_uint("number", i);
+            feature.add_tag_string("key", "value");
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        tile.add_layer(tile.create_layer("Empty")).unwrap();
+        let sizes = tile.size_breakdown();
+        assert_eq!(sizes.len(), 2);
+        let total: usize = sizes.iter().map(|s| s.total()).sum();
+        assert_eq!(total, tile.compute_size());
+        assert_eq!(total, tile.to_bytes().unwrap().len());
+        let s = &sizes[0];
+        assert_eq!(s.name(), "First Layer");
+        assert_eq!(s.keys(), 13);
+        assert_eq!(s.tags(), 100 * 6);
+        assert!(s.geometry() > s.tags());
+        assert_eq!(sizes[1].geometry(), 0);
+    }
+
+    #[test]
+    fn test_geom_stats() {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("Lines");
+        let geom = GeomEncoder::new(GeomType::Linestring, Transform::default())
+            .point(1.0, 1.0)
+            .unwrap()
+            .point(1.0, 1.0)
+            .unwrap()
+            .point(4.0, 0.0)
+            .unwrap()
+            .point(104.0, 0.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        tile.add_layer(layer.into_feature(geom).into_layer())
+            .unwrap();
+        let stats = tile.geom_stats().unwrap();
+        let s = &stats[0];
+        assert_eq!(s.name(), "Lines");
+        assert_eq!(s.vertices(), 4);
+        assert_eq!(s.commands(), 2);
+        assert_eq!(s.bytes(), 13);
+        assert_eq!(s.bytes_per_vertex(), 3.25);
+        assert_eq!(&s.histogram()[..8], &[1, 1, 1, 0, 0, 0, 0, 1]);
+        assert_eq!(
+            s.to_string(),
+            "Lines: 4 vertices, 13 bytes (3.25 bytes/vertex) [1 1 1 0 0 0 0 1]"
+        );
+    }
+}
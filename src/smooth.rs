@@ -0,0 +1,250 @@
+// smooth.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Smoothing of jagged lines and polygons.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::Result;
+use pointy::{Float, Pt, Transform};
+
+/// Maximum number of smoothing iterations (each doubles the vertex count)
+const MAX_ITERATIONS: u32 = 8;
+
+/// Smooths jagged machine-generated geometry, such as isochrones or raster
+/// footprints, with Chaikin's corner-cutting algorithm.
+///
+/// Each iteration replaces every segment with two points, at 1/4 and 3/4
+/// of its length; the result approaches a quadratic B-spline.  Segments can
+/// first be densified, so that long edges keep their shape and only corners
+/// are rounded.  Distances are in tile units (after applying the transform),
+/// so a smoother can be configured for each layer as rendered.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, Smoother};
+/// # use pointy::Transform;
+/// # fn main() -> Result<(), Error> {
+/// let smoother = Smoother::new(Transform::default())
+///     .with_iterations(3)
+///     .with_densify(64.0);
+/// let square = [(0.0, 0.0), (256.0, 0.0), (256.0, 256.0), (0.0, 256.0)];
+/// let ring = smoother.smooth_ring(&square);
+/// assert_eq!(ring.len(), 16 * 8);
+/// let geom_data = smoother.encode_polygon(&[square.to_vec()])?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Smoother<F>
+where
+    F: Float,
+{
+    transform: Transform<F>,
+    iterations: u32,
+    densify: F,
+}
+
+impl<F> Smoother<F>
+where
+    F: Float,
+{
+    /// Create a new smoother, with 2 iterations and no densification.
+    ///
+    /// * `transform` Transform to tile coördinates.
+    pub fn new(transform: Transform<F>) -> Self {
+        Smoother {
+            transform,
+            iterations: 2,
+            densify: F::zero(),
+        }
+    }
+
+    /// Set the number of smoothing iterations (at most 8).
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.min(MAX_ITERATIONS);
+        self
+    }
+
+    /// Set the maximum segment length before smoothing (tile units).
+    ///
+    /// Longer segments are split evenly; 0 for no densification.
+    pub fn with_densify(mut self, max_len: F) -> Self {
+        self.densify = max_len;
+        self
+    }
+
+    /// Transform and densify vertices
+    fn prepare(&self, pts: &[(F, F)], closed: bool) -> Vec<Pt<F>> {
+        let mut verts: Vec<Pt<F>> =
+            pts.iter().map(|p| self.transform * *p).collect();
+        verts.dedup();
+        if closed && verts.len() > 1 && verts.first() == verts.last() {
+            verts.pop();
+        }
+        if self.densify.is_nan() || self.densify <= F::zero() {
+            return verts;
+        }
+        let n = if closed {
+            verts.len()
+        } else {
+            verts.len().max(1) - 1
+        };
+        let mut out = Vec::with_capacity(verts.len());
+        for i in 0..n {
+            let (p0, p1) = (verts[i], verts[(i + 1) % verts.len()]);
+            let len = (p1.x() - p0.x()).hypot(p1.y() - p0.y());
+            let pieces = (len / self.densify).ceil().to_usize().unwrap_or(1);
+            let pieces = F::from(pieces.max(1)).unwrap();
+            let mut j = F::zero();
+            while j < pieces {
+                let t = j / pieces;
+                out.push(Pt::new(
+                    p0.x() + (p1.x() - p0.x()) * t,
+                    p0.y() + (p1.y() - p0.y()) * t,
+                ));
+                j = j + F::one();
+            }
+        }
+        if !closed {
+            out.extend(verts.last());
+        }
+        out
+    }
+
+    /// Cut corners of vertices once
+    fn chaikin(verts: &[Pt<F>], closed: bool) -> Vec<Pt<F>> {
+        let quarter = F::from(0.25).unwrap();
+        let three_quarters = F::from(0.75).unwrap();
+        let cut = |p0: Pt<F>, p1: Pt<F>, t: F| {
+            Pt::new(
+                p0.x() + (p1.x() - p0.x()) * t,
+                p0.y() + (p1.y() - p0.y()) * t,
+            )
+        };
+        let n = if closed { verts.len() } else { verts.len() - 1 };
+        let mut out = Vec::with_capacity(n * 2 + 2);
+        if !closed {
+            out.push(verts[0]);
+        }
+        for i in 0..n {
+            let (p0, p1) = (verts[i], verts[(i + 1) % verts.len()]);
+            out.push(cut(p0, p1, quarter));
+            out.push(cut(p0, p1, three_quarters));
+        }
+        if !closed {
+            out.push(verts[verts.len() - 1]);
+        }
+        out
+    }
+
+    /// Smooth vertices
+    fn smooth(&self, pts: &[(F, F)], closed: bool) -> Vec<Pt<F>> {
+        let mut verts = self.prepare(pts, closed);
+        if verts.len() < 3 {
+            return verts;
+        }
+        for _ in 0..self.iterations {
+            verts = Self::chaikin(&verts, closed);
+        }
+        verts
+    }
+
+    /// Smooth a linestring, keeping its end points.
+    ///
+    /// * `line` Linestring vertices (before transform).
+    ///
+    /// Returns vertices in tile coördinates.
+    pub fn smooth_line(&self, line: &[(F, F)]) -> Vec<Pt<F>> {
+        self.smooth(line, false)
+    }
+
+    /// Smooth a polygon ring.
+    ///
+    /// * `ring` Ring vertices (before transform); the first vertex may be
+    ///   repeated at the end.
+    ///
+    /// Returns vertices in tile coördinates, without repeating the first.
+    pub fn smooth_ring(&self, ring: &[(F, F)]) -> Vec<Pt<F>> {
+        self.smooth(ring, true)
+    }
+
+    /// Smooth linestrings, encoding them as (multi)linestring data.
+    ///
+    /// * `lines` Linestrings (before transform).
+    pub fn encode_lines(&self, lines: &[Vec<(F, F)>]) -> Result<GeomData> {
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring, Transform::default());
+        for line in lines {
+            for p in self.smooth_line(line) {
+                enc.add_point(p.x(), p.y())?;
+            }
+            enc.complete_geom()?;
+        }
+        enc.encode()
+    }
+
+    /// Smooth polygon rings, encoding them as (multi)polygon data.
+    ///
+    /// * `rings` Polygon rings (before transform), exterior rings followed
+    ///   by their interior rings.
+    ///
+    /// Rings keep their winding order.
+    pub fn encode_polygon(&self, rings: &[Vec<(F, F)>]) -> Result<GeomData> {
+        let mut enc = GeomEncoder::new(GeomType::Polygon, Transform::default());
+        for ring in rings {
+            for p in self.smooth_ring(ring) {
+                enc.add_point(p.x(), p.y())?;
+            }
+            enc.complete_geom()?;
+        }
+        enc.encode()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_smooth_ring() {
+        let s = Smoother::new(Transform::default()).with_iterations(1);
+        let ring = s.smooth_ring(&[
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 4.0),
+            (0.0, 4.0),
+            (0.0, 0.0),
+        ]);
+        assert_eq!(ring.len(), 8);
+        assert_eq!(ring[0], Pt::new(1.0, 0.0));
+        assert_eq!(ring[1], Pt::new(3.0, 0.0));
+        assert_eq!(ring[7], Pt::new(0.0, 1.0));
+        let s = s.with_iterations(0);
+        assert_eq!(s.smooth_ring(&[(0.0, 0.0), (1.0, 1.0)]).len(), 2);
+    }
+
+    #[test]
+    fn test_smooth_line() {
+        let s = Smoother::new(Transform::with_scale(2.0, 2.0))
+            .with_iterations(1)
+            .with_densify(4.0);
+        let line = s.smooth_line(&[(0.0, 0.0), (4.0, 0.0), (4.0, 2.0)]);
+        // densified to (0,0) (4,0) (8,0) (8,4), then corners cut
+        assert_eq!(line.len(), 8);
+        assert_eq!(line[0], Pt::new(0.0, 0.0));
+        assert_eq!(line[4], Pt::new(7.0, 0.0));
+        assert_eq!(line[5], Pt::new(8.0, 1.0));
+        assert_eq!(line[7], Pt::new(8.0, 4.0));
+    }
+
+    #[test]
+    fn test_encode() {
+        let s = Smoother::new(Transform::default());
+        let square = vec![(0.0, 0.0), (64.0, 0.0), (64.0, 64.0), (0.0, 64.0)];
+        let hole = vec![(16.0, 16.0), (16.0, 48.0), (48.0, 48.0), (48.0, 16.0)];
+        let geom = s.encode_polygon(&[square.clone(), hole]).unwrap();
+        assert!(geom.validate().is_ok());
+        let geom = s.encode_lines(&[square]).unwrap();
+        assert!(geom.validate().is_ok());
+    }
+}
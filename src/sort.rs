@@ -0,0 +1,218 @@
+// sort.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Key / value table sorting.
+//!
+use crate::vector_tile::tile::{Layer as VtLayer, Value};
+use std::cmp::Ordering;
+
+/// Size saving from [Tile::sort_tables].
+///
+/// With the `gzip` feature, gzip-compressed sizes are also measured.
+///
+/// [Tile::sort_tables]: struct.Tile.html#method.sort_tables
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TableSort {
+    before: usize,
+    after: usize,
+    #[cfg(feature = "gzip")]
+    gzip_before: usize,
+    #[cfg(feature = "gzip")]
+    gzip_after: usize,
+}
+
+impl TableSort {
+    /// Create a new table sort report.
+    pub(crate) fn new(before: usize, after: usize) -> Self {
+        TableSort {
+            before,
+            after,
+            #[cfg(feature = "gzip")]
+            gzip_before: 0,
+            #[cfg(feature = "gzip")]
+            gzip_after: 0,
+        }
+    }
+
+    /// Set gzip-compressed sizes.
+    #[cfg(feature = "gzip")]
+    pub(crate) fn with_gzip(mut self, before: usize, after: usize) -> Self {
+        self.gzip_before = before;
+        self.gzip_after = after;
+        self
+    }
+
+    /// Get encoded size before sorting (bytes).
+    pub fn before(&self) -> usize {
+        self.before
+    }
+
+    /// Get encoded size after sorting (bytes).
+    pub fn after(&self) -> usize {
+        self.after
+    }
+
+    /// Get gzip-compressed size before sorting (bytes).
+    #[cfg(feature = "gzip")]
+    pub fn gzip_before(&self) -> usize {
+        self.gzip_before
+    }
+
+    /// Get gzip-compressed size after sorting (bytes).
+    #[cfg(feature = "gzip")]
+    pub fn gzip_after(&self) -> usize {
+        self.gzip_after
+    }
+}
+
+/// Get rank of a value type, for sorting
+fn type_rank(v: &Value) -> u8 {
+    if v.string_value.is_some() {
+        0
+    } else if v.float_value.is_some() {
+        1
+    } else if v.double_value.is_some() {
+        2
+    } else if v.int_value.is_some() {
+        3
+    } else if v.uint_value.is_some() {
+        4
+    } else if v.sint_value.is_some() {
+        5
+    } else {
+        6
+    }
+}
+
+/// Compare two values, grouping by type
+fn cmp_value(a: &Value, b: &Value) -> Ordering {
+    type_rank(a)
+        .cmp(&type_rank(b))
+        .then_with(|| a.string_value.cmp(&b.string_value))
+        .then_with(|| {
+            let a = a.float_value.map(f64::from).or(a.double_value);
+            let b = b.float_value.map(f64::from).or(b.double_value);
+            match (a, b) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                _ => Ordering::Equal,
+            }
+        })
+        .then_with(|| a.int_value.cmp(&b.int_value))
+        .then_with(|| a.uint_value.cmp(&b.uint_value))
+        .then_with(|| a.sint_value.cmp(&b.sint_value))
+        .then_with(|| a.bool_value.cmp(&b.bool_value))
+}
+
+/// Make a table order: most used first, then by comparison function.
+///
+/// Unused entries are dropped.  Returns (order, remap) where `remap` maps old
+/// indices to new.
+fn table_order<T>(
+    table: &[T],
+    uses: &[usize],
+    cmp: impl Fn(&T, &T) -> Ordering,
+) -> (Vec<usize>, Vec<u32>) {
+    let mut order: Vec<usize> =
+        (0..table.len()).filter(|i| uses[*i] > 0).collect();
+    order.sort_by(|a, b| {
+        uses[*b]
+            .cmp(&uses[*a])
+            .then_with(|| cmp(&table[*a], &table[*b]))
+    });
+    let mut remap = vec![0; table.len()];
+    for (new, old) in order.iter().enumerate() {
+        remap[*old] = new as u32;
+    }
+    (order, remap)
+}
+
+/// Sort key and value tables of a layer, rewriting tag indices.
+///
+/// Tables are ordered by number of uses, so that common entries get small
+/// indices, and similar entries are grouped together.  Tags which refer to
+/// missing entries are left unchanged.
+pub(crate) fn sort_layer(layer: &mut VtLayer) {
+    let mut key_uses = vec![0; layer.keys.len()];
+    let mut val_uses = vec![0; layer.values.len()];
+    for feature in &layer.features {
+        for tag in feature.tags.chunks_exact(2) {
+            let (k, v) = (tag[0] as usize, tag[1] as usize);
+            if k >= key_uses.len() || v >= val_uses.len() {
+                return;
+            }
+            key_uses[k] += 1;
+            val_uses[v] += 1;
+        }
+    }
+    let (key_order, key_remap) =
+        table_order(&layer.keys, &key_uses, |a, b| a.cmp(b));
+    let (val_order, val_remap) =
+        table_order(&layer.values, &val_uses, cmp_value);
+    for feature in &mut layer.features {
+        for tag in feature.tags.chunks_exact_mut(2) {
+            tag[0] = key_remap[tag[0] as usize];
+            tag[1] = val_remap[tag[1] as usize];
+        }
+    }
+    layer.keys = key_order.iter().map(|i| layer.keys[*i].clone()).collect();
+    layer.values = val_order.iter().map(|i| layer.values[*i].clone()).collect();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    fn make_tile() -> Tile {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("Layer");
+        for i in 0..200 {
+            let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(0.0, 0.0)
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.add_tag_uint("rare", i);
+            if i >= 150 {
+                feature.add_tag_string("common", "yes");
+            }
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        tile
+    }
+
+    #[test]
+    fn test_sort_layer() {
+        let mut tile = make_tile();
+        let report = tile.sort_tables().unwrap();
+        assert_eq!(report.before(), make_tile().compute_size());
+        assert_eq!(report.after(), tile.compute_size());
+        // "yes" index drops from 151 to 0, saving a byte in 50 features;
+        // uint 127 index moves from 127 to 128, costing one byte
+        assert_eq!(report.before() - report.after(), 49);
+        let layer = &tile.vt_layers()[0];
+        assert_eq!(layer.keys, vec!["rare", "common"]);
+        assert_eq!(layer.values[0].string_value.as_deref(), Some("yes"));
+        assert_eq!(layer.values[1].uint_value, Some(0));
+        assert_eq!(layer.features[5].tags, vec![0, 6]);
+        assert_eq!(layer.features[160].tags, vec![0, 161, 1, 0]);
+    }
+
+    #[test]
+    fn test_cmp_value() {
+        let mut a = Value::new();
+        a.set_string_value("a".to_string());
+        let mut b = Value::new();
+        b.set_double_value(-1.0);
+        let mut c = Value::new();
+        c.set_double_value(2.0);
+        assert_eq!(cmp_value(&a, &b), Ordering::Less);
+        assert_eq!(cmp_value(&b, &c), Ordering::Less);
+        assert_eq!(cmp_value(&c, &c), Ordering::Equal);
+    }
+}
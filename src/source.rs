@@ -0,0 +1,186 @@
+// source.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Feature sources for tiling.
+//!
+use crate::error::Result;
+use crate::geometry::Geometry;
+use crate::tile::Feature;
+use pointy::{BBox, Pt};
+
+/// Tag value of a [SourceFeature].
+///
+/// [SourceFeature]: struct.SourceFeature.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum SourceValue {
+    /// String value
+    String(String),
+
+    /// Integer value
+    Int(i64),
+
+    /// Floating-point value
+    Double(f64),
+}
+
+/// Feature read from a [FeatureSource], in source coördinates.
+///
+/// [FeatureSource]: trait.FeatureSource.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceFeature {
+    /// Feature ID
+    id: Option<u64>,
+
+    /// Geometry
+    geometry: Geometry<f64>,
+
+    /// Tags (key / value pairs)
+    tags: Vec<(String, SourceValue)>,
+}
+
+/// Source of features for tiling, queried by bounding box.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{FeatureSource, Geometry, SourceFeature};
+/// use pointy::{BBox, Pt};
+///
+/// let mut source = vec![
+///     SourceFeature::new(Geometry::Points(vec![(1.0, 1.0)])).with_id(1),
+///     SourceFeature::new(Geometry::Points(vec![(9.0, 9.0)])).with_id(2),
+/// ];
+/// let bbox = BBox::from((Pt::new(0.0, 0.0), Pt::new(5.0, 5.0)));
+/// let features = source.features_in(bbox)?;
+/// assert_eq!(features.len(), 1);
+/// assert_eq!(features[0].id(), Some(1));
+/// # Ok(()) }
+/// ```
+pub trait FeatureSource {
+    /// Get features which may intersect a bounding box.
+    ///
+    /// Sources with an index may return features whose bounds intersect
+    /// the box, even if their geometry does not.
+    fn features_in(&mut self, bbox: BBox<f64>) -> Result<Vec<SourceFeature>>;
+}
+
+/// Get the bounding box of a geometry
+pub(crate) fn geometry_bbox(geometry: &Geometry<f64>) -> Option<BBox<f64>> {
+    let pts: Vec<Pt<f64>> = match geometry {
+        Geometry::Points(pts) => pts.iter().map(|p| Pt::from(*p)).collect(),
+        Geometry::Linestrings(lines) => {
+            lines.iter().flatten().map(|p| Pt::from(*p)).collect()
+        }
+        Geometry::Polygons(polygons) => polygons
+            .iter()
+            .flatten()
+            .flatten()
+            .map(|p| Pt::from(*p))
+            .collect(),
+    };
+    (!pts.is_empty()).then(|| BBox::new(pts))
+}
+
+impl SourceFeature {
+    /// Create a new source feature.
+    pub fn new(geometry: Geometry<f64>) -> Self {
+        SourceFeature {
+            id: None,
+            geometry,
+            tags: vec![],
+        }
+    }
+
+    /// Set the feature ID.
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Add a tag.
+    pub fn with_tag(mut self, key: &str, value: SourceValue) -> Self {
+        self.tags.push((key.to_string(), value));
+        self
+    }
+
+    /// Get the feature ID.
+    pub fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    /// Get the geometry.
+    pub fn geometry(&self) -> &Geometry<f64> {
+        &self.geometry
+    }
+
+    /// Get the tags.
+    pub fn tags(&self) -> &[(String, SourceValue)] {
+        &self.tags
+    }
+
+    /// Set the ID and tags of a tile feature.
+    pub fn add_tags(&self, feature: &mut Feature) {
+        if let Some(id) = self.id {
+            feature.set_id(id);
+        }
+        for (key, value) in &self.tags {
+            match value {
+                SourceValue::String(v) => feature.add_tag_string(key, v),
+                SourceValue::Int(v) => feature.add_tag_int(key, *v),
+                SourceValue::Double(v) => feature.add_tag_double(key, *v),
+            };
+        }
+    }
+}
+
+impl FeatureSource for Vec<SourceFeature> {
+    fn features_in(&mut self, bbox: BBox<f64>) -> Result<Vec<SourceFeature>> {
+        Ok(self
+            .iter()
+            .filter(|f| {
+                geometry_bbox(&f.geometry).is_some_and(|b| b.intersects(bbox))
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    #[test]
+    fn test_geometry_bbox() {
+        let geom = Geometry::Linestrings(vec![vec![(1.0, 5.0), (3.0, -2.0)]]);
+        let bbox = geometry_bbox(&geom).unwrap();
+        assert_eq!(bbox.x_min(), 1.0);
+        assert_eq!(bbox.y_min(), -2.0);
+        assert_eq!(bbox.x_max(), 3.0);
+        assert_eq!(bbox.y_max(), 5.0);
+        assert!(geometry_bbox(&Geometry::Points(vec![])).is_none());
+    }
+
+    #[test]
+    fn test_add_tags() {
+        let source = SourceFeature::new(Geometry::Points(vec![(1.0, 1.0)]))
+            .with_id(7)
+            .with_tag("name", SourceValue::String("Main".to_string()))
+            .with_tag("lanes", SourceValue::Int(2));
+        let tile = Tile::new(4096);
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(1.0, 1.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("roads").into_feature(geom);
+        source.add_tags(&mut feature);
+        assert_eq!(feature.num_tags(), 4);
+        let layer = feature.into_layer();
+        assert_eq!(layer.num_features(), 1);
+    }
+}
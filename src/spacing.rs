@@ -0,0 +1,184 @@
+// spacing.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Symbol spacing along lines.
+//!
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::Result;
+use pointy::{Float, Pt, Transform};
+
+/// Maximum number of points placed along one linestring
+const MAX_POINTS: usize = 1 << 16;
+
+/// Places points at regular intervals along linestrings.
+///
+/// Spacing is measured in tile units (after applying the transform), so that
+/// symbols such as shields or arrows are evenly spaced as rendered.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, LineSpacer};
+/// # use pointy::Transform;
+/// # fn main() -> Result<(), Error> {
+/// let spacer = LineSpacer::new(Transform::default(), 100.0);
+/// let pts = spacer.place(&[(0.0, 0.0), (250.0, 0.0)]);
+/// assert_eq!(pts.len(), 3);
+/// let geom_data = spacer.encode(&[(0.0, 0.0), (250.0, 0.0)])?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LineSpacer<F>
+where
+    F: Float,
+{
+    transform: Transform<F>,
+    spacing: F,
+    offset: F,
+}
+
+impl<F> LineSpacer<F>
+where
+    F: Float,
+{
+    /// Create a new line spacer.
+    ///
+    /// * `transform` Transform to tile coördinates.
+    /// * `spacing` Distance between points (tile units).
+    ///
+    /// The first point is placed half of `spacing` from the line start.
+    pub fn new(transform: Transform<F>, spacing: F) -> Self {
+        let offset = spacing / (F::one() + F::one());
+        LineSpacer {
+            transform,
+            spacing,
+            offset,
+        }
+    }
+
+    /// Set distance from line start to the first point (tile units).
+    pub fn with_offset(mut self, offset: F) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Place points along a linestring.
+    ///
+    /// * `line` Linestring vertices (before transform).
+    ///
+    /// Returns points in tile coördinates.  If spacing is not positive and
+    /// finite, no points are placed.  Segments with non-finite length are
+    /// skipped.  At most 65536 points are placed, and placement stops if
+    /// spacing is too small to advance along the line.
+    pub fn place(&self, line: &[(F, F)]) -> Vec<Pt<F>> {
+        let mut pts = vec![];
+        if !self.spacing.is_finite() || self.spacing <= F::zero() {
+            return pts;
+        }
+        let mut next = self.offset.max(F::zero());
+        let mut dist = F::zero();
+        let mut verts = line.iter().map(|p| self.transform * *p);
+        let mut p0 = match verts.next() {
+            Some(p) => p,
+            None => return pts,
+        };
+        for p1 in verts {
+            let dx = p1.x() - p0.x();
+            let dy = p1.y() - p0.y();
+            let len = dx.hypot(dy);
+            if !len.is_finite() {
+                p0 = p1;
+                continue;
+            }
+            while next <= dist + len && len > F::zero() {
+                let t = (next - dist) / len;
+                pts.push(Pt::new(p0.x() + dx * t, p0.y() + dy * t));
+                let n = next + self.spacing;
+                if n <= next || pts.len() >= MAX_POINTS {
+                    return pts;
+                }
+                next = n;
+            }
+            dist = dist + len;
+            p0 = p1;
+        }
+        pts
+    }
+
+    /// Place points along a linestring, encoding them as multipoint data.
+    ///
+    /// * `line` Linestring vertices (before transform).
+    pub fn encode(&self, line: &[(F, F)]) -> Result<GeomData> {
+        let mut enc = GeomEncoder::new(GeomType::Point, Transform::default());
+        for p in self.place(line) {
+            enc.add_point(p.x(), p.y())?;
+        }
+        enc.encode()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_place() {
+        let s = LineSpacer::new(Transform::default(), 10.0);
+        let pts = s.place(&[(0.0, 0.0), (10.0, 0.0), (10.0, 20.0)]);
+        assert_eq!(
+            pts,
+            vec![Pt::new(5.0, 0.0), Pt::new(10.0, 5.0), Pt::new(10.0, 15.0)]
+        );
+    }
+
+    #[test]
+    fn test_offset() {
+        let s = LineSpacer::new(Transform::default(), 10.0).with_offset(0.0);
+        let pts = s.place(&[(0.0, 0.0), (0.0, 20.0)]);
+        assert_eq!(
+            pts,
+            vec![Pt::new(0.0, 0.0), Pt::new(0.0, 10.0), Pt::new(0.0, 20.0)]
+        );
+    }
+
+    #[test]
+    fn test_transform() {
+        let s = LineSpacer::new(Transform::with_scale(100.0, 100.0), 50.0);
+        let pts = s.place(&[(0.0, 0.0), (1.0, 0.0)]);
+        assert_eq!(pts, vec![Pt::new(25.0, 0.0), Pt::new(75.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_degenerate() {
+        let s = LineSpacer::new(Transform::default(), 0.0);
+        assert!(s.place(&[(0.0, 0.0), (10.0, 0.0)]).is_empty());
+        let s = LineSpacer::new(Transform::default(), 1.0);
+        assert!(s.place(&[]).is_empty());
+        assert!(s.place(&[(1.0, 1.0), (1.0, 1.0)]).is_empty());
+        let s = LineSpacer::new(Transform::default(), f64::INFINITY);
+        assert!(s.place(&[(0.0, 0.0), (10.0, 0.0)]).is_empty());
+        let s = LineSpacer::new(Transform::default(), 1.0);
+        assert!(s.place(&[(0.0, 0.0), (f64::INFINITY, 0.0)]).is_empty());
+        // finite points, but overflowing length
+        let line = [(-1e308, 0.0), (1e308, 0.0), (1e308, 2.0)];
+        let pts = vec![Pt::new(1e308, 0.5), Pt::new(1e308, 1.5)];
+        assert_eq!(s.place(&line), pts);
+    }
+
+    #[test]
+    fn test_non_advancing() {
+        // spacing is lost to rounding at this offset
+        let s = LineSpacer::new(Transform::default(), 1e-10).with_offset(1e10);
+        let pts = s.place(&[(0.0, 0.0), (2e10, 0.0)]);
+        assert_eq!(pts, vec![Pt::new(1e10, 0.0)]);
+        let s = LineSpacer::new(Transform::default(), 1e-3);
+        assert_eq!(s.place(&[(0.0, 0.0), (1e3, 0.0)]).len(), MAX_POINTS);
+    }
+
+    #[test]
+    fn test_encode() {
+        let s = LineSpacer::new(Transform::default(), 10.0);
+        let v = s.encode(&[(0.0, 0.0), (20.0, 0.0)]).unwrap().into_vec();
+        assert_eq!(v, vec!(17, 10, 0, 20, 0));
+    }
+}
@@ -0,0 +1,247 @@
+// split.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Splitting huge polygons into per-tile pieces.
+//!
+use crate::clip::Rect;
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::Result;
+use crate::mapgrid::{MapGrid, TileId};
+use num_traits::FromPrimitive;
+use pointy::{Float, Transform};
+
+/// Rings of a polygon
+type Rings<F> = Vec<Vec<(F, F)>>;
+
+/// Splits huge polygons (such as coastline land / water) into tiles.
+///
+/// Instead of clipping the whole polygon once per tile, it is clipped
+/// recursively into quadrants, starting from zoom 0.  Each step only handles
+/// the pieces inside its parent tile, so the work at each zoom level is
+/// roughly proportional to the number of vertices.
+///
+/// Exterior rings should be counter-clockwise in map coördinates (Y up), and
+/// holes clockwise, so that they are correctly oriented after the Y flip.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, MapGrid, PolygonSplitter};
+/// # fn main() -> Result<(), Error> {
+/// let splitter = PolygonSplitter::new(MapGrid::default(), 1).with_buffer(64);
+/// let land = vec![
+///     (-1.0e7, -1.0e7),
+///     (1.0e7, -1.0e7),
+///     (1.0e7, 1.0e7),
+///     (-1.0e7, 1.0e7),
+/// ];
+/// let tiles = splitter.split(&[land])?;
+/// assert_eq!(tiles.len(), 4);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PolygonSplitter<F>
+where
+    F: Float + FromPrimitive,
+{
+    grid: MapGrid<F>,
+    zoom: u32,
+    extent: u32,
+    buffer: u32,
+}
+
+impl<F> PolygonSplitter<F>
+where
+    F: Float + FromPrimitive,
+{
+    /// Create a new polygon splitter.
+    ///
+    /// * `grid` Map grid.
+    /// * `zoom` Zoom level of output tiles.
+    ///
+    /// The default extent is 4096, with no buffer.
+    pub fn new(grid: MapGrid<F>, zoom: u32) -> Self {
+        PolygonSplitter {
+            grid,
+            zoom: zoom.min(31),
+            extent: 4096,
+            buffer: 0,
+        }
+    }
+
+    /// Set the tile extent.
+    pub fn with_extent(mut self, extent: u32) -> Self {
+        self.extent = extent;
+        self
+    }
+
+    /// Set the buffer around each tile (tile units).
+    pub fn with_buffer(mut self, buffer: u32) -> Self {
+        self.buffer = buffer;
+        self
+    }
+
+    /// Split a polygon into tiles.
+    ///
+    /// * `rings` Polygon rings (map coördinates).
+    ///
+    /// Returns encoded polygon geometry for each tile containing part of
+    /// the polygon, in quadtree order.
+    pub fn split(
+        &self,
+        rings: &[Vec<(F, F)>],
+    ) -> Result<Vec<(TileId, GeomData)>> {
+        let t = self.grid.tile_transform(TileId::new(0, 0, 0)?);
+        let rings = rings
+            .iter()
+            .map(|ring| {
+                ring.iter()
+                    .map(|p| {
+                        let p = t * *p;
+                        (p.x(), p.y())
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut tiles = vec![];
+        self.split_tile(0, 0, 0, rings, &mut tiles)?;
+        Ok(tiles)
+    }
+
+    /// Split many polygons into tiles, passing user data through.
+    ///
+    /// * `polygons` User data and polygon rings (map coördinates).
+    ///
+    /// Returns encoded polygon geometry for each tile containing part of a
+    /// polygon, with a clone of that polygon's data, so results can be
+    /// correlated with source records.  Pieces are in quadtree order of
+    /// tiles, and input order within each tile.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, MapGrid, PolygonSplitter};
+    /// # fn main() -> Result<(), Error> {
+    /// let splitter = PolygonSplitter::new(MapGrid::default(), 1);
+    /// let west = vec![(-1.0e7, -1.0e7), (-1.0, -1.0e7), (-1.0, 1.0e7)];
+    /// let east = vec![(1.0, -1.0e7), (1.0e7, -1.0e7), (1.0e7, 1.0e7)];
+    /// let pieces = splitter.split_with(&[("west", vec![west]), ("east", vec![east])])?;
+    /// let names: Vec<_> = pieces.iter().map(|(_, _, name)| *name).collect();
+    /// assert_eq!(names, ["west", "east", "west", "east"]);
+    /// # Ok(()) }
+    /// ```
+    pub fn split_with<T>(
+        &self,
+        polygons: &[(T, Rings<F>)],
+    ) -> Result<Vec<(TileId, GeomData, T)>>
+    where
+        T: Clone,
+    {
+        let mut pieces = vec![];
+        for (data, rings) in polygons {
+            for (tid, geom) in self.split(rings)? {
+                pieces.push((tid, geom, data.clone()));
+            }
+        }
+        pieces.sort_by_key(|(tid, _, _)| quadtree_key(tid));
+        Ok(pieces)
+    }
+
+    /// Clip rings to one tile, then recurse into its quadrants
+    fn split_tile(
+        &self,
+        x: u32,
+        y: u32,
+        z: u32,
+        rings: Rings<F>,
+        tiles: &mut Vec<(TileId, GeomData)>,
+    ) -> Result<()> {
+        let size = F::one() / F::from_u32(1 << z).unwrap();
+        let extent = F::from_u32(self.extent).unwrap();
+        let buffer = size * F::from_u32(self.buffer).unwrap() / extent;
+        let x0 = F::from_u32(x).unwrap() * size;
+        let y0 = F::from_u32(y).unwrap() * size;
+        let rect = Rect::new(
+            x0 - buffer,
+            y0 - buffer,
+            x0 + size + buffer,
+            y0 + size + buffer,
+        );
+        let rings: Rings<F> = rings
+            .iter()
+            .map(|ring| rect.clip_ring(ring))
+            .filter(|ring| !ring.is_empty())
+            .collect();
+        if rings.is_empty() {
+            return Ok(());
+        }
+        if z < self.zoom {
+            for (cx, cy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let (cx, cy) = (x * 2 + cx, y * 2 + cy);
+                self.split_tile(cx, cy, z + 1, rings.clone(), tiles)?;
+            }
+            return Ok(());
+        }
+        let scale = extent / size;
+        let transform = Transform::with_scale(scale, scale).translate(
+            -F::from_u32(x).unwrap() * extent,
+            -F::from_u32(y).unwrap() * extent,
+        );
+        let mut enc = GeomEncoder::new(GeomType::Polygon, transform);
+        for ring in &rings {
+            for p in ring {
+                enc.add_point(p.0, p.1)?;
+            }
+            enc.complete_geom()?;
+        }
+        tiles.push((TileId::new(x, y, z)?, enc.encode()?));
+        Ok(())
+    }
+}
+
+/// Get quadtree (Morton) order key of a tile
+fn quadtree_key(tid: &TileId) -> u64 {
+    let mut key = 0;
+    for bit in 0..32 {
+        key |= u64::from((tid.x() >> bit) & 1) << (bit * 2);
+        key |= u64::from((tid.y() >> bit) & 1) << (bit * 2 + 1);
+    }
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::decode_vertices;
+    use pointy::{BBox, Pt};
+
+    fn grid() -> MapGrid<f64> {
+        let bbox = BBox::from((Pt::new(0.0, 0.0), Pt::new(4.0, 4.0)));
+        MapGrid::new(0, bbox)
+    }
+
+    #[test]
+    fn test_split() {
+        let splitter = PolygonSplitter::new(grid(), 1);
+        let ring = vec![(0.5, 0.5), (3.5, 0.5), (3.5, 3.5), (0.5, 3.5)];
+        let tiles = splitter.split(&[ring]).unwrap();
+        assert_eq!(tiles.len(), 4);
+        let (tid, geom) = tiles.into_iter().next().unwrap();
+        assert_eq!((tid.x(), tid.y(), tid.z()), (0, 0, 1));
+        let mut verts = decode_vertices(&geom.into_vec()).unwrap();
+        verts.sort();
+        assert_eq!(
+            verts,
+            vec![(1024, 1024), (1024, 4096), (4096, 1024), (4096, 4096)]
+        );
+    }
+
+    #[test]
+    fn test_sparse() {
+        let splitter = PolygonSplitter::new(grid(), 8).with_buffer(16);
+        let island = vec![(1.001, 1.001), (1.005, 1.001), (1.005, 1.005)];
+        let other = vec![(3.501, 3.501), (3.505, 3.501), (3.505, 3.505)];
+        let tiles = splitter.split(&[island, other]).unwrap();
+        assert_eq!(tiles.len(), 2);
+        assert!(tiles.iter().all(|(tid, _)| tid.z() == 8));
+    }
+}
@@ -0,0 +1,507 @@
+// stream.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Framing for pushing tiles over streaming channels.
+//!
+use crate::archive::{TileSink, TileSource};
+use crate::delta::TileDelta;
+use crate::error::{Error, Result};
+use crate::mapgrid::TileId;
+use crate::tile::Tile;
+use std::io::{
+    stdin, stdout, BufReader, BufWriter, ErrorKind, Read, Stdin, Stdout, Write,
+};
+
+/// Length of frame header (bytes)
+const HEADER_LEN: usize = 14;
+
+/// Maximum length of frame payload (bytes)
+const MAX_PAYLOAD_LEN: usize = 64 * 1024 * 1024;
+
+/// Kind of frame payload
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FrameKind {
+    /// Complete encoded tile
+    Tile = 1,
+
+    /// Encoded [TileDelta]
+    Delta = 2,
+}
+
+/// Tile or tile delta framed for a websocket / SSE channel.
+///
+/// The binary frame layout is (all integers big-endian):
+///
+/// | Bytes | Field                     |
+/// |-------|---------------------------|
+/// | 1     | Kind (1: tile, 2: delta)  |
+/// | 1     | Zoom                      |
+/// | 4     | X                         |
+/// | 4     | Y                         |
+/// | 4     | Payload length            |
+/// | *     | Payload                   |
+///
+/// Payloads are limited to 64 MiB.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, Frame, Tile, TileId};
+/// # fn main() -> Result<(), Error> {
+/// let tid = TileId::new(1, 2, 3)?;
+/// let frame = Frame::tile(tid, &Tile::new(4096))?;
+/// let data = frame.to_bytes()?;
+/// let frame = Frame::read_from(&mut &data[..])?.unwrap();
+/// assert_eq!(frame.tid().z(), 3);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Frame {
+    kind: FrameKind,
+    tid: TileId,
+    payload: Vec<u8>,
+}
+
+/// Write a frame header and payload
+fn write_frame(
+    out: &mut dyn Write,
+    kind: FrameKind,
+    tid: TileId,
+    payload: &[u8],
+) -> Result<()> {
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(Error::InvalidFrame());
+    }
+    let len = payload.len() as u32;
+    let mut header = [0; HEADER_LEN];
+    header[0] = kind as u8;
+    header[1] = tid.z() as u8;
+    header[2..6].copy_from_slice(&tid.x().to_be_bytes());
+    header[6..10].copy_from_slice(&tid.y().to_be_bytes());
+    header[10..14].copy_from_slice(&len.to_be_bytes());
+    out.write_all(&header)?;
+    out.write_all(payload)?;
+    Ok(())
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = Error;
+
+    fn try_from(v: u8) -> Result<Self> {
+        match v {
+            1 => Ok(FrameKind::Tile),
+            2 => Ok(FrameKind::Delta),
+            _ => Err(Error::InvalidFrame()),
+        }
+    }
+}
+
+impl Frame {
+    /// Create a frame containing a tile.
+    ///
+    /// * `tid` Tile ID.
+    /// * `tile` Tile to encode.
+    pub fn tile(tid: TileId, tile: &Tile) -> Result<Self> {
+        Frame::new(FrameKind::Tile, tid, tile.to_bytes()?)
+    }
+
+    /// Create a frame containing a tile delta.
+    ///
+    /// * `tid` Tile ID.
+    /// * `delta` Tile delta to encode.
+    pub fn delta(tid: TileId, delta: &TileDelta) -> Result<Self> {
+        Frame::new(FrameKind::Delta, tid, delta.to_bytes()?)
+    }
+
+    /// Create a frame with an encoded payload.
+    ///
+    /// * `kind` Kind of payload.
+    /// * `tid` Tile ID.
+    /// * `payload` Encoded tile or tile delta.
+    ///
+    /// Returns an error if the payload is too large for a frame.
+    pub fn new(kind: FrameKind, tid: TileId, payload: Vec<u8>) -> Result<Self> {
+        if payload.len() > MAX_PAYLOAD_LEN {
+            return Err(Error::InvalidFrame());
+        }
+        Ok(Frame { kind, tid, payload })
+    }
+
+    /// Get the kind of payload.
+    pub fn kind(&self) -> FrameKind {
+        self.kind
+    }
+
+    /// Get the tile ID.
+    pub fn tid(&self) -> TileId {
+        self.tid
+    }
+
+    /// Get the encoded payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Decode the payload as a tile delta.
+    ///
+    /// Returns an error if the frame does not contain a delta.
+    pub fn to_delta(&self) -> Result<TileDelta> {
+        match self.kind {
+            FrameKind::Delta => TileDelta::from_bytes(&self.payload),
+            FrameKind::Tile => Err(Error::InvalidFrame()),
+        }
+    }
+
+    /// Write the frame.
+    ///
+    /// * `out` Writer to output the frame.
+    pub fn write_to(&self, out: &mut dyn Write) -> Result<()> {
+        write_frame(out, self.kind, self.tid, &self.payload)
+    }
+
+    /// Encode the frame and return the bytes.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut v = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        self.write_to(&mut v)?;
+        Ok(v)
+    }
+
+    /// Read one frame.
+    ///
+    /// * `inp` Reader to input the frame.
+    ///
+    /// Returns `None` at end of stream, or an error if the frame is invalid
+    /// (including a payload length over the limit) or truncated.  The payload
+    /// buffer grows as data is read, so a bogus length cannot allocate more
+    /// than was actually received.
+    pub fn read_from(inp: &mut dyn Read) -> Result<Option<Self>> {
+        let mut header = [0; HEADER_LEN];
+        match inp.read_exact(&mut header[..1]) {
+            Ok(()) => (),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        inp.read_exact(&mut header[1..])?;
+        let kind = FrameKind::try_from(header[0])?;
+        let be = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let z = u32::from(header[1]);
+        let tid = TileId::new(be(&header[2..6]), be(&header[6..10]), z)?;
+        let len = be(&header[10..14]) as usize;
+        if len > MAX_PAYLOAD_LEN {
+            return Err(Error::InvalidFrame());
+        }
+        let mut payload = Vec::new();
+        inp.take(len as u64).read_to_end(&mut payload)?;
+        if payload.len() < len {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        Ok(Some(Frame { kind, tid, payload }))
+    }
+
+    /// Format the frame as a server-sent event.
+    ///
+    /// The event type is `tile` or `delta`, the event ID is the tile ID
+    /// (`z/x/y`) and the data is the base64-encoded payload.
+    pub fn to_sse(&self) -> String {
+        let event = match self.kind {
+            FrameKind::Tile => "tile",
+            FrameKind::Delta => "delta",
+        };
+        format!(
+            "event: {}\nid: {}\ndata: {}\n\n",
+            event,
+            self.tid,
+            base64(&self.payload)
+        )
+    }
+}
+
+/// Tile source for a stream of tile [Frame]s.
+///
+/// This reads the simple interchange format written by
+/// [write_tileset_stream], for piping tiles between processes.  Delta frames
+/// are not allowed.
+///
+/// [Frame]: struct.Frame.html
+/// [write_tileset_stream]: fn.write_tileset_stream.html
+#[derive(Debug)]
+pub struct StreamSource<R: Read> {
+    inp: R,
+}
+
+impl<R: Read> StreamSource<R> {
+    /// Create a new stream tile source.
+    ///
+    /// * `inp` Reader of tile frames (a buffered reader is recommended).
+    pub fn new(inp: R) -> Self {
+        StreamSource { inp }
+    }
+}
+
+impl StreamSource<BufReader<Stdin>> {
+    /// Create a stream tile source reading standard input.
+    ///
+    /// This is for tools in a Unix pipeline, such as
+    /// `generator | filter | writer`.
+    pub fn stdin() -> Self {
+        StreamSource::new(BufReader::new(stdin()))
+    }
+}
+
+impl<R: Read> TileSource for StreamSource<R> {
+    fn next_tile(&mut self) -> Result<Option<(TileId, Vec<u8>)>> {
+        match Frame::read_from(&mut self.inp)? {
+            Some(Frame {
+                kind: FrameKind::Tile,
+                tid,
+                payload,
+            }) => Ok(Some((tid, payload))),
+            Some(_) => Err(Error::InvalidFrame()),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Tile sink for a stream of tile [Frame]s.
+///
+/// This writes the same format as [write_tileset_stream], one tile at a
+/// time, for reading with a [StreamSource].
+///
+/// # Example
+/// ```no_run
+/// # use mvt::{Error, StreamSink, StreamSource, TileSink, TileSource};
+/// # fn main() -> Result<(), Error> {
+/// // pass through tiles at zoom 10 or below
+/// let mut source = StreamSource::stdin();
+/// let mut sink = StreamSink::stdout();
+/// while let Some((tid, data)) = source.next_tile()? {
+///     if tid.z() <= 10 {
+///         sink.put_tile(tid, &data)?;
+///     }
+/// }
+/// sink.finish()?;
+/// # Ok(()) }
+/// ```
+///
+/// [Frame]: struct.Frame.html
+/// [StreamSource]: struct.StreamSource.html
+/// [write_tileset_stream]: fn.write_tileset_stream.html
+#[derive(Debug)]
+pub struct StreamSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> StreamSink<W> {
+    /// Create a new stream tile sink.
+    ///
+    /// * `out` Writer of tile frames (a buffered writer is recommended).
+    pub fn new(out: W) -> Self {
+        StreamSink { out }
+    }
+
+    /// Flush the stream, returning the writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl StreamSink<BufWriter<Stdout>> {
+    /// Create a stream tile sink writing standard output.
+    ///
+    /// Call [finish] after the last tile, to check for write errors.
+    ///
+    /// [finish]: struct.StreamSink.html#method.finish
+    pub fn stdout() -> Self {
+        StreamSink::new(BufWriter::new(stdout()))
+    }
+}
+
+impl<W: Write> TileSink for StreamSink<W> {
+    fn put_tile(&mut self, tid: TileId, data: &[u8]) -> Result<()> {
+        write_frame(&mut self.out, FrameKind::Tile, tid, data)
+    }
+}
+
+/// Write many tiles into one stream of tile [Frame]s.
+///
+/// * `out` Writer to output the stream.
+/// * `tiles` Tile IDs and tiles to write.
+///
+/// Each tile is framed with its ID and length; use [StreamSource] to read
+/// them back.  Returns the number of tiles written.
+///
+/// # Example
+/// ```
+/// # use mvt::{write_tileset_stream, Error, StreamSource};
+/// # use mvt::{Tile, TileId, TileSource};
+/// # fn main() -> Result<(), Error> {
+/// let tiles = (0..4).map(|x| (TileId::new(x, 0, 2).unwrap(), Tile::new(4096)));
+/// let mut data = vec![];
+/// assert_eq!(write_tileset_stream(&mut data, tiles)?, 4);
+/// let mut source = StreamSource::new(&data[..]);
+/// while let Some((tid, data)) = source.next_tile()? {
+///     let tile = Tile::from_bytes(&data)?;
+///     println!("{tid}: {} layers", tile.num_layers());
+/// }
+/// # Ok(()) }
+/// ```
+///
+/// [Frame]: struct.Frame.html
+/// [StreamSource]: struct.StreamSource.html
+pub fn write_tileset_stream<I>(out: &mut dyn Write, tiles: I) -> Result<usize>
+where
+    I: IntoIterator<Item = (TileId, Tile)>,
+{
+    let mut count = 0;
+    for (tid, tile) in tiles {
+        Frame::tile(tid, &tile)?.write_to(out)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Encode bytes as standard base64 (with padding)
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n =
+            (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[(n >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tid = TileId::new(246, 368, 10).unwrap();
+        let f0 = Frame::new(FrameKind::Tile, tid, vec![1, 2, 3]).unwrap();
+        let f1 = Frame::delta(tid, &TileDelta::default()).unwrap();
+        let mut data = f0.to_bytes().unwrap();
+        f1.write_to(&mut data).unwrap();
+        assert_eq!(data.len(), HEADER_LEN * 2 + 3);
+        let mut inp = &data[..];
+        let f = Frame::read_from(&mut inp).unwrap().unwrap();
+        assert_eq!(f.kind(), FrameKind::Tile);
+        assert_eq!(f.tid().to_string(), "10/246/368");
+        assert_eq!(f.payload(), &[1, 2, 3]);
+        let f = Frame::read_from(&mut inp).unwrap().unwrap();
+        assert_eq!(f.kind(), FrameKind::Delta);
+        assert!(f.to_delta().unwrap().is_empty());
+        assert!(Frame::read_from(&mut inp).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let data = [3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(Frame::read_from(&mut &data[..]).is_err());
+        let data = [1, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(Frame::read_from(&mut &data[..]).is_err());
+        let data = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        assert!(matches!(
+            Frame::read_from(&mut &data[..]),
+            Err(Error::Io(_))
+        ));
+        let data = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0];
+        assert!(matches!(
+            Frame::read_from(&mut &data[..]),
+            Err(Error::InvalidFrame())
+        ));
+        let tid = TileId::new(0, 0, 0).unwrap();
+        let payload = vec![0; MAX_PAYLOAD_LEN + 1];
+        assert!(Frame::new(FrameKind::Tile, tid, payload).is_err());
+    }
+
+    #[test]
+    fn test_tileset_stream() {
+        let mut tile = Tile::new(4096);
+        tile.add_layer(tile.create_layer("water")).unwrap();
+        let t0 = TileId::new(0, 0, 0).unwrap();
+        let t1 = TileId::new(1, 1, 1).unwrap();
+        let tiles = vec![(t0, Tile::new(4096)), (t1, tile)];
+        let mut data = vec![];
+        assert_eq!(write_tileset_stream(&mut data, tiles).unwrap(), 2);
+        let mut source = StreamSource::new(&data[..]);
+        let (tid, _) = source.next_tile().unwrap().unwrap();
+        assert_eq!(tid, t0);
+        let (tid, tile) = source.next_tile().unwrap().unwrap();
+        assert_eq!(tid, t1);
+        assert_eq!(Tile::from_bytes(&tile).unwrap().num_layers(), 1);
+        assert!(source.next_tile().unwrap().is_none());
+        let data = Frame::delta(t0, &TileDelta::default())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        assert!(StreamSource::new(&data[..]).next_tile().is_err());
+        // truncated payload
+        let mut data = vec![];
+        write_tileset_stream(&mut data, [(t1, Tile::new(4096))]).unwrap();
+        Frame::tile(t0, &Tile::new(4096))
+            .unwrap()
+            .write_to(&mut data)
+            .unwrap();
+        data.push(1);
+        let mut source = StreamSource::new(&data[..]);
+        assert!(source.next_tile().unwrap().is_some());
+        assert!(source.next_tile().unwrap().is_some());
+        assert!(source.next_tile().is_err());
+    }
+
+    #[test]
+    fn test_stream_sink() {
+        let t0 = TileId::new(0, 0, 0).unwrap();
+        let t1 = TileId::new(3, 2, 2).unwrap();
+        let mut sink = StreamSink::new(vec![]);
+        sink.put_tile(t0, b"abc").unwrap();
+        sink.put_tile(t1, b"").unwrap();
+        let data = sink.finish().unwrap();
+        let mut expected = Frame::new(FrameKind::Tile, t0, b"abc".to_vec())
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        Frame::new(FrameKind::Tile, t1, vec![])
+            .unwrap()
+            .write_to(&mut expected)
+            .unwrap();
+        assert_eq!(data, expected);
+        let mut source = StreamSource::new(&data[..]);
+        assert_eq!(source.next_tile().unwrap(), Some((t0, b"abc".to_vec())));
+        assert_eq!(source.next_tile().unwrap(), Some((t1, vec![])));
+        assert_eq!(source.next_tile().unwrap(), None);
+    }
+
+    #[test]
+    fn test_sse() {
+        let tid = TileId::new(0, 0, 0).unwrap();
+        let f = Frame::new(FrameKind::Tile, tid, b"foo".to_vec()).unwrap();
+        assert_eq!(f.to_sse(), "event: tile\nid: 0/0/0\ndata: Zm9v\n\n");
+    }
+}
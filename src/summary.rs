@@ -0,0 +1,164 @@
+// summary.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Header-only tile summaries.
+//!
+use crate::error::{Error, Result};
+use crate::wire::{Field, Fields, WireValue};
+use std::fmt;
+
+/// Summary of one layer in an encoded tile.
+///
+/// See [Tile::scan].
+///
+/// [Tile::scan]: struct.Tile.html#method.scan
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LayerSummary {
+    name: String,
+    version: u32,
+    extent: u32,
+    num_features: usize,
+}
+
+/// Summary of an encoded tile: layer names, versions, extents and feature
+/// counts.
+///
+/// See [Tile::scan].
+///
+/// [Tile::scan]: struct.Tile.html#method.scan
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TileSummary {
+    layers: Vec<LayerSummary>,
+}
+
+/// Get a `uint32` field value
+fn uint32(value: WireValue) -> Result<u32> {
+    match value {
+        WireValue::Varint(v) => {
+            u32::try_from(v).map_err(|_| Error::InvalidLayer())
+        }
+        _ => Err(Error::InvalidLayer()),
+    }
+}
+
+impl LayerSummary {
+    /// Scan a layer message body, skipping features and tables
+    fn scan(body: &[u8]) -> Result<Self> {
+        let mut name = None;
+        let mut version = 1;
+        let mut extent = 4096;
+        let mut num_features = 0;
+        for field in Fields::new(body) {
+            let Field { number, value, .. } = field?;
+            match (number, value) {
+                (1, WireValue::Bytes(n)) => {
+                    let n = std::str::from_utf8(n)
+                        .map_err(|_| Error::InvalidLayer())?;
+                    name = Some(n.to_string());
+                }
+                (2, WireValue::Bytes(_)) => num_features += 1,
+                (5, value) => extent = uint32(value)?,
+                (15, value) => version = uint32(value)?,
+                (1 | 2, _) => return Err(Error::InvalidLayer()),
+                _ => (),
+            }
+        }
+        let name = name.ok_or(Error::InvalidLayer())?;
+        Ok(LayerSummary {
+            name,
+            version,
+            extent,
+            num_features,
+        })
+    }
+
+    /// Get the layer name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the layer spec version.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Get the layer extent.
+    pub fn extent(&self) -> u32 {
+        self.extent
+    }
+
+    /// Get the number of features (including [metadata] features).
+    ///
+    /// [metadata]: struct.Layer.html#method.with_metadata
+    pub fn num_features(&self) -> usize {
+        self.num_features
+    }
+}
+
+impl fmt::Display for LayerSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: v{}, extent {}, {} features",
+            self.name, self.version, self.extent, self.num_features
+        )
+    }
+}
+
+impl TileSummary {
+    /// Scan an encoded tile
+    pub(crate) fn scan(data: &[u8]) -> Result<Self> {
+        let mut layers = vec![];
+        for field in Fields::new(data) {
+            match field? {
+                Field {
+                    number: 3,
+                    value: WireValue::Bytes(body),
+                    ..
+                } => layers.push(LayerSummary::scan(body)?),
+                Field { number: 3, .. } => return Err(Error::InvalidLayer()),
+                _ => (),
+            }
+        }
+        Ok(TileSummary { layers })
+    }
+
+    /// Get the layer summaries, in tile order.
+    pub fn layers(&self) -> &[LayerSummary] {
+        &self.layers
+    }
+
+    /// Get the total number of features in all layers.
+    pub fn num_features(&self) -> usize {
+        self.layers.iter().map(|l| l.num_features).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        // layer { version: 2, name: "a", features: [{}, {}] }
+        let a = [
+            0x1a, 0x09, 0x78, 2, 0x0a, 0x01, b'a', 0x12, 0x00, 0x12, 0x00,
+        ];
+        // layer { name: "b", extent: 256 }
+        let b = [0x1a, 0x06, 0x0a, 0x01, b'b', 0x28, 0x80, 0x02];
+        let summary = TileSummary::scan(&[&a[..], &b[..]].concat()).unwrap();
+        assert_eq!(summary.layers().len(), 2);
+        assert_eq!(summary.num_features(), 2);
+        assert_eq!(
+            summary.layers()[0].to_string(),
+            "a: v2, extent 4096, 2 features"
+        );
+        let b = &summary.layers()[1];
+        assert_eq!((b.name(), b.version(), b.extent()), ("b", 1, 256));
+        assert_eq!(TileSummary::scan(&[]).unwrap(), TileSummary::default());
+        // layer without a name
+        assert!(TileSummary::scan(&[0x1a, 0x02, 0x78, 2]).is_err());
+        assert!(TileSummary::scan(&a[..a.len() - 1]).is_err());
+    }
+}
@@ -4,14 +4,39 @@
 //
 //! Tile, Layer and Feature structs.
 //!
-use crate::encoder::{GeomData, GeomType};
+use crate::cache::LAYER_KEY;
+use crate::chunk::TileChunks;
+use crate::dedup::FloatDedup;
+use crate::encoder::{count_vertices, GeomData, GeomType};
 use crate::error::{Error, Result};
+use crate::interpolate::interpolate_layer;
+use crate::legacy::{parse_layer, parse_tile, parse_tile_bytes};
+use crate::lenient::{decode_checked, DecodeReport};
+use crate::limits::DecodeLimits;
+use crate::mapgrid::TileId;
+use crate::metadata::{metadata_feature, read_metadata, METADATA_PREFIX};
+use crate::options::{EncodeOptions, EncodeReport};
+use crate::size::{GeomStats, LayerSize};
+use crate::sort::{sort_layer, TableSort};
+use crate::summary::TileSummary;
 use crate::vector_tile::tile::{
     Feature as VtFeature, GeomType as VtGeomType, Layer as VtLayer, Value,
 };
 use crate::vector_tile::Tile as VecTile;
-use protobuf::{CodedOutputStream, EnumOrUnknown, Message};
-use std::io::Write;
+use crate::view::LayerView;
+use crate::wire::{
+    find_layer, keep_unknown, layer_header, scan_layer, Field, Fields,
+    WireValue,
+};
+#[cfg(feature = "gzip")]
+use flate2::{
+    read::{MultiGzDecoder, ZlibDecoder},
+    write::GzEncoder,
+    Compression,
+};
+use protobuf::{CodedInputStream, CodedOutputStream, EnumOrUnknown, Message};
+use std::collections::HashMap;
+use std::io::{Read, Write};
 
 /// A tile represents a rectangular region of a map.
 ///
@@ -44,6 +69,23 @@ use std::io::Write;
 pub struct Tile {
     vec_tile: VecTile,
     extent: u32,
+    options: EncodeOptions,
+    report: Vec<EncodeReport>,
+    encoded: Vec<EncodedLayer>,
+    float_dedup: HashMap<String, FloatDedup>,
+}
+
+/// Pre-encoded layer, spliced into an encoded tile
+struct EncodedLayer {
+    name: String,
+    data: Vec<u8>,
+}
+
+/// Position and checked prefix of a layer taken by `get_or_create_layer`
+#[derive(Clone, Copy, Debug)]
+struct TakenLayer {
+    index: usize,
+    mark: LayerMark,
 }
 
 /// A layer is a set of related features in a tile.
@@ -60,6 +102,17 @@ pub struct Tile {
 /// ```
 pub struct Layer {
     layer: VtLayer,
+    float_dedup: FloatDedup,
+    skipped_points: usize,
+    taken: Option<TakenLayer>,
+}
+
+/// Lengths of layer features, keys and values, for undoing additions
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct LayerMark {
+    features: usize,
+    keys: usize,
+    values: usize,
 }
 
 /// A Feature contains map geometry with related metadata.
@@ -81,22 +134,31 @@ pub struct Layer {
 ///     .point(1.0, 2.0)?
 ///     .point(7.0, 6.0)?
 ///     .encode()?;
-/// let feature = layer.into_feature(geom_data);
-/// // ...
-/// // add any tags or ID to the feature
-/// // ...
+/// let mut feature = layer.into_feature(geom_data);
+/// feature
+///     .set_id(1)
+///     .add_tag_string("name", "First")
+///     .add_tag_uint("rank", 3);
 /// let layer = feature.into_layer();
 /// # Ok(())
 /// # }
 /// ```
 ///
+/// A feature must be completed with [Feature.into_layer] or explicitly
+/// abandoned with [Feature.layer].  Dropping it otherwise loses both the
+/// feature and its layer; this is logged, and is a debug assertion failure.
+///
 /// [Layer.into_feature]: struct.Layer.html#method.into_feature
 /// [Feature.into_layer]: struct.Feature.html#method.into_layer
+/// [Feature.layer]: struct.Feature.html#method.layer
+#[must_use = "call `into_layer` to add the feature to its layer"]
 pub struct Feature {
     feature: VtFeature,
     layer: Layer,
     num_keys: usize,
     num_values: usize,
+    skipped_points: usize,
+    done: bool,
 }
 
 impl Tile {
@@ -105,7 +167,326 @@ impl Tile {
     /// * `extent` Height / width of tile bounds.
     pub fn new(extent: u32) -> Self {
         let vec_tile = VecTile::new();
-        Tile { vec_tile, extent }
+        Tile {
+            vec_tile,
+            extent,
+            options: EncodeOptions::default(),
+            report: vec![],
+            encoded: vec![],
+            float_dedup: HashMap::new(),
+        }
+    }
+
+    /// Set validation options, applied when layers are added.
+    pub fn with_options(mut self, options: EncodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Get validation options.
+    pub fn options(&self) -> EncodeOptions {
+        self.options
+    }
+
+    /// Get reports of fixes made in lenient mode.
+    ///
+    /// Only layers with fixes are included, with one report per layer.
+    pub fn encode_report(&self) -> &[EncodeReport] {
+        &self.report
+    }
+
+    /// Decode a tile from bytes.
+    ///
+    /// * `data` Encoded tile (can be a slice of a memory-mapped archive).
+    ///
+    /// The extent is taken from the first layer (4096 if there are no
+    /// layers).  Layer names, keys and string values are copied out of
+    /// `data`, so it can be unmapped afterwards; a [TileView] borrows them
+    /// instead.
+    ///
+    /// Version 1 layers are supported; a layer with no version field is
+    /// decoded as version 1 (see [LayerView::version]).
+    ///
+    /// Returns an error if the data is invalid, or [Error::WrongExtent] if
+    /// the layers do not all have the same extent.  Such tiles can still be
+    /// read with a [TileView], which has an extent for each layer.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(512);
+    /// tile.add_layer(tile.create_layer("First Layer"))?;
+    /// let data = tile.to_bytes()?;
+    /// let tile = Tile::from_bytes(&data)?;
+    /// assert_eq!(tile.extent(), 512);
+    /// assert_eq!(tile.num_layers(), 1);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [Error::WrongExtent]: enum.Error.html#variant.WrongExtent
+    /// [LayerView::version]: struct.LayerView.html#method.version
+    /// [TileView]: struct.TileView.html
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_vec_tile(parse_tile_bytes(data)?)
+    }
+
+    /// Read and decode a tile.
+    ///
+    /// * `input` Reader of an encoded tile, such as a file or decompressor.
+    ///
+    /// The tile is parsed as it is read, without first buffering all
+    /// encoded bytes.  Returns an error if reading fails, or any error from
+    /// [from_bytes].
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// let data = tile.to_bytes()?;
+    /// let tile = Tile::read_from(&mut &data[..])?;
+    /// assert_eq!(tile.num_layers(), 1);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [from_bytes]: struct.Tile.html#method.from_bytes
+    pub fn read_from(input: &mut dyn Read) -> Result<Self> {
+        let mut is = CodedInputStream::new(input);
+        Self::from_vec_tile(parse_tile(&mut is)?)
+    }
+
+    /// Decode a tile from bytes, which may be gzip or zlib compressed.
+    ///
+    /// * `data` Encoded tile, possibly compressed.
+    ///
+    /// The compression is detected from the leading (magic) bytes; data
+    /// which is neither gzip nor zlib is decoded as with [from_bytes].
+    /// Multi-member gzip streams (such as from [LayerCache::to_bytes_gzip])
+    /// are supported.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// let data = tile.to_bytes_gzip()?;
+    /// let tile = Tile::from_compressed_bytes(&data)?;
+    /// assert_eq!(tile.num_layers(), 1);
+    /// # use std::io::Write;
+    /// # let mut enc = flate2::write::ZlibEncoder::new(
+    /// #     vec![],
+    /// #     flate2::Compression::default(),
+    /// # );
+    /// # enc.write_all(&tile.to_bytes()?)?;
+    /// # let zlib = enc.finish()?;
+    /// # assert_eq!(Tile::from_compressed_bytes(&zlib)?.num_layers(), 1);
+    /// # let plain = tile.to_bytes()?;
+    /// # assert_eq!(Tile::from_compressed_bytes(&plain)?.num_layers(), 1);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [from_bytes]: struct.Tile.html#method.from_bytes
+    /// [LayerCache::to_bytes_gzip]: struct.LayerCache.html#method.to_bytes_gzip
+    #[cfg(feature = "gzip")]
+    pub fn from_compressed_bytes(data: &[u8]) -> Result<Self> {
+        match data {
+            [0x1f, 0x8b, ..] => Self::read_from(&mut MultiGzDecoder::new(data)),
+            [cmf, flg, ..]
+                if cmf & 0x0f == 8
+                    && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 =>
+            {
+                Self::read_from(&mut ZlibDecoder::new(data))
+            }
+            _ => Self::from_bytes(data),
+        }
+    }
+
+    /// Decode a tile from bytes, checking all features.
+    ///
+    /// * `data` Encoded tile.
+    ///
+    /// Unlike [from_bytes], feature tags must refer to keys and values in
+    /// their layer, and geometry command integers must be well formed.
+    ///
+    /// [from_bytes]: struct.Tile.html#method.from_bytes
+    pub fn from_bytes_strict(data: &[u8]) -> Result<Self> {
+        let (vec_tile, _, _) = decode_checked(data, false)?;
+        Self::from_vec_tile(vec_tile)
+    }
+
+    /// Decode a tile from bytes, skipping invalid layers and features.
+    ///
+    /// * `data` Encoded tile.
+    ///
+    /// Data which would be an error in [from_bytes_strict] is dropped
+    /// instead: malformed layers, layers whose extent does not match the
+    /// first layer, and features with invalid tags or geometry.  Dropped
+    /// data is logged and counted in the returned [DecodeReport].
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// let mut data = tile.to_bytes()?;
+    /// data.extend([0x1a, 0x05, 0x0a]); // truncated layer
+    /// assert!(Tile::from_bytes_strict(&data).is_err());
+    /// let (tile, report) = Tile::from_bytes_lenient(&data)?;
+    /// assert_eq!(tile.num_layers(), 1);
+    /// assert_eq!(report.dropped_layers(), 1);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [DecodeReport]: struct.DecodeReport.html
+    /// [from_bytes_strict]: struct.Tile.html#method.from_bytes_strict
+    pub fn from_bytes_lenient(data: &[u8]) -> Result<(Self, DecodeReport)> {
+        let (vec_tile, extent, report) = decode_checked(data, true)?;
+        let tile = Tile {
+            vec_tile,
+            ..Tile::new(extent)
+        };
+        Ok((tile, report))
+    }
+
+    /// Decode a tile from untrusted bytes, checking limits first.
+    ///
+    /// * `data` Encoded tile.
+    /// * `limits` Decode limits.
+    ///
+    /// Returns [Error::LimitExceeded] if a limit is exceeded, or any error
+    /// from [from_bytes].
+    ///
+    /// [Error::LimitExceeded]: enum.Error.html#variant.LimitExceeded
+    /// [from_bytes]: struct.Tile.html#method.from_bytes
+    pub fn from_bytes_with_limits(
+        data: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<Self> {
+        limits.check_tile(data)?;
+        let mut is = CodedInputStream::from_bytes(data);
+        if let Some(max_nesting) = limits.max_nesting() {
+            is.set_recursion_limit(max_nesting);
+        }
+        Self::from_vec_tile(parse_tile(&mut is)?)
+    }
+
+    /// Decode selected layers of a tile from bytes.
+    ///
+    /// * `data` Encoded tile.
+    /// * `filter` Function to select layers by name.
+    ///
+    /// Unselected layers are skipped without being parsed (only their names
+    /// are read).
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// tile.add_layer(tile.create_layer("roads"))?;
+    /// let data = tile.to_bytes()?;
+    /// let tile = Tile::from_bytes_filtered(&data, |name| name == "roads")?;
+    /// assert_eq!(tile.num_layers(), 1);
+    /// # Ok(()) }
+    /// ```
+    pub fn from_bytes_filtered<P>(data: &[u8], filter: P) -> Result<Self>
+    where
+        P: Fn(&str) -> bool,
+    {
+        let mut vec_tile = VecTile::new();
+        for field in Fields::new(data) {
+            match field? {
+                Field {
+                    number: 3,
+                    value: WireValue::Bytes(body),
+                    ..
+                } => {
+                    if filter(&layer_header(body)?.0) {
+                        vec_tile.layers.push(parse_layer(body)?);
+                    }
+                }
+                Field { number: 3, .. } => (),
+                field => keep_unknown(
+                    vec_tile.special_fields.mut_unknown_fields(),
+                    &field,
+                ),
+            }
+        }
+        Self::from_vec_tile(vec_tile)
+    }
+
+    /// Create a tile from a decoded tile
+    fn from_vec_tile(vec_tile: VecTile) -> Result<Self> {
+        let extent = vec_tile.layers.first().map_or(4096, |l| l.extent());
+        if vec_tile.layers.iter().any(|l| l.extent() != extent) {
+            return Err(Error::WrongExtent());
+        }
+        Ok(Tile {
+            vec_tile,
+            ..Tile::new(extent)
+        })
+    }
+
+    /// Get the framed bytes of one layer from an encoded tile.
+    ///
+    /// * `data` Encoded tile.
+    /// * `name` Layer name.
+    ///
+    /// Only layer headers are scanned; features are not decoded.  The result
+    /// can be spliced into another tile with [add_encoded_layer].
+    ///
+    /// Returns `None` if no layer has the given name, or an error if the
+    /// data is malformed.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// tile.add_layer(tile.create_layer("roads"))?;
+    /// let data = tile.to_bytes()?;
+    /// let roads = Tile::layer_bytes(&data, "roads")?.unwrap();
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_encoded_layer(roads)?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [add_encoded_layer]: struct.Tile.html#method.add_encoded_layer
+    pub fn layer_bytes<'a>(
+        data: &'a [u8],
+        name: &str,
+    ) -> Result<Option<&'a [u8]>> {
+        find_layer(data, name)
+    }
+
+    /// Scan an encoded tile, summarizing its layers.
+    ///
+    /// Only layer names, versions and extents are read, and features are
+    /// counted; geometry, tags and value tables are not decoded.
+    ///
+    /// Returns an error if the data is malformed.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// let summary = Tile::scan(&tile.to_bytes()?)?;
+    /// let water = &summary.layers()[0];
+    /// assert_eq!(water.name(), "water");
+    /// assert_eq!(water.version(), 2);
+    /// assert_eq!(water.num_features(), 0);
+    /// # Ok(()) }
+    /// ```
+    pub fn scan(data: &[u8]) -> Result<TileSummary> {
+        TileSummary::scan(data)
     }
 
     /// Get extent, or height / width of tile bounds.
@@ -113,9 +494,124 @@ impl Tile {
         self.extent
     }
 
-    /// Get the number of layers.
+    /// Get the number of layers, including [encoded layers].
+    ///
+    /// [encoded layers]: struct.Tile.html#method.add_encoded_layer
     pub fn num_layers(&self) -> usize {
-        self.vec_tile.layers.len()
+        self.vec_tile.layers.len() + self.encoded.len()
+    }
+
+    /// Get the total number of features in all layers, including [encoded
+    /// layers].
+    ///
+    /// Nothing is serialized; encoded layers are only scanned.  This includes
+    /// any metadata features.  Returns an error if an encoded layer is
+    /// malformed.
+    ///
+    /// [encoded layers]: struct.Tile.html#method.add_encoded_layer
+    pub fn total_features(&self) -> Result<usize> {
+        let mut total =
+            self.vec_tile.layers.iter().map(|l| l.features.len()).sum();
+        for layer in &self.encoded {
+            total += LayerView::from_framed(&layer.data)?.num_features();
+        }
+        Ok(total)
+    }
+
+    /// Get the total number of vertices in all layers, including [encoded
+    /// layers].
+    ///
+    /// Only command integers are scanned; no coördinates are decoded and
+    /// nothing is serialized.  This can be used to reject oversized tiles
+    /// before writing them.
+    ///
+    /// Returns an error if any geometry or encoded layer is malformed.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType, Tile};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// let geom = GeomEncoder::new(GeomType::Linestring, Transform::default())
+    ///     .point(0.0, 0.0)?
+    ///     .point(10.0, 0.0)?
+    ///     .point(10.0, 10.0)?
+    ///     .encode()?;
+    /// let layer = tile.create_layer("roads").into_feature(geom).into_layer();
+    /// tile.add_layer(layer)?;
+    /// assert_eq!(tile.total_features()?, 1);
+    /// assert_eq!(tile.total_vertices()?, 3);
+    /// let mut other = Tile::new(4096);
+    /// other.add_encoded_layer(&tile.layer_to_bytes("roads")?.unwrap())?;
+    /// assert_eq!(other.total_features()?, 1);
+    /// assert_eq!(other.total_vertices()?, 3);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [encoded layers]: struct.Tile.html#method.add_encoded_layer
+    pub fn total_vertices(&self) -> Result<usize> {
+        let mut total = 0;
+        for layer in &self.vec_tile.layers {
+            for feature in &layer.features {
+                total += count_vertices(&feature.geometry)?;
+            }
+        }
+        for layer in &self.encoded {
+            for feature in LayerView::from_framed(&layer.data)?.features() {
+                total += feature?.vertex_count()?;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Get read-only views of all layers, including [encoded layers].
+    ///
+    /// Returns an error if a layer contains an invalid value, or an encoded
+    /// layer is malformed.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// let tile = Tile::from_bytes(&tile.to_bytes()?)?;
+    /// for layer in tile.layers()? {
+    ///     assert_eq!(layer.name(), "water");
+    ///     assert_eq!(layer.extent(), 4096);
+    ///     assert_eq!(layer.features().count(), 0);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [encoded layers]: struct.Tile.html#method.add_encoded_layer
+    pub fn layers(&self) -> Result<Vec<LayerView<'_>>> {
+        let decoded = self.vec_tile.layers.iter().map(LayerView::from_vt);
+        let encoded = self
+            .encoded
+            .iter()
+            .map(|layer| LayerView::from_framed(&layer.data));
+        decoded.chain(encoded).collect()
+    }
+
+    /// Check if the tile has a layer with the given name
+    pub(crate) fn has_layer(&self, name: &str) -> bool {
+        self.vec_tile.layers.iter().any(|l| l.name() == name)
+            || self.encoded.iter().any(|l| l.name == name)
+    }
+
+    /// Get metadata of a layer (see [Layer::with_metadata]).
+    ///
+    /// Returns `None` if no layer has the given name.
+    ///
+    /// [Layer::with_metadata]: struct.Layer.html#method.with_metadata
+    pub fn layer_metadata(&self, name: &str) -> Option<Vec<(String, String)>> {
+        self.vec_tile
+            .layers
+            .iter()
+            .find(|layer| layer.name() == name)
+            .map(read_metadata)
     }
 
     /// Create a new layer.
@@ -125,28 +621,210 @@ impl Tile {
         Layer::new(name, self.extent)
     }
 
+    /// Create a new layer, checking that its name is not already used.
+    ///
+    /// * `name` Layer name.
+    ///
+    /// Returns [Error::DuplicateName] if the tile already has a layer with
+    /// the same name (which would fail in [add_layer]).
+    ///
+    /// [add_layer]: struct.Tile.html#method.add_layer
+    /// [Error::DuplicateName]: enum.Error.html#variant.DuplicateName
+    pub fn create_layer_checked(&self, name: &str) -> Result<Layer> {
+        if self.has_layer(name) {
+            return Err(Error::DuplicateName());
+        }
+        Ok(self.create_layer(name))
+    }
+
+    /// Take an existing layer for appending, or create a new one.
+    ///
+    /// * `name` Layer name.
+    ///
+    /// An existing layer is removed from the tile (see [remove_layer]); add
+    /// it back with [add_layer] after appending features.  It is then
+    /// restored at its original position, and only the appended features
+    /// and values are checked.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType, Tile};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// for x in [1.0, 2.0] {
+    ///     let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+    ///         .point(x, 2.0)?
+    ///         .encode()?;
+    ///     let layer = tile.get_or_create_layer("places")?;
+    ///     tile.add_layer(layer.into_feature(geom).into_layer())?;
+    /// }
+    /// assert_eq!(tile.num_layers(), 1);
+    /// assert!(tile.create_layer_checked("places").is_err());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [add_layer]: struct.Tile.html#method.add_layer
+    /// [remove_layer]: struct.Tile.html#method.remove_layer
+    pub fn get_or_create_layer(&mut self, name: &str) -> Result<Layer> {
+        let index = self.vec_tile.layers.iter().position(|l| l.name() == name);
+        match self.remove_layer(name)? {
+            Some(mut layer) => {
+                layer.taken = index.map(|index| TakenLayer {
+                    index,
+                    mark: layer.mark(),
+                });
+                Ok(layer)
+            }
+            None => Ok(self.create_layer(name)),
+        }
+    }
+
     /// Add a layer.
     ///
     /// * `layer` The layer.
     ///
+    /// Layers are checked using the tile's [EncodeOptions].
+    ///
     /// Returns an error if:
     /// * a layer with the same name already exists
     /// * the layer extent does not match the tile extent
-    pub fn add_layer(&mut self, layer: Layer) -> Result<()> {
+    /// * a strict check fails
+    ///
+    /// [EncodeOptions]: struct.EncodeOptions.html
+    pub fn add_layer(&mut self, mut layer: Layer) -> Result<()> {
         if layer.layer.extent != Some(self.extent) {
             return Err(Error::WrongExtent());
         }
-        if self
-            .vec_tile
-            .layers
-            .iter()
-            .any(|n| n.name == layer.layer.name)
-        {
-            Err(Error::DuplicateName())
+        let name = layer.layer.name().to_string();
+        if self.has_layer(&name) {
+            return Err(Error::DuplicateName());
+        }
+        let (index, first) = match layer.taken.take() {
+            Some(t) => (t.index, (t.mark.features, t.mark.values)),
+            None => (self.vec_tile.layers.len(), (0, 0)),
+        };
+        let mut report = self.options.check_layer(&mut layer.layer, first)?;
+        report.add_skipped_points(layer.skipped_points);
+        if !report.is_empty() {
+            match self.report.iter_mut().find(|r| r.name() == name) {
+                Some(r) => r.merge(&report),
+                None => self.report.push(report),
+            }
+        }
+        if layer.float_dedup == FloatDedup::default() {
+            self.float_dedup.remove(&name);
         } else {
-            self.vec_tile.layers.push(layer.layer);
-            Ok(())
+            self.float_dedup.insert(name, layer.float_dedup);
+        }
+        let index = index.min(self.vec_tile.layers.len());
+        self.vec_tile.layers.insert(index, layer.layer);
+        Ok(())
+    }
+
+    /// Remove a layer by name, returning it.
+    ///
+    /// * `name` Layer name.
+    ///
+    /// The layer keeps its key / value tables (and float deduplication), so
+    /// features can be added to it before adding it back to this (or
+    /// another) tile.  An [encoded
+    /// layer] is decoded.  Returns `None` if no layer has the given name.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType, Tile};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// # let mut tile = Tile::new(4096);
+    /// # tile.add_layer(tile.create_layer("roads"))?;
+    /// # tile.add_layer(tile.create_layer("water"))?;
+    /// # let data = tile.to_bytes()?;
+    /// let mut tile = Tile::from_bytes(&data)?;
+    /// tile.remove_layer("water")?;
+    /// let roads = tile.remove_layer("roads")?.unwrap();
+    /// let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+    ///     .point(1.0, 2.0)?
+    ///     .encode()?;
+    /// let mut feature = roads.into_feature(geom);
+    /// feature.add_tag_string("name", "Main Street");
+    /// tile.add_layer(feature.into_layer())?;
+    /// tile.add_layer(tile.create_layer("places"))?;
+    /// let data = tile.to_bytes()?;
+    /// # assert_eq!(Tile::from_bytes(&data)?.num_layers(), 2);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [encoded layer]: struct.Tile.html#method.add_encoded_layer
+    pub fn remove_layer(&mut self, name: &str) -> Result<Option<Layer>> {
+        let layers = &mut self.vec_tile.layers;
+        if let Some(i) = layers.iter().position(|l| l.name() == name) {
+            let layer = layers.remove(i);
+            return Ok(Some(self.layer_from_vt(layer)));
+        }
+        let Some(i) = self.encoded.iter().position(|l| l.name == name) else {
+            return Ok(None);
+        };
+        let body = match Fields::new(&self.encoded[i].data).next() {
+            Some(Ok(Field {
+                value: WireValue::Bytes(body),
+                ..
+            })) => body,
+            _ => return Err(Error::InvalidLayer()),
+        };
+        let layer = parse_layer(body)?;
+        self.encoded.remove(i);
+        Ok(Some(self.layer_from_vt(layer)))
+    }
+
+    /// Make a removed layer, with the float dedup it was added with
+    fn layer_from_vt(&mut self, layer: VtLayer) -> Layer {
+        let dedup = self.float_dedup.remove(layer.name()).unwrap_or_default();
+        Layer::from_vt(layer).with_float_dedup(dedup)
+    }
+
+    /// Add a pre-encoded layer, without decoding it.
+    ///
+    /// * `data` Framed `Tile.layers` submessage, such as from a
+    ///   [CachedLayer].
+    ///
+    /// Only the framing and layer header are checked; features are not
+    /// decoded or validated.  Encoded layers are written after all other
+    /// layers, and are not included by methods which inspect layers (such as
+    /// [size_breakdown]).
+    ///
+    /// Returns an error if:
+    /// * the data is not a framed layer
+    /// * a layer with the same name already exists
+    /// * the layer extent does not match the tile extent
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, LayerCache, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut cache = LayerCache::default();
+    /// let mut tile = Tile::new(4096);
+    /// let digest = cache.insert(tile.create_layer("boundaries"))?;
+    /// tile.add_encoded_layer(cache.get(&digest).unwrap().as_bytes())?;
+    /// assert_eq!(tile.num_layers(), 1);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [CachedLayer]: struct.CachedLayer.html
+    /// [size_breakdown]: struct.Tile.html#method.size_breakdown
+    pub fn add_encoded_layer(&mut self, data: &[u8]) -> Result<()> {
+        let (name, extent) = scan_layer(data)?;
+        if extent != self.extent {
+            return Err(Error::WrongExtent());
+        }
+        if self.has_layer(&name) {
+            return Err(Error::DuplicateName());
         }
+        self.encoded.push(EncodedLayer {
+            name,
+            data: data.to_vec(),
+        });
+        Ok(())
     }
 
     /// Write the tile.
@@ -156,9 +834,90 @@ impl Tile {
         let mut os = CodedOutputStream::new(&mut out);
         let _ = self.vec_tile.write_to(&mut os);
         os.flush()?;
+        drop(os);
+        for layer in &self.encoded {
+            out.write_all(&layer.data)?;
+        }
         Ok(())
     }
 
+    /// Remove unknown fields, such as vendor extensions.
+    ///
+    /// Unknown fields of the tile, its layers, features and values are
+    /// kept when decoding, and written back when encoding.  Removing them
+    /// does not affect [encoded layers], which are written as added.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// let mut data = tile.to_bytes()?;
+    /// data.extend([0x80, 0x01, 0x07]); // extension field 16: 7
+    /// let mut tile = Tile::from_bytes(&data)?;
+    /// assert_eq!(tile.to_bytes()?, data);
+    /// tile.strip_unknown_fields();
+    /// assert_eq!(tile.to_bytes()?.len(), data.len() - 3);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [encoded layers]: struct.Tile.html#method.add_encoded_layer
+    pub fn strip_unknown_fields(&mut self) {
+        self.vec_tile.special_fields.mut_unknown_fields().clear();
+        for layer in &mut self.vec_tile.layers {
+            layer.special_fields.mut_unknown_fields().clear();
+            for feature in &mut layer.features {
+                feature.special_fields.mut_unknown_fields().clear();
+            }
+            for value in &mut layer.values {
+                value.special_fields.mut_unknown_fields().clear();
+            }
+        }
+    }
+
+    /// Encode one layer as a framed `Tile.layers` submessage.
+    ///
+    /// * `name` Layer name.
+    ///
+    /// Since encoded tiles are a sequence of framed layers, the result can
+    /// be transplanted into another tile with [add_encoded_layer] (or
+    /// appended to its encoded bytes) without decoding or re-encoding.
+    /// [Encoded layers] are returned as added.
+    ///
+    /// Returns `None` if no layer has the given name.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// tile.add_layer(tile.create_layer("roads"))?;
+    /// let roads = tile.layer_to_bytes("roads")?.unwrap();
+    /// let mut other = Tile::new(4096);
+    /// other.add_encoded_layer(&roads)?;
+    /// assert_eq!(other.layers()?[0].name(), "roads");
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [add_encoded_layer]: struct.Tile.html#method.add_encoded_layer
+    /// [Encoded layers]: struct.Tile.html#method.add_encoded_layer
+    pub fn layer_to_bytes(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        if let Some(layer) =
+            self.vec_tile.layers.iter().find(|l| l.name() == name)
+        {
+            let mut data = vec![LAYER_KEY];
+            data.extend(layer.write_length_delimited_to_bytes()?);
+            return Ok(Some(data));
+        }
+        Ok(self
+            .encoded
+            .iter()
+            .find(|l| l.name == name)
+            .map(|l| l.data.clone()))
+    }
+
     /// Encode the tile and return the bytes.
     pub fn to_bytes(&self) -> Result<Vec<u8>> {
         let mut v = Vec::with_capacity(self.compute_size());
@@ -166,16 +925,161 @@ impl Tile {
         Ok(v)
     }
 
+    /// Encode the tile in chunks.
+    ///
+    /// * `max_len` Maximum chunk length (bytes); a chunk can exceed it by
+    ///   the size of one feature, key, value or [encoded layer].
+    ///
+    /// Encoding happens as the [TileChunks] iterator advances, so the
+    /// caller can yield between chunks.  This is useful for huge tiles,
+    /// which could otherwise block an async runtime worker.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// let mut data = vec![];
+    /// for chunk in tile.chunks(64 * 1024) {
+    ///     data.extend(chunk?);
+    ///     // yield to other tasks here
+    /// }
+    /// assert_eq!(data, tile.to_bytes()?);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [encoded layer]: struct.Tile.html#method.add_encoded_layer
+    /// [TileChunks]: struct.TileChunks.html
+    pub fn chunks(&self, max_len: usize) -> TileChunks<'_> {
+        let encoded = self.encoded.iter().map(|l| &l.data[..]).collect();
+        TileChunks::new(&self.vec_tile, encoded, max_len)
+    }
+
+    /// Encode the tile and return gzip-compressed bytes.
+    #[cfg(feature = "gzip")]
+    pub fn to_bytes_gzip(&self) -> Result<Vec<u8>> {
+        let v = Vec::with_capacity(self.compute_size());
+        let mut enc = GzEncoder::new(v, Compression::default());
+        self.write_to(&mut enc)?;
+        Ok(enc.finish()?)
+    }
+
+    /// Encode the tile and return zstd-compressed bytes.
+    ///
+    /// * `level` Compression level (1-22, or 0 for default).
+    #[cfg(feature = "zstd")]
+    pub fn to_bytes_zstd(&self, level: i32) -> Result<Vec<u8>> {
+        let v = Vec::with_capacity(self.compute_size());
+        let mut enc = zstd::stream::write::Encoder::new(v, level)?;
+        self.write_to(&mut enc)?;
+        Ok(enc.finish()?)
+    }
+
+    /// Encode the tile and return brotli-compressed bytes.
+    ///
+    /// * `quality` Compression quality (0-11).
+    #[cfg(feature = "brotli")]
+    pub fn to_bytes_brotli(&self, quality: u32) -> Result<Vec<u8>> {
+        let v = Vec::with_capacity(self.compute_size());
+        let mut enc = brotli::CompressorWriter::new(v, 4096, quality, 22);
+        self.write_to(&mut enc)?;
+        Ok(enc.into_inner())
+    }
+
     /// Compute the encoded size in bytes.
     pub fn compute_size(&self) -> usize {
         self.vec_tile.compute_size() as usize
+            + self.encoded.iter().map(|l| l.data.len()).sum::<usize>()
+    }
+
+    /// Sort key and value tables of all layers, rewriting tag indices.
+    ///
+    /// Entries are ordered by number of uses, so that common entries get
+    /// small indices, and similar entries are grouped together, which improves
+    /// compression.  Unused entries are dropped.
+    ///
+    /// Returns the measured sizes before and after sorting.
+    pub fn sort_tables(&mut self) -> Result<TableSort> {
+        #[cfg(feature = "gzip")]
+        let gzip_before = self.to_bytes_gzip()?.len();
+        let before = self.compute_size();
+        for layer in self.vec_tile.layers.iter_mut() {
+            sort_layer(layer);
+        }
+        let report = TableSort::new(before, self.compute_size());
+        #[cfg(feature = "gzip")]
+        let report = report.with_gzip(gzip_before, self.to_bytes_gzip()?.len());
+        Ok(report)
+    }
+
+    /// Get a breakdown of the encoded size of each layer.
+    ///
+    /// This is computed without encoding the tile.
+    pub fn size_breakdown(&self) -> Vec<LayerSize> {
+        self.vec_tile.layers.iter().map(LayerSize::new).collect()
+    }
+
+    /// Get geometry compression statistics of each layer.
+    ///
+    /// Returns an error if any geometry data is malformed.
+    pub fn geom_stats(&self) -> Result<Vec<GeomStats>> {
+        self.vec_tile.layers.iter().map(GeomStats::new).collect()
+    }
+
+    /// Get the encoded layers.
+    pub(crate) fn vt_layers(&self) -> &[VtLayer] {
+        &self.vec_tile.layers
+    }
+
+    /// Get mutable access to the encoded layers
+    pub(crate) fn vt_layers_mut(&mut self) -> &mut Vec<VtLayer> {
+        &mut self.vec_tile.layers
+    }
+
+    /// Interpolate point features between two versions of a tile.
+    ///
+    /// * `other` Later version of the tile.
+    /// * `t` Interpolation factor, from 0 (this tile) to 1 (other tile).
+    ///
+    /// Point features with matching IDs in layers of the same name are moved
+    /// along a straight line; duplicate IDs are matched in order.  All other
+    /// features and layers are taken from whichever version is nearer to
+    /// `t`, in the order of that version.
+    ///
+    /// Returns an error if the tile extents do not match.
+    pub fn interpolate(&self, other: &Tile, t: f64) -> Result<Tile> {
+        if self.extent != other.extent {
+            return Err(Error::WrongExtent());
+        }
+        let t = t.clamp(0.0, 1.0);
+        let (near, far) = if t < 0.5 {
+            (&self.vec_tile.layers, &other.vec_tile.layers)
+        } else {
+            (&other.vec_tile.layers, &self.vec_tile.layers)
+        };
+        let mut tile = Tile::new(self.extent);
+        for layer in near {
+            let layer = match far.iter().find(|l| l.name == layer.name) {
+                Some(f) if t < 0.5 => interpolate_layer(layer, f, t)?,
+                Some(f) => interpolate_layer(f, layer, t)?,
+                None => Layer::from_vt(layer.clone()),
+            };
+            tile.add_layer(layer)?;
+        }
+        Ok(tile)
     }
 }
 
 impl Default for Layer {
     fn default() -> Self {
         let layer = VtLayer::new();
-        Layer { layer }
+        Layer {
+            layer,
+            float_dedup: FloatDedup::default(),
+            skipped_points: 0,
+            taken: None,
+        }
     }
 }
 
@@ -184,12 +1088,23 @@ impl Layer {
     ///
     /// * `name` Layer name.
     /// * `extent` Width / height of tile bounds.
-    fn new(name: &str, extent: u32) -> Self {
+    pub(crate) fn new(name: &str, extent: u32) -> Self {
         let mut layer = VtLayer::new();
         layer.set_version(2);
         layer.set_name(name.to_string());
         layer.set_extent(extent);
-        Layer { layer }
+        Layer {
+            layer,
+            float_dedup: FloatDedup::default(),
+            skipped_points: 0,
+            taken: None,
+        }
+    }
+
+    /// Set comparison of float / double values for deduplication.
+    pub fn with_float_dedup(mut self, float_dedup: FloatDedup) -> Self {
+        self.float_dedup = float_dedup;
+        self
     }
 
     /// Get the layer name.
@@ -198,13 +1113,105 @@ impl Layer {
     }
 
     /// Get number of features (count).
+    ///
+    /// This includes the metadata feature, if any.
     pub fn num_features(&self) -> usize {
         self.layer.features.len()
     }
 
+    /// Get the total number of vertices of all features.
+    ///
+    /// Only command integers are scanned; no coördinates are decoded.  This
+    /// can be checked against a renderer's vertex budget.
+    ///
+    /// Returns an error if any feature's geometry is malformed.
+    pub fn total_vertices(&self) -> Result<usize> {
+        self.layer
+            .features
+            .iter()
+            .map(|f| count_vertices(&f.geometry))
+            .sum()
+    }
+
+    /// Set a layer metadata value (attribution, source version, etc).
+    ///
+    /// * `key` Metadata key.
+    /// * `value` Metadata value.
+    ///
+    /// Metadata is stored in a hidden feature, using a convention described
+    /// in [METADATA_PREFIX].  Setting an existing key replaces its value.
+    ///
+    /// # Example
+    /// ```
+    /// use mvt::Tile;
+    ///
+    /// let layer = Tile::new(4096)
+    ///     .create_layer("roads")
+    ///     .with_metadata("attribution", "OpenStreetMap contributors")
+    ///     .with_metadata("source", "v2");
+    /// assert_eq!(layer.metadata()[1], ("source".into(), "v2".into()));
+    /// ```
+    ///
+    /// [METADATA_PREFIX]: constant.METADATA_PREFIX.html
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        let kidx = self.key_pos(&format!("{METADATA_PREFIX}{key}")) as u32;
+        let mut val = Value::new();
+        val.set_string_value(value.to_string());
+        let vidx = self.val_pos(val) as u32;
+        let idx = metadata_feature(&self.layer).unwrap_or_else(|| {
+            let mut feature = VtFeature::new();
+            feature.type_ = Some(EnumOrUnknown::new(VtGeomType::UNKNOWN));
+            self.layer.features.insert(0, feature);
+            // checked features of a taken layer move along with it
+            if let Some(taken) = &mut self.taken {
+                taken.mark.features += 1;
+            }
+            0
+        });
+        let tags = &mut self.layer.features[idx].tags;
+        match tags.chunks_exact_mut(2).find(|tag| tag[0] == kidx) {
+            Some(tag) => tag[1] = vidx,
+            None => tags.extend([kidx, vidx]),
+        }
+        self
+    }
+
+    /// Get layer metadata key / value pairs.
+    pub fn metadata(&self) -> Vec<(String, String)> {
+        read_metadata(&self.layer)
+    }
+
+    /// Copy the metadata of an encoded layer into the layer.
+    pub(crate) fn copy_metadata(mut self, src_layer: &VtLayer) -> Self {
+        for (key, value) in read_metadata(src_layer) {
+            self = self.with_metadata(&key, &value);
+        }
+        self
+    }
+
+    /// Create a new feature with validated geometry, giving it ownership of
+    /// the layer.
+    ///
+    /// * `geom_data` Geometry data (consumed by this method).
+    ///
+    /// Returns [Error::InvalidGeometry] if the geometry does not meet the
+    /// specification (see [GeomData::validate]).  The layer is consumed even
+    /// on error; validate first to keep it.
+    ///
+    /// [Error::InvalidGeometry]: enum.Error.html#variant.InvalidGeometry
+    /// [GeomData::validate]: struct.GeomData.html#method.validate
+    pub fn try_into_feature(self, geom_data: GeomData) -> Result<Feature> {
+        geom_data.validate()?;
+        Ok(self.into_feature(geom_data))
+    }
+
     /// Create a new feature, giving it ownership of the layer.
     ///
     /// * `geom_data` Geometry data (consumed by this method).
+    ///
+    /// The feature must be completed with [Feature.into_layer].
+    ///
+    /// [Feature.into_layer]: struct.Feature.html#method.into_layer
     pub fn into_feature(self, geom_data: GeomData) -> Feature {
         let num_keys = self.layer.keys.len();
         let num_values = self.layer.values.len();
@@ -214,13 +1221,97 @@ impl Layer {
             GeomType::Linestring => VtGeomType::LINESTRING,
             GeomType::Polygon => VtGeomType::POLYGON,
         }));
+        let skipped_points = geom_data.skipped_points();
         feature.geometry = geom_data.into_vec();
         Feature {
             feature,
             layer: self,
             num_keys,
             num_values,
+            skipped_points,
+            done: false,
+        }
+    }
+
+    /// Copy an encoded feature (with its tags) into the layer.
+    ///
+    /// * `src_layer` Layer containing the feature.
+    /// * `src` Feature to copy.
+    /// * `geometry` Geometry data for the copy.
+    ///
+    /// Features with unknown geometry type are skipped; use
+    /// `copy_metadata` for the metadata feature.
+    pub(crate) fn copy_feature(
+        self,
+        src_layer: &VtLayer,
+        src: &VtFeature,
+        geometry: Vec<u32>,
+    ) -> Self {
+        let geom_tp = match src.type_() {
+            VtGeomType::POINT => GeomType::Point,
+            VtGeomType::LINESTRING => GeomType::Linestring,
+            VtGeomType::POLYGON => GeomType::Polygon,
+            VtGeomType::UNKNOWN => return self,
+        };
+        let mut feature = self.into_feature(GeomData::new(geom_tp, geometry));
+        if let Some(id) = src.id {
+            feature.set_id(id);
         }
+        for tag in src.tags.chunks_exact(2) {
+            let key = src_layer.keys.get(tag[0] as usize);
+            let value = src_layer.values.get(tag[1] as usize);
+            if let (Some(key), Some(value)) = (key, value) {
+                feature.add_tag(key, value.clone());
+            }
+        }
+        feature.into_layer()
+    }
+
+    /// Get a mark of the current features, keys and values.
+    pub(crate) fn mark(&self) -> LayerMark {
+        LayerMark {
+            features: self.layer.features.len(),
+            keys: self.layer.keys.len(),
+            values: self.layer.values.len(),
+        }
+    }
+
+    /// Remove features, keys and values added after a mark.
+    ///
+    /// Returns the number of features removed.  Keys and values added after
+    /// the mark are only used by features added after it.
+    pub(crate) fn truncate(&mut self, mark: LayerMark) -> usize {
+        let n_features = self.layer.features.len();
+        if let Some(taken) = &mut self.taken {
+            // The checked prefix can only shrink
+            taken.mark.features = taken.mark.features.min(mark.features);
+            taken.mark.keys = taken.mark.keys.min(mark.keys);
+            taken.mark.values = taken.mark.values.min(mark.values);
+        }
+        self.layer.features.truncate(mark.features);
+        self.layer.keys.truncate(mark.keys);
+        self.layer.values.truncate(mark.values);
+        n_features.saturating_sub(mark.features)
+    }
+
+    /// Push an encoded feature without checking it.
+    pub(crate) fn push_feature(&mut self, feature: VtFeature) {
+        self.layer.features.push(feature);
+    }
+
+    /// Create a layer from an encoded layer.
+    pub(crate) fn from_vt(layer: VtLayer) -> Self {
+        Layer {
+            layer,
+            float_dedup: FloatDedup::default(),
+            skipped_points: 0,
+            taken: None,
+        }
+    }
+
+    /// Get the encoded layer, consuming the layer.
+    pub(crate) fn into_vt(self) -> VtLayer {
+        self.layer
     }
 
     /// Get position of a key in the layer keys.  If the key is not found, it
@@ -239,10 +1330,12 @@ impl Layer {
     /// Get position of a value in the layer values.  If the value is not found,
     /// it is added as the last value.
     fn val_pos(&mut self, value: Value) -> usize {
+        let dedup = self.float_dedup;
+        let value = dedup.canonical(value);
         self.layer
             .values
             .iter()
-            .position(|v| *v == value)
+            .position(|v| dedup.matches(v, &value))
             .unwrap_or_else(|| {
                 self.layer.values.push(value);
                 self.layer.values.len() - 1
@@ -253,25 +1346,61 @@ impl Layer {
 impl Feature {
     /// Complete the feature, returning ownership of the layer.
     pub fn into_layer(mut self) -> Layer {
-        self.layer.layer.features.push(self.feature);
-        self.layer
+        let feature = std::mem::take(&mut self.feature);
+        let mut layer = self.take_layer();
+        layer.layer.features.push(feature);
+        layer.skipped_points += self.skipped_points;
+        layer
     }
 
     /// Get the layer, abandoning the feature.
     pub fn layer(mut self) -> Layer {
+        let mut layer = self.take_layer();
         // Reset key/value lengths
-        self.layer.layer.keys.truncate(self.num_keys);
-        self.layer.layer.values.truncate(self.num_values);
-        self.layer
+        layer.layer.keys.truncate(self.num_keys);
+        layer.layer.values.truncate(self.num_values);
+        layer
+    }
+
+    /// Take the layer, marking the feature done
+    fn take_layer(&mut self) -> Layer {
+        self.done = true;
+        std::mem::take(&mut self.layer)
     }
 
     /// Set the feature ID.
-    pub fn set_id(&mut self, id: u64) {
+    pub fn set_id(&mut self, id: u64) -> &mut Self {
         let layer = &self.layer.layer;
         if layer.features.iter().any(|f| f.id == Some(id)) {
             warn!("Duplicate feature ID ({}) in layer {:?}", id, &layer.name);
         }
         self.feature.set_id(id);
+        self
+    }
+
+    /// Get the number of vertices in the geometry.
+    ///
+    /// Only command integers are scanned; no coördinates are decoded.
+    /// Returns an error if the geometry is malformed.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType, Tile};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let geom = GeomEncoder::new(GeomType::Linestring, Transform::default())
+    ///     .point(0.0, 0.0)?
+    ///     .point(10.0, 0.0)?
+    ///     .point(10.0, 10.0)?
+    ///     .encode()?;
+    /// let feature = Tile::new(4096).create_layer("roads").into_feature(geom);
+    /// assert_eq!(feature.vertex_count()?, 3);
+    /// let layer = feature.into_layer();
+    /// assert_eq!(layer.total_vertices()?, 3);
+    /// # Ok(()) }
+    /// ```
+    pub fn vertex_count(&self) -> Result<usize> {
+        count_vertices(&self.feature.geometry)
     }
 
     /// Get number of tags (count).
@@ -280,59 +1409,284 @@ impl Feature {
     }
 
     /// Add a tag of string type.
-    pub fn add_tag_string(&mut self, key: &str, val: &str) {
+    pub fn add_tag_string(&mut self, key: &str, val: &str) -> &mut Self {
         let mut value = Value::new();
         value.set_string_value(val.to_string());
         self.add_tag(key, value);
+        self
     }
 
     /// Add a tag of double type.
-    pub fn add_tag_double(&mut self, key: &str, val: f64) {
+    pub fn add_tag_double(&mut self, key: &str, val: f64) -> &mut Self {
         let mut value = Value::new();
         value.set_double_value(val);
         self.add_tag(key, value);
+        self
     }
 
     /// Add a tag of float type.
-    pub fn add_tag_float(&mut self, key: &str, val: f32) {
+    pub fn add_tag_float(&mut self, key: &str, val: f32) -> &mut Self {
         let mut value = Value::new();
         value.set_float_value(val);
         self.add_tag(key, value);
+        self
     }
 
     /// Add a tag of int type.
-    pub fn add_tag_int(&mut self, key: &str, val: i64) {
+    pub fn add_tag_int(&mut self, key: &str, val: i64) -> &mut Self {
         let mut value = Value::new();
         value.set_int_value(val);
         self.add_tag(key, value);
+        self
     }
 
     /// Add a tag of uint type.
-    pub fn add_tag_uint(&mut self, key: &str, val: u64) {
+    pub fn add_tag_uint(&mut self, key: &str, val: u64) -> &mut Self {
         let mut value = Value::new();
         value.set_uint_value(val);
         self.add_tag(key, value);
+        self
     }
 
     /// Add a tag of sint type.
-    pub fn add_tag_sint(&mut self, key: &str, val: i64) {
+    pub fn add_tag_sint(&mut self, key: &str, val: i64) -> &mut Self {
         let mut value = Value::new();
         value.set_sint_value(val);
         self.add_tag(key, value);
+        self
     }
 
     /// Add a tag of bool type.
-    pub fn add_tag_bool(&mut self, key: &str, val: bool) {
+    pub fn add_tag_bool(&mut self, key: &str, val: bool) -> &mut Self {
         let mut value = Value::new();
         value.set_bool_value(val);
         self.add_tag(key, value);
+        self
+    }
+
+    /// Add a tag of double type, converting a ground width to tile units.
+    ///
+    /// * `key` Tag key.
+    /// * `meters` Width on the ground (meters), such as of a road.
+    /// * `tid` Tile ID (for latitude).
+    ///
+    /// The width is scaled for Web Mercator distortion at the tile's
+    /// latitude (see [TileId::meters_to_tile_units]), so features can be
+    /// rendered at their true width.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType, Tile, TileId};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let tile = Tile::new(4096);
+    /// let geom = GeomEncoder::new(GeomType::Linestring, Transform::default())
+    ///     .point(0.0, 2048.0)?
+    ///     .point(4096.0, 2048.0)?
+    ///     .encode()?;
+    /// let mut feature = tile.create_layer("roads").into_feature(geom);
+    /// feature.add_tag_width_m("width", 12.5, TileId::new(61, 93, 8)?);
+    /// let layer = feature.into_layer();
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [TileId::meters_to_tile_units]: struct.TileId.html#method.meters_to_tile_units
+    pub fn add_tag_width_m(
+        &mut self,
+        key: &str,
+        meters: f64,
+        tid: TileId,
+    ) -> &mut Self {
+        let extent = self.layer.layer.extent();
+        self.add_tag_double(key, tid.meters_to_tile_units(meters, extent))
     }
 
     /// Add a tag.
-    fn add_tag(&mut self, key: &str, value: Value) {
+    pub(crate) fn add_tag(&mut self, key: &str, value: Value) {
         let kidx = self.layer.key_pos(key);
         self.feature.tags.push(kidx as u32);
         let vidx = self.layer.val_pos(value);
         self.feature.tags.push(vidx as u32);
     }
 }
+
+impl Drop for Feature {
+    fn drop(&mut self) {
+        if !self.done && !std::thread::panicking() {
+            warn!(
+                "Feature dropped without into_layer; layer {:?} lost",
+                self.layer.name()
+            );
+            debug_assert!(self.done, "Feature dropped without into_layer");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomEncoder;
+    use crate::view::TileView;
+    use pointy::Transform;
+
+    fn tile() -> Tile {
+        let mut tile = Tile::new(4096);
+        let mut layer = tile.create_layer("points");
+        for i in 0..100u32 {
+            let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(f64::from(i), f64::from(i * 2))
+                .unwrap()
+                .encode()
+                .unwrap();
+            let mut feature = layer.into_feature(geom);
+            feature.set_id(u64::from(i));
+            let kind = if i % 2 == 0 { "even" } else { "odd" };
+            feature.add_tag_string("kind", kind);
+            layer = feature.into_layer();
+        }
+        tile.add_layer(layer).unwrap();
+        tile
+    }
+
+    /// Encode a framed layer with no features
+    fn layer_bytes(name: &str) -> Vec<u8> {
+        let mut tile = Tile::new(256);
+        tile.add_layer(tile.create_layer(name)).unwrap();
+        tile.to_bytes().unwrap()
+    }
+
+    #[test]
+    fn test_add_encoded_layer() {
+        let (a, b) = (layer_bytes("a"), layer_bytes("b"));
+        let mut full = Tile::new(256);
+        for name in ["c", "a", "b"] {
+            full.add_layer(full.create_layer(name)).unwrap();
+        }
+        let data = full.to_bytes().unwrap();
+        let mut tile = Tile::new(256);
+        tile.add_layer(tile.create_layer("c")).unwrap();
+        tile.add_encoded_layer(&a).unwrap();
+        tile.add_encoded_layer(&b).unwrap();
+        assert_eq!(tile.num_layers(), 3);
+        assert_eq!(tile.compute_size(), data.len());
+        assert_eq!(tile.to_bytes().unwrap(), data);
+        assert!(matches!(
+            tile.add_encoded_layer(&a),
+            Err(Error::DuplicateName())
+        ));
+        assert!(tile.add_layer(tile.create_layer("b")).is_err());
+        assert!(tile.add_encoded_layer(&a[1..]).is_err());
+        assert!(matches!(
+            Tile::new(512).add_encoded_layer(&a),
+            Err(Error::WrongExtent())
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let data = tile().to_bytes().unwrap();
+        let decoded = Tile::from_bytes(&data).unwrap();
+        assert_eq!(decoded.extent(), 4096);
+        assert_eq!(decoded.to_bytes().unwrap(), data);
+        assert_eq!(Tile::from_bytes(&[]).unwrap().extent(), 4096);
+        assert!(Tile::from_bytes(&[0xFF]).is_err());
+        // layers with different extents can only be viewed
+        let mut mixed = data.clone();
+        mixed.extend(layer_bytes("small"));
+        assert!(matches!(
+            Tile::from_bytes(&mixed),
+            Err(Error::WrongExtent())
+        ));
+        let view = TileView::new(&mixed).unwrap();
+        assert_eq!(view.layer("small").unwrap().extent(), 256);
+    }
+
+    #[test]
+    fn test_remove_layer() {
+        let mut tile = Tile::new(256);
+        let layer = tile
+            .create_layer("a")
+            .with_float_dedup(FloatDedup::Canonical);
+        tile.add_layer(layer).unwrap();
+        tile.add_encoded_layer(&layer_bytes("b")).unwrap();
+        let layer = tile.remove_layer("b").unwrap().unwrap();
+        assert_eq!(layer.name(), Some("b"));
+        assert_eq!(layer.float_dedup, FloatDedup::Exact);
+        assert_eq!(tile.num_layers(), 1);
+        assert!(tile.remove_layer("b").unwrap().is_none());
+        let layer = tile.remove_layer("a").unwrap().unwrap();
+        assert_eq!(layer.float_dedup, FloatDedup::Canonical);
+        assert_eq!(tile.num_layers(), 0);
+    }
+
+    #[test]
+    fn test_read_modify_write() {
+        let point = |x| {
+            GeomEncoder::new(GeomType::Point, Transform::default())
+                .point(x, 1.0)
+                .unwrap()
+                .encode()
+                .unwrap()
+        };
+        let mut tile = Tile::new(4096);
+        let mut feature = tile.create_layer("roads").into_feature(point(1.0));
+        feature.add_tag_string("kind", "road");
+        feature.add_tag_uint("lanes", 2);
+        tile.add_layer(feature.into_layer()).unwrap();
+        tile.add_layer(tile.create_layer("water")).unwrap();
+        let data = tile.to_bytes().unwrap();
+        // decode, drop a layer, and append to another
+        let mut tile = Tile::from_bytes(&data).unwrap();
+        assert!(tile.remove_layer("water").unwrap().is_some());
+        let roads = tile.remove_layer("roads").unwrap().unwrap();
+        assert_eq!(roads.num_features(), 1);
+        let mut feature = roads.into_feature(point(2.0));
+        feature.add_tag_string("kind", "road");
+        feature.add_tag_uint("lanes", 4);
+        tile.add_layer(feature.into_layer()).unwrap();
+        tile.add_layer(tile.create_layer("places")).unwrap();
+        let data = tile.to_bytes().unwrap();
+        // re-encoded
+        let tile = Tile::from_bytes(&data).unwrap();
+        let layers = tile.layers().unwrap();
+        let names: Vec<_> = layers.iter().map(|l| l.name()).collect();
+        assert_eq!(names, ["roads", "places"]);
+        assert_eq!(layers[0].num_features(), 2);
+        // key / value tables were reused
+        assert_eq!(layers[0].keys().len(), 2);
+        assert_eq!(layers[0].values().len(), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_to_bytes_zstd() {
+        let tile = tile();
+        let data = tile.to_bytes().unwrap();
+        for level in [0, 1, 3, 19] {
+            let zstd = tile.to_bytes_zstd(level).unwrap();
+            assert_eq!(zstd::decode_all(&zstd[..]).unwrap(), data);
+        }
+        // level 0 is the default level (3)
+        assert_eq!(
+            tile.to_bytes_zstd(0).unwrap(),
+            tile.to_bytes_zstd(3).unwrap()
+        );
+        assert!(tile.to_bytes_zstd(19).unwrap().len() < data.len());
+    }
+
+    #[test]
+    #[cfg(feature = "brotli")]
+    fn test_to_bytes_brotli() {
+        let tile = tile();
+        let data = tile.to_bytes().unwrap();
+        for quality in [0, 5, 11] {
+            let br = tile.to_bytes_brotli(quality).unwrap();
+            let mut out = vec![];
+            brotli::Decompressor::new(&br[..], 4096)
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, data);
+        }
+        assert!(tile.to_bytes_brotli(11).unwrap().len() < data.len());
+    }
+}
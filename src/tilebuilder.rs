@@ -0,0 +1,579 @@
+// tilebuilder.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Tile builder, with several layers in progress.
+//!
+use crate::encoder::GeomData;
+use crate::error::Result;
+use crate::tile::{Feature, Layer, LayerMark, Tile};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Builder for a tile with several layers under construction.
+///
+/// Layers are addressed by name, so features can be added to any of them in
+/// source order.  They are added to the tile (in order of first use) by
+/// [finish].  If the tile already has a layer with the same name, features
+/// are appended to it, keeping its position.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{GeomEncoder, GeomType, Tile, TileBuilder};
+/// use pointy::Transform;
+///
+/// let mut builder = TileBuilder::new(Tile::new(4096));
+/// for (kind, x) in [("road", 1.0), ("place", 2.0), ("road", 3.0)] {
+///     let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+///         .point(x, 0.0)?
+///         .encode()?;
+///     builder.add_feature(kind, geom, |feature| {
+///         feature.add_tag_double("x", x);
+///     })?;
+/// }
+/// let tile = builder.finish()?;
+/// assert_eq!(tile.num_layers(), 2);
+/// # Ok(()) }
+/// ```
+///
+/// [finish]: struct.TileBuilder.html#method.finish
+pub struct TileBuilder {
+    /// Tile to add layers to
+    tile: Tile,
+
+    /// Layers in progress, with marks of their content from the tile
+    layers: Vec<(Layer, LayerMark)>,
+
+    /// Layer priorities (default 0)
+    priorities: HashMap<String, i32>,
+
+    /// Time budget
+    budget: Option<Duration>,
+
+    /// Deadline for dropping the next priority level
+    deadline: Option<Instant>,
+
+    /// Layers with this priority or lower are dropped
+    threshold: Option<i32>,
+
+    /// All further features are skipped
+    exhausted: bool,
+
+    /// Degradation report
+    report: BudgetReport,
+}
+
+/// Features and layers dropped by a [TileBuilder] over its time budget.
+///
+/// See [TileBuilder::with_budget].
+///
+/// [TileBuilder]: struct.TileBuilder.html
+/// [TileBuilder::with_budget]: struct.TileBuilder.html#method.with_budget
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BudgetReport {
+    dropped_layers: Vec<String>,
+    dropped_features: usize,
+    skipped_features: usize,
+}
+
+impl BudgetReport {
+    /// Get the names of dropped layers, in order of dropping.
+    pub fn dropped_layers(&self) -> &[String] {
+        &self.dropped_layers
+    }
+
+    /// Get the number of features removed with dropped layers.
+    ///
+    /// Only features added by the builder are removed; features which were
+    /// already in a layer of the tile are kept.
+    pub fn dropped_features(&self) -> usize {
+        self.dropped_features
+    }
+
+    /// Get the number of features skipped after layers were dropped.
+    pub fn skipped_features(&self) -> usize {
+        self.skipped_features
+    }
+
+    /// Check if nothing was dropped.
+    pub fn is_empty(&self) -> bool {
+        self.dropped_layers.is_empty()
+            && self.dropped_features == 0
+            && self.skipped_features == 0
+    }
+}
+
+impl fmt::Display for BudgetReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "dropped {} layers, {} features; skipped {} features",
+            self.dropped_layers.len(),
+            self.dropped_features,
+            self.skipped_features
+        )
+    }
+}
+
+/// Guard which returns a layer to its slot if a feature is abandoned
+/// (e.g. a tag function panics)
+struct FeatureGuard<'a> {
+    slot: &'a mut Layer,
+    feature: Option<Feature>,
+}
+
+impl Drop for FeatureGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(feature) = self.feature.take() {
+            *self.slot = feature.layer();
+        }
+    }
+}
+
+impl<'a> FeatureGuard<'a> {
+    /// Take the layer from a slot, creating a feature
+    fn new(slot: &'a mut Layer, geom_data: GeomData) -> Self {
+        let feature = Some(std::mem::take(slot).into_feature(geom_data));
+        FeatureGuard { slot, feature }
+    }
+
+    /// Complete the feature, returning the layer to its slot
+    fn complete<T>(mut self, tags: T)
+    where
+        T: FnOnce(&mut Feature),
+    {
+        // keep the feature in the guard while tags are set
+        if let Some(feature) = &mut self.feature {
+            tags(feature);
+        }
+        if let Some(feature) = self.feature.take() {
+            *self.slot = feature.into_layer();
+        }
+    }
+}
+
+impl TileBuilder {
+    /// Create a new tile builder.
+    ///
+    /// * `tile` Tile to add layers to.
+    pub fn new(tile: Tile) -> Self {
+        TileBuilder {
+            tile,
+            layers: vec![],
+            priorities: HashMap::new(),
+            budget: None,
+            deadline: None,
+            threshold: None,
+            exhausted: false,
+            report: BudgetReport::default(),
+        }
+    }
+
+    /// Set the priority of a layer (default 0).
+    ///
+    /// * `name` Layer name.
+    /// * `priority` Lower priority layers are dropped first when over the
+    ///   time budget.
+    pub fn with_priority(mut self, name: &str, priority: i32) -> Self {
+        self.priorities.insert(name.to_string(), priority);
+        self
+    }
+
+    /// Set a soft time budget, starting now.
+    ///
+    /// When adding a feature after the budget has run out, all layers in
+    /// progress with the lowest priority are dropped, and later features
+    /// for them are skipped.  If still running after another quarter of the
+    /// budget, the next priority level is dropped, and so on.  Layers with
+    /// the highest priority are never dropped; once they are the only ones
+    /// left (after dropping at least one level), all further features are
+    /// skipped.  So [finish] returns a partial, valid tile instead of
+    /// missing a deadline.
+    ///
+    /// Dropping a layer only removes features added by the builder; any
+    /// content it already had in the tile is kept.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::Error;
+    /// # fn main() -> Result<(), Error> {
+    /// use mvt::{GeomEncoder, GeomType, Tile, TileBuilder};
+    /// use pointy::Transform;
+    /// use std::time::Duration;
+    ///
+    /// let mut builder = TileBuilder::new(Tile::new(4096))
+    ///     .with_priority("roads", 1)
+    ///     .with_budget(Duration::from_millis(50));
+    /// for kind in ["roads", "buildings"] {
+    ///     let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+    ///         .point(0.0, 0.0)?
+    ///         .encode()?;
+    ///     builder.add_feature(kind, geom, |_| ())?;
+    /// }
+    /// if !builder.report().is_empty() {
+    ///     println!("over budget: {}", builder.report());
+    /// }
+    /// let tile = builder.finish()?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [finish]: struct.TileBuilder.html#method.finish
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self.deadline = Some(Instant::now() + budget);
+        self
+    }
+
+    /// Get the report of features and layers dropped over budget.
+    pub fn report(&self) -> &BudgetReport {
+        &self.report
+    }
+
+    /// Get the priority of a layer
+    fn priority(&self, name: &str) -> i32 {
+        self.priorities.get(name).copied().unwrap_or_default()
+    }
+
+    /// Check if a layer has been dropped
+    fn is_dropped(&self, name: &str) -> bool {
+        self.exhausted
+            || self.threshold.is_some_and(|t| self.priority(name) <= t)
+    }
+
+    /// Drop the lowest priority level, if over budget
+    fn check_budget(&mut self) {
+        let (Some(budget), Some(deadline)) = (self.budget, self.deadline)
+        else {
+            return;
+        };
+        let now = Instant::now();
+        if now < deadline {
+            return;
+        }
+        self.deadline = Some(now + budget / 4);
+        let mut levels: Vec<i32> = self
+            .layers
+            .iter()
+            .filter_map(|(l, _)| l.name())
+            .map(|n| self.priority(n))
+            .filter(|p| self.threshold.is_none_or(|t| *p > t))
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+        if levels.len() < 2 {
+            // only the highest priority is left; skip if any were dropped
+            if levels.len() == 1 && self.threshold.is_some() {
+                self.exhausted = true;
+                warn!("Over time budget: {}", self.report);
+            }
+            return;
+        }
+        let threshold = levels[0];
+        self.threshold = Some(threshold);
+        let layers = std::mem::take(&mut self.layers);
+        for (mut layer, mark) in layers {
+            let name = layer.name().unwrap_or_default().to_string();
+            if self.priority(&name) == threshold {
+                self.report.dropped_features += layer.truncate(mark);
+                self.report.dropped_layers.push(name);
+                if mark == LayerMark::default() {
+                    continue;
+                }
+            }
+            self.layers.push((layer, mark));
+        }
+        warn!("Over time budget: {}", self.report);
+    }
+
+    /// Get the number of layers in progress.
+    pub fn num_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Get a layer in progress, creating it on first use
+    fn layer(&mut self, name: &str) -> Result<&mut Layer> {
+        let pos = self.layers.iter().position(|(l, _)| l.name() == Some(name));
+        let i = match pos {
+            Some(i) => i,
+            None => {
+                let layer = self.tile.get_or_create_layer(name)?;
+                let mark = layer.mark();
+                self.layers.push((layer, mark));
+                self.layers.len() - 1
+            }
+        };
+        Ok(&mut self.layers[i].0)
+    }
+
+    /// Add a feature to a layer.
+    ///
+    /// * `name` Layer name.
+    /// * `geom_data` Geometry data (consumed by this method).
+    /// * `tags` Function to set ID and tags of the feature.
+    ///
+    /// Features are skipped if their layer was dropped over the time budget
+    /// (see [with_budget]).  If `tags` panics, the feature is abandoned, but
+    /// the layer is kept.
+    ///
+    /// [with_budget]: struct.TileBuilder.html#method.with_budget
+    pub fn add_feature<T>(
+        &mut self,
+        name: &str,
+        geom_data: GeomData,
+        tags: T,
+    ) -> Result<()>
+    where
+        T: FnOnce(&mut Feature),
+    {
+        self.check_budget();
+        if self.is_dropped(name) {
+            self.report.skipped_features += 1;
+            return Ok(());
+        }
+        FeatureGuard::new(self.layer(name)?, geom_data).complete(tags);
+        Ok(())
+    }
+
+    /// Ingest one source feature, routing it to layers by a classifier.
+    ///
+    /// * `source` Source feature (such as an OSM way or GeoPackage row).
+    /// * `geom_data` Geometry data (consumed by this method).
+    /// * `classify` Function returning names of the target layers.
+    /// * `tags` Function to set ID and tags, for each target layer.
+    ///
+    /// Returns the number of layers the feature was added to.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::Error;
+    /// # fn main() -> Result<(), Error> {
+    /// use mvt::{GeomEncoder, GeomType, Tile, TileBuilder};
+    /// use pointy::Transform;
+    ///
+    /// let source = [("motorway", "I-35"), ("rail", "BNSF"), ("bench", "")];
+    /// let mut builder = TileBuilder::new(Tile::new(4096));
+    /// for (kind, name) in source {
+    ///     let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+    ///         .point(0.0, 0.0)?
+    ///         .encode()?;
+    ///     builder.ingest(
+    ///         &(kind, name),
+    ///         geom,
+    ///         |(kind, _)| match *kind {
+    ///             "motorway" => vec!["roads", "labels"],
+    ///             "rail" => vec!["transit"],
+    ///             _ => vec![],
+    ///         },
+    ///         |layer, (kind, name), feature| {
+    ///             match layer {
+    ///                 "labels" => feature.add_tag_string("name", name),
+    ///                 _ => feature.add_tag_string("kind", kind),
+    ///             };
+    ///         },
+    ///     )?;
+    /// }
+    /// assert_eq!(builder.finish()?.num_layers(), 3);
+    /// # Ok(()) }
+    /// ```
+    pub fn ingest<S, C, N, T>(
+        &mut self,
+        source: &S,
+        geom_data: GeomData,
+        classify: C,
+        mut tags: T,
+    ) -> Result<usize>
+    where
+        C: FnOnce(&S) -> Vec<N>,
+        N: AsRef<str>,
+        T: FnMut(&str, &S, &mut Feature),
+    {
+        let names = classify(source);
+        let mut geom_data = Some(geom_data);
+        for (i, name) in names.iter().enumerate() {
+            let geom = if i + 1 < names.len() {
+                geom_data.clone()
+            } else {
+                geom_data.take()
+            };
+            if let Some(geom) = geom {
+                let name = name.as_ref();
+                self.add_feature(name, geom, |f| tags(name, source, f))?;
+            }
+        }
+        Ok(names.len())
+    }
+
+    /// Add all layers in progress to the tile, and return it.
+    ///
+    /// Returns an error if a layer fails the tile's checks (see
+    /// [Tile::add_layer]).
+    ///
+    /// [Tile::add_layer]: struct.Tile.html#method.add_layer
+    pub fn finish(mut self) -> Result<Tile> {
+        for (layer, _) in self.layers {
+            self.tile.add_layer(layer)?;
+        }
+        Ok(self.tile)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use pointy::Transform;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    fn point(x: f64) -> GeomData {
+        GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(x, 0.0)
+            .unwrap()
+            .encode()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut tile = Tile::new(256);
+        let layer = tile.create_layer("b").into_feature(point(9.0));
+        tile.add_layer(layer.into_layer()).unwrap();
+        let mut builder = TileBuilder::new(tile);
+        for (name, x) in [("a", 1.0), ("b", 2.0), ("a", 3.0), ("c", 4.0)] {
+            builder
+                .add_feature(name, point(x), |f| {
+                    f.set_id(x as u64);
+                })
+                .unwrap();
+        }
+        assert_eq!(builder.num_layers(), 3);
+        let tile = builder.finish().unwrap();
+        let layers = tile.layers().unwrap();
+        let names: Vec<_> = layers.iter().map(|l| l.name()).collect();
+        assert_eq!(names, ["b", "a", "c"]);
+        let counts: Vec<_> = layers.iter().map(|l| l.num_features()).collect();
+        assert_eq!(counts, [2, 2, 1]);
+        let feature = layers[1].feature(1).unwrap().unwrap();
+        assert_eq!(feature.id(), Some(3));
+    }
+
+    #[test]
+    fn test_tags_panic() {
+        let mut builder = TileBuilder::new(Tile::new(256));
+        builder
+            .add_feature("a", point(1.0), |f| {
+                f.add_tag_string("name", "first");
+            })
+            .unwrap();
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            builder.add_feature("a", point(2.0), |f| {
+                f.add_tag_string("name", "second");
+                panic!("tags");
+            })
+        }));
+        assert!(res.is_err());
+        builder.add_feature("a", point(3.0), |_| ()).unwrap();
+        let tile = builder.finish().unwrap();
+        let layers = tile.layers().unwrap();
+        assert_eq!(layers[0].num_features(), 2);
+        assert_eq!(layers[0].values().len(), 1);
+    }
+
+    #[test]
+    fn test_ingest() {
+        let mut builder = TileBuilder::new(Tile::new(256));
+        let classify = |x: &u64| match x % 3 {
+            0 => vec!["fizz", "all"],
+            _ => vec!["all"],
+        };
+        for x in 0..6 {
+            let n = builder
+                .ingest(&x, point(x as f64), classify, |layer, x, f| {
+                    f.set_id(*x).add_tag_string("layer", layer);
+                })
+                .unwrap();
+            assert_eq!(n, if x % 3 == 0 { 2 } else { 1 });
+        }
+        let n = builder.ingest(
+            &9,
+            point(9.0),
+            |_| Vec::<String>::new(),
+            |_, _, _| (),
+        );
+        assert_eq!(n.unwrap(), 0);
+        let tile = builder.finish().unwrap();
+        let layers = tile.layers().unwrap();
+        assert_eq!(layers[0].num_features(), 2);
+        assert_eq!(layers[1].num_features(), 6);
+        let feature = layers[0].feature(1).unwrap().unwrap();
+        assert_eq!(feature.id(), Some(3));
+        let (_, value) = feature.tags(&layers[0]).next().unwrap();
+        assert_eq!(value.as_str(), Some("fizz"));
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut builder = TileBuilder::new(Tile::new(256))
+            .with_priority("roads", 2)
+            .with_priority("places", 1);
+        for (name, x) in [("roads", 1.0), ("places", 2.0), ("trees", 3.0)] {
+            builder.add_feature(name, point(x), |_| ()).unwrap();
+            builder.add_feature(name, point(x), |_| ()).unwrap();
+        }
+        assert!(builder.report().is_empty());
+        // every feature over budget drops a priority level
+        let mut builder = builder.with_budget(Duration::ZERO);
+        builder.add_feature("trees", point(4.0), |_| ()).unwrap();
+        assert_eq!(builder.report().dropped_layers(), ["trees"]);
+        assert_eq!(builder.report().dropped_features(), 2);
+        assert_eq!(builder.report().skipped_features(), 1);
+        builder.add_feature("roads", point(5.0), |_| ()).unwrap();
+        assert_eq!(builder.report().dropped_layers(), ["trees", "places"]);
+        assert_eq!(builder.report().dropped_features(), 4);
+        assert_eq!(builder.report().skipped_features(), 1);
+        builder.add_feature("roads", point(6.0), |_| ()).unwrap();
+        builder.add_feature("bikes", point(7.0), |_| ()).unwrap();
+        assert_eq!(builder.report().skipped_features(), 3);
+        assert_eq!(
+            builder.report().to_string(),
+            "dropped 2 layers, 4 features; skipped 3 features"
+        );
+        let tile = builder.finish().unwrap();
+        let layers = tile.layers().unwrap();
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].name(), "roads");
+        assert_eq!(layers[0].num_features(), 3);
+    }
+
+    #[test]
+    fn test_budget_existing() {
+        let mut tile = Tile::new(256);
+        let mut feature = tile.create_layer("places").into_feature(point(1.0));
+        feature.add_tag_string("name", "old");
+        tile.add_layer(feature.into_layer()).unwrap();
+        let mut builder = TileBuilder::new(tile)
+            .with_priority("roads", 1)
+            .with_budget(Duration::ZERO);
+        // nothing to drop yet: not exhausted
+        for name in ["places", "roads"] {
+            builder
+                .add_feature(name, point(2.0), |f| {
+                    f.add_tag_string("name", "new");
+                })
+                .unwrap();
+        }
+        assert!(builder.report().is_empty());
+        builder.add_feature("places", point(3.0), |_| ()).unwrap();
+        assert_eq!(builder.report().dropped_layers(), ["places"]);
+        assert_eq!(builder.report().dropped_features(), 1);
+        assert_eq!(builder.report().skipped_features(), 1);
+        let tile = builder.finish().unwrap();
+        let layers = tile.layers().unwrap();
+        assert_eq!(layers.len(), 2);
+        let places = layers.iter().find(|l| l.name() == "places").unwrap();
+        assert_eq!(places.num_features(), 1);
+        assert_eq!(places.values().len(), 1);
+    }
+}
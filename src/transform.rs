@@ -0,0 +1,163 @@
+// transform.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Transform parameters and display.
+//!
+#[cfg(feature = "serde")]
+use crate::error::{Error, Result};
+use pointy::{Float, Transform};
+use std::fmt;
+
+/// Parameter getters for affine [Transform]s.
+///
+/// The transform maps `(x, y)` to `(a·x + b·y + c, d·x + e·y + f)`.
+/// Parameters are found by transforming the origin and unit vectors, so they
+/// may include rounding error for transforms with large offsets.
+///
+/// # Example
+/// ```
+/// use mvt::TransformParams;
+/// use pointy::Transform;
+///
+/// let t = Transform::with_scale(0.25, -0.5).translate(16.0, 8.0);
+/// assert_eq!(t.coefficients(), [0.25, 0.0, 16.0, 0.0, -0.5, 8.0]);
+/// assert_eq!(t.scale_factors(), (0.25, 0.5));
+/// assert_eq!(t.translation(), (16.0, 8.0));
+/// assert_eq!(t.display().to_string(), "[0.25 0 16; 0 -0.5 8]");
+/// ```
+///
+/// [Transform]: https://docs.rs/pointy/latest/pointy/struct.Transform.html
+pub trait TransformParams<F>
+where
+    F: Float,
+{
+    /// Get the coefficients `[a, b, c, d, e, f]`.
+    fn coefficients(&self) -> [F; 6];
+
+    /// Get the translation `(c, f)`: where the origin is mapped.
+    fn translation(&self) -> (F, F) {
+        let [_, _, c, _, _, f] = self.coefficients();
+        (c, f)
+    }
+
+    /// Get the scale factors: lengths of the transformed X and Y unit
+    /// vectors.
+    fn scale_factors(&self) -> (F, F) {
+        let [a, b, _, d, e, _] = self.coefficients();
+        (a.hypot(d), b.hypot(e))
+    }
+
+    /// Get a value which displays as `[a b c; d e f]`.
+    fn display(&self) -> TransformDisplay<F> {
+        TransformDisplay(self.coefficients())
+    }
+}
+
+impl<F> TransformParams<F> for Transform<F>
+where
+    F: Float,
+{
+    fn coefficients(&self) -> [F; 6] {
+        let o = *self * (F::zero(), F::zero());
+        let px = *self * (F::one(), F::zero());
+        let py = *self * (F::zero(), F::one());
+        [
+            px.x() - o.x(),
+            py.x() - o.x(),
+            o.x(),
+            px.y() - o.y(),
+            py.y() - o.y(),
+            o.y(),
+        ]
+    }
+}
+
+/// Make an axis-aligned transform from coefficients `[a, b, c, d, e, f]`.
+///
+/// Returns [Error::InvalidValue] if `b` or `d` is nonzero (rotated or
+/// skewed), or any coefficient is not finite.
+///
+/// [Error::InvalidValue]: enum.Error.html#variant.InvalidValue
+#[cfg(feature = "serde")]
+pub(crate) fn from_coefficients<F>(c: [F; 6]) -> Result<Transform<F>>
+where
+    F: Float,
+{
+    check_coefficients(&c)?;
+    let [a, _, c, _, e, f] = c;
+    Ok(Transform::with_scale(a, e).translate(c, f))
+}
+
+/// Check that coefficients `[a, b, c, d, e, f]` are axis-aligned.
+///
+/// Returns [Error::InvalidValue] if `b` or `d` is nonzero (rotated or
+/// skewed), or any coefficient is not finite.
+///
+/// [Error::InvalidValue]: enum.Error.html#variant.InvalidValue
+#[cfg(feature = "serde")]
+pub(crate) fn check_coefficients<F>(c: &[F; 6]) -> Result<()>
+where
+    F: Float,
+{
+    let [a, b, c, d, e, f] = *c;
+    if !b.is_zero()
+        || !d.is_zero()
+        || [a, c, e, f].iter().any(|v| !v.is_finite())
+    {
+        return Err(Error::InvalidValue());
+    }
+    Ok(())
+}
+
+/// Displayable [Transform] coefficients (see [TransformParams::display]).
+///
+/// [Transform]: https://docs.rs/pointy/latest/pointy/struct.Transform.html
+/// [TransformParams::display]: trait.TransformParams.html#method.display
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransformDisplay<F>([F; 6])
+where
+    F: Float;
+
+impl<F> fmt::Display for TransformDisplay<F>
+where
+    F: Float + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "[{a} {b} {c}; {d} {e} {g}]")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapgrid::{MapGrid, TileId};
+
+    #[test]
+    fn test_tile_transform() {
+        let grid = MapGrid::<f64>::default();
+        let tid = TileId::new(3, 5, 4).unwrap();
+        let t = grid.tile_transform(tid);
+        let bbox = grid.tile_bbox(tid);
+        let (sx, sy) = t.scale_factors();
+        assert!((sx * bbox.x_span() - 1.0).abs() < 1e-9);
+        assert!((sy * bbox.y_span() - 1.0).abs() < 1e-9);
+        let [_, b, _, d, e, _] = t.coefficients();
+        assert_eq!((b, d), (0.0, 0.0));
+        assert!(e < 0.0);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_from_coefficients() {
+        let grid = MapGrid::<f64>::default();
+        let t = grid.tile_transform(TileId::new(3, 5, 4).unwrap());
+        let c = t.coefficients();
+        assert_eq!(from_coefficients(c).unwrap().coefficients(), c);
+        assert!(from_coefficients([1.0, 0.5, 0.0, 0.0, 1.0, 0.0]).is_err());
+        assert!(from_coefficients([f64::NAN, 0.0, 0.0, 0.0, 1.0, 0.0]).is_err());
+        assert!(check_coefficients(&c).is_ok());
+        assert!(check_coefficients(&[1.0, 0.0, 0.0, 0.5, 1.0, 0.0]).is_err());
+    }
+}
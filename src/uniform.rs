@@ -0,0 +1,125 @@
+// uniform.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Empty / uniform tile synthesis.
+//!
+use crate::duplicate::TileDigest;
+use crate::encoder::{GeomData, GeomEncoder, GeomType};
+use crate::error::Result;
+use crate::tile::Tile;
+use pointy::Transform;
+
+/// Encoded empty tile (no layers).
+pub const EMPTY_TILE: &[u8] = &[];
+
+/// Pre-encoded tile with one full-extent polygon, such as all-water tiles.
+///
+/// A pyramid builder can encode this once and write the same bytes (or a
+/// link, keyed by [digest]) for every tile it covers.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, UniformTile};
+/// # fn main() -> Result<(), Error> {
+/// let water = UniformTile::new(4096, 64, "water", &[("natural", "water")])?;
+/// let data = water.as_bytes();
+/// # Ok(()) }
+/// ```
+///
+/// [digest]: struct.UniformTile.html#method.digest
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UniformTile {
+    data: Vec<u8>,
+}
+
+/// Encode a polygon covering the full tile extent.
+///
+/// * `extent` Height / width of tile bounds.
+/// * `buffer` Distance to extend past tile bounds on each side.
+pub fn full_extent_polygon(extent: u32, buffer: u32) -> Result<GeomData> {
+    let lo = -f64::from(buffer);
+    let hi = f64::from(extent) + f64::from(buffer);
+    GeomEncoder::new(GeomType::Polygon, Transform::default())
+        .point(lo, lo)?
+        .point(hi, lo)?
+        .point(hi, hi)?
+        .point(lo, hi)?
+        .encode()
+}
+
+impl UniformTile {
+    /// Create an empty tile.
+    pub fn empty() -> Self {
+        UniformTile {
+            data: EMPTY_TILE.to_vec(),
+        }
+    }
+
+    /// Create a uniform tile.
+    ///
+    /// * `extent` Height / width of tile bounds.
+    /// * `buffer` Distance the polygon extends past tile bounds.
+    /// * `layer` Layer name.
+    /// * `tags` Key / value string tags of the polygon feature.
+    pub fn new(
+        extent: u32,
+        buffer: u32,
+        layer: &str,
+        tags: &[(&str, &str)],
+    ) -> Result<Self> {
+        let mut tile = Tile::new(extent);
+        let geom = full_extent_polygon(extent, buffer)?;
+        let mut feature = tile.create_layer(layer).into_feature(geom);
+        for (key, val) in tags {
+            feature.add_tag_string(key, val);
+        }
+        tile.add_layer(feature.into_layer())?;
+        let data = tile.to_bytes()?;
+        Ok(UniformTile { data })
+    }
+
+    /// Get the encoded tile.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Decode the tile.
+    pub fn to_tile(&self) -> Result<Tile> {
+        Tile::from_bytes(&self.data)
+    }
+
+    /// Get the tile digest, for matching against encoded tiles.
+    pub fn digest(&self) -> TileDigest {
+        TileDigest::new(&self.data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_polygon() {
+        let geom = full_extent_polygon(4096, 0).unwrap().into_vec();
+        assert_eq!(geom, vec![9, 0, 0, 26, 8192, 0, 0, 8192, 8191, 0, 15]);
+        let geom = full_extent_polygon(256, 8).unwrap().into_vec();
+        assert_eq!(&geom[..3], &[9, 15, 15]);
+    }
+
+    #[test]
+    fn test_uniform() {
+        let empty = UniformTile::empty();
+        assert!(empty.as_bytes().is_empty());
+        assert_eq!(empty.to_tile().unwrap().num_layers(), 0);
+        let water = UniformTile::new(4096, 64, "water", &[("a", "b")]).unwrap();
+        let again = UniformTile::new(4096, 64, "water", &[("a", "b")]).unwrap();
+        assert_eq!(water.digest(), again.digest());
+        assert_ne!(water.digest(), empty.digest());
+        let tile = water.to_tile().unwrap();
+        assert_eq!(tile.extent(), 4096);
+        let layer = &tile.vt_layers()[0];
+        assert_eq!(layer.name(), "water");
+        assert_eq!(layer.features[0].tags, vec![0, 0]);
+    }
+}
@@ -0,0 +1,910 @@
+// view.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Borrowed (zero-copy) views of encoded tiles.
+//!
+use crate::encoder::{count_vertices, GeomType};
+use crate::error::{DecodeLocation, Error, Result};
+use crate::geometry::{Geometry, Winding};
+use crate::limits::DecodeLimits;
+use crate::mapgrid::TileId;
+use crate::vector_tile::tile::{
+    Feature as VtFeature, GeomType as VtGeomType, Layer as VtLayer, Value,
+};
+use crate::wire::{push_u32, Field, Fields, WireValue};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Borrowed view of an encoded tile.
+///
+/// Unlike [Tile::from_bytes], names, keys and string values are not copied;
+/// they borrow from the encoded data.  Only strings which are not valid
+/// UTF-8 are copied (with replacement characters).
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{GeomEncoder, GeomType, Tile, TileView, ValueView};
+/// use pointy::Transform;
+///
+/// let mut tile = Tile::new(4096);
+/// let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+///     .point(1.0, 2.0)?
+///     .encode()?;
+/// let mut feature = tile.create_layer("places").into_feature(geom);
+/// feature.add_tag_string("name", "Home");
+/// tile.add_layer(feature.into_layer())?;
+/// let data = tile.to_bytes()?;
+///
+/// let view = TileView::new(&data)?;
+/// let layer = view.layer("places").unwrap();
+/// let feature = layer.feature(0).unwrap()?;
+/// let (key, value) = feature.tags(layer).next().unwrap();
+/// assert_eq!(key, "name");
+/// assert_eq!(value, &ValueView::String("Home".into()));
+/// # Ok(()) }
+/// ```
+///
+/// [Tile::from_bytes]: struct.Tile.html#method.from_bytes
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileView<'a> {
+    /// Layers
+    layers: Vec<LayerView<'a>>,
+}
+
+/// Borrowed view of a layer, from an encoded tile or a [Tile].
+///
+/// [Tile]: struct.Tile.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerView<'a> {
+    /// Layer name
+    name: Cow<'a, str>,
+
+    /// Spec version
+    version: u32,
+
+    /// Extent
+    extent: u32,
+
+    /// Tag keys
+    keys: Vec<Cow<'a, str>>,
+
+    /// Tag values
+    values: Vec<ValueView<'a>>,
+
+    /// Features
+    features: FeatureSource<'a>,
+}
+
+/// Source of features in a layer
+#[derive(Clone, Debug, PartialEq)]
+enum FeatureSource<'a> {
+    /// Encoded feature message bodies
+    Encoded(Vec<&'a [u8]>),
+
+    /// Decoded features
+    Decoded(&'a [VtFeature]),
+}
+
+/// Iterator of lazily decoded features in a [LayerView].
+///
+/// [LayerView]: struct.LayerView.html
+pub struct Features<'l, 'a> {
+    /// Layer containing the features
+    layer: &'l LayerView<'a>,
+
+    /// Remaining feature indices
+    range: Range<usize>,
+}
+
+/// Borrowed view of a tag value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueView<'a> {
+    /// String value
+    String(Cow<'a, str>),
+
+    /// Float value
+    Float(f32),
+
+    /// Double value
+    Double(f64),
+
+    /// Int value
+    Int(i64),
+
+    /// Uint value
+    Uint(u64),
+
+    /// Sint value
+    Sint(i64),
+
+    /// Bool value
+    Bool(bool),
+}
+
+/// View of a feature.
+///
+/// Tags are key / value indices into the tables of its [LayerView].
+///
+/// [LayerView]: struct.LayerView.html
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeatureView {
+    /// Feature ID
+    id: Option<u64>,
+
+    /// Geometry type
+    geom_type: Option<GeomType>,
+
+    /// Tag indices
+    tags: Vec<u32>,
+
+    /// Geometry command integers
+    geometry: Vec<u32>,
+
+    /// Spec version of the layer
+    version: u32,
+}
+
+/// Get the name of a layer message body
+fn layer_name(body: &[u8]) -> Result<Cow<'_, str>> {
+    for field in Fields::new(body) {
+        let field = field?;
+        if let (1, WireValue::Bytes(name)) = (field.number, field.value) {
+            return Ok(string(name));
+        }
+    }
+    Err(Error::InvalidLayer())
+}
+
+/// Get a string, copying only if it is not valid UTF-8
+fn string(bytes: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(bytes)
+}
+
+/// Get the raw bytes of a fixed-size value
+fn fixed<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut raw = [0; N];
+    raw.copy_from_slice(bytes);
+    raw
+}
+
+impl<'a> TileView<'a> {
+    /// Create a view of an encoded tile.
+    ///
+    /// Returns an error if the tile or layer data is malformed.  Features are
+    /// decoded on demand, so a tag referring to a missing key or value is
+    /// reported by [LayerView::feature] instead.
+    ///
+    /// [LayerView::feature]: struct.LayerView.html#method.feature
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        Self::scan(data, |_| Ok(true))
+    }
+
+    /// Create a view of selected layers of an encoded tile.
+    ///
+    /// * `data` Encoded tile.
+    /// * `filter` Function to select layers by name.
+    ///
+    /// Unselected layers are skipped without scanning their key / value
+    /// tables or features (only their names are read).
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, Tile, TileView};
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// tile.add_layer(tile.create_layer("water"))?;
+    /// tile.add_layer(tile.create_layer("roads"))?;
+    /// let data = tile.to_bytes()?;
+    /// let view = TileView::filtered(&data, |name| name == "roads")?;
+    /// assert_eq!(view.layers().len(), 1);
+    /// # Ok(()) }
+    /// ```
+    pub fn filtered<P>(data: &'a [u8], filter: P) -> Result<Self>
+    where
+        P: Fn(&str) -> bool,
+    {
+        Self::scan(data, |body| Ok(filter(&layer_name(body)?)))
+    }
+
+    /// Scan layers of an encoded tile, selecting by layer message body
+    fn scan<P>(data: &'a [u8], select: P) -> Result<Self>
+    where
+        P: Fn(&[u8]) -> Result<bool>,
+    {
+        let mut layers = vec![];
+        let mut fields = Fields::new(data);
+        let mut n_layer = 0;
+        loop {
+            let at = DecodeLocation::new(n_layer, None, fields.offset());
+            match fields.next() {
+                None => break,
+                Some(Ok(Field {
+                    number: 3,
+                    value: WireValue::Bytes(body),
+                    ..
+                })) => {
+                    if select(body).map_err(|e| e.at(at))? {
+                        let layer =
+                            LayerView::new(body).map_err(|e| e.at(at))?;
+                        layers.push(layer);
+                    }
+                    n_layer += 1;
+                }
+                Some(Ok(_)) => (),
+                Some(Err(e)) => return Err(e.at(at)),
+            }
+        }
+        Ok(TileView { layers })
+    }
+
+    /// Create a view of an untrusted encoded tile, checking limits first.
+    ///
+    /// Returns [Error::LimitExceeded] if a limit is exceeded, or any error
+    /// from [new].
+    ///
+    /// [Error::LimitExceeded]: enum.Error.html#variant.LimitExceeded
+    /// [new]: struct.TileView.html#method.new
+    pub fn with_limits(data: &'a [u8], limits: &DecodeLimits) -> Result<Self> {
+        limits.check_tile(data)?;
+        Self::new(data)
+    }
+
+    /// Get all layers.
+    pub fn layers(&self) -> &[LayerView<'a>] {
+        &self.layers
+    }
+
+    /// Get a layer by name.
+    pub fn layer(&self, name: &str) -> Option<&LayerView<'a>> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+}
+
+impl<'a> LayerView<'a> {
+    /// Create a view of a layer message body
+    fn new(body: &'a [u8]) -> Result<Self> {
+        let mut layer = LayerView {
+            name: Cow::Borrowed(""),
+            version: 1,
+            extent: 4096,
+            keys: vec![],
+            values: vec![],
+            features: FeatureSource::Encoded(vec![]),
+        };
+        let mut features = vec![];
+        for field in Fields::new(body) {
+            let field = field?;
+            match (field.number, field.value) {
+                (15, WireValue::Varint(v)) => layer.version = v as u32,
+                (1, WireValue::Bytes(name)) => layer.name = string(name),
+                (2, WireValue::Bytes(f)) => features.push(f),
+                (3, WireValue::Bytes(key)) => layer.keys.push(string(key)),
+                (4, WireValue::Bytes(v)) => {
+                    layer.values.push(ValueView::new(v)?)
+                }
+                (5, WireValue::Varint(e)) => layer.extent = e as u32,
+                (1..=5 | 15, _) => return Err(Error::InvalidLayer()),
+                _ => (),
+            }
+        }
+        layer.features = FeatureSource::Encoded(features);
+        Ok(layer)
+    }
+
+    /// Create a view of a framed `Tile.layers` field
+    pub(crate) fn from_framed(data: &'a [u8]) -> Result<Self> {
+        match Fields::new(data).next() {
+            Some(Ok(Field {
+                number: 3,
+                value: WireValue::Bytes(body),
+                ..
+            })) => Self::new(body),
+            _ => Err(Error::InvalidLayer()),
+        }
+    }
+
+    /// Create a view of a decoded layer
+    pub(crate) fn from_vt(layer: &'a VtLayer) -> Result<Self> {
+        Ok(LayerView {
+            name: Cow::Borrowed(layer.name()),
+            version: layer.version(),
+            extent: layer.extent(),
+            keys: layer
+                .keys
+                .iter()
+                .map(|k| Cow::Borrowed(k.as_str()))
+                .collect(),
+            values: layer
+                .values
+                .iter()
+                .map(ValueView::from_vt)
+                .collect::<Result<_>>()?,
+            features: FeatureSource::Decoded(&layer.features),
+        })
+    }
+
+    /// Get the layer name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the spec version.
+    ///
+    /// This is 1 for legacy layers, including those with no version field.
+    /// Polygon rings of version 1 layers are grouped by [Winding::FirstRing]
+    /// (unless another rule is given).
+    ///
+    /// [Winding::FirstRing]: enum.Winding.html#variant.FirstRing
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Get the extent.
+    pub fn extent(&self) -> u32 {
+        self.extent
+    }
+
+    /// Get the tag keys.
+    pub fn keys(&self) -> &[Cow<'a, str>] {
+        &self.keys
+    }
+
+    /// Get the tag values.
+    pub fn values(&self) -> &[ValueView<'a>] {
+        &self.values
+    }
+
+    /// Get the number of features, without decoding them.
+    pub fn num_features(&self) -> usize {
+        match &self.features {
+            FeatureSource::Encoded(bodies) => bodies.len(),
+            FeatureSource::Decoded(features) => features.len(),
+        }
+    }
+
+    /// Get an iterator of features, decoded on demand.
+    pub fn features(&self) -> Features<'_, 'a> {
+        Features {
+            layer: self,
+            range: 0..self.num_features(),
+        }
+    }
+
+    /// Decode one feature.
+    ///
+    /// * `n` Index of feature in layer.
+    pub fn feature(&self, n: usize) -> Option<Result<FeatureView>> {
+        match &self.features {
+            FeatureSource::Encoded(bodies) => {
+                bodies.get(n).map(|body| FeatureView::new(body, self))
+            }
+            FeatureSource::Decoded(features) => {
+                features.get(n).map(|f| FeatureView::from_vt(f, self))
+            }
+        }
+    }
+}
+
+impl<'a> ValueView<'a> {
+    /// Create a view of a value message body
+    ///
+    /// Unknown fields (such as extensions) are skipped.
+    pub(crate) fn new(body: &'a [u8]) -> Result<Self> {
+        let mut value = None;
+        for field in Fields::new(body) {
+            let field = field?;
+            value = Some(match (field.number, field.value) {
+                (1, WireValue::Bytes(s)) => ValueView::String(string(s)),
+                (2, WireValue::Fixed32(f)) => {
+                    ValueView::Float(f32::from_le_bytes(fixed(f)))
+                }
+                (3, WireValue::Fixed64(f)) => {
+                    ValueView::Double(f64::from_le_bytes(fixed(f)))
+                }
+                (4, WireValue::Varint(v)) => ValueView::Int(v as i64),
+                (5, WireValue::Varint(v)) => ValueView::Uint(v),
+                (6, WireValue::Varint(v)) => {
+                    ValueView::Sint((v >> 1) as i64 ^ -((v & 1) as i64))
+                }
+                (7, WireValue::Varint(v)) => ValueView::Bool(v != 0),
+                (1..=7, _) => return Err(Error::InvalidLayer()),
+                _ => continue,
+            });
+        }
+        value.ok_or(Error::InvalidLayer())
+    }
+
+    /// Create a view of a decoded value
+    fn from_vt(value: &'a Value) -> Result<Self> {
+        if let Some(s) = &value.string_value {
+            Ok(ValueView::String(Cow::Borrowed(s)))
+        } else if let Some(v) = value.float_value {
+            Ok(ValueView::Float(v))
+        } else if let Some(v) = value.double_value {
+            Ok(ValueView::Double(v))
+        } else if let Some(v) = value.int_value {
+            Ok(ValueView::Int(v))
+        } else if let Some(v) = value.uint_value {
+            Ok(ValueView::Uint(v))
+        } else if let Some(v) = value.sint_value {
+            Ok(ValueView::Sint(v))
+        } else if let Some(v) = value.bool_value {
+            Ok(ValueView::Bool(v))
+        } else {
+            Err(Error::InvalidLayer())
+        }
+    }
+
+    /// Get the string value, if any.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ValueView::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Get a float or double value, if any.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ValueView::Float(v) => Some(f64::from(*v)),
+            ValueView::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Get an integer value, if any (and it fits in an `i64`).
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            ValueView::Int(v) | ValueView::Sint(v) => Some(*v),
+            ValueView::Uint(v) => i64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Get an integer value, if any (and it fits in a `u64`).
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            ValueView::Int(v) | ValueView::Sint(v) => u64::try_from(*v).ok(),
+            ValueView::Uint(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Get the bool value, if any.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ValueView::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl FeatureView {
+    /// Decode a feature message body
+    ///
+    /// Returns an error if a tag refers to a missing key or value.
+    fn new(body: &[u8], layer: &LayerView) -> Result<Self> {
+        let mut feature = FeatureView {
+            version: layer.version,
+            ..Default::default()
+        };
+        for field in Fields::new(body) {
+            let field = field?;
+            match (field.number, field.value) {
+                (1, WireValue::Varint(id)) => feature.id = Some(id),
+                (2, value) => push_u32(&mut feature.tags, value)?,
+                (3, WireValue::Varint(tp)) => {
+                    feature.geom_type = match tp {
+                        1 => Some(GeomType::Point),
+                        2 => Some(GeomType::Linestring),
+                        3 => Some(GeomType::Polygon),
+                        _ => None,
+                    }
+                }
+                (4, value) => push_u32(&mut feature.geometry, value)?,
+                (1 | 3, _) => return Err(Error::InvalidLayer()),
+                _ => (),
+            }
+        }
+        feature.check_tags(layer)
+    }
+
+    /// Create a view of a decoded feature
+    fn from_vt(feature: &VtFeature, layer: &LayerView) -> Result<Self> {
+        FeatureView {
+            id: feature.id,
+            geom_type: match feature.type_() {
+                VtGeomType::POINT => Some(GeomType::Point),
+                VtGeomType::LINESTRING => Some(GeomType::Linestring),
+                VtGeomType::POLYGON => Some(GeomType::Polygon),
+                VtGeomType::UNKNOWN => None,
+            },
+            tags: feature.tags.clone(),
+            geometry: feature.geometry.clone(),
+            version: layer.version,
+        }
+        .check_tags(layer)
+    }
+
+    /// Check that tags refer to keys and values in the layer
+    fn check_tags(self, layer: &LayerView) -> Result<Self> {
+        let (n_keys, n_values) = (layer.keys.len(), layer.values.len());
+        if !self.tags.len().is_multiple_of(2)
+            || self.tags.chunks_exact(2).any(|kv| {
+                kv[0] as usize >= n_keys || kv[1] as usize >= n_values
+            })
+        {
+            return Err(Error::InvalidLayer());
+        }
+        Ok(self)
+    }
+
+    /// Get the feature ID.
+    pub fn id(&self) -> Option<u64> {
+        self.id
+    }
+
+    /// Get the geometry type (`None` if unknown).
+    pub fn geom_type(&self) -> Option<GeomType> {
+        self.geom_type
+    }
+
+    /// Get the geometry command integers.
+    pub fn geometry_data(&self) -> &[u32] {
+        &self.geometry
+    }
+
+    /// Get the number of vertices in the geometry.
+    ///
+    /// Only command integers are scanned; no coördinates are decoded.
+    /// Returns an error if the geometry is malformed.
+    pub fn vertex_count(&self) -> Result<usize> {
+        count_vertices(&self.geometry)
+    }
+
+    /// Decode the geometry, in tile coördinates.
+    ///
+    /// Polygon rings are grouped into polygons by winding order, using the
+    /// rules of the layer's spec version.  Returns an error if the geometry
+    /// type is unknown or the command integers are malformed.
+    pub fn geometry(&self) -> Result<Geometry> {
+        self.geometry_with_winding(Winding::default())
+    }
+
+    /// Decode the geometry, with a winding order rule.
+    ///
+    /// * `winding` Rule for grouping polygon rings.
+    ///
+    /// Use [Winding::FirstRing] for producers which do not follow the
+    /// version 2 winding order.
+    ///
+    /// [Winding::FirstRing]: enum.Winding.html#variant.FirstRing
+    pub fn geometry_with_winding(&self, winding: Winding) -> Result<Geometry> {
+        let geom_tp = self.geom_type.ok_or(Error::InvalidGeometry())?;
+        Geometry::decode(geom_tp, &self.geometry, winding.rule(self.version))
+    }
+
+    /// Decode the geometry, reprojected to Web Mercator longitude / latitude
+    /// (degrees).
+    ///
+    /// * `layer` Layer containing the feature.
+    /// * `tid` Tile ID of the tile containing the layer.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType, Geometry, Tile, TileId};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// let mut tile = Tile::new(4096);
+    /// let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+    ///     .point(2048.0, 2048.0)?
+    ///     .encode()?;
+    /// let layer = tile.create_layer("places").into_feature(geom).into_layer();
+    /// tile.add_layer(layer)?;
+    /// let layers = tile.layers()?;
+    /// let feature = layers[0].feature(0).unwrap()?;
+    /// let tid = TileId::new(0, 0, 0)?;
+    /// let geom = feature.to_lonlat(&layers[0], tid)?;
+    /// assert_eq!(geom, Geometry::Points(vec![(0.0, 0.0)]));
+    /// # Ok(()) }
+    /// ```
+    pub fn to_lonlat(
+        &self,
+        layer: &LayerView,
+        tid: TileId,
+    ) -> Result<Geometry<f64>> {
+        let extent = f64::from(layer.extent);
+        Ok(self.geometry()?.map(|(x, y)| {
+            tid.lonlat(f64::from(x) / extent, f64::from(y) / extent)
+        }))
+    }
+
+    /// Decode the geometry as `geo-types` geometry, in tile coördinates.
+    ///
+    /// For world coördinates, convert the result of [to_lonlat] instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType, Tile, TileId};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// # let mut tile = Tile::new(4096);
+    /// # let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+    /// #     .point(2048.0, 2048.0)?
+    /// #     .encode()?;
+    /// # let layer = tile.create_layer("places").into_feature(geom).into_layer();
+    /// # tile.add_layer(layer)?;
+    /// let layers = tile.layers()?;
+    /// let feature = layers[0].feature(0).unwrap()?;
+    /// let local = feature.to_geo()?;
+    /// assert_eq!(local, geo_types::Point::new(2048.0, 2048.0).into());
+    /// let tid = TileId::new(0, 0, 0)?;
+    /// let world = geo_types::Geometry::from(feature.to_lonlat(&layers[0], tid)?);
+    /// assert_eq!(world, geo_types::Point::new(0.0, 0.0).into());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [to_lonlat]: struct.FeatureView.html#method.to_lonlat
+    #[cfg(feature = "geo-types")]
+    pub fn to_geo(&self) -> Result<geo_types::Geometry<f64>> {
+        let geom = self.geometry()?;
+        Ok(geom.map(|(x, y)| (f64::from(x), f64::from(y))).into())
+    }
+
+    /// Deserialize the tags into a user-defined type (with `serde`).
+    ///
+    /// * `layer` Layer containing the feature.
+    ///
+    /// Tags are presented as a map from keys to values, so struct fields
+    /// match tag keys; missing tags can be `Option` fields, and unknown tags
+    /// are ignored unless the type denies them.  Numeric values convert to
+    /// any Rust number type which can hold them, and string values to unit
+    /// enum variants.
+    ///
+    /// Returns [Error::Deserialize] if the tags do not match the type.
+    ///
+    /// # Example
+    /// ```
+    /// # use mvt::{Error, GeomEncoder, GeomType, Tile};
+    /// # use pointy::Transform;
+    /// # fn main() -> Result<(), Error> {
+    /// #[derive(serde::Deserialize)]
+    /// struct RoadProps {
+    ///     name: String,
+    ///     lanes: u32,
+    /// }
+    ///
+    /// # let mut tile = Tile::new(4096);
+    /// # let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+    /// #     .point(1.0, 1.0)?
+    /// #     .encode()?;
+    /// # let mut feature = tile.create_layer("roads").into_feature(geom);
+    /// # feature.add_tag_string("name", "Main St").add_tag_int("lanes", 2);
+    /// # tile.add_layer(feature.into_layer())?;
+    /// let layers = tile.layers()?;
+    /// let feature = layers[0].feature(0).unwrap()?;
+    /// let props: RoadProps = feature.properties_as(&layers[0])?;
+    /// assert_eq!(props.name, "Main St");
+    /// assert_eq!(props.lanes, 2);
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [Error::Deserialize]: enum.Error.html#variant.Deserialize
+    #[cfg(feature = "serde")]
+    pub fn properties_as<T>(&self, layer: &LayerView) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        crate::props::properties_as(self, layer)
+    }
+
+    /// Calculate the length (meters) of the geometry, on a sphere.
+    ///
+    /// * `layer` Layer containing the feature.
+    /// * `tid` Tile ID of the tile containing the layer.
+    ///
+    /// See [Geometry::length_m].
+    ///
+    /// [Geometry::length_m]: enum.Geometry.html#method.length_m
+    pub fn length_m(&self, layer: &LayerView, tid: TileId) -> Result<f64> {
+        Ok(self.to_lonlat(layer, tid)?.length_m())
+    }
+
+    /// Calculate the area (square meters) of the geometry, on a sphere.
+    ///
+    /// * `layer` Layer containing the feature.
+    /// * `tid` Tile ID of the tile containing the layer.
+    ///
+    /// See [Geometry::area_m2].
+    ///
+    /// [Geometry::area_m2]: enum.Geometry.html#method.area_m2
+    pub fn area_m2(&self, layer: &LayerView, tid: TileId) -> Result<f64> {
+        Ok(self.to_lonlat(layer, tid)?.area_m2())
+    }
+
+    /// Get the tag index pairs.
+    pub fn tag_indices(&self) -> &[u32] {
+        &self.tags
+    }
+
+    /// Get the tags, looked up in the feature's layer.
+    ///
+    /// * `layer` Layer containing the feature.
+    pub fn tags<'l, 'a>(
+        &'l self,
+        layer: &'l LayerView<'a>,
+    ) -> impl Iterator<Item = (&'l str, &'l ValueView<'a>)> {
+        self.tags.chunks_exact(2).filter_map(|kv| {
+            let key = layer.keys.get(kv[0] as usize)?;
+            let value = layer.values.get(kv[1] as usize)?;
+            Some((key.as_ref(), value))
+        })
+    }
+
+    /// Get a tag value by key, looked up in the feature's layer.
+    ///
+    /// * `layer` Layer containing the feature.
+    /// * `key` Tag key.
+    pub fn tag<'l, 'a>(
+        &'l self,
+        layer: &'l LayerView<'a>,
+        key: &str,
+    ) -> Option<&'l ValueView<'a>> {
+        self.tags(layer).find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+}
+
+impl Iterator for Features<'_, '_> {
+    type Item = Result<FeatureView>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().and_then(|n| self.layer.feature(n))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.range.nth(n).and_then(|n| self.layer.feature(n))
+    }
+}
+
+impl ExactSizeIterator for Features<'_, '_> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomEncoder;
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    #[test]
+    fn test_view() {
+        let mut tile = Tile::new(512);
+        let geom = GeomEncoder::new(GeomType::Linestring, Transform::default())
+            .point(1.0, 2.0)
+            .unwrap()
+            .point(3.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("roads").into_feature(geom);
+        feature
+            .set_id(7)
+            .add_tag_string("name", "Main")
+            .add_tag_double("width", 12.5)
+            .add_tag_sint("level", -1)
+            .add_tag_bool("oneway", true);
+        tile.add_layer(feature.into_layer()).unwrap();
+        tile.add_layer(tile.create_layer("empty")).unwrap();
+        let data = tile.to_bytes().unwrap();
+        let view = TileView::new(&data).unwrap();
+        assert_eq!(view.layers().len(), 2);
+        let layer = view.layer("roads").unwrap();
+        assert_eq!((layer.version(), layer.extent()), (2, 512));
+        assert!(matches!(layer.keys()[0], Cow::Borrowed("name")));
+        assert_eq!(layer.num_features(), 1);
+        let feature = layer.features().next().unwrap().unwrap();
+        assert_eq!(feature.id(), Some(7));
+        assert_eq!(feature.geom_type(), Some(GeomType::Linestring));
+        assert_eq!(feature.geometry_data(), &[9, 2, 4, 10, 4, 0]);
+        assert_eq!(
+            feature.geometry().unwrap(),
+            Geometry::Linestrings(vec![vec![(1, 2), (3, 2)]])
+        );
+        let tags = feature.tags(layer).collect::<Vec<_>>();
+        assert_eq!(tags.len(), 4);
+        assert_eq!(tags[0].1.as_str(), Some("Main"));
+        assert_eq!(tags[1], ("width", &ValueView::Double(12.5)));
+        assert_eq!(tags[2], ("level", &ValueView::Sint(-1)));
+        assert_eq!(tags[3], ("oneway", &ValueView::Bool(true)));
+        let tag = |key| feature.tag(layer, key);
+        assert_eq!(tag("width").and_then(ValueView::as_f64), Some(12.5));
+        assert_eq!(tag("level").and_then(ValueView::as_i64), Some(-1));
+        assert_eq!(tag("level").and_then(ValueView::as_u64), None);
+        assert_eq!(tag("oneway").and_then(ValueView::as_bool), Some(true));
+        assert_eq!(tag("name").and_then(ValueView::as_f64), None);
+        assert_eq!(tag("missing"), None);
+        assert_eq!(view.layer("empty").unwrap().features().len(), 0);
+        match TileView::new(&data[..data.len() - 1]) {
+            Err(Error::Decode(loc, _)) => assert_eq!(loc.layer(), 1),
+            r => panic!("unexpected {r:?}"),
+        }
+        let limits = DecodeLimits::untrusted();
+        assert!(TileView::with_limits(&data, &limits).is_ok());
+        let limits = limits.with_max_string_len(4);
+        assert!(TileView::with_limits(&data, &limits).is_err());
+    }
+
+    #[test]
+    fn test_tile_layers() {
+        let mut tile = Tile::new(256);
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(3.0, 4.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("points").into_feature(geom);
+        feature.set_id(9).add_tag_uint("n", 5);
+        tile.add_layer(feature.into_layer()).unwrap();
+        let data = tile.to_bytes().unwrap();
+        let view = TileView::new(&data).unwrap();
+        let encoded = Tile::layer_bytes(&data, "points").unwrap().unwrap();
+        let mut other = Tile::new(256);
+        other.add_encoded_layer(encoded).unwrap();
+        let tiles = [tile, Tile::from_bytes(&data).unwrap(), other];
+        for tile in &tiles {
+            let layers = tile.layers().unwrap();
+            assert_eq!(layers.len(), 1);
+            let layer = &layers[0];
+            assert_eq!(layer.name(), view.layers()[0].name());
+            assert_eq!(layer.version(), 2);
+            let feature = layer.features().next().unwrap().unwrap();
+            assert_eq!(feature, view.layers()[0].feature(0).unwrap().unwrap());
+            let tags = feature.tags(layer).collect::<Vec<_>>();
+            assert_eq!(tags, vec![("n", &ValueView::Uint(5))]);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        // layer { name: "\xff", keys: ["k"], features: [{ tags: [0] }] }
+        let data = [
+            0x1a, 0x0b, 0x0a, 0x01, 0xff, 0x1a, 0x01, b'k', 0x12, 0x03, 0x12,
+            0x01, 0x00,
+        ];
+        let view = TileView::new(&data).unwrap();
+        let layer = &view.layers()[0];
+        assert_eq!(layer.num_features(), 1);
+        let view =
+            TileView::filtered(&data, |name| name == "\u{fffd}").unwrap();
+        assert_eq!(view.layers().len(), 1);
+        assert!(TileView::filtered(&data[..5], |_| true).is_err());
+        assert!(layer.feature(0).unwrap().is_err());
+        assert!(layer.feature(1).is_none());
+        let data = [0x1a, 0x03, 0x0a, 0x01, 0xff];
+        let view = TileView::new(&data).unwrap();
+        assert_eq!(view.layers()[0].name(), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_value_extension() {
+        // value { 8: 1, string_value: "x" }
+        let value = [0x40, 0x01, 0x0a, 0x01, b'x'];
+        assert_eq!(
+            ValueView::new(&value).unwrap(),
+            ValueView::String(Cow::Borrowed("x"))
+        );
+        // string_value with varint wire type
+        assert!(ValueView::new(&[0x08, 0x01]).is_err());
+        assert!(ValueView::new(&[0x40, 0x01]).is_err());
+        let mut data = vec![0x1a, 0x0a, 0x0a, 0x01, b'a', 0x22, 0x05];
+        data.extend(value);
+        let view = TileView::new(&data).unwrap();
+        assert_eq!(view.layers()[0].values().len(), 1);
+    }
+}
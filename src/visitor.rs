@@ -0,0 +1,397 @@
+// visitor.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Push (visitor) decoding of encoded tiles.
+//!
+use crate::encoder::{CommandCursor, GeomType};
+use crate::error::{DecodeLocation, Error, Result};
+use crate::view::ValueView;
+use crate::wire::{offset_of, push_u32, Field, Fields, WireValue};
+use std::borrow::Cow;
+
+/// Callbacks for [decode_with].
+///
+/// All methods have empty default implementations, so a visitor only needs
+/// to implement those it is interested in.  For each layer, the calls are:
+///
+/// * `layer_begin`, then for each feature (if it returns `true`):
+///   * `feature`
+///   * `tag` for each tag
+///   * `part_begin` at each `MoveTo` command (once for all points of a
+///     multipoint), `point` for each vertex and `close_path` at each
+///     `ClosePath` command
+///   * `feature_end`
+/// * `layer_end`
+///
+/// Coördinates are absolute, in tile units.
+///
+/// [decode_with]: fn.decode_with.html
+pub trait TileVisitor {
+    /// Begin a layer; return `false` to skip its features.
+    fn layer_begin(
+        &mut self,
+        _name: &str,
+        _version: u32,
+        _extent: u32,
+    ) -> bool {
+        true
+    }
+
+    /// End a layer.
+    fn layer_end(&mut self) {}
+
+    /// Begin a feature.
+    fn feature(&mut self, _id: Option<u64>, _geom_type: Option<GeomType>) {}
+
+    /// Visit a tag of the current feature.
+    fn tag(&mut self, _key: &str, _value: &ValueView) {}
+
+    /// Begin a geometry part (`MoveTo` command).
+    fn part_begin(&mut self) {}
+
+    /// Visit a vertex of the current feature.
+    fn point(&mut self, _x: i32, _y: i32) {}
+
+    /// Close the current ring (`ClosePath` command).
+    fn close_path(&mut self) {}
+
+    /// End a feature.
+    fn feature_end(&mut self) {}
+}
+
+/// Layer header and tables, borrowed from a layer message body
+struct LayerTables<'a> {
+    name: Cow<'a, str>,
+    version: u32,
+    extent: u32,
+    keys: Vec<Cow<'a, str>>,
+    values: Vec<ValueView<'a>>,
+}
+
+impl<'a> LayerTables<'a> {
+    /// Scan a layer message body, skipping features
+    fn scan(body: &'a [u8]) -> Result<Self> {
+        let mut layer = LayerTables {
+            name: Cow::Borrowed(""),
+            version: 1,
+            extent: 4096,
+            keys: vec![],
+            values: vec![],
+        };
+        for field in Fields::new(body) {
+            let field = field?;
+            match (field.number, field.value) {
+                (15, WireValue::Varint(v)) => layer.version = v as u32,
+                (1, WireValue::Bytes(n)) => {
+                    layer.name = String::from_utf8_lossy(n)
+                }
+                (2, WireValue::Bytes(_)) => (),
+                (3, WireValue::Bytes(k)) => {
+                    layer.keys.push(String::from_utf8_lossy(k))
+                }
+                (4, WireValue::Bytes(v)) => {
+                    layer.values.push(ValueView::new(v)?)
+                }
+                (5, WireValue::Varint(e)) => layer.extent = e as u32,
+                (1..=5 | 15, _) => return Err(Error::InvalidLayer()),
+                _ => (),
+            }
+        }
+        Ok(layer)
+    }
+
+    /// Visit one feature message body
+    fn visit_feature<V>(
+        &self,
+        body: &[u8],
+        visitor: &mut V,
+        buf: &mut Vec<u32>,
+    ) -> Result<()>
+    where
+        V: TileVisitor + ?Sized,
+    {
+        let mut id = None;
+        let mut geom_type = None;
+        for field in Fields::new(body) {
+            match field? {
+                Field {
+                    number: 1,
+                    value: WireValue::Varint(v),
+                    ..
+                } => id = Some(v),
+                Field {
+                    number: 3,
+                    value: WireValue::Varint(tp),
+                    ..
+                } => {
+                    geom_type = match tp {
+                        1 => Some(GeomType::Point),
+                        2 => Some(GeomType::Linestring),
+                        3 => Some(GeomType::Polygon),
+                        _ => None,
+                    }
+                }
+                Field { number: 1 | 3, .. } => {
+                    return Err(Error::InvalidLayer())
+                }
+                _ => (),
+            }
+        }
+        visitor.feature(id, geom_type);
+        buf.clear();
+        for field in Fields::new(body) {
+            if let Field {
+                number: 2, value, ..
+            } = field?
+            {
+                push_u32(buf, value)?;
+            }
+        }
+        if !buf.len().is_multiple_of(2) {
+            return Err(Error::InvalidLayer());
+        }
+        for kv in buf.chunks_exact(2) {
+            let key = self.keys.get(kv[0] as usize);
+            let value = self.values.get(kv[1] as usize);
+            match (key, value) {
+                (Some(key), Some(value)) => visitor.tag(key, value),
+                _ => return Err(Error::InvalidLayer()),
+            }
+        }
+        buf.clear();
+        for field in Fields::new(body) {
+            if let Field {
+                number: 4, value, ..
+            } = field?
+            {
+                push_u32(buf, value)?;
+            }
+        }
+        let mut cursor = CommandCursor::default();
+        let mut prev = 0;
+        while let Some(cmd) = cursor.next(buf) {
+            let (cmd, x, y) = cmd?;
+            match cmd {
+                7 => visitor.close_path(),
+                _ => {
+                    if cmd == 1 && prev != 1 {
+                        visitor.part_begin();
+                    }
+                    visitor.point(x, y);
+                }
+            }
+            prev = cmd;
+        }
+        visitor.feature_end();
+        Ok(())
+    }
+}
+
+/// Decode an encoded tile, pushing its contents to a visitor.
+///
+/// * `data` Encoded tile.
+/// * `visitor` Visitor to call for layers, features, tags and vertices.
+///
+/// Nothing is materialized except each layer's key and value tables (which
+/// borrow strings from `data`), and one reused buffer for feature tags and
+/// geometry.  This is suited to streaming through huge tiles.
+///
+/// Returns an error if the data is malformed; callbacks for data before the
+/// error have already been made.
+///
+/// # Example
+/// ```
+/// # use mvt::Error;
+/// # fn main() -> Result<(), Error> {
+/// use mvt::{decode_with, GeomEncoder, GeomType, Tile, TileVisitor};
+/// use pointy::Transform;
+///
+/// #[derive(Default)]
+/// struct Count {
+///     features: usize,
+///     points: usize,
+/// }
+///
+/// impl TileVisitor for Count {
+///     fn feature(&mut self, _id: Option<u64>, _tp: Option<GeomType>) {
+///         self.features += 1;
+///     }
+///     fn point(&mut self, _x: i32, _y: i32) {
+///         self.points += 1;
+///     }
+/// }
+///
+/// let mut tile = Tile::new(4096);
+/// let geom = GeomEncoder::new(GeomType::Linestring, Transform::default())
+///     .point(0.0, 0.0)?
+///     .point(10.0, 0.0)?
+///     .encode()?;
+/// let layer = tile.create_layer("roads").into_feature(geom).into_layer();
+/// tile.add_layer(layer)?;
+/// let mut count = Count::default();
+/// decode_with(&tile.to_bytes()?, &mut count)?;
+/// assert_eq!((count.features, count.points), (1, 2));
+/// # Ok(()) }
+/// ```
+pub fn decode_with<V>(data: &[u8], visitor: &mut V) -> Result<()>
+where
+    V: TileVisitor + ?Sized,
+{
+    let mut buf = vec![];
+    let mut fields = Fields::new(data);
+    let mut n_layer = 0;
+    loop {
+        let offset = fields.offset();
+        let at = DecodeLocation::new(n_layer, None, offset);
+        let body = match fields.next() {
+            None => break,
+            Some(Ok(Field {
+                number: 3,
+                value: WireValue::Bytes(body),
+                ..
+            })) => body,
+            Some(Ok(Field { number: 3, .. })) => {
+                return Err(Error::InvalidLayer().at(at))
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.at(at)),
+        };
+        let layer = LayerTables::scan(body).map_err(|e| e.at(at))?;
+        if visitor.layer_begin(&layer.name, layer.version, layer.extent) {
+            let mut n_feature = 0;
+            for field in Fields::new(body) {
+                if let Field {
+                    number: 2,
+                    value: WireValue::Bytes(f),
+                    ..
+                } = field.map_err(|e| e.at(at))?
+                {
+                    let at = DecodeLocation::new(
+                        n_layer,
+                        Some(n_feature),
+                        offset_of(data, f),
+                    );
+                    layer
+                        .visit_feature(f, visitor, &mut buf)
+                        .map_err(|e| e.at(at))?;
+                    n_feature += 1;
+                }
+            }
+        }
+        visitor.layer_end();
+        n_layer += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::GeomEncoder;
+    use crate::tile::Tile;
+    use pointy::Transform;
+
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl TileVisitor for Log {
+        fn layer_begin(
+            &mut self,
+            name: &str,
+            version: u32,
+            extent: u32,
+        ) -> bool {
+            self.0.push(format!("layer {name} v{version} {extent}"));
+            name != "skip"
+        }
+        fn layer_end(&mut self) {
+            self.0.push("end".into());
+        }
+        fn feature(&mut self, id: Option<u64>, tp: Option<GeomType>) {
+            self.0.push(format!("feature {id:?} {tp:?}"));
+        }
+        fn tag(&mut self, key: &str, value: &ValueView) {
+            self.0.push(format!("{key}={value:?}"));
+        }
+        fn part_begin(&mut self) {
+            self.0.push("part".into());
+        }
+        fn point(&mut self, x: i32, y: i32) {
+            self.0.push(format!("{x},{y}"));
+        }
+        fn close_path(&mut self) {
+            self.0.push("close".into());
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut tile = Tile::new(256);
+        let geom = GeomEncoder::new(GeomType::Polygon, Transform::default())
+            .point(0.0, 0.0)
+            .unwrap()
+            .point(4.0, 0.0)
+            .unwrap()
+            .point(4.0, 4.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = tile.create_layer("a").into_feature(geom);
+        feature.set_id(7).add_tag_bool("big", true);
+        tile.add_layer(feature.into_layer()).unwrap();
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(1.0, 2.0)
+            .unwrap()
+            .point(3.0, 4.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let layer = tile.create_layer("skip").into_feature(geom).into_layer();
+        tile.add_layer(layer).unwrap();
+        let data = tile.to_bytes().unwrap();
+        let mut log = Log::default();
+        decode_with(&data, &mut log).unwrap();
+        assert_eq!(
+            log.0,
+            [
+                "layer a v2 256",
+                "feature Some(7) Some(Polygon)",
+                "big=Bool(true)",
+                "part",
+                "0,0",
+                "4,0",
+                "4,4",
+                "close",
+                "end",
+                "layer skip v2 256",
+                "end",
+            ]
+        );
+        assert!(decode_with(&data[..data.len() - 1], &mut log).is_err());
+    }
+
+    #[test]
+    fn test_decode_location() {
+        let mut tile = Tile::new(256);
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(1.0, 2.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let layer = tile.create_layer("a").into_feature(geom).into_layer();
+        tile.add_layer(layer).unwrap();
+        let mut data = tile.to_bytes().unwrap();
+        // corrupt the feature's geometry command (MoveTo with count 1)
+        let pos = data.iter().position(|b| *b == 9).unwrap();
+        data[pos] = 8 << 3 | 2;
+        match decode_with(&data, &mut Log::default()) {
+            Err(Error::Decode(loc, _)) => {
+                assert_eq!(loc.layer(), 0);
+                assert_eq!(loc.feature(), Some(0));
+                assert!(loc.offset() > 0 && loc.offset() < pos);
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+    }
+}
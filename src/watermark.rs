@@ -0,0 +1,179 @@
+// watermark.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Keyed geometry watermarking.
+//!
+use crate::encoder::decode_vertices;
+use crate::error::Result;
+
+/// Secret key for watermarking quantized geometry.
+///
+/// Each vertex is nudged by at most one tile unit per axis, so that the
+/// lowest bit of each quantized coördinate matches a keyed hash of the
+/// remaining bits.  Unmarked geometry only matches by chance (about one vertex
+/// in four), so a tile set encoded with the key can be identified later.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, GeomEncoder, GeomType, Watermark};
+/// # use pointy::Transform;
+/// # fn main() -> Result<(), Error> {
+/// let wm = Watermark::new(0x5eed);
+/// let geom_data = GeomEncoder::new(GeomType::Point, Transform::default())
+///     .watermark(wm)
+///     .point(25.0, 17.0)?
+///     .encode()?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Watermark {
+    key: u64,
+}
+
+/// Result of [Watermark::detect].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct WatermarkScore {
+    matched: usize,
+    total: usize,
+}
+
+/// Mix bits of a 64-bit value (splitmix64 finalizer)
+pub(crate) fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl Watermark {
+    /// Create a new watermark.
+    ///
+    /// * `key` Secret key.
+    pub fn new(key: u64) -> Self {
+        Watermark { key }
+    }
+
+    /// Get the watermark bits for a pair of coördinates.
+    fn bits(&self, x: i32, y: i32) -> (i32, i32) {
+        let cell = (((x >> 1) as u32 as u64) << 32) | ((y >> 1) as u32 as u64);
+        let h = mix(self.key ^ mix(cell));
+        ((h & 1) as i32, ((h >> 1) & 1) as i32)
+    }
+
+    /// Apply the watermark to quantized coördinates.
+    pub(crate) fn apply(&self, x: i32, y: i32) -> (i32, i32) {
+        let (bx, by) = self.bits(x, y);
+        ((x & !1) | bx, (y & !1) | by)
+    }
+
+    /// Check whether quantized coördinates carry the watermark.
+    fn is_marked(&self, x: i32, y: i32) -> bool {
+        self.bits(x, y) == (x & 1, y & 1)
+    }
+
+    /// Detect the watermark in encoded geometry.
+    ///
+    /// * `geometry` Geometry command integers of one feature.
+    ///
+    /// Returns an error if the command integers are malformed.
+    pub fn detect(&self, geometry: &[u32]) -> Result<WatermarkScore> {
+        let mut score = WatermarkScore::default();
+        for (x, y) in decode_vertices(geometry)? {
+            score.add(self.is_marked(x, y));
+        }
+        Ok(score)
+    }
+}
+
+impl WatermarkScore {
+    /// Record one vertex.
+    fn add(&mut self, matched: bool) {
+        if matched {
+            self.matched += 1;
+        }
+        self.total += 1;
+    }
+
+    /// Get number of vertices which carry the watermark.
+    pub fn matched(&self) -> usize {
+        self.matched
+    }
+
+    /// Get total number of vertices checked.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Get ratio of marked vertices (0 to 1).
+    ///
+    /// Watermarked geometry scores 1; unmarked geometry about 0.25.
+    pub fn ratio(&self) -> f64 {
+        if self.total > 0 {
+            self.matched as f64 / self.total as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Merge with another score (for checking many features).
+    pub fn merge(self, other: Self) -> Self {
+        WatermarkScore {
+            matched: self.matched + other.matched,
+            total: self.total + other.total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use pointy::Transform;
+
+    fn linestring(wm: Option<Watermark>) -> Vec<u32> {
+        let mut enc =
+            GeomEncoder::new(GeomType::Linestring, Transform::default());
+        if let Some(wm) = wm {
+            enc = enc.watermark(wm);
+        }
+        for i in 0..200 {
+            let x = (i * 37 % 4096) as f64;
+            let y = (i * 101 % 4096) as f64;
+            enc.add_point(x, y).unwrap();
+        }
+        enc.encode().unwrap().into_vec()
+    }
+
+    #[test]
+    fn test_apply() {
+        let wm = Watermark::new(12345);
+        for (x, y) in [(0, 0), (-5, 7), (4095, 4096), (-1, -1)] {
+            let (mx, my) = wm.apply(x, y);
+            assert!((mx - x).abs() <= 1);
+            assert!((my - y).abs() <= 1);
+            assert!(wm.is_marked(mx, my));
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        let wm = Watermark::new(0xDEAD_BEEF);
+        let score = wm.detect(&linestring(Some(wm))).unwrap();
+        assert_eq!(score.total(), 200);
+        assert_eq!(score.matched(), 200);
+        let score = wm.detect(&linestring(None)).unwrap();
+        assert_eq!(score.total(), 200);
+        assert!(score.ratio() < 0.5);
+        let other = Watermark::new(0xCAFE);
+        let score = other.detect(&linestring(Some(wm))).unwrap();
+        assert!(score.ratio() < 0.5);
+    }
+
+    #[test]
+    fn test_detect_invalid() {
+        let wm = Watermark::new(1);
+        assert!(wm.detect(&[9, 50]).is_err());
+        assert!(wm.detect(&[3]).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+// wire.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Protobuf wire format scanning, for splicing encoded messages.
+//!
+use crate::error::{Error, Result};
+use protobuf::UnknownFields;
+
+/// Field value in wire format
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum WireValue<'a> {
+    /// Varint (wire type 0)
+    Varint(u64),
+
+    /// 64-bit value (wire type 1)
+    Fixed64(&'a [u8]),
+
+    /// Length-delimited value (wire type 2)
+    Bytes(&'a [u8]),
+
+    /// 32-bit value (wire type 5)
+    Fixed32(&'a [u8]),
+}
+
+/// Field in wire format
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Field<'a> {
+    /// Field number
+    pub number: u32,
+
+    /// Field value
+    pub value: WireValue<'a>,
+
+    /// Raw bytes of the whole field (key and value)
+    pub raw: &'a [u8],
+}
+
+/// Iterator of top-level fields in a message
+pub(crate) struct Fields<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+/// Read a varint
+pub(crate) fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let b = *data.get(*pos).ok_or(Error::InvalidLayer())?;
+        *pos += 1;
+        value |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::InvalidLayer())
+}
+
+/// Take a slice of `len` bytes
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: u64) -> Result<&'a [u8]> {
+    let len = usize::try_from(len).map_err(|_| Error::InvalidLayer())?;
+    let end = pos.checked_add(len).ok_or(Error::InvalidLayer())?;
+    let bytes = data.get(*pos..end).ok_or(Error::InvalidLayer())?;
+    *pos = end;
+    Ok(bytes)
+}
+
+impl<'a> Fields<'a> {
+    /// Scan fields of an encoded message
+    pub fn new(data: &'a [u8]) -> Self {
+        Fields { data, pos: 0 }
+    }
+
+    /// Get the byte offset of the next field
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// Read the next field
+    fn read_field(&mut self) -> Result<Field<'a>> {
+        let start = self.pos;
+        let key = read_varint(self.data, &mut self.pos)?;
+        let number =
+            u32::try_from(key >> 3).map_err(|_| Error::InvalidLayer())?;
+        if number == 0 {
+            return Err(Error::InvalidLayer());
+        }
+        let value = match key & 0x7 {
+            0 => WireValue::Varint(read_varint(self.data, &mut self.pos)?),
+            1 => WireValue::Fixed64(take(self.data, &mut self.pos, 8)?),
+            2 => {
+                let len = read_varint(self.data, &mut self.pos)?;
+                WireValue::Bytes(take(self.data, &mut self.pos, len)?)
+            }
+            5 => WireValue::Fixed32(take(self.data, &mut self.pos, 4)?),
+            _ => return Err(Error::InvalidLayer()),
+        };
+        let raw = &self.data[start..self.pos];
+        Ok(Field { number, value, raw })
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<Field<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let field = self.read_field();
+        if field.is_err() {
+            // stop after an error
+            self.pos = self.data.len();
+        }
+        Some(field)
+    }
+}
+
+/// Keep a field as an unknown field of a decoded message
+pub(crate) fn keep_unknown(unknown: &mut UnknownFields, field: &Field) {
+    match field.value {
+        WireValue::Varint(v) => unknown.add_varint(field.number, v),
+        WireValue::Fixed64(v) => {
+            let mut buf = [0; 8];
+            buf.copy_from_slice(v);
+            unknown.add_fixed64(field.number, u64::from_le_bytes(buf));
+        }
+        WireValue::Bytes(v) => {
+            unknown.add_length_delimited(field.number, v.into())
+        }
+        WireValue::Fixed32(v) => {
+            let mut buf = [0; 4];
+            buf.copy_from_slice(v);
+            unknown.add_fixed32(field.number, u32::from_le_bytes(buf));
+        }
+    }
+}
+
+/// Get the byte offset of a slice within the data containing it
+pub(crate) fn offset_of(data: &[u8], part: &[u8]) -> usize {
+    (part.as_ptr() as usize).saturating_sub(data.as_ptr() as usize)
+}
+
+/// Append values of a repeated `uint32` field, packed or not
+pub(crate) fn push_u32(out: &mut Vec<u32>, value: WireValue) -> Result<()> {
+    match value {
+        WireValue::Varint(v) => out.push(v as u32),
+        WireValue::Bytes(packed) => {
+            let mut pos = 0;
+            while pos < packed.len() {
+                out.push(read_varint(packed, &mut pos)? as u32);
+            }
+        }
+        _ => return Err(Error::InvalidLayer()),
+    }
+    Ok(())
+}
+
+/// Scan a framed `Tile.layers` field, returning layer name and extent.
+///
+/// Checks framing of the layer's fields, without decoding features.
+pub(crate) fn scan_layer(data: &[u8]) -> Result<(String, u32)> {
+    let mut fields = Fields::new(data);
+    let body = match fields.next() {
+        Some(Ok(Field {
+            number: 3,
+            value: WireValue::Bytes(body),
+            ..
+        })) => body,
+        _ => return Err(Error::InvalidLayer()),
+    };
+    if fields.next().is_some() {
+        return Err(Error::InvalidLayer());
+    }
+    layer_header(body)
+}
+
+/// Scan a layer message body, returning layer name and extent
+pub(crate) fn layer_header(body: &[u8]) -> Result<(String, u32)> {
+    let mut name = None;
+    let mut extent = 4096;
+    for field in Fields::new(body) {
+        match field? {
+            Field {
+                number: 1,
+                value: WireValue::Bytes(n),
+                ..
+            } => {
+                let n = std::str::from_utf8(n)
+                    .map_err(|_| Error::InvalidLayer())?;
+                name = Some(n.to_string());
+            }
+            Field {
+                number: 5,
+                value: WireValue::Varint(e),
+                ..
+            } => {
+                extent = u32::try_from(e).map_err(|_| Error::InvalidLayer())?;
+            }
+            Field { number: 1 | 5, .. } => return Err(Error::InvalidLayer()),
+            _ => (),
+        }
+    }
+    let name = name.ok_or(Error::InvalidLayer())?;
+    Ok((name, extent))
+}
+
+/// Find a framed `Tile.layers` field by layer name, in an encoded tile
+pub(crate) fn find_layer<'a>(
+    data: &'a [u8],
+    name: &str,
+) -> Result<Option<&'a [u8]>> {
+    for field in Fields::new(data) {
+        if let Field {
+            number: 3,
+            value: WireValue::Bytes(body),
+            raw,
+        } = field?
+        {
+            if layer_header(body)?.0 == name {
+                return Ok(Some(raw));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fields() {
+        let data = [0x08, 0x96, 0x01, 0x12, 0x02, b'h', b'i', 0x1d, 1, 2, 3, 4];
+        let fields = Fields::new(&data).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0].value, WireValue::Varint(150));
+        assert_eq!(fields[1].value, WireValue::Bytes(b"hi"));
+        assert_eq!(fields[1].raw, &data[3..7]);
+        assert_eq!(fields[2].number, 3);
+        assert!(Fields::new(&[0x12, 0x05, 0]).any(|f| f.is_err()));
+        assert!(Fields::new(&[0x08, 0x80]).any(|f| f.is_err()));
+        assert!(Fields::new(&[0x0b]).any(|f| f.is_err()));
+        let mut out = vec![];
+        push_u32(&mut out, WireValue::Varint(7)).unwrap();
+        push_u32(&mut out, WireValue::Bytes(&[0x96, 0x01, 0x03])).unwrap();
+        assert_eq!(out, vec![7, 150, 3]);
+        assert!(push_u32(&mut out, WireValue::Bytes(&[0x96])).is_err());
+    }
+
+    #[test]
+    fn test_scan_layer() {
+        // layer { name: "a", extent: 256, version: 2 }
+        let data = [0x1a, 0x08, 0x0a, 0x01, b'a', 0x28, 0x80, 0x02, 0x78, 2];
+        assert_eq!(scan_layer(&data).unwrap(), ("a".to_string(), 256));
+        assert!(scan_layer(&data[..9]).is_err());
+        assert!(scan_layer(&data[2..]).is_err());
+        assert!(scan_layer(&[0x1a, 0x02, 0x78, 2]).is_err());
+        let mut tile = vec![0x1a, 0x03, 0x0a, 0x01, b'b'];
+        tile.extend_from_slice(&data);
+        assert_eq!(find_layer(&tile, "a").unwrap(), Some(&data[..]));
+        assert_eq!(find_layer(&tile, "b").unwrap(), Some(&tile[..5]));
+        assert_eq!(find_layer(&tile, "c").unwrap(), None);
+        assert!(find_layer(&tile[..12], "c").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+// zdict.rs
+//
+// Copyright (c) 2019-2022  Minnesota Department of Transportation
+//
+//! Shared zstd dictionaries.
+//!
+use crate::error::{Error, Result};
+use crate::tile::Tile;
+use std::io::Read;
+
+/// Default zstd compression level
+const DEFAULT_LEVEL: i32 = 3;
+
+/// Default maximum decompressed size (bytes)
+const DEFAULT_MAX_SIZE: usize = 64 * 1024 * 1024;
+
+/// Shared zstd dictionary for compressing tiles.
+///
+/// Tiles from one source usually have near-identical layer names, keys and
+/// values.  A dictionary trained on sample tiles lets each tile be compressed
+/// without repeating that vocabulary.  The same dictionary must be used to
+/// decompress, so it is typically distributed to clients once, out of band.
+///
+/// # Example
+/// ```
+/// # use mvt::{Error, Tile, ZstdDict};
+/// # fn sample() -> Result<Tile, Error> {
+/// #     let mut tile = Tile::new(4096);
+/// #     tile.add_layer(tile.create_layer("First Layer"))?;
+/// #     Ok(tile)
+/// # }
+/// # fn main() -> Result<(), Error> {
+/// let samples = (0..500).map(|_| sample()).collect::<Result<Vec<_>, _>>()?;
+/// let dict = ZstdDict::train(&samples, 4096)?;
+/// let tile = sample()?;
+/// let data = dict.compress(&tile)?;
+/// assert_eq!(dict.decompress(&data)?, tile.to_bytes()?);
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ZstdDict {
+    /// Dictionary content
+    dict: Vec<u8>,
+
+    /// Compression level
+    level: i32,
+
+    /// Maximum decompressed size (bytes)
+    max_size: usize,
+}
+
+impl ZstdDict {
+    /// Train a dictionary from sample tiles.
+    ///
+    /// * `samples` Sample tiles.
+    /// * `max_size` Maximum dictionary size (bytes).
+    pub fn train(samples: &[Tile], max_size: usize) -> Result<Self> {
+        let samples = samples
+            .iter()
+            .map(|tile| tile.to_bytes())
+            .collect::<Result<Vec<_>>>()?;
+        let dict = zstd::dict::from_samples(&samples, max_size)?;
+        Ok(Self::from_bytes(dict))
+    }
+
+    /// Create a dictionary from previously trained bytes.
+    pub fn from_bytes(dict: Vec<u8>) -> Self {
+        ZstdDict {
+            dict,
+            level: DEFAULT_LEVEL,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Set the compression level.
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set the maximum decompressed size (bytes), 64 MiB by default.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Get the dictionary bytes, for distributing to clients.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.dict
+    }
+
+    /// Encode a tile and compress it with the dictionary.
+    pub fn compress(&self, tile: &Tile) -> Result<Vec<u8>> {
+        let v = Vec::with_capacity(tile.compute_size());
+        let mut enc = zstd::stream::write::Encoder::with_dictionary(
+            v, self.level, &self.dict,
+        )?;
+        tile.write_to(&mut enc)?;
+        Ok(enc.finish()?)
+    }
+
+    /// Decompress data compressed with the dictionary.
+    ///
+    /// Returns the encoded tile bytes, or [Error::LimitExceeded] if they
+    /// would be larger than the maximum size.
+    ///
+    /// [Error::LimitExceeded]: enum.Error.html#variant.LimitExceeded
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let dec =
+            zstd::stream::read::Decoder::with_dictionary(data, &self.dict)?;
+        let mut v = Vec::new();
+        dec.take((self.max_size as u64).saturating_add(1))
+            .read_to_end(&mut v)?;
+        if v.len() > self.max_size {
+            return Err(Error::LimitExceeded("decompressed size"));
+        }
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::encoder::{GeomEncoder, GeomType};
+    use pointy::Transform;
+
+    fn sample(i: u64) -> Tile {
+        let mut tile = Tile::new(4096);
+        let layer = tile.create_layer("Roads");
+        let geom = GeomEncoder::new(GeomType::Point, Transform::default())
+            .point(i as f64, 0.0)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let mut feature = layer.into_feature(geom);
+        feature.set_id(i);
+        feature.add_tag_string("surface", "asphalt");
+        feature.add_tag_uint("lanes", i % 4);
+        feature.add_tag_string("name", &format!("Road {i}"));
+        tile.add_layer(feature.into_layer()).unwrap();
+        tile
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let samples: Vec<Tile> = (0..500).map(sample).collect();
+        let dict = ZstdDict::train(&samples, 4096).unwrap().with_level(9);
+        let dict = ZstdDict::from_bytes(dict.as_bytes().to_vec());
+        let tile = sample(100);
+        let data = dict.compress(&tile).unwrap();
+        assert_eq!(dict.decompress(&data).unwrap(), tile.to_bytes().unwrap());
+        let other = ZstdDict::from_bytes(vec![0; 8]);
+        assert!(other.decompress(&data).is_err());
+        let len = tile.to_bytes().unwrap().len();
+        let dict = dict.with_max_size(len);
+        assert!(dict.decompress(&data).is_ok());
+        let dict = dict.with_max_size(len - 1);
+        assert!(matches!(
+            dict.decompress(&data),
+            Err(Error::LimitExceeded(_))
+        ));
+    }
+}